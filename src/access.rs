@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Header carrying the client's API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Fields each API key may query and see, loaded from the JSON object in
+/// `API_KEYS_FILE`: key => list of field names, `["*"]` for every field.
+///
+/// Without the file every request sees every field. With it, requests must
+/// send a listed key in `X-API-Key`.
+#[derive(Debug, Default)]
+pub struct AccessPolicy {
+    keys: HashMap<String, FieldAccess>,
+}
+
+/// Fields hidden from one API key
+#[derive(Debug, Clone, Default)]
+pub struct FieldAccess {
+    hidden: HashSet<String>,
+}
+
+impl AccessPolicy {
+    /// Load the policy named by `API_KEYS_FILE`, if set. `fields` are the
+    /// index's field names; allowlists naming anything else are rejected
+    /// so a typo can't silently hide a field.
+    pub fn from_env(fields: &[String]) -> Result<Self> {
+        let Ok(path) = std::env::var("API_KEYS_FILE") else {
+            return Ok(Self::default());
+        };
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Cannot read API_KEYS_FILE {}: {}", path, e))?;
+        let allowlists: HashMap<String, Vec<String>> = serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Invalid API_KEYS_FILE {}: {}", path, e))?;
+        Self::from_allowlists(allowlists, fields)
+    }
+
+    /// Policy from key => allowed field names, checked against the index's
+    /// `fields` as in `from_env`
+    pub fn from_allowlists(allowlists: HashMap<String, Vec<String>>, fields: &[String]) -> Result<Self> {
+        let mut keys = HashMap::with_capacity(allowlists.len());
+        for (key, allowed) in allowlists {
+            if let Some(unknown) = allowed.iter().find(|f| *f != "*" && !fields.contains(f)) {
+                return Err(anyhow!("API key allowlist names unknown field '{}'", unknown));
+            }
+            let hidden = if allowed.iter().any(|f| f == "*") {
+                HashSet::new()
+            } else {
+                fields.iter().filter(|f| !allowed.contains(f)).cloned().collect()
+            };
+            keys.insert(key, FieldAccess { hidden });
+        }
+        Ok(Self { keys })
+    }
+
+    /// Whether requests have to present an API key
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Field access for a request's key; None when keys are required and
+    /// the key is missing or unknown
+    pub fn resolve(&self, key: Option<&str>) -> Option<FieldAccess> {
+        if !self.is_enabled() {
+            return Some(FieldAccess::default());
+        }
+        key.and_then(|key| self.keys.get(key)).cloned()
+    }
+}
+
+impl FieldAccess {
+    pub fn allows(&self, field: &str) -> bool {
+        !self.hidden.contains(field)
+    }
+
+    /// First of `fields` this key may not query
+    pub fn forbidden<'a>(&self, fields: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        fields.into_iter().find(|field| !self.allows(field))
+    }
+
+    /// Remove hidden fields from a result record, including its per-field
+    /// extras (`match_offsets`, `snippets`)
+    pub fn redact(&self, record: &mut Value) {
+        if self.hidden.is_empty() {
+            return;
+        }
+        if let Value::Object(fields) = record {
+            fields.retain(|name, _| self.allows(name));
+            for extra in ["match_offsets", "snippets"] {
+                if let Some(Value::Object(by_field)) = fields.get_mut(extra) {
+                    by_field.retain(|name, _| self.allows(name));
+                }
+            }
+        }
+    }
+
+    /// Remove hidden fields from a `stats` summary
+    pub fn redact_stats(&self, stats: &mut Value) {
+        for section in ["non_empty", "top_values"] {
+            if let Some(Value::Object(fields)) = stats.get_mut(section) {
+                fields.retain(|name, _| self.allows(name));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> Vec<String> {
+        ["master_id", "name", "mobile", "address", "email"].map(String::from).to_vec()
+    }
+
+    #[test]
+    fn allowlists_hide_the_rest_and_reject_unknown_fields() {
+        let policy = AccessPolicy::from_allowlists(HashMap::from([
+            ("limited".to_string(), vec!["master_id".to_string(), "name".to_string(), "mobile".to_string()]),
+            ("full".to_string(), vec!["*".to_string()]),
+        ]), &fields()).unwrap();
+        assert!(policy.is_enabled());
+        assert!(policy.resolve(None).is_none());
+        assert!(policy.resolve(Some("other")).is_none());
+        assert!(policy.resolve(Some("full")).unwrap().forbidden(["address", "email"]).is_none());
+
+        let limited = policy.resolve(Some("limited")).unwrap();
+        assert_eq!(limited.forbidden(["name", "email", "address"]), Some("email"));
+        let mut record = serde_json::json!({
+            "master_id": "m1", "name": "ravi", "address": "delhi", "email": "r@x.in",
+            "match_offsets": {"name": [[0, 4]], "address": [[0, 5]]},
+            "snippets": {"address": "<b>delhi</b>"},
+        });
+        limited.redact(&mut record);
+        assert_eq!(record, serde_json::json!({
+            "master_id": "m1", "name": "ravi", "match_offsets": {"name": [[0, 4]]}, "snippets": {},
+        }));
+
+        let err = AccessPolicy::from_allowlists(HashMap::from([
+            ("typo".to_string(), vec!["adress".to_string()]),
+        ]), &fields()).unwrap_err();
+        assert!(err.to_string().contains("unknown field 'adress'"), "{err}");
+        // No keys configured: every request sees everything
+        assert!(AccessPolicy::default().resolve(None).unwrap().allows("email"));
+    }
+}
@@ -1,21 +1,93 @@
-use crate::search_service::SearchService;
+use crate::access::{AccessPolicy, FieldAccess, API_KEY_HEADER};
+use crate::compare::compare;
+use crate::proto::{accepts_protobuf, encode_search_response, PROTOBUF_CONTENT_TYPE};
+use crate::query_parser::{parse_timestamp, PhraseMode, QueryError, QueryOp, MAX_FUZZY_DISTANCE};
+use crate::search_service::{ReloadStatus, SearchConfig, SearchOptions, SearchResults, SearchService, SortKey, GRAPH_LINK_FIELDS, RESULTS_HASH_MAX_MATCHES};
 use actix_web::{web, App, HttpServer, HttpResponse, Result as ActixResult};
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::{HttpMessage, HttpRequest};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
 use actix_cors::Cors;
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use anyhow::anyhow;
 use std::result::Result;
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)] // Misspelled keys are reported, not silently ignored
 pub struct SearchRequest {
+    // Record fields also accept their common capitalizations (`Name`, `FName`, `masterId`...)
+    #[serde(alias = "Name", alias = "NAME")]
     pub name: Option<String>,
+    #[serde(alias = "FName", alias = "Fname", alias = "fName", alias = "FNAME")]
     pub fname: Option<String>,
+    #[serde(alias = "Address", alias = "ADDRESS")]
     pub address: Option<String>,
+    #[serde(alias = "Mobile", alias = "MOBILE")]
     pub mobile: Option<String>,
+    #[serde(alias = "Alt", alias = "ALT")]
     pub alt: Option<String>,
+    #[serde(alias = "masterId", alias = "MasterId", alias = "masterID", alias = "MasterID", alias = "MASTER_ID")]
     pub master_id: Option<String>,
+    #[serde(alias = "Email", alias = "EMAIL")]
     pub email: Option<String>,
-    pub filter: Option<String>, // "AND" or "OR", default is "AND"
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>, // Any other indexed column by name, e.g. {"pincode": "110001"}
+    #[serde(default)]
+    pub not: BTreeMap<String, String>, // Leave out records matching any of these, e.g. {"address": "test"}
+    pub filter: Option<String>, // "AND" or "OR" (case-insensitive), default is "AND"; others are rejected
+    #[serde(default)]
+    pub token_ops: HashMap<String, String>, // Per field: "AND" (default) requires every word of its value, "OR" any word
+    #[serde(default)]
+    pub fuzzy: bool, // Match name, fname and address words despite typos
+    pub fuzzy_distance: Option<u8>, // Typos tolerated per word with `fuzzy`, 1 (default) or 2
+    #[serde(default)]
+    pub boosts: BTreeMap<String, f32>, // Per field score weight, e.g. {"name": 3.0} ranks name matches higher
+    #[serde(default)]
+    pub match_offsets: bool, // Include matched token byte offsets per result
+    #[serde(default)]
+    pub snippets: bool, // Include highlighted fragments of matched TEXT fields per result
+    pub min_score: Option<f32>, // Suppress matches scoring below this threshold
+    pub phrase_mode: Option<String>, // "auto" (default), "phrase", "tokens", "phrase_then_tokens" or "blend"
+    pub limit: Option<usize>, // Maximum results; server default when omitted, clamped to the server cap
+    #[serde(default)]
+    pub debug_query: bool, // Include the executed Tantivy query structure
+    #[serde(default)]
+    pub stats: bool, // Include field fill counts and top values over the returned results
+    #[serde(default)]
+    pub field_match_counts: bool, // Include how many returned results match each queried field
+    #[serde(default)]
+    pub stored_bytes: bool, // Include the approximate stored size of the returned results (export sizing)
+    #[serde(default)]
+    pub omit_empty: bool, // Leave empty fields out of results instead of returning ""
+    #[serde(default)]
+    pub found_via: bool, // Label each result direct, key_linked or alt_linked (how a mobile/email fan-out reached it)
+    #[serde(default)]
+    pub source_rows: bool, // Include each result's source CSV line (`source_row`) for audits
+    #[serde(default)]
+    pub results_hash: bool, // Include a hash of every match's identity key, to tell if results changed between polls
+    pub indexed_since: Option<String>, // Unix seconds or RFC 3339: only records indexed after it (delta polling)
+    #[serde(default)]
+    pub sort: Vec<SortSpec>, // Result order, first key deciding and later ones breaking ties; relevance when empty
+    #[serde(default)]
+    pub dedup: Vec<String>, // Fields forming a composite key; only the best-ranked result per key is returned
+    pub recency_half_life_days: Option<f64>, // Rank newer records higher: scores halve per this many days of age
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SortSpec {
+    pub field: String,
+    pub order: Option<String>, // "asc" (default) or "desc"
 }
 
 #[derive(Debug, Serialize)]
@@ -23,96 +95,1068 @@ pub struct SearchResponse {
     pub results: Vec<serde_json::Value>,
     pub total_matches: usize,
     pub results_returned: usize,
+    pub phrase_fallback: bool,
+    pub executed_query: String, // Query as run after normalization and ignored clauses; re-runnable as-is
+    pub index_version: u64, // changes when a reload swaps in a new index generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results_hash: Option<String>, // changes when the set of matching identity keys does
     pub query_parse_time_ms: f64,
     pub search_execution_time_ms: f64,
     pub document_retrieval_time_ms: f64,
     pub total_time_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_match_counts: Option<BTreeMap<String, usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicates_removed: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stored_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_retrievals: Option<usize>, // Results skipped as unreadable (SKIP_FAILED_DOCS)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zero_reason: Option<&'static str>, // Why no results were returned, see SearchResults::zero_reason
 }
 
+/// Request fields matched with typo tolerance when `fuzzy` is set
+const FUZZY_FIELDS: &[&str] = &["name", "fname", "address"];
+
 /// Convert SearchRequest to query string
-fn build_query_string(req: &SearchRequest) -> Result<String, anyhow::Error> {
+fn build_query_string(req: &SearchRequest, config: &SearchConfig) -> Result<String, anyhow::Error> {
     let mut clauses = Vec::new();
-    let filter_op = req.filter.as_deref().unwrap_or("AND").to_uppercase();
+    let filter_op = parse_filter(req.filter.as_deref())?;
 
-    if let Some(ref name) = req.name {
-        if !name.trim().is_empty() {
-            clauses.push(format!("name:{}", name.trim()));
-        }
+    let mut fields: Vec<(&str, Option<&str>)> = vec![
+        ("name", req.name.as_deref()),
+        ("fname", req.fname.as_deref()),
+        ("address", req.address.as_deref()),
+        ("mobile", req.mobile.as_deref()),
+        ("alt", req.alt.as_deref()),
+        ("master_id", req.master_id.as_deref()),
+        ("email", req.email.as_deref()),
+    ];
+    for (field, value) in &req.fields {
+        check_field_name(field)?;
+        fields.push((field, Some(value)));
     }
-    if let Some(ref fname) = req.fname {
-        if !fname.trim().is_empty() {
-            clauses.push(format!("fname:{}", fname.trim()));
-        }
+
+    let fuzzy_distance = match (req.fuzzy, req.fuzzy_distance) {
+        (false, None) => None,
+        (false, Some(_)) => return Err(QueryError::new("INVALID_FUZZY", "fuzzy_distance requires fuzzy: true").into()),
+        (true, distance) => Some(distance.unwrap_or(1)),
+    };
+    if let Some(distance) = fuzzy_distance.filter(|d| *d > MAX_FUZZY_DISTANCE) {
+        return Err(QueryError::new(
+            "INVALID_FUZZY",
+            format!("fuzzy_distance {} is above the maximum of {}", distance, MAX_FUZZY_DISTANCE),
+        ).into());
     }
-    if let Some(ref address) = req.address {
-        if !address.trim().is_empty() {
-            clauses.push(format!("address:{}", address.trim()));
+
+    for field in req.token_ops.keys() {
+        if !fields.iter().any(|(name, _)| name == field) {
+            return Err(QueryError::new(
+                "INVALID_TOKEN_OP",
+                format!("token_ops names unknown field '{}'", field),
+            ).into());
         }
     }
-    if let Some(ref mobile) = req.mobile {
-        if !mobile.trim().is_empty() {
-            clauses.push(format!("mobile:{}", mobile.trim()));
+
+    for (field, boost) in &req.boosts {
+        if !fields.iter().any(|(name, value)| name == field && value.is_some_and(|v| !v.is_empty())) {
+            return Err(QueryError::new(
+                "INVALID_BOOST",
+                format!("boosts names field '{}' which is not searched on", field),
+            ).into());
         }
-    }
-    if let Some(ref alt) = req.alt {
-        if !alt.trim().is_empty() {
-            clauses.push(format!("alt:{}", alt.trim()));
+        if !(boost.is_finite() && *boost > 0.0) {
+            return Err(QueryError::new(
+                "INVALID_BOOST",
+                format!("Boost {} for field '{}' must be a positive number", boost, field),
+            ).into());
         }
     }
-    if let Some(ref master_id) = req.master_id {
-        if !master_id.trim().is_empty() {
-            clauses.push(format!("master_id:{}", master_id.trim()));
+
+    for (field, value) in fields {
+        // Absent and "" both mean "not searched on"
+        let Some(value) = value.filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let value = sanitize_field_value(field, value, config)?;
+        // A whitespace-only value is a client bug, not a wildcard
+        if value.is_empty() {
+            return Err(QueryError::empty_value(field).into());
         }
-    }
-    if let Some(ref email) = req.email {
-        if !email.trim().is_empty() {
-            clauses.push(format!("email:{}", email.trim()));
+        // field|value ORs the words of the value instead of AND-ing them
+        let separator = match parse_token_op(field, req.token_ops.get(field))? {
+            QueryOp::And => ':',
+            QueryOp::Or => '|',
+        };
+        // field:value~N matches each word within N typos
+        if let Some(distance) = fuzzy_distance.filter(|_| FUZZY_FIELDS.contains(&field)) {
+            if separator == '|' {
+                return Err(QueryError::new(
+                    "INVALID_FUZZY",
+                    format!("fuzzy cannot be combined with token_ops OR on field '{}'", field),
+                ).into());
+            }
+            clauses.push(format!("{}:{}~{}{}", field, value, distance, boost_suffix(req, field)));
+            continue;
         }
+        clauses.push(format!("{}{}{}{}", field, separator, value, boost_suffix(req, field)));
     }
 
     if clauses.is_empty() {
         return Err(anyhow!("No search fields provided"));
     }
 
-    let op = if filter_op == "OR" { " OR " } else { " AND " };
-    Ok(clauses.join(op))
+    let op = match filter_op {
+        QueryOp::And => " AND ",
+        QueryOp::Or => " OR ",
+    };
+    let mut query = clauses.join(op);
+    // Exclusions apply to the whole query whatever the filter
+    for (field, value) in req.not.iter().filter(|(_, v)| !v.is_empty()) {
+        check_field_name(field)?;
+        let value = sanitize_field_value(field, value, config)?;
+        if value.is_empty() {
+            return Err(QueryError::empty_value(field).into());
+        }
+        query.push_str(&format!(" AND NOT {}:{}", field, value));
+    }
+    Ok(query)
+}
+
+/// `^N` for a field weighted in the request's `boosts`, else nothing
+fn boost_suffix(req: &SearchRequest, field: &str) -> String {
+    req.boosts.get(field).map(|boost| format!("^{}", boost)).unwrap_or_default()
+}
+
+/// Only plain names, so a key can't smuggle query syntax in
+fn check_field_name(field: &str) -> Result<(), QueryError> {
+    if !field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(QueryError::new("UNKNOWN_FIELD", format!("Invalid field name '{}'", field)));
+    }
+    Ok(())
+}
+
+/// Neutralize control characters and enforce the configured length limit, so an
+/// abusive multi-megabyte value never reaches the tokenizer or searcher
+fn sanitize_field_value(field: &str, value: &str, config: &SearchConfig) -> Result<String, QueryError> {
+    let limit = config.field_length_limit(field);
+    let length = value.chars().count();
+    if length > limit {
+        return Err(QueryError::new(
+            "FIELD_TOO_LONG",
+            format!("Field '{}' is {} characters long, the limit is {}", field, length, limit),
+        ));
+    }
+    // Control characters (tabs, newlines, NULs...) become plain separators
+    let cleaned: String = value.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    Ok(cleaned.trim().to_string())
+}
+
+/// Parse the request `filter` combining the fields.
+/// Accepts "AND" (the default when omitted) or "OR", case-insensitively;
+/// any other value is rejected instead of silently falling back to AND.
+fn parse_filter(filter: Option<&str>) -> Result<QueryOp, QueryError> {
+    match filter.map(str::trim) {
+        None => Ok(QueryOp::And),
+        Some(f) if f.eq_ignore_ascii_case("AND") => Ok(QueryOp::And),
+        Some(f) if f.eq_ignore_ascii_case("OR") => Ok(QueryOp::Or),
+        Some(other) => Err(QueryError::new(
+            "INVALID_FILTER",
+            format!("Unknown filter '{}', expected \"AND\" or \"OR\"", other),
+        )),
+    }
+}
+
+/// Parse the request `sort` keys; `order` is "asc" (the default) or "desc",
+/// case-insensitively
+fn parse_sort(sort: &[SortSpec]) -> Result<Vec<SortKey>, QueryError> {
+    sort.iter()
+        .map(|spec| {
+            let descending = match spec.order.as_deref().map(str::trim) {
+                None => false,
+                Some(o) if o.eq_ignore_ascii_case("asc") => false,
+                Some(o) if o.eq_ignore_ascii_case("desc") => true,
+                Some(other) => return Err(QueryError::new(
+                    "INVALID_SORT",
+                    format!("Unknown sort order '{}', expected \"asc\" or \"desc\"", other),
+                )),
+            };
+            Ok(SortKey { field: spec.field.clone(), descending })
+        })
+        .collect()
+}
+
+/// Parse a field's `token_ops` entry, case-insensitively; omitted means "AND"
+fn parse_token_op(field: &str, op: Option<&String>) -> Result<QueryOp, QueryError> {
+    match op.map(|op| op.trim()) {
+        None => Ok(QueryOp::And),
+        Some(op) if op.eq_ignore_ascii_case("AND") => Ok(QueryOp::And),
+        Some(op) if op.eq_ignore_ascii_case("OR") => Ok(QueryOp::Or),
+        Some(other) => Err(QueryError::new(
+            "INVALID_TOKEN_OP",
+            format!("Unknown token_ops value '{}' for field '{}', expected \"AND\" or \"OR\"", other, field),
+        )),
+    }
+}
+
+/// Parse the request `phrase_mode`, case-insensitively; omitted means "auto"
+fn parse_phrase_mode(mode: Option<&str>) -> Result<PhraseMode, QueryError> {
+    let Some(mode) = mode.map(str::trim) else {
+        return Ok(PhraseMode::Auto);
+    };
+    match mode.to_lowercase().as_str() {
+        "auto" => Ok(PhraseMode::Auto),
+        "phrase" => Ok(PhraseMode::Phrase),
+        "tokens" => Ok(PhraseMode::Tokens),
+        "phrase_then_tokens" => Ok(PhraseMode::PhraseThenTokens),
+        "blend" => Ok(PhraseMode::Blend),
+        _ => Err(QueryError::new(
+            "INVALID_PHRASE_MODE",
+            format!(
+                "Unknown phrase_mode '{}', expected \"auto\", \"phrase\", \"tokens\", \"phrase_then_tokens\" or \"blend\"",
+                mode
+            ),
+        )),
+    }
 }
 
 /// Search endpoint handler
 async fn search_handler(
     req: web::Json<SearchRequest>,
     service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
 ) -> ActixResult<HttpResponse> {
     // Build query string from request
-    let query_str = match build_query_string(&req) {
+    let query_str = match build_query_string(&req, service.config()) {
         Ok(q) => q,
+        Err(e) => return Ok(error_response(&e, StatusCode::BAD_REQUEST, "Invalid request")),
+    };
+    // Ordering or grouping on a field reveals its values as much as querying it does
+    let mut fields = service.query_fields(&query_str).unwrap_or_default();
+    fields.extend(req.sort.iter().map(|key| key.field.trim().to_lowercase()));
+    fields.extend(req.dedup.iter().map(|name| name.trim().to_lowercase()));
+    let access = match authorize(&http_req, &policy, &fields) {
+        Ok(access) => access,
+        Err(response) => return Ok(response),
+    };
+
+    let phrase_mode = match parse_phrase_mode(req.phrase_mode.as_deref()) {
+        Ok(mode) => mode,
+        Err(e) => return Ok(error_response(&e.into(), StatusCode::BAD_REQUEST, "Invalid request")),
+    };
+
+    let indexed_since = match req.indexed_since.as_deref().map(parse_timestamp).transpose() {
+        Ok(since) => since,
         Err(e) => {
-            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-                "error": format!("Invalid request: {}", e)
-            })));
+            let e = anyhow::Error::from(QueryError::new("INVALID_WATERMARK", format!("Invalid indexed_since: {}", e)));
+            return Ok(error_response(&e, StatusCode::BAD_REQUEST, "Invalid request"));
         }
     };
 
+    let sort = match parse_sort(&req.sort) {
+        Ok(sort) => sort,
+        Err(e) => return Ok(error_response(&e.into(), StatusCode::BAD_REQUEST, "Invalid request")),
+    };
+
+    let recency_half_life = match req.recency_half_life_days {
+        None => None,
+        Some(days) => match Duration::try_from_secs_f64(days * 86_400.0) {
+            Ok(half_life) if days > 0.0 => Some(half_life),
+            _ => {
+                let e = anyhow::Error::from(QueryError::new(
+                    "INVALID_RECENCY",
+                    format!("recency_half_life_days must be a positive number of days, got {}", days),
+                ));
+                return Ok(error_response(&e, StatusCode::BAD_REQUEST, "Invalid request"));
+            }
+        },
+    };
+
+    let (limit, clamped) = service.config().resolve_limit(req.limit);
+    let mut warnings: Vec<String> = Vec::new();
+    if clamped {
+        warnings.push(format!("limit reduced to the maximum of {}", limit));
+    }
+
     // Execute search
-    match service.search(&query_str) {
+    let options = SearchOptions {
+        match_offsets: req.match_offsets,
+        snippets: req.snippets,
+        min_score: req.min_score,
+        phrase_mode,
+        limit: Some(limit),
+        debug_query: req.debug_query,
+        stats: req.stats,
+        field_match_counts: req.field_match_counts,
+        stored_bytes: req.stored_bytes,
+        omit_empty: req.omit_empty,
+        found_via: req.found_via,
+        source_row: req.source_rows,
+        results_hash: req.results_hash,
+        indexed_since,
+        sort,
+        dedup_key: req.dedup.clone(),
+        recency_half_life,
+    };
+
+    let Some(_permit) = service.try_begin_search() else {
+        return Ok(overloaded_response());
+    };
+    match service.search(&query_str, &options) {
         Ok(results) => {
-            let response = SearchResponse {
-                results: results.results,
-                total_matches: results.total_matches,
-                results_returned: results.results_returned,
-                query_parse_time_ms: results.query_parse_time_ms,
-                search_execution_time_ms: results.search_execution_time_ms,
-                document_retrieval_time_ms: results.document_retrieval_time_ms,
-                total_time_ms: results.total_time_ms,
-            };
-            Ok(HttpResponse::Ok().json(response))
+            log_slow_query(&http_req, service.config(), &query_str, &results);
+            if req.results_hash && results.results_hash.is_none() {
+                warnings.push(format!("results_hash left out: more than {} matches", RESULTS_HASH_MAX_MATCHES));
+            }
+            Ok(search_response(results, warnings, &access, &http_req))
+        }
+        Err(e) => {
+            if e.downcast_ref::<QueryError>().is_none() {
+                eprintln!("[{}] Search failed for query {:?}: {}", request_id(&http_req), query_str, e);
+            }
+            Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Search failed"))
+        }
+    }
+}
+
+/// Log a search that took longer than `slow_query_ms`, with the query and
+/// where the time went
+fn log_slow_query(http_req: &HttpRequest, config: &SearchConfig, query: &str, results: &SearchResults) {
+    if let Some(entry) = slow_query_entry(&request_id(http_req), config, query, results) {
+        eprintln!("{}", entry);
+    }
+}
+
+/// The slow-query log line for a search, None when it was fast enough
+fn slow_query_entry(request_id: &str, config: &SearchConfig, query: &str, results: &SearchResults) -> Option<String> {
+    if config.slow_query_ms.is_none_or(|threshold| results.total_time_ms <= threshold) {
+        return None;
+    }
+    Some(format!(
+        "[WARN] [{}] Slow query {:?}: {:.1}ms total (parse {:.1}ms, search {:.1}ms, retrieval {:.1}ms), {} matches, {} returned",
+        request_id,
+        query,
+        results.total_time_ms,
+        results.query_parse_time_ms,
+        results.search_execution_time_ms,
+        results.document_retrieval_time_ms,
+        results.total_matches,
+        results.results_returned,
+    ))
+}
+
+/// Build the search response, as protobuf when the client asks for it and
+/// JSON otherwise
+fn search_response(
+    mut results: SearchResults,
+    mut warnings: Vec<String>,
+    access: &FieldAccess,
+    http_req: &HttpRequest,
+) -> HttpResponse {
+    results.results.iter_mut().for_each(|record| access.redact(record));
+    if let Some(stats) = &mut results.stats {
+        access.redact_stats(stats);
+    }
+    for field in &results.dropped_clauses {
+        warnings.push(format!("ignored field '{}': nothing searchable in its value", field));
+    }
+    if let Some(failed) = results.failed_retrievals.filter(|&failed| failed > 0) {
+        warnings.push(format!("{} results could not be read and were left out", failed));
+    }
+    let warning = (!warnings.is_empty()).then(|| warnings.join("; "));
+    // Hidden fields don't count towards what the client could export
+    let stored_bytes = results.stored_bytes.map(|by_field| {
+        by_field.iter().filter(|(field, _)| access.allows(field)).map(|(_, bytes)| bytes).sum()
+    });
+    let response = SearchResponse {
+        results: results.results,
+        total_matches: results.total_matches,
+        results_returned: results.results_returned,
+        phrase_fallback: results.phrase_fallback,
+        executed_query: results.executed_query,
+        index_version: results.index_version,
+        results_hash: results.results_hash,
+        query_parse_time_ms: results.query_parse_time_ms,
+        search_execution_time_ms: results.search_execution_time_ms,
+        document_retrieval_time_ms: results.document_retrieval_time_ms,
+        total_time_ms: results.total_time_ms,
+        warning,
+        debug_query: results.debug_query,
+        stats: results.stats,
+        field_match_counts: results.field_match_counts,
+        duplicates_removed: results.duplicates_removed,
+        stored_bytes,
+        failed_retrievals: results.failed_retrievals,
+        zero_reason: results.zero_reason,
+    };
+    let wants_protobuf = http_req.headers().get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(accepts_protobuf);
+    if wants_protobuf {
+        return HttpResponse::Ok()
+            .content_type(PROTOBUF_CONTENT_TYPE)
+            .body(encode_search_response(&response));
+    }
+    HttpResponse::Ok().json(response)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateRequest {
+    #[serde(default)]
+    pub params: Vec<String>, // Values for the template's {0}, {1}... placeholders
+    pub phrase_mode: Option<String>,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub debug_query: bool,
+}
+
+/// Template endpoint handler: run a configured query template by name.
+/// Parameters only ever fill clause values, so they cannot add clauses or
+/// operators to the template.
+async fn template_handler(
+    name: web::Path<String>,
+    req: web::Json<TemplateRequest>,
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let Some(template) = service.config().query_templates.get(name.as_str()) else {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": format!("No query template named '{}'", name),
+            "code": "UNKNOWN_TEMPLATE",
+        })));
+    };
+    let fields = service.query_fields(template).unwrap_or_default();
+    let access = match authorize(&http_req, &policy, &fields) {
+        Ok(access) => access,
+        Err(response) => return Ok(response),
+    };
+
+    let phrase_mode = match parse_phrase_mode(req.phrase_mode.as_deref()) {
+        Ok(mode) => mode,
+        Err(e) => return Ok(error_response(&e.into(), StatusCode::BAD_REQUEST, "Invalid request")),
+    };
+
+    let (limit, clamped) = service.config().resolve_limit(req.limit);
+    let mut warnings: Vec<String> = Vec::new();
+    if clamped {
+        warnings.push(format!("limit reduced to the maximum of {}", limit));
+    }
+
+    let options = SearchOptions {
+        phrase_mode,
+        limit: Some(limit),
+        debug_query: req.debug_query,
+        ..SearchOptions::default()
+    };
+
+    let Some(_permit) = service.try_begin_search() else {
+        return Ok(overloaded_response());
+    };
+    match service.search_template(&name, &req.params, &options) {
+        Ok(results) => {
+            let query = format!("template {} {:?}", name, req.params);
+            log_slow_query(&http_req, service.config(), &query, &results);
+            Ok(search_response(results, warnings, &access, &http_req))
         }
         Err(e) => {
-            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": format!("Search failed: {}", e)
+            if e.downcast_ref::<QueryError>().is_none() {
+                eprintln!("[{}] Template {} failed: {}", request_id(&http_req), name, e);
+            }
+            Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Search failed"))
+        }
+    }
+}
+
+/// Parse endpoint handler: validate a search request without executing it.
+/// Takes the same body as /search and returns the parsed clauses, the query
+/// that would run and its estimated cost, or the same 400 error /search would give.
+async fn parse_handler(
+    req: web::Json<SearchRequest>,
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let query_str = match build_query_string(&req, service.config()) {
+        Ok(q) => q,
+        Err(e) => return Ok(error_response(&e, StatusCode::BAD_REQUEST, "Invalid request")),
+    };
+    let fields = service.query_fields(&query_str).unwrap_or_default();
+    if let Err(response) = authorize(&http_req, &policy, &fields) {
+        return Ok(response);
+    }
+
+    let phrase_mode = match parse_phrase_mode(req.phrase_mode.as_deref()) {
+        Ok(mode) => mode,
+        Err(e) => return Ok(error_response(&e.into(), StatusCode::BAD_REQUEST, "Invalid request")),
+    };
+
+    match service.validate(&query_str, phrase_mode) {
+        Ok((parsed, query, cost)) => {
+            let clauses: Vec<serde_json::Value> = parsed.clauses.iter()
+                .map(|clause| serde_json::json!({
+                    "field": clause.field,
+                    "value": clause.value,
+                    "mode": format!("{:?}", clause.mode).to_lowercase(),
+                }))
+                .collect();
+            let ops: Vec<String> = parsed.ops.iter()
+                .map(|op| format!("{:?}", op).to_uppercase())
+                .collect();
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "valid": true,
+                "query_string": query_str,
+                "clauses": clauses,
+                "ops": ops,
+                "query": query,
+                "cost": cost,
+            })))
+        }
+        Err(e) => Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Query validation failed")),
+    }
+}
+
+/// Normalize endpoint handler: how each value of a `{"field": "value"}`
+/// body would be searched, e.g. `{"mobile": "+91 98111 11111"}` gives
+/// `{"normalized": {"mobile": "9811111111"}}`. Values with nothing
+/// searchable left are listed under `empty` instead.
+async fn normalize_handler(
+    req: web::Json<BTreeMap<String, String>>,
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let fields: Vec<String> = req.keys().cloned().collect();
+    if let Err(response) = authorize(&http_req, &policy, &fields) {
+        return Ok(response);
+    }
+
+    let mut normalized = BTreeMap::new();
+    let mut empty = Vec::new();
+    for (field, value) in req.iter() {
+        let value = match sanitize_field_value(field, value, service.config()) {
+            Ok(value) => value,
+            Err(e) => return Ok(error_response(&e.into(), StatusCode::BAD_REQUEST, "Invalid request")),
+        };
+        match service.normalize(field, &value) {
+            Ok(Some(value)) => {
+                normalized.insert(field, value);
+            }
+            Ok(None) => empty.push(field),
+            Err(e) => return Ok(error_response(&e, StatusCode::BAD_REQUEST, "Invalid request")),
+        }
+    }
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "normalized": normalized,
+        "empty": empty,
+    })))
+}
+
+/// Map an error to the JSON error envelope.
+/// `QueryError`s are client mistakes and always become a 400 carrying their
+/// code; anything else is reported with the given fallback status.
+fn error_response(e: &anyhow::Error, fallback: StatusCode, context: &str) -> HttpResponse {
+    match e.downcast_ref::<QueryError>() {
+        Some(query_error) => HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Invalid query: {}", query_error.message),
+            "code": query_error.code,
+        })),
+        None => HttpResponse::build(fallback).json(serde_json::json!({
+            "error": format!("{}: {}", context, e)
+        })),
+    }
+}
+
+/// Seconds a client turned away by MAX_IN_FLIGHT_SEARCHES is asked to wait
+const OVERLOADED_RETRY_AFTER_SECS: u64 = 1;
+
+/// 503 for a search arriving while MAX_IN_FLIGHT_SEARCHES are running
+fn overloaded_response() -> HttpResponse {
+    HttpResponse::ServiceUnavailable()
+        .insert_header(("Retry-After", OVERLOADED_RETRY_AFTER_SECS.to_string()))
+        .json(serde_json::json!({
+            "error": "Too many searches in progress, retry shortly",
+            "code": "OVERLOADED",
+        }))
+}
+
+/// Field access of the request's API key (see `access.rs`), or the error
+/// to send back: 401 when keys are required and it has no known key, 403
+/// when it queries, sorts or groups on a field hidden from its key
+fn authorize(http_req: &HttpRequest, policy: &AccessPolicy, fields: &[String]) -> Result<FieldAccess, HttpResponse> {
+    let key = http_req.headers().get(API_KEY_HEADER).and_then(|v| v.to_str().ok());
+    let access = policy.resolve(key).ok_or_else(|| HttpResponse::Unauthorized().json(serde_json::json!({
+        "error": format!("Missing or unknown API key in {}", API_KEY_HEADER),
+        "code": "UNAUTHORIZED",
+    })))?;
+    if let Some(field) = access.forbidden(fields.iter().map(String::as_str)) {
+        return Err(HttpResponse::Forbidden().json(serde_json::json!({
+            "error": format!("Field '{}' is not accessible with this API key", field),
+            "code": "FIELD_FORBIDDEN",
+            "field": field,
+        })));
+    }
+    Ok(access)
+}
+
+/// Maximum neighbors per side for the context endpoint
+const MAX_CONTEXT_NEIGHBORS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct ContextRequest {
+    pub master_id: String,
+    #[serde(default)]
+    pub neighbors: usize, // Adjacent master_ids per side, capped at MAX_CONTEXT_NEIGHBORS
+}
+
+/// Context endpoint handler: a master_id's records, fan-out siblings and neighbors
+async fn context_handler(
+    req: web::Query<ContextRequest>,
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let access = match authorize(&http_req, &policy, std::slice::from_ref(&service.config().key_field)) {
+        Ok(access) => access,
+        Err(response) => return Ok(response),
+    };
+    if req.master_id.trim().is_empty() {
+        let e = anyhow::Error::from(QueryError::empty_value("master_id"));
+        return Ok(error_response(&e, StatusCode::BAD_REQUEST, "Invalid request"));
+    }
+
+    let neighbors = req.neighbors.min(MAX_CONTEXT_NEIGHBORS);
+    match service.search_context(&req.master_id, neighbors) {
+        Ok(mut context) => {
+            for record in context.matches.iter_mut().chain(&mut context.siblings) {
+                access.redact(record);
+            }
+            // Neighbors wrap their records: {master_id, position, records}
+            for neighbor in &mut context.neighbors {
+                if let Some(serde_json::Value::Array(records)) = neighbor.get_mut("records") {
+                    records.iter_mut().for_each(|record| access.redact(record));
+                }
+            }
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "master_id": req.master_id.trim(),
+                "matches": context.matches,
+                "siblings": context.siblings,
+                "neighbors": context.neighbors,
             })))
         }
+        Err(e) => Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Context search failed")),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareRequest {
+    pub a: String, // identity keys (master_id by default) of the two records
+    pub b: String,
+}
+
+/// Fields a record comparison reads
+const COMPARE_FIELDS: &[&str] = &["name", "fname", "mobile", "alt", "address", "email"];
+
+/// Compare endpoint handler: how likely two identity keys are the same
+/// person, with the score of each signal behind it
+async fn compare_handler(
+    req: web::Query<CompareRequest>,
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let key_field = service.config().key_field.clone();
+    let fields: Vec<String> = COMPARE_FIELDS.iter().map(|f| f.to_string()).chain([key_field.clone()]).collect();
+    if let Err(response) = authorize(&http_req, &policy, &fields) {
+        return Ok(response);
+    }
+    for (param, key) in [("a", &req.a), ("b", &req.b)] {
+        if key.trim().is_empty() {
+            let e = anyhow::Error::from(QueryError::empty_value(param));
+            return Ok(error_response(&e, StatusCode::BAD_REQUEST, "Invalid request"));
+        }
+    }
+
+    let mut profiles = Vec::with_capacity(2);
+    for key in [&req.a, &req.b] {
+        match service.record_profile(key) {
+            Ok(Some(profile)) => profiles.push(profile),
+            Ok(None) => return Ok(HttpResponse::NotFound().json(serde_json::json!({
+                "error": format!("No records with {} '{}'", key_field, key.trim()),
+                "code": "UNKNOWN_KEY",
+            }))),
+            Err(e) => return Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Comparison failed")),
+        }
+    }
+    let comparison = compare(&profiles[0], &profiles[1]);
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "a": req.a.trim(),
+        "b": req.b.trim(),
+        "score": comparison.score,
+        "linked": comparison.linked,
+        "signals": comparison.signals,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphRequest {
+    pub field: String, // mobile, email or the identity key field
+    pub value: String,
+}
+
+/// Graph endpoint handler: the identity graph behind a fan-out, as nodes,
+/// weighted edges and an adjacency list, for visualization
+async fn graph_handler(
+    req: web::Query<GraphRequest>,
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let field = req.field.trim().to_lowercase();
+    let mut fields = vec![field.clone(), service.config().key_field.clone()];
+    fields.extend(GRAPH_LINK_FIELDS.iter().map(|name| name.to_string()));
+    if let Err(response) = authorize(&http_req, &policy, &fields) {
+        return Ok(response);
+    }
+
+    match service.fanout_graph(&field, &req.value) {
+        Ok(graph) => {
+            let nodes: Vec<_> = graph.nodes.iter()
+                .map(|(id, (kind, value))| serde_json::json!({"id": id, "kind": kind, "value": value}))
+                .collect();
+            let edges: Vec<_> = graph.edges.iter()
+                .map(|((source, target, field), rows)| serde_json::json!({"source": source, "target": target, "field": field, "rows": rows}))
+                .collect();
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "seed": {"field": field, "value": req.value.trim()},
+                "nodes": nodes,
+                "edges": edges,
+                "adjacency": graph.adjacency(),
+            })))
+        }
+        Err(e) => Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Graph lookup failed")),
+    }
+}
+
+/// Maximum master_ids per /records request
+const MAX_RECORDS_BATCH: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecordsRequest {
+    pub master_ids: Vec<String>,
+}
+
+/// Bulk lookup endpoint handler: records for a batch of master_ids
+async fn records_handler(
+    req: web::Json<RecordsRequest>,
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let access = match authorize(&http_req, &policy, std::slice::from_ref(&service.config().key_field)) {
+        Ok(access) => access,
+        Err(response) => return Ok(response),
+    };
+    if req.master_ids.len() > MAX_RECORDS_BATCH {
+        let e = anyhow::Error::from(QueryError::new(
+            "BATCH_TOO_LARGE",
+            format!("{} master_ids requested, the limit is {}", req.master_ids.len(), MAX_RECORDS_BATCH),
+        ));
+        return Ok(error_response(&e, StatusCode::BAD_REQUEST, "Invalid request"));
+    }
+
+    match service.lookup_records(&req.master_ids) {
+        Ok(mut lookup) => {
+            lookup.records.values_mut().flatten().for_each(|record| access.redact(record));
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "found": lookup.records.len(),
+                "records": lookup.records,
+                "not_found": lookup.not_found,
+            })))
+        }
+        Err(e) => Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Record lookup failed")),
+    }
+}
+
+/// Default and maximum number of terms returned by /terms
+const DEFAULT_TERMS_LIMIT: usize = 100;
+const MAX_TERMS_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct TermsRequest {
+    pub field: String,
+    pub limit: Option<usize>, // Capped at MAX_TERMS_LIMIT
+}
+
+/// Terms endpoint handler: most frequent distinct values of an indexed field
+async fn terms_handler(
+    req: web::Query<TermsRequest>,
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    let field = req.field.trim().to_lowercase();
+    if let Err(response) = authorize(&http_req, &policy, std::slice::from_ref(&field)) {
+        return Ok(response);
+    }
+    let limit = req.limit.unwrap_or(DEFAULT_TERMS_LIMIT).min(MAX_TERMS_LIMIT);
+    match service.field_terms(&field, limit) {
+        Ok(terms) => {
+            let terms: Vec<_> = terms.into_iter()
+                .map(|(term, doc_freq)| serde_json::json!({"term": term, "doc_freq": doc_freq}))
+                .collect();
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "field": field,
+                "terms_returned": terms.len(),
+                "terms": terms,
+            })))
+        }
+        Err(e) => Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Terms lookup failed")),
+    }
+}
+
+/// Turn every JSON body failure into the JSON error envelope instead of
+/// actix's plain-text responses:
+/// - unknown keys and wrong types: 400 INVALID_REQUEST / UNKNOWN_FIELD,
+///   naming the offending key when serde reports one
+/// - malformed or truncated JSON: 400 BAD_JSON
+/// - wrong content type: 415 UNSUPPORTED_MEDIA_TYPE
+/// - body over the configured limit: 413 PAYLOAD_TOO_LARGE
+fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let (status, code, message) = match &err {
+        JsonPayloadError::Deserialize(serde_err) if serde_err.is_data() => {
+            let message = serde_err.to_string();
+            let mut body = serde_json::json!({
+                "error": format!("Invalid request: {}", message),
+                "code": "INVALID_REQUEST",
+            });
+            // serde: "unknown field `Name`, expected one of `name`, ..."
+            if let Some(rest) = message.strip_prefix("unknown field `")
+                && let Some((field, expected)) = rest.split_once('`')
+            {
+                body["code"] = serde_json::json!("UNKNOWN_FIELD");
+                body["field"] = serde_json::json!(field);
+                // Capitalized aliases are accepted but not advertised
+                let keys: Vec<&str> = expected.split('`').skip(1).step_by(2)
+                    .filter(|key| !key.contains(char::is_uppercase))
+                    .collect();
+                let hint = match suggest_field(field, &keys) {
+                    Some(suggestion) => {
+                        body["suggestion"] = serde_json::json!(suggestion);
+                        format!("did you mean `{}`?", suggestion)
+                    }
+                    None => format!("expected one of `{}`", keys.join("`, `")),
+                };
+                body["error"] = serde_json::json!(format!("Invalid request: unknown field `{}`, {}", field, hint));
+            }
+            return InternalError::from_response(err, HttpResponse::BadRequest().json(body)).into();
+        }
+        JsonPayloadError::Deserialize(serde_err) => {
+            (StatusCode::BAD_REQUEST, "BAD_JSON", format!("Malformed JSON: {}", serde_err))
+        }
+        JsonPayloadError::ContentType => (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "UNSUPPORTED_MEDIA_TYPE",
+            "Expected Content-Type: application/json".to_string(),
+        ),
+        JsonPayloadError::Overflow { limit } | JsonPayloadError::OverflowKnownLength { limit, .. } => (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "PAYLOAD_TOO_LARGE",
+            format!("Request body exceeds the limit of {} bytes", limit),
+        ),
+        other => (StatusCode::BAD_REQUEST, "BAD_JSON", format!("Unreadable request body: {}", other)),
+    };
+
+    let body = serde_json::json!({ "error": message, "code": code });
+    InternalError::from_response(err, HttpResponse::build(status).json(body)).into()
+}
+
+/// The expected key that `field` differs from only in case, `_`, `-` or
+/// spaces, e.g. `min_score` for `MinScore`
+fn suggest_field<'a>(field: &str, keys: &[&'a str]) -> Option<&'a str> {
+    let fold = |key: &str| -> String {
+        key.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+    };
+    let folded = fold(field);
+    keys.iter().copied().find(|key| fold(key) == folded)
+}
+
+/// Reload endpoint handler: switch to the latest fully committed generation.
+/// With API keys configured, only a key seeing every field may reload.
+async fn reload_handler(
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    // Swapping what every client sees needs access to every field, as writing does
+    if let Err(response) = authorize(&http_req, &policy, &service.field_names()) {
+        return Ok(response);
+    }
+    match service.reload() {
+        Ok(ReloadStatus::Reloaded(opstamp)) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "status": "reloaded",
+            "opstamp": opstamp,
+        }))),
+        Ok(ReloadStatus::Unchanged(opstamp)) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "status": "unchanged",
+            "opstamp": opstamp,
+        }))),
+        Ok(ReloadStatus::NotReady { committed, ready }) => Ok(HttpResponse::Conflict().json(serde_json::json!({
+            "error": "Latest commit is not marked ready; an index build may be in progress",
+            "code": "INDEX_NOT_READY",
+            "committed_opstamp": committed,
+            "ready_opstamp": ready,
+        }))),
+        Err(e) => Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Reload failed")),
+    }
+}
+
+/// Reload `service` every `interval`, logging only when it swaps in a new
+/// generation or first finds one not marked ready. Reloads with nothing new
+/// to pick up just compare opstamps. The thread ends with the service.
+fn spawn_periodic_reload(service: Weak<SearchService>, interval: Duration) {
+    std::thread::spawn(move || {
+        let mut reported_not_ready = None;
+        loop {
+            std::thread::sleep(interval);
+            let Some(service) = service.upgrade() else {
+                break;
+            };
+            let previous = service.index_version();
+            match service.reload() {
+                Ok(ReloadStatus::Reloaded(opstamp)) => {
+                    println!("Periodic reload: opstamp {} -> {}", previous, opstamp);
+                }
+                Ok(ReloadStatus::Unchanged(_)) => {}
+                Ok(ReloadStatus::NotReady { committed, ready }) => {
+                    if reported_not_ready != Some(committed) {
+                        eprintln!("Periodic reload: commit {} is not marked ready (ready: {:?})", committed, ready);
+                        reported_not_ready = Some(committed);
+                    }
+                }
+                Err(e) => eprintln!("Periodic reload failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Records handed to the index writer at a time while a bulk request streams in
+const BULK_BATCH_SIZE: usize = 1_000;
+/// Longest accepted NDJSON line of a bulk request
+const MAX_BULK_LINE_BYTES: usize = 1_048_576;
+/// Failed records described in a bulk response; the rest are only counted
+const MAX_BULK_ERRORS: usize = 20;
+
+/// Bulk indexing endpoint handler: NDJSON records (one JSON object per line,
+/// keyed by CSV column name) are indexed as the body streams in, committed
+/// every `bulk_commit_every` records and at the end, then searchable.
+/// Records that can't be indexed are counted and skipped.
+async fn bulk_index_handler(
+    mut payload: web::Payload,
+    service: web::Data<Arc<SearchService>>,
+    policy: web::Data<AccessPolicy>,
+    http_req: HttpRequest,
+) -> ActixResult<HttpResponse> {
+    // Writing needs access to every field
+    if let Err(response) = authorize(&http_req, &policy, &service.field_names()) {
+        return Ok(response);
+    }
+    let builder = match service.bulk_builder() {
+        Ok(builder) => builder,
+        Err(e) => return Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Bulk indexing failed")),
+    };
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let indexed_at = tantivy::DateTime::from_timestamp_secs(now as i64);
+    let commit_every = service.config().bulk_commit_every;
+
+    let mut pending: Vec<u8> = Vec::new();
+    let mut batch = Vec::with_capacity(BULK_BATCH_SIZE);
+    let (mut line_no, mut added, mut failed, mut uncommitted) = (0usize, 0usize, 0usize, 0usize);
+    let mut errors = Vec::new();
+    let mut stream_error = None;
+    let mut done = false;
+    while !done {
+        match payload.next().await {
+            Some(Ok(chunk)) => pending.extend_from_slice(&chunk),
+            Some(Err(e)) => {
+                stream_error = Some(format!("Request body failed after line {}: {}", line_no, e));
+                break;
+            }
+            None => done = true,
+        }
+        // Complete lines only, until the body ends
+        let end = match pending.iter().rposition(|&b| b == b'\n') {
+            _ if done => pending.len(),
+            Some(pos) => pos + 1,
+            None if pending.len() > MAX_BULK_LINE_BYTES => {
+                stream_error = Some(format!("Line {} is longer than {} bytes", line_no + 1, MAX_BULK_LINE_BYTES));
+                break;
+            }
+            None => continue,
+        };
+        let chunk: Vec<u8> = pending.drain(..end).collect();
+        let chunk = chunk.strip_suffix(b"\n").unwrap_or(&chunk);
+        for line in chunk.split(|&b| b == b'\n') {
+            line_no += 1;
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            let doc = serde_json::from_slice(line)
+                .map_err(anyhow::Error::from)
+                .and_then(|record| builder.build_json(&record, indexed_at));
+            match doc {
+                Ok(doc) => batch.push(doc),
+                Err(e) => {
+                    failed += 1;
+                    if errors.len() < MAX_BULK_ERRORS {
+                        errors.push(serde_json::json!({ "line": line_no, "error": e.to_string() }));
+                    }
+                }
+            }
+        }
+
+        if batch.len() >= BULK_BATCH_SIZE || (done && !batch.is_empty()) {
+            let count = batch.len();
+            if let Err(e) = service.bulk_add(std::mem::take(&mut batch)) {
+                return Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Bulk indexing failed"));
+            }
+            added += count;
+            uncommitted += count;
+        }
+        if uncommitted >= commit_every {
+            if let Err(e) = service.bulk_commit() {
+                return Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Bulk commit failed"));
+            }
+            uncommitted = 0;
+        }
+    }
+
+    // Whatever was added is committed, even when the body broke off
+    if let Err(e) = service.bulk_commit() {
+        return Ok(error_response(&e, StatusCode::INTERNAL_SERVER_ERROR, "Bulk commit failed"));
     }
+    let mut body = serde_json::json!({
+        "added": added,
+        "failed": failed,
+        "errors": errors,
+        "index_version": service.index_version(),
+    });
+    if let Some(error) = stream_error {
+        body["error"] = serde_json::json!(error);
+        body["code"] = serde_json::json!("INVALID_BODY");
+        return Ok(HttpResponse::BadRequest().json(body));
+    }
+    Ok(HttpResponse::Ok().json(body))
 }
 
 /// Health check endpoint
@@ -122,10 +1166,80 @@ async fn health_handler() -> ActixResult<HttpResponse> {
     })))
 }
 
-/// Start the HTTP API server
-pub async fn start_server(index_dir: String, host: String, port: u16) -> Result<(), std::io::Error> {
+/// Header carrying the request id, accepted from clients and echoed back
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Id of the request being handled, available from request extensions
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// Id of the request being handled, for log lines
+fn request_id(req: &HttpRequest) -> String {
+    req.extensions().get::<RequestId>().map(|id| id.0.clone()).unwrap_or_default()
+}
+
+/// Use the client's request id when it's a sane header value (visible ASCII,
+/// at most 128 chars), otherwise generate one unique to this process
+fn request_id_for(req: &HttpRequest) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    if let Some(id) = req.headers().get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok())
+        && !id.is_empty()
+        && id.len() <= 128
+        && id.chars().all(|c| c.is_ascii_graphic())
+    {
+        return id.to_string();
+    }
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    format!("{:x}-{:x}", nanos, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Tag every request with an id: stored for handlers, logged with the
+/// outcome and returned in the response header
+async fn tag_request(req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = request_id_for(req.request());
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let start = Instant::now();
+    let mut res = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+    println!(
+        "[{}] {} {} -> {} in {:.1}ms",
+        request_id,
+        method,
+        path,
+        res.status().as_u16(),
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    Ok(res)
+}
+
+/// Certificate chain and private key for serving HTTPS, both PEM
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Start the HTTP API server, over HTTPS when `tls` is given
+pub async fn start_server(
+    index_dir: String,
+    host: String,
+    port: u16,
+    config: SearchConfig,
+    warmup: Option<String>,
+    tls: Option<TlsFiles>,
+) -> Result<(), std::io::Error> {
+    // Misconfigured TLS fails before the index is opened
+    let tls_config = tls.as_ref().map(load_tls_config).transpose()?;
+
     // Initialize search service
-    let service = match SearchService::new(&index_dir) {
+    let service = match SearchService::new(&index_dir, config) {
         Ok(s) => Arc::new(s),
         Err(e) => {
             eprintln!("Failed to initialize search service: {}", e);
@@ -133,23 +1247,941 @@ pub async fn start_server(index_dir: String, host: String, port: u16) -> Result<
         }
     };
 
-    println!("Starting HTTP server on {}:{}", host, port);
+    // Prime caches with the expected query patterns before taking traffic
+    if let Some(path) = &warmup
+        && let Err(e) = service.warm_up(path)
+    {
+        eprintln!("Failed to warm up: {}", e);
+        std::process::exit(1);
+    }
+
+    let policy = match AccessPolicy::from_env(&service.field_names()) {
+        Ok(policy) => web::Data::new(policy),
+        Err(e) => {
+            eprintln!("Failed to load API keys: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if policy.is_enabled() {
+        println!("API keys required in {}", API_KEY_HEADER);
+    }
+
+    if let Some(interval) = service.config().reload_interval {
+        println!("Reloading new index generations every {}s", interval.as_secs());
+        spawn_periodic_reload(Arc::downgrade(&service), interval);
+    }
+
+    // A host that looks like a path is a Unix domain socket
+    let socket_path = host.contains('/').then(|| PathBuf::from(&host));
+    match &socket_path {
+        Some(_) if tls_config.is_some() => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "TLS is not supported on a Unix domain socket",
+            ));
+        }
+        Some(path) => {
+            prepare_socket_path(path)?;
+            println!("Starting HTTP server on unix:{}", path.display());
+        }
+        None if tls_config.is_some() => println!("Starting HTTPS server on {}:{}", host, port),
+        None => println!("Starting HTTP server on {}:{}", host, port),
+    }
     println!("Index directory: {}", index_dir);
+    let (default_limit, _) = service.config().resolve_limit(None);
+    println!("Result limit: {} by default, at most {}", default_limit, service.config().max_limit);
 
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
             .allow_any_method()
             .allow_any_header()
+            .expose_headers([REQUEST_ID_HEADER])
             .max_age(3600);
 
         App::new()
+            .wrap(from_fn(tag_request))
             .wrap(cors)
             .app_data(web::Data::new(service.clone()))
-            .route("/search", web::post().to(search_handler))
-            .route("/health", web::get().to(health_handler))
-    })
-    .bind(format!("{}:{}", host, port))?
-    .run()
-    .await
+            .app_data(policy.clone())
+            .configure(routes)
+    });
+
+    let Some(path) = socket_path else {
+        let addr = format!("{}:{}", host, port);
+        return match tls_config {
+            Some(tls_config) => server.bind_rustls_0_23(addr, tls_config)?.run().await,
+            None => server.bind(addr)?.run().await,
+        };
+    };
+    let result = match server.bind_uds(&path) {
+        Ok(server) => server.run().await,
+        Err(e) => Err(e),
+    };
+    // Normally already gone after a graceful shutdown
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            eprintln!("Failed to remove socket {}: {}", path.display(), e);
+        }
+        _ => {}
+    }
+    result
+}
+
+/// JSON body handling and the endpoints, served with a
+/// `web::Data<Arc<SearchService>>` and a `web::Data<AccessPolicy>`
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg
+        // Room for a full /records batch; the default 32 KiB is too small
+        .app_data(web::JsonConfig::default().limit(256 * 1024).error_handler(json_error_handler))
+        .route("/search", web::post().to(search_handler))
+        .route("/parse", web::post().to(parse_handler))
+        .route("/normalize", web::post().to(normalize_handler))
+        .route("/template/{name}", web::post().to(template_handler))
+        .route("/context", web::get().to(context_handler))
+        .route("/compare", web::get().to(compare_handler))
+        .route("/records", web::post().to(records_handler))
+        .route("/graph", web::get().to(graph_handler))
+        .route("/terms", web::get().to(terms_handler))
+        .route("/reload", web::post().to(reload_handler))
+        .route("/index/bulk", web::post().to(bulk_index_handler))
+        .route("/health", web::get().to(health_handler));
+}
+
+/// Server config from the PEM files in `tls`, with errors naming the file
+/// that is missing or can't be parsed
+fn load_tls_config(tls: &TlsFiles) -> Result<rustls::ServerConfig, std::io::Error> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    let invalid = |what: &str, path: &Path, detail: String| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Invalid TLS {} {}: {}", what, path.display(), detail))
+    };
+    for (what, path) in [("certificate", &tls.cert), ("key", &tls.key)] {
+        if !path.is_file() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("TLS {} file not found: {}", what, path.display()),
+            ));
+        }
+    }
+
+    let certs = CertificateDer::pem_file_iter(&tls.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid("certificate", &tls.cert, e.to_string()))?;
+    if certs.is_empty() {
+        return Err(invalid("certificate", &tls.cert, "no certificates found".to_string()));
+    }
+    let key = PrivateKeyDer::from_pem_file(&tls.key)
+        .map_err(|e| invalid("key", &tls.key, e.to_string()))?;
+    rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| invalid("certificate/key pair", &tls.cert, e.to_string()))
+}
+
+/// Make sure a socket can be bound at `path`: its directory must exist and a
+/// leftover socket from an unclean shutdown is removed. Refuses to replace
+/// anything that isn't a socket.
+fn prepare_socket_path(path: &Path) -> Result<(), std::io::Error> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty() && !p.is_dir()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Socket directory does not exist: {}", parent.display()),
+        ));
+    }
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path),
+        Ok(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("Refusing to replace non-socket file: {}", path.display()),
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::indexer::{append_index, IndexOptions};
+    use crate::test_support::{index_batches, write_csv};
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use tempfile::TempDir;
+
+    fn request(body: serde_json::Value) -> SearchRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    fn query_error(body: serde_json::Value) -> QueryError {
+        build_query_string(&request(body), &SearchConfig::default())
+            .unwrap_err()
+            .downcast::<QueryError>()
+            .unwrap()
+    }
+
+    #[test]
+    fn whitespace_only_request_fields_are_empty_value_errors() {
+        assert_eq!(query_error(serde_json::json!({"mobile": "   "})).code, "EMPTY_VALUE");
+        assert_eq!(query_error(serde_json::json!({"name": "ravi", "address": "\t "})).code, "EMPTY_VALUE");
+        // An empty string means the field isn't searched on
+        let query = build_query_string(&request(serde_json::json!({"name": "ravi", "mobile": ""})), &SearchConfig::default()).unwrap();
+        assert_eq!(query, "name:ravi");
+    }
+
+    #[test]
+    fn filter_is_and_by_default_or_when_asked_and_rejected_otherwise() {
+        let query = |filter: serde_json::Value| {
+            let mut body = serde_json::json!({"name": "ravi", "address": "delhi"});
+            if !filter.is_null() {
+                body["filter"] = filter;
+            }
+            build_query_string(&request(body), &SearchConfig::default())
+        };
+        assert_eq!(query(serde_json::Value::Null).unwrap(), "name:ravi AND address:delhi");
+        assert_eq!(query("and".into()).unwrap(), "name:ravi AND address:delhi");
+        assert_eq!(query("or".into()).unwrap(), "name:ravi OR address:delhi");
+        assert_eq!(query(" Or ".into()).unwrap(), "name:ravi OR address:delhi");
+        for unknown in ["XOR", "", "&&"] {
+            let err = query(unknown.into()).unwrap_err().downcast::<QueryError>().unwrap();
+            assert_eq!(err.code, "INVALID_FILTER", "{unknown}");
+        }
+    }
+
+    #[test]
+    fn over_length_fields_are_rejected_and_control_characters_stripped() {
+        let config = SearchConfig {
+            max_field_length: 10,
+            field_length_limits: HashMap::from([("address".to_string(), 20)]),
+            ..SearchConfig::default()
+        };
+        let build = |body: serde_json::Value| build_query_string(&request(body), &config);
+
+        let err = build(serde_json::json!({"name": "a".repeat(11)})).unwrap_err().downcast::<QueryError>().unwrap();
+        assert_eq!(err.code, "FIELD_TOO_LONG");
+        assert!(err.message.contains("11 characters long, the limit is 10"), "{}", err.message);
+        // Limits count characters, not bytes, and can be raised per field
+        assert!(build(serde_json::json!({"name": "é".repeat(10)})).is_ok());
+        assert!(build(serde_json::json!({"address": "a".repeat(20)})).is_ok());
+        assert!(build(serde_json::json!({"address": "a".repeat(21)})).is_err());
+
+        assert_eq!(build(serde_json::json!({"address": "mg\u{0}\troad\n"})).unwrap(), "address:mg  road");
+    }
+
+    /// Service over an index of the given CSV rows
+    fn service(dir: &TempDir, rows: &[&str], config: SearchConfig) -> Arc<SearchService> {
+        Arc::new(SearchService::new(&index_batches(dir, &[rows]), config).unwrap())
+    }
+
+    /// Status and JSON body (null when not JSON) of one request to the API
+    async fn call(service: &Arc<SearchService>, policy: AccessPolicy, req: TestRequest) -> (StatusCode, serde_json::Value) {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(service.clone()))
+                .app_data(web::Data::new(policy))
+                .configure(routes),
+        ).await;
+        let response = call_service(&app, req.to_request()).await;
+        let status = response.status();
+        let body = read_body(response).await;
+        (status, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[actix_web::test]
+    async fn unknown_request_fields_are_named_in_the_error() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let search = |body: serde_json::Value| TestRequest::post().uri("/search").set_json(body);
+
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"nmae": "ravi"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "UNKNOWN_FIELD");
+        assert_eq!(body["field"], "nmae");
+        let error = body["error"].as_str().unwrap();
+        assert!(error.starts_with("Invalid request: unknown field `nmae`, expected one of `name`, `fname`"), "{error}");
+
+        // Wrong types are reported too, not silently ignored
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": 5}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "INVALID_REQUEST");
+
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "ravi", "filter": "xor"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "INVALID_FILTER");
+
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "ravi"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total_matches"], 1);
+    }
+
+    #[actix_web::test]
+    async fn terms_lists_the_most_frequent_values_with_their_doc_freqs() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m2,9800000002,amit,amit shah,,,delhi",
+            "m3,9800000003,ravi,ravi verma,,,pune",
+            "m4,9800000004,neha,neha gupta,,,mumbai",
+            "m5,9800000005,ravi,ravi gupta,,,pune",
+            "m6,9800000006,zoe,zoe kumar,,,delhi",
+        ], SearchConfig::default());
+        let terms = |query: &str| TestRequest::get().uri(&format!("/terms?{}", query));
+
+        let (status, body) = call(&service, AccessPolicy::default(), terms("field=address&limit=2")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["terms_returned"], 2);
+        assert_eq!(body["terms"], serde_json::json!([
+            {"term": "delhi", "doc_freq": 3},
+            {"term": "pune", "doc_freq": 2},
+        ]));
+        // Ties are listed alphabetically
+        let (_, body) = call(&service, AccessPolicy::default(), terms("field=fname")).await;
+        let listed: Vec<(&str, u64)> = body["terms"].as_array().unwrap().iter()
+            .map(|t| (t["term"].as_str().unwrap(), t["doc_freq"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(listed, [("ravi", 3), ("amit", 1), ("neha", 1), ("zoe", 1)]);
+
+        let (status, body) = call(&service, AccessPolicy::default(), terms("field=pincode")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "UNKNOWN_FIELD");
+        let (status, body) = call(&service, AccessPolicy::default(), terms("field=indexed_at")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "FIELD_NOT_SEARCHABLE");
+    }
+
+    #[actix_web::test]
+    async fn limit_has_a_default_and_is_clamped_to_the_configured_cap() {
+        let dir = TempDir::new().unwrap();
+        let rows: Vec<String> = (0..12).map(|i| format!("m{i},98000000{i:02},ravi,ravi kumar,,,delhi")).collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let service = service(&dir, &rows, SearchConfig { default_limit: 3, max_limit: 5, ..SearchConfig::default() });
+        let search = |body: serde_json::Value| TestRequest::post().uri("/search").set_json(body);
+
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"address": "delhi"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["total_matches"].as_u64(), body["results_returned"].as_u64()), (Some(12), Some(3)));
+        assert!(body.get("warning").is_none());
+
+        let (_, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"address": "delhi", "limit": 4}))).await;
+        assert_eq!(body["results_returned"], 4);
+        assert!(body.get("warning").is_none());
+
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"address": "delhi", "limit": 500}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results_returned"], 5);
+        assert_eq!(body["warning"], "limit reduced to the maximum of 5");
+    }
+
+    #[actix_web::test]
+    async fn records_returns_found_ids_grouped_and_lists_the_missing() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m1,9800000002,ravi,ravi kumar,,,noida",
+            "m2,9800000003,amit,amit shah,,,pune",
+            "m3,9800000004,neha,neha gupta,,,agra",
+        ], SearchConfig::default());
+        let records = |ids: serde_json::Value| TestRequest::post().uri("/records").set_json(serde_json::json!({"master_ids": ids}));
+
+        let (status, body) = call(&service, AccessPolicy::default(), records(serde_json::json!(["m2", "x9", " m1 ", "m2", "", "m404"]))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["found"], 2);
+        let found = body["records"].as_object().unwrap();
+        assert_eq!(found.keys().collect::<Vec<_>>(), ["m1", "m2"]);
+        assert_eq!(found["m1"].as_array().unwrap().len(), 2);
+        assert_eq!(found["m2"][0]["name"], "amit shah");
+        assert_eq!(body["not_found"], serde_json::json!(["m404", "x9"]));
+
+        let too_many: Vec<String> = (0..=MAX_RECORDS_BATCH).map(|i| format!("m{}", i)).collect();
+        let (status, body) = call(&service, AccessPolicy::default(), records(serde_json::json!(too_many))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "BATCH_TOO_LARGE");
+    }
+
+    #[actix_web::test]
+    async fn request_ids_are_echoed_or_generated() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(service))
+                .wrap(from_fn(tag_request))
+                .configure(routes),
+        ).await;
+        let request_id = |req: TestRequest| {
+            let app = &app;
+            async move {
+                let response = call_service(app, req.to_request()).await;
+                response.headers().get(REQUEST_ID_HEADER).map(|v| v.to_str().unwrap().to_string())
+            }
+        };
+        let search = || TestRequest::post().uri("/search").set_json(serde_json::json!({"name": "ravi"}));
+
+        assert_eq!(request_id(search().insert_header((REQUEST_ID_HEADER, "trace-42"))).await.as_deref(), Some("trace-42"));
+        // Errors carry it too
+        let bad = TestRequest::post().uri("/search").set_json(serde_json::json!({"nmae": "ravi"}));
+        assert_eq!(request_id(bad.insert_header((REQUEST_ID_HEADER, "trace-43"))).await.as_deref(), Some("trace-43"));
+
+        // Missing or unusable ids are replaced by distinct generated ones
+        let first = request_id(search()).await.unwrap();
+        let second = request_id(search().insert_header((REQUEST_ID_HEADER, "has spaces"))).await.unwrap();
+        assert!(!first.is_empty() && first != second && second != "has spaces");
+    }
+
+    #[actix_web::test]
+    async fn server_answers_over_a_unix_socket() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = TempDir::new().unwrap();
+        service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        let socket = dir.path().join("api.sock");
+        // Left behind as by a server that was killed; replaced on start
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+
+        actix_web::rt::spawn(start_server(index_dir, socket.to_str().unwrap().to_string(), 0, SearchConfig::default(), None, None));
+        let mut stream = None;
+        for _ in 0..100 {
+            if let Ok(connected) = tokio::net::UnixStream::connect(&socket).await {
+                stream = Some(connected);
+                break;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        let mut stream = stream.expect("server never accepted on the socket");
+        stream.write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.to_lowercase().contains("x-request-id:"), "{response}");
+
+        // Handlers checking API keys get the access policy registered on start
+        let mut stream = tokio::net::UnixStream::connect(&socket).await.unwrap();
+        stream.write_all(b"POST /reload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200") && response.contains("\"unchanged\""), "{response}");
+    }
+
+    #[test]
+    fn only_stale_sockets_are_replaced() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("not-a-socket");
+        std::fs::write(&file, "keep").unwrap();
+        assert_eq!(prepare_socket_path(&file).unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep");
+        let missing_dir = dir.path().join("missing").join("api.sock");
+        assert_eq!(prepare_socket_path(&missing_dir).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    }
+
+    /// Self-signed pair for localhost checked in under tests/tls
+    fn tls_fixture() -> TlsFiles {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("tls");
+        TlsFiles { cert: dir.join("cert.pem"), key: dir.join("key.pem") }
+    }
+
+    #[actix_web::test]
+    async fn server_answers_over_tls_when_given_a_certificate() {
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, ServerName};
+        use std::io::{Read, Write};
+
+        let dir = TempDir::new().unwrap();
+        service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        actix_web::rt::spawn(start_server(index_dir, "127.0.0.1".to_string(), port, SearchConfig::default(), None, Some(tls_fixture())));
+
+        // Only a client trusting the fixture certificate gets through
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(CertificateDer::from_pem_file(tls_fixture().cert).unwrap()).unwrap();
+        let client = Arc::new(rustls::ClientConfig::builder().with_root_certificates(roots).with_no_client_auth());
+        let response = actix_web::rt::task::spawn_blocking(move || {
+            let mut tcp = None;
+            for _ in 0..100 {
+                if let Ok(connected) = std::net::TcpStream::connect(("127.0.0.1", port)) {
+                    tcp = Some(connected);
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            let tcp = tcp.expect("server never accepted on the port");
+            let connection = rustls::ClientConnection::new(client, ServerName::try_from("localhost").unwrap()).unwrap();
+            let mut stream = rustls::StreamOwned::new(connection, tcp);
+            stream.write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+            let mut response = String::new();
+            // The server may close without a TLS close_notify; the response is read by then
+            let _ = stream.read_to_string(&mut response);
+            response
+        }).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    }
+
+    #[test]
+    fn tls_misconfiguration_names_the_file() {
+        assert!(load_tls_config(&tls_fixture()).is_ok());
+
+        let dir = TempDir::new().unwrap();
+        let missing = TlsFiles { cert: dir.path().join("missing.pem"), ..tls_fixture() };
+        let err = load_tls_config(&missing).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("certificate file not found") && err.to_string().contains("missing.pem"), "{err}");
+
+        let garbage = dir.path().join("garbage.pem");
+        std::fs::write(&garbage, "not a pem file").unwrap();
+        let err = load_tls_config(&TlsFiles { key: garbage.clone(), ..tls_fixture() }).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("Invalid TLS key") && err.to_string().contains("garbage.pem"), "{err}");
+        let err = load_tls_config(&TlsFiles { cert: garbage, ..tls_fixture() }).unwrap_err();
+        assert!(err.to_string().contains("Invalid TLS certificate"), "{err}");
+
+        // A certificate where the key should be
+        let err = load_tls_config(&TlsFiles { key: tls_fixture().cert, ..tls_fixture() }).unwrap_err();
+        assert!(err.to_string().contains("Invalid TLS key"), "{err}");
+    }
+
+    #[actix_web::test]
+    async fn index_version_changes_after_a_reload() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let search = || TestRequest::post().uri("/search").set_json(serde_json::json!({"address": "delhi"}));
+
+        let (_, before) = call(&service, AccessPolicy::default(), search()).await;
+        let csv = write_csv(&dir, "more.csv", &["m2,9800000002,amit,amit shah,,,delhi"]);
+        append_index(&csv, dir.path().join("index").to_str().unwrap(), &IndexOptions::default()).unwrap();
+        // Not picked up until reloaded
+        let (_, unchanged) = call(&service, AccessPolicy::default(), search()).await;
+        assert_eq!(unchanged["index_version"], before["index_version"]);
+        assert_eq!(unchanged["total_matches"], 1);
+
+        let (status, reload) = call(&service, AccessPolicy::default(), TestRequest::post().uri("/reload")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(reload["status"], "reloaded");
+        let (_, after) = call(&service, AccessPolicy::default(), search()).await;
+        assert_eq!(after["total_matches"], 2);
+        assert_eq!(after["index_version"], reload["opstamp"]);
+        assert!(after["index_version"].as_u64() > before["index_version"].as_u64());
+    }
+
+    #[actix_web::test]
+    async fn parse_returns_the_structure_without_searching_or_the_error() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let parse = |body: serde_json::Value| TestRequest::post().uri("/parse").set_json(body);
+
+        let (status, body) = call(&service, AccessPolicy::default(), parse(serde_json::json!({"name": "ravi kumar", "address": "delhi", "filter": "or"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["valid"], true);
+        assert_eq!(body["query_string"], "name:ravi kumar OR address:delhi");
+        assert_eq!(body["clauses"], serde_json::json!([
+            {"field": "name", "value": "ravi kumar", "mode": "tokenized"},
+            {"field": "address", "value": "delhi", "mode": "tokenized"},
+        ]));
+        assert_eq!(body["ops"], serde_json::json!(["OR"]));
+        assert!(body["query"].as_str().unwrap().starts_with("BooleanQuery"), "{}", body["query"]);
+        assert!(body.get("results").is_none());
+
+        let (status, body) = call(&service, AccessPolicy::default(), parse(serde_json::json!({"name": "!!!"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "EMPTY_VALUE");
+        let (status, body) = call(&service, AccessPolicy::default(), parse(serde_json::json!({"fields": {"pincode": "110001"}}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "UNKNOWN_FIELD");
+    }
+
+    #[test]
+    fn token_ops_or_a_fields_words_and_reject_unknown_values() {
+        let config = SearchConfig::default();
+        let build = |body: serde_json::Value| build_query_string(&request(body), &config);
+        assert_eq!(build(serde_json::json!({"name": "john jane"})).unwrap(), "name:john jane");
+        assert_eq!(build(serde_json::json!({"name": "john jane", "token_ops": {"name": "or"}})).unwrap(), "name|john jane");
+        assert_eq!(build(serde_json::json!({"name": "john jane", "token_ops": {"name": "AND"}})).unwrap(), "name:john jane");
+        for token_ops in [serde_json::json!({"name": "xor"}), serde_json::json!({"nmae": "or"})] {
+            let err = build(serde_json::json!({"name": "john jane", "token_ops": token_ops})).unwrap_err();
+            assert_eq!(err.downcast::<QueryError>().unwrap().code, "INVALID_TOKEN_OP");
+        }
+    }
+
+    #[actix_web::test]
+    async fn malformed_json_gets_the_structured_error_shape() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let post = |content_type: &str, payload: &'static str| TestRequest::post()
+            .uri("/search")
+            .insert_header(("content-type", content_type.to_string()))
+            .set_payload(payload);
+
+        let (status, body) = call(&service, AccessPolicy::default(), post("application/json", "{\"name\": \"ravi\"")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "BAD_JSON");
+        assert!(body["error"].as_str().unwrap().starts_with("Malformed JSON: "), "{body}");
+
+        let (status, body) = call(&service, AccessPolicy::default(), post("text/plain", "{\"name\": \"ravi\"}")).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body["code"], "UNSUPPORTED_MEDIA_TYPE");
+    }
+
+    #[actix_web::test]
+    async fn templates_run_by_name_with_their_parameters_as_values() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &[
+            "m1,9800000001,ravi,ravi kumar,,,pune",
+            "m2,9800000002,amit,amit shah,9800000001,,delhi",
+        ], SearchConfig {
+            query_templates: HashMap::from([
+                ("phone_lookup".to_string(), "mobile:{0} OR alt:{0}".to_string()),
+                ("by_name".to_string(), "name:{0}".to_string()),
+            ]),
+            ..SearchConfig::default()
+        });
+        let template = |name: &str, params: serde_json::Value| TestRequest::post()
+            .uri(&format!("/template/{}", name))
+            .set_json(serde_json::json!({"params": params}));
+
+        let (status, body) = call(&service, AccessPolicy::default(), template("phone_lookup", serde_json::json!(["9800000001"]))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total_matches"], 2);
+        // The OR stays inside the name value instead of widening the query
+        let (status, body) = call(&service, AccessPolicy::default(), template("by_name", serde_json::json!(["ravi OR address:delhi"]))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total_matches"], 0);
+
+        let (status, body) = call(&service, AccessPolicy::default(), template("by_name", serde_json::json!(["ra*"]))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "INVALID_PARAMETER");
+        let (status, body) = call(&service, AccessPolicy::default(), template("by_mobile", serde_json::json!(["1"]))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "UNKNOWN_TEMPLATE");
+    }
+
+    #[actix_web::test]
+    async fn restricted_keys_see_redacted_records_and_cannot_query_hidden_fields() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &[
+            "m1,9800000001,ravi,ravi kumar,,ravi@x.in,delhi",
+            "m2,9800000002,amit,amit shah,,amit@x.in,pune",
+        ], SearchConfig::default());
+        let policy = || AccessPolicy::from_allowlists(HashMap::from([
+            ("limited".to_string(), ["master_id", "mobile", "fname", "name"].map(String::from).to_vec()),
+            ("full".to_string(), vec!["*".to_string()]),
+        ]), &service.field_names()).unwrap();
+        let search = |key: &str, body: serde_json::Value| TestRequest::post().uri("/search")
+            .insert_header((API_KEY_HEADER, key.to_string()))
+            .set_json(body);
+
+        let (status, body) = call(&service, policy(), search("limited", serde_json::json!({"name": "ravi"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        let record = &body["results"][0];
+        assert_eq!(record["name"], "ravi kumar");
+        assert!(record.get("address").is_none() && record.get("email").is_none(), "{record}");
+        let (_, body) = call(&service, policy(), search("full", serde_json::json!({"name": "ravi"}))).await;
+        assert_eq!(body["results"][0]["address"], "delhi");
+
+        let (status, body) = call(&service, policy(), search("limited", serde_json::json!({"address": "delhi"}))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "FIELD_FORBIDDEN");
+        assert_eq!(body["field"], "address");
+        let (status, _) = call(&service, policy(), TestRequest::post().uri("/search").set_json(serde_json::json!({"name": "ravi"}))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Neighbor records are redacted like the matched ones
+        let context = TestRequest::get().uri("/context?master_id=m1&neighbors=1").insert_header((API_KEY_HEADER, "limited"));
+        let (status, body) = call(&service, policy(), context).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["matches"][0].get("address").is_none(), "{body}");
+        let neighbor = &body["neighbors"][0];
+        assert_eq!(neighbor["master_id"], "m2");
+        assert_eq!(neighbor["records"][0]["name"], "amit shah");
+        assert!(neighbor["records"][0].get("address").is_none(), "{neighbor}");
+    }
+
+    #[actix_web::test]
+    async fn compare_scores_overlapping_records_as_the_same_person() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &[
+            "m1,9800000001,ravi,ravi kumar,,ravi@x.in,12 mg road delhi",
+            "m2,9811111111,ravi,Kumar Ravii,9800000001,ravi@x.in,12 mg road new delhi",
+            "m3,9822222222,neha,neha gupta,,neha@y.in,4 park street kolkata",
+        ], SearchConfig::default());
+        let compare = |a: &str, b: &str| TestRequest::get().uri(&format!("/compare?a={}&b={}", a, b));
+
+        let (status, body) = call(&service, AccessPolicy::default(), compare("m1", "m2")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["linked"], true);
+        assert!(body["score"].as_f64().unwrap() > 0.8, "{body}");
+        assert_eq!(body["signals"]["mobile"]["score"], 1.0);
+        assert_eq!(body["signals"]["email"]["shared"], serde_json::json!(["ravi@x.in"]));
+        assert_eq!(body["signals"]["address"]["shared"], serde_json::json!(["12", "delhi", "mg", "road"]));
+
+        let (_, body) = call(&service, AccessPolicy::default(), compare("m1", "m3")).await;
+        assert_eq!(body["linked"], false);
+        assert!(body["score"].as_f64().unwrap() < 0.3, "{body}");
+
+        let (status, body) = call(&service, AccessPolicy::default(), compare("m1", "m404")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "UNKNOWN_KEY");
+    }
+
+    #[actix_web::test]
+    async fn bulk_pushed_records_are_searchable_after_the_request() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig { bulk_indexing: true, ..SearchConfig::default() });
+        let search = || TestRequest::post().uri("/search").set_json(serde_json::json!({"address": "pune"}));
+        let (_, body) = call(&service, AccessPolicy::default(), search()).await;
+        assert_eq!(body["total_matches"], 0);
+
+        let ndjson = concat!(
+            "{\"master_id\": \"m2\", \"name\": \"amit shah\", \"mobile\": 9800000002, \"address\": \"pune\"}\n",
+            "\n",
+            "{\"master_id\": \"m3\", \"name\": \"sita ram\", \"adress\": \"pune\"}\n",
+            "{not json\n",
+            "{\"master_id\": \"m4\", \"name\": \"neha gupta\", \"address\": \"pune\"}",
+        );
+        let (status, body) = call(&service, AccessPolicy::default(), TestRequest::post().uri("/index/bulk").set_payload(ndjson)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["added"], 2);
+        assert_eq!(body["failed"], 2);
+        assert_eq!(body["errors"][0], serde_json::json!({"line": 3, "error": "unknown field 'adress'"}));
+        assert_eq!(body["errors"][1]["line"], 4);
+
+        let (_, body) = call(&service, AccessPolicy::default(), search()).await;
+        assert_eq!(body["total_matches"], 2);
+        let mut ids: Vec<&str> = body["results"].as_array().unwrap().iter().map(|r| r["master_id"].as_str().unwrap()).collect();
+        ids.sort();
+        assert_eq!(ids, ["m2", "m4"]);
+        assert_eq!(body["results"].as_array().unwrap().iter().find(|r| r["master_id"] == "m2").unwrap()["mobile"], "9800000002");
+    }
+
+    #[test]
+    fn searches_over_the_slow_query_threshold_are_logged() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let mut results = service.search("name:ravi", &SearchOptions::default()).unwrap();
+        // As if the search had stalled
+        results.total_time_ms = 2500.0;
+        let entry = |slow_query_ms: Option<f64>| {
+            slow_query_entry("req-1", &SearchConfig { slow_query_ms, ..SearchConfig::default() }, "name:ravi", &results)
+        };
+
+        let logged = entry(Some(1000.0)).unwrap();
+        assert!(logged.starts_with("[WARN] [req-1] Slow query \"name:ravi\": 2500.0ms total (parse "), "{logged}");
+        assert!(logged.ends_with("1 matches, 1 returned"), "{logged}");
+        assert_eq!(entry(Some(2500.0)), None);
+        assert_eq!(entry(None), None);
+    }
+
+    #[actix_web::test]
+    async fn not_leaves_out_records_matching_the_exclusion() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &[
+            "m1,9800000001,ravi,ravi kumar,,,12 mg road delhi",
+            "m2,9800000002,ravi,ravi verma,,,test address delhi",
+            "m3,9800000003,amit,amit shah,,,test lane pune",
+        ], SearchConfig::default());
+        let search = |body: serde_json::Value| TestRequest::post().uri("/search").set_json(body);
+        let ids = |body: &serde_json::Value| {
+            let mut ids: Vec<String> = body["results"].as_array().unwrap().iter().map(|r| r["master_id"].as_str().unwrap().to_string()).collect();
+            ids.sort();
+            ids
+        };
+
+        let (_, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "ravi"}))).await;
+        assert_eq!(ids(&body), ["m1", "m2"]);
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "ravi", "not": {"address": "test"}}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(&body), ["m1"]);
+        // With OR the exclusion still covers every alternative
+        let or = serde_json::json!({"name": "ravi", "address": "pune", "filter": "or", "not": {"address": "test"}});
+        assert_eq!(build_query_string(&request(or.clone()), &SearchConfig::default()).unwrap(), "name:ravi OR address:pune AND NOT address:test");
+        let (_, body) = call(&service, AccessPolicy::default(), search(or)).await;
+        assert_eq!(ids(&body), ["m1"]);
+
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "ravi", "not": {"address": "  "}}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "EMPTY_VALUE");
+    }
+
+    #[actix_web::test]
+    async fn too_short_values_name_the_field_and_its_minimum() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig {
+            min_value_lengths: HashMap::from([("name".to_string(), 3)]),
+            ..SearchConfig::default()
+        });
+        let search = |body: serde_json::Value| TestRequest::post().uri("/search").set_json(body);
+
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "r.a"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "VALUE_TOO_SHORT");
+        assert_eq!(body["error"], "Invalid query: Field 'name' value 'r.a' is too short, the minimum is 3 letters or digits");
+
+        let (status, _) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "rav"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        // Other fields and exclusions have no minimum
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"address": "de", "not": {"name": "x"}}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total_matches"], 0);
+    }
+
+    #[actix_web::test]
+    async fn fuzzy_requests_find_misspelled_names() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m2,9800000002,amit,amit shah,,,pune",
+        ], SearchConfig::default());
+        let search = |body: serde_json::Value| TestRequest::post().uri("/search").set_json(body);
+
+        let (_, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "ravy kumr"}))).await;
+        assert_eq!(body["total_matches"], 0);
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "ravy kumr", "fuzzy": true}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total_matches"], 1);
+        assert_eq!(body["results"][0]["master_id"], "m1");
+        // Two typos in one word need distance 2
+        let (_, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "rvy", "fuzzy": true}))).await;
+        assert_eq!(body["total_matches"], 0);
+        let (_, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"name": "rvy", "fuzzy": true, "fuzzy_distance": 2}))).await;
+        assert_eq!(body["results"][0]["master_id"], "m1");
+
+        // Only text fields are fuzzy; the mobile stays exact
+        let query = build_query_string(&request(serde_json::json!({"name": "ravy", "mobile": "9800000001", "fuzzy": true})), &SearchConfig::default()).unwrap();
+        assert_eq!(query, "name:ravy~1 AND mobile:9800000001");
+        for body in [serde_json::json!({"name": "ravy", "fuzzy_distance": 1}), serde_json::json!({"name": "ravy", "fuzzy": true, "fuzzy_distance": 3})] {
+            let (status, body) = call(&service, AccessPolicy::default(), search(body)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body["code"], "INVALID_FUZZY");
+        }
+    }
+
+    #[actix_web::test]
+    async fn searches_beyond_the_in_flight_limit_get_a_503() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig {
+            max_in_flight_searches: Some(2),
+            ..SearchConfig::default()
+        });
+        let search = || TestRequest::post().uri("/search").set_json(serde_json::json!({"name": "ravi"}));
+
+        // One search already running leaves room for this one
+        let first = service.try_begin_search().unwrap();
+        let (status, body) = call(&service, AccessPolicy::default(), search()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["total_matches"], 1);
+
+        let second = service.try_begin_search().unwrap();
+        assert!(service.try_begin_search().is_none());
+        let (status, body) = call(&service, AccessPolicy::default(), search()).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["code"], "OVERLOADED");
+        assert_eq!(overloaded_response().headers().get("Retry-After").unwrap(), "1");
+
+        // A finished search frees its slot, including the turned-away ones
+        drop(second);
+        let (status, _) = call(&service, AccessPolicy::default(), search()).await;
+        assert_eq!(status, StatusCode::OK);
+        drop(first);
+        assert!(service.try_begin_search().is_some());
+    }
+
+    #[actix_web::test]
+    async fn boosting_a_field_ranks_its_matches_first() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &[
+            "m1,9800000001,ravi,ravi kumar,,,pune",
+            "m2,9800000002,amit,amit shah,,,delhi",
+        ], SearchConfig::default());
+        let search = |body: serde_json::Value| TestRequest::post().uri("/search").set_json(body);
+        let order = |body: &serde_json::Value| body["results"].as_array().unwrap().iter()
+            .map(|r| r["master_id"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        for (boosts, expected) in [
+            (serde_json::json!({"name": 10.0}), ["m1", "m2"]),
+            (serde_json::json!({"address": 10.0}), ["m2", "m1"]),
+        ] {
+            let body = serde_json::json!({"name": "ravi", "address": "delhi", "filter": "or", "boosts": boosts});
+            let (status, body) = call(&service, AccessPolicy::default(), search(body)).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(order(&body), expected);
+        }
+        let query = build_query_string(&request(serde_json::json!({"name": "ravi", "boosts": {"name": 2.5}})), &SearchConfig::default()).unwrap();
+        assert_eq!(query, "name:ravi^2.5");
+
+        for boosts in [serde_json::json!({"email": 2.0}), serde_json::json!({"name": 0.0}), serde_json::json!({"name": -1.0})] {
+            assert_eq!(query_error(serde_json::json!({"name": "ravi", "boosts": boosts})).code, "INVALID_BOOST");
+        }
+    }
+
+    #[actix_web::test]
+    async fn normalize_shows_values_as_a_search_would_use_them() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let normalize = |body: serde_json::Value| TestRequest::post().uri("/normalize").set_json(body);
+
+        let (status, body) = call(&service, AccessPolicy::default(), normalize(serde_json::json!({
+            "mobile": "+91 98000 00001",
+            "email": "Ravi.Kumar@Example.COM",
+            "name": "!!!",
+        }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["normalized"], serde_json::json!({"mobile": "9800000001", "email": "ravi.kumar@example.com"}));
+        assert_eq!(body["empty"], serde_json::json!(["name"]));
+
+        let (status, body) = call(&service, AccessPolicy::default(), normalize(serde_json::json!({"nmae": "ravi"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "UNKNOWN_FIELD");
+    }
+
+    #[actix_web::test]
+    async fn periodic_reload_picks_up_an_external_commit() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &["m1,9800000001,ravi,ravi kumar,,,delhi"], SearchConfig::default());
+        let interval = Duration::from_millis(50);
+        spawn_periodic_reload(Arc::downgrade(&service), interval);
+        let before = service.index_version();
+
+        let csv = write_csv(&dir, "more.csv", &["m2,9800000002,ravi,ravi shah,,,pune"]);
+        append_index(&csv, dir.path().join("index").to_str().unwrap(), &IndexOptions::default()).unwrap();
+
+        // Within a few intervals, without any /reload request
+        let deadline = Instant::now() + interval * 40;
+        while service.index_version() == before && Instant::now() < deadline {
+            std::thread::sleep(interval);
+        }
+        assert_ne!(service.index_version(), before);
+        let results = service.search("name:ravi", &SearchOptions::default()).unwrap();
+        assert_eq!(results.total_matches, 2);
+    }
+
+    #[actix_web::test]
+    async fn capitalized_field_keys_search_like_their_snake_case_names() {
+        let dir = TempDir::new().unwrap();
+        let service = service(&dir, &[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m2,9800000002,amit,amit shah,,,delhi",
+        ], SearchConfig::default());
+        let search = |body: serde_json::Value| TestRequest::post().uri("/search").set_json(body);
+
+        for body in [
+            serde_json::json!({"Name": "ravi"}),
+            serde_json::json!({"FName": "ravi", "ADDRESS": "delhi"}),
+            serde_json::json!({"masterId": "m1"}),
+        ] {
+            let (status, response) = call(&service, AccessPolicy::default(), search(body.clone())).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            assert_eq!(response["total_matches"], 1, "{}", body);
+            assert_eq!(response["results"][0]["master_id"], "m1", "{}", body);
+        }
+        assert_eq!(request(serde_json::json!({"Email": "a@b.in"})).email.as_deref(), Some("a@b.in"));
+
+        // Other spellings are an error naming the key to use, never dropped
+        let (status, body) = call(&service, AccessPolicy::default(), search(serde_json::json!({"NaMe": "ravi"}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "UNKNOWN_FIELD");
+        assert_eq!(body["suggestion"], "name");
+    }
 }
@@ -0,0 +1,167 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Weight of each signal in the composite score; signals without data on
+/// both sides are left out and the rest re-weighted
+const SIGNAL_WEIGHTS: [(&str, f64); 4] = [("name", 0.35), ("mobile", 0.3), ("address", 0.2), ("email", 0.15)];
+
+/// Name tokens at least this similar count as shared
+const NAME_TOKEN_MATCH: f64 = 0.8;
+
+/// What one identity key's rows say about the person, for `compare`
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub key: String,
+    pub name_tokens: BTreeSet<String>,
+    pub address_tokens: BTreeSet<String>,
+    /// Canonical mobile and alt numbers
+    pub phones: BTreeSet<String>,
+    pub emails: BTreeSet<String>,
+    /// Keys the profile's phones fan out to
+    pub linked_keys: BTreeSet<String>,
+}
+
+impl Profile {
+    pub fn add_name(&mut self, value: &str) {
+        self.name_tokens.extend(tokens(value));
+    }
+
+    pub fn add_address(&mut self, value: &str) {
+        self.address_tokens.extend(tokens(value));
+    }
+}
+
+/// One signal's contribution: `score` in 0..=1, None when either side has
+/// nothing to compare
+#[derive(Debug, Serialize)]
+pub struct Signal {
+    pub score: Option<f64>,
+    pub weight: f64,
+    pub shared: Vec<String>,
+}
+
+/// Likely-same-person comparison of two profiles
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    /// Weighted mean of the available signal scores, 0..=1
+    pub score: f64,
+    /// Either key is reached by fan-out from the other's phones
+    pub linked: bool,
+    pub signals: BTreeMap<&'static str, Signal>,
+}
+
+/// Score how likely two profiles are the same person from name similarity
+/// (typo-tolerant), shared or fan-out-linked phones, address overlap and
+/// email match
+pub fn compare(a: &Profile, b: &Profile) -> Comparison {
+    let linked = a.linked_keys.contains(&b.key) || b.linked_keys.contains(&a.key);
+    let mut signals = BTreeMap::new();
+    for (name, weight) in SIGNAL_WEIGHTS {
+        let (score, shared) = match name {
+            "name" => name_similarity(&a.name_tokens, &b.name_tokens),
+            "mobile" => {
+                let shared = shared(&a.phones, &b.phones);
+                let score = (!a.phones.is_empty() && !b.phones.is_empty())
+                    .then_some(if linked || !shared.is_empty() { 1.0 } else { 0.0 });
+                (score, shared)
+            }
+            "address" => (jaccard(&a.address_tokens, &b.address_tokens), shared(&a.address_tokens, &b.address_tokens)),
+            _ => {
+                let shared = shared(&a.emails, &b.emails);
+                let score = (!a.emails.is_empty() && !b.emails.is_empty())
+                    .then_some(if shared.is_empty() { 0.0 } else { 1.0 });
+                (score, shared)
+            }
+        };
+        signals.insert(name, Signal { score, weight, shared });
+    }
+
+    let (total, weights) = signals.values()
+        .filter_map(|signal| signal.score.map(|score| (score * signal.weight, signal.weight)))
+        .fold((0.0, 0.0), |(total, weights), (score, weight)| (total + score, weights + weight));
+    let score = if weights > 0.0 { total / weights } else { 0.0 };
+    Comparison { score, linked, signals }
+}
+
+fn tokens(value: &str) -> impl Iterator<Item = String> + '_ {
+    value.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn shared(a: &BTreeSet<String>, b: &BTreeSet<String>) -> Vec<String> {
+    a.intersection(b).cloned().collect()
+}
+
+fn jaccard(a: &BTreeSet<String>, b: &BTreeSet<String>) -> Option<f64> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    Some(a.intersection(b).count() as f64 / a.union(b).count() as f64)
+}
+
+/// Mean over both sides' tokens of their best match on the other side, so
+/// `ravi kumar` vs `kumar ravi` scores 1 and `ravi` vs `ravii` nearly so
+fn name_similarity(a: &BTreeSet<String>, b: &BTreeSet<String>) -> (Option<f64>, Vec<String>) {
+    if a.is_empty() || b.is_empty() {
+        return (None, Vec::new());
+    }
+    let best = |token: &String, others: &BTreeSet<String>| {
+        others.iter().map(|other| token_similarity(token, other)).fold(0.0, f64::max)
+    };
+    let total: f64 = a.iter().map(|t| best(t, b)).sum::<f64>() + b.iter().map(|t| best(t, a)).sum::<f64>();
+    let shared = a.iter().filter(|t| best(t, b) >= NAME_TOKEN_MATCH).cloned().collect();
+    (Some(total / (a.len() + b.len()) as f64), shared)
+}
+
+/// 1 minus the edit distance relative to the longer token
+fn token_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    1.0 - row[b.len()] as f64 / longest as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(key: &str, name: &str, phones: &[&str]) -> Profile {
+        let mut profile = Profile { key: key.to_string(), ..Profile::default() };
+        profile.add_name(name);
+        profile.phones = phones.iter().map(|p| p.to_string()).collect();
+        profile
+    }
+
+    #[test]
+    fn signals_without_data_on_both_sides_are_left_out() {
+        let a = profile("m1", "Ravi Kumar", &["9800000001"]);
+        let b = profile("m2", "kumar  ravi", &["9800000001"]);
+        let comparison = compare(&a, &b);
+        assert_eq!(comparison.score, 1.0);
+        assert!(comparison.signals["email"].score.is_none());
+        assert!(comparison.signals["address"].score.is_none());
+
+        // One typo in a token still mostly matches
+        let (score, shared) = name_similarity(&a.name_tokens, &profile("m3", "ravii kumar", &[]).name_tokens);
+        assert!((score.unwrap() - 0.9).abs() < 1e-9);
+        assert_eq!(shared, ["kumar", "ravi"]);
+
+        let unrelated = compare(&a, &profile("m4", "neha gupta", &["9822222222"]));
+        assert_eq!(unrelated.signals["mobile"].score, Some(0.0));
+        assert!(unrelated.score < 0.3, "{}", unrelated.score);
+    }
+}
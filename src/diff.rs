@@ -0,0 +1,245 @@
+use anyhow::{Result, anyhow};
+use serde_json::json;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tantivy::collector::DocSetCollector;
+use tantivy::query::TermQuery;
+use tantivy::schema::{Field, FieldType, IndexRecordOption, Schema, Value};
+use tantivy::termdict::TermMerger;
+use tantivy::{Index, InvertedIndexReader, ReloadPolicy, Searcher, TantivyDocument, Term};
+
+/// Stored field values of one row, keyed by field name
+type Record = BTreeMap<String, String>;
+
+/// Diff two indexes keyed by master_id and report added, removed and
+/// modified records.
+///
+/// Both master_id term dictionaries are walked in sorted order and merge-joined,
+/// so only the rows of the master_id currently being compared are held in
+/// memory, regardless of index size. Returns the printed summary.
+pub fn diff_indexes(old_dir: &str, new_dir: &str, details: bool) -> Result<serde_json::Value> {
+    println!("Old index: {}", old_dir);
+    println!("New index: {}", new_dir);
+
+    let old_searcher = open_searcher(old_dir)?;
+    let new_searcher = open_searcher(new_dir)?;
+    let old_field = master_id_field(old_searcher.schema())?;
+    let new_field = master_id_field(new_searcher.schema())?;
+
+    // Keep the per-segment inverted indexes alive while their terms are streamed
+    let old_inverted = inverted_indexes(&old_searcher, old_field)?;
+    let new_inverted = inverted_indexes(&new_searcher, new_field)?;
+    let mut old_terms = term_merger(&old_inverted)?;
+    let mut new_terms = term_merger(&new_inverted)?;
+
+    let mut old_has = old_terms.advance();
+    let mut new_has = new_terms.advance();
+
+    let (mut added, mut removed, mut modified, mut unchanged) = (0u64, 0u64, 0u64, 0u64);
+
+    while old_has || new_has {
+        let order = match (old_has, new_has) {
+            (true, true) => old_terms.key().cmp(new_terms.key()),
+            (true, false) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+
+        let key_bytes = match order {
+            Ordering::Greater => new_terms.key(),
+            _ => old_terms.key(),
+        };
+        let master_id = String::from_utf8_lossy(key_bytes).to_string();
+
+        // A term can outlive its documents until segments merge: fetch the
+        // live rows rather than trusting the dictionary alone
+        let old_rows = if order != Ordering::Greater {
+            fetch_records(&old_searcher, old_field, key_bytes)?
+        } else {
+            Vec::new()
+        };
+        let new_rows = if order != Ordering::Less {
+            fetch_records(&new_searcher, new_field, key_bytes)?
+        } else {
+            Vec::new()
+        };
+
+        match (old_rows.is_empty(), new_rows.is_empty()) {
+            (true, true) => {}
+            (true, false) => {
+                added += 1;
+                if details {
+                    println!("{}", json!({"status": "added", "master_id": master_id, "rows": new_rows}));
+                }
+            }
+            (false, true) => {
+                removed += 1;
+                if details {
+                    println!("{}", json!({"status": "removed", "master_id": master_id, "rows": old_rows}));
+                }
+            }
+            (false, false) if old_rows == new_rows => unchanged += 1,
+            (false, false) => {
+                modified += 1;
+                if details {
+                    println!("{}", json!({
+                        "status": "modified",
+                        "master_id": master_id,
+                        "changes": describe_changes(&old_rows, &new_rows),
+                    }));
+                }
+            }
+        }
+
+        if order != Ordering::Greater {
+            old_has = old_terms.advance();
+        }
+        if order != Ordering::Less {
+            new_has = new_terms.advance();
+        }
+    }
+
+    let summary = json!({
+        "added": added,
+        "removed": removed,
+        "modified": modified,
+        "unchanged": unchanged,
+    });
+    println!("{}", serde_json::to_string_pretty(&summary)?);
+
+    Ok(summary)
+}
+
+fn open_searcher(index_dir: &str) -> Result<Searcher> {
+    let index = Index::open_in_dir(index_dir)?;
+    let reader = index.reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()?;
+    Ok(reader.searcher())
+}
+
+fn master_id_field(schema: &Schema) -> Result<Field> {
+    schema.get_field("master_id")
+        .map_err(|_| anyhow!("Index has no master_id field"))
+}
+
+fn inverted_indexes(searcher: &Searcher, field: Field) -> Result<Vec<Arc<InvertedIndexReader>>> {
+    searcher.segment_readers()
+        .iter()
+        .map(|segment| Ok(segment.inverted_index(field)?))
+        .collect()
+}
+
+/// Merge the sorted per-segment term streams into one sorted stream
+fn term_merger(inverted: &[Arc<InvertedIndexReader>]) -> Result<TermMerger<'_>> {
+    let streams = inverted
+        .iter()
+        .map(|inv| inv.terms().stream())
+        .collect::<std::io::Result<Vec<_>>>()?;
+    Ok(TermMerger::new(streams))
+}
+
+/// Live rows for one master_id, sorted so row order doesn't register as a change
+fn fetch_records(searcher: &Searcher, field: Field, key: &[u8]) -> Result<Vec<Record>> {
+    let term = Term::from_field_text(field, &String::from_utf8_lossy(key));
+    let query = TermQuery::new(term, IndexRecordOption::Basic);
+    let addresses = searcher.search(&query, &DocSetCollector)?;
+
+    let schema = searcher.schema();
+    let mut records = Vec::with_capacity(addresses.len());
+    for addr in addresses {
+        let doc: TantivyDocument = searcher.doc(addr)?;
+        records.push(to_record(&doc, schema));
+    }
+    records.sort();
+    Ok(records)
+}
+
+/// Non-empty stored text fields of a document. Empty values are left out so
+/// a field added to the schema between builds doesn't count as a change, and
+/// the build timestamp is skipped since it changes on every rebuild by design.
+fn to_record(doc: &TantivyDocument, schema: &Schema) -> Record {
+    schema.fields()
+        .filter(|(_, entry)| entry.is_stored() && matches!(entry.field_type(), FieldType::Str(_)))
+        .filter_map(|(field, entry)| {
+            let value = doc.get_first(field).and_then(|v| Value::as_str(&v))?;
+            (!value.is_empty()).then(|| (entry.name().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Field-level changes for a single-row master_id, row-level otherwise
+fn describe_changes(old_rows: &[Record], new_rows: &[Record]) -> serde_json::Value {
+    if let ([old], [new]) = (old_rows, new_rows) {
+        let mut fields = serde_json::Map::new();
+        for (name, old_value) in old {
+            let new_value = new.get(name).map(String::as_str).unwrap_or("");
+            if old_value != new_value {
+                fields.insert(name.clone(), json!({"old": old_value, "new": new_value}));
+            }
+        }
+        for (name, new_value) in new {
+            if !old.contains_key(name) {
+                fields.insert(name.clone(), json!({"old": "", "new": new_value}));
+            }
+        }
+        return json!({"fields": fields});
+    }
+
+    let rows_removed: Vec<&Record> = old_rows.iter().filter(|r| !new_rows.contains(r)).collect();
+    let rows_added: Vec<&Record> = new_rows.iter().filter(|r| !old_rows.contains(r)).collect();
+    json!({"rows_removed": rows_removed, "rows_added": rows_added})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::build;
+    use tempfile::TempDir;
+
+    #[test]
+    fn diff_reports_added_removed_modified_and_unchanged_ids() {
+        let dir = TempDir::new().unwrap();
+        let old_dir = build(&dir, "old", &[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m2,9800000002,amit,amit shah,,,pune",
+            "m3,9800000003,sita,sita ram,,,agra",
+            "m4,9800000004,gita,gita das,,,goa",
+            "m4,9800000005,gita,gita das,,,goa",
+        ]);
+        let new_dir = build(&dir, "new", &[
+            // Row order is no change
+            "m4,9800000005,gita,gita das,,,goa",
+            "m4,9800000004,gita,gita das,,,goa",
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m3,9800000003,sita,sita ram,,,mumbai",
+            "m5,9800000006,john,john doe,,,chennai",
+        ]);
+
+        let summary = diff_indexes(&old_dir, &new_dir, true).unwrap();
+        assert_eq!(summary, json!({"added": 1, "removed": 1, "modified": 1, "unchanged": 2}));
+        let reverse = diff_indexes(&new_dir, &old_dir, false).unwrap();
+        assert_eq!(reverse, json!({"added": 1, "removed": 1, "modified": 1, "unchanged": 2}));
+    }
+
+    #[test]
+    fn single_row_changes_are_reported_per_field() {
+        let record = |pairs: &[(&str, &str)]| -> Record {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        let old = record(&[("master_id", "m3"), ("address", "agra"), ("email", "s@x.com")]);
+        let new = record(&[("master_id", "m3"), ("address", "mumbai"), ("alt", "9800000009")]);
+        assert_eq!(describe_changes(std::slice::from_ref(&old), std::slice::from_ref(&new)), json!({"fields": {
+            "address": {"old": "agra", "new": "mumbai"},
+            "email": {"old": "s@x.com", "new": ""},
+            "alt": {"old": "", "new": "9800000009"},
+        }}));
+
+        // Several rows under one id are compared whole
+        let extra = record(&[("master_id", "m3"), ("address", "pune")]);
+        assert_eq!(describe_changes(&[old.clone(), extra.clone()], &[old.clone(), new.clone()]), json!({
+            "rows_removed": [extra],
+            "rows_added": [new],
+        }));
+    }
+}
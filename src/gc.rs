@@ -0,0 +1,157 @@
+use crate::ready::open_writer;
+use anyhow::Result;
+use std::path::Path;
+use tantivy::{Index, IndexWriter, TantivyDocument};
+use walkdir::WalkDir;
+
+/// Default share of deleted docs that makes a segment worth compacting
+pub const DEFAULT_DELETED_RATIO: f64 = 0.2;
+
+/// Garbage-collect deleted documents by merging only the segments whose
+/// deleted-doc ratio is at least `min_deleted_ratio`.
+/// Clean segments are left untouched, unlike a blanket optimize.
+pub fn gc_index(index_dir: &str, min_deleted_ratio: f64) -> Result<()> {
+    println!("Opening index from: {}", index_dir);
+    let index = Index::open_in_dir(index_dir)?;
+
+    let size_before = dir_size(index_dir);
+
+    // Segment metas keep their files from being garbage collected while
+    // alive, so only the ids outlive this block
+    let metas = index.searchable_segment_metas()?;
+    let segment_count = metas.len();
+    let mut candidates = Vec::new();
+
+    for meta in &metas {
+        let max_doc = meta.max_doc();
+        let deleted = meta.num_deleted_docs();
+        let ratio = if max_doc > 0 { deleted as f64 / max_doc as f64 } else { 0.0 };
+
+        println!(
+            "  Segment {} | docs: {} | deleted: {} ({:.1}%)",
+            meta.id().short_uuid_string(),
+            max_doc,
+            deleted,
+            ratio * 100.0
+        );
+
+        if deleted > 0 && ratio >= min_deleted_ratio {
+            candidates.push(meta.id());
+        }
+    }
+    drop(metas);
+
+    if candidates.is_empty() {
+        println!(
+            "No segments at or above {:.1}% deleted docs - nothing to collect",
+            min_deleted_ratio * 100.0
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Compacting {} of {} segments...",
+        candidates.len(),
+        segment_count
+    );
+
+    let mut writer: IndexWriter<TantivyDocument> = open_writer(&index, index_dir, 50_000_000)?;
+    writer.merge(&candidates).wait()?;
+    // Merged-away segment files are only removed by an explicit GC pass
+    writer.garbage_collect_files().wait()?;
+    writer.wait_merging_threads()?;
+
+    let size_after = dir_size(index_dir);
+
+    println!("✓ Garbage collection complete!");
+    println!("  Size before: {} bytes", size_before);
+    println!("  Size after: {} bytes", size_after);
+    println!("  Bytes reclaimed: {}", size_before.saturating_sub(size_after));
+
+    Ok(())
+}
+
+/// Total on-disk size of all files under a directory
+pub fn dir_size(dir: &str) -> u64 {
+    WalkDir::new(Path::new(dir))
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|meta| meta.is_file())
+        .map(|meta| meta.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::{append_index, build_index, IndexOptions};
+    use crate::test_support::write_csv;
+    use tantivy::Term;
+    use tempfile::TempDir;
+
+    fn write_rows(dir: &TempDir, file: &str, prefix: &str, count: usize) -> String {
+        let rows: Vec<String> = (0..count)
+            .map(|i| format!("{prefix}{i},98{i:08},fname{i},name {i},,u{i}@x.com,house {i} delhi"))
+            .collect();
+        write_csv(dir, file, &rows.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn gc_compacts_mostly_deleted_segments_only() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(&write_rows(&dir, "a.csv", "a", 2000), &index_dir, &IndexOptions::default()).unwrap();
+        append_index(&write_rows(&dir, "b.csv", "b", 2000), &index_dir, &IndexOptions::default()).unwrap();
+
+        let index = Index::open_in_dir(&index_dir).unwrap();
+        let master_id = index.schema().get_field("master_id").unwrap();
+        let mut writer: IndexWriter<TantivyDocument> = index.writer(50_000_000).unwrap();
+        for i in 0..1800 {
+            writer.delete_term(Term::from_field_text(master_id, &format!("a{i}")));
+        }
+        writer.commit().unwrap();
+        drop(writer);
+
+        let segments = |index: &Index| -> Vec<(String, u32)> {
+            let mut metas: Vec<_> = index.searchable_segment_metas().unwrap().iter()
+                .map(|m| (m.id().uuid_string(), m.num_deleted_docs()))
+                .collect();
+            metas.sort();
+            metas
+        };
+        let before = segments(&index);
+        assert_eq!(before.iter().filter(|(_, deleted)| *deleted == 1800).count(), 1);
+        let clean = before.iter().find(|(_, deleted)| *deleted == 0).unwrap().clone();
+        let size_before = dir_size(&index_dir);
+
+        gc_index(&index_dir, DEFAULT_DELETED_RATIO).unwrap();
+
+        let index = Index::open_in_dir(&index_dir).unwrap();
+        let after = segments(&index);
+        assert!(after.contains(&clean), "clean segment was rewritten: {:?}", after);
+        assert!(after.iter().all(|(_, deleted)| *deleted == 0));
+        assert_eq!(index.reader().unwrap().searcher().num_docs(), 2200);
+        assert!(dir_size(&index_dir) < size_before);
+    }
+
+    #[test]
+    fn gc_leaves_an_index_below_the_ratio_alone() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(&write_rows(&dir, "a.csv", "a", 100), &index_dir, &IndexOptions::default()).unwrap();
+        let index = Index::open_in_dir(&index_dir).unwrap();
+        let master_id = index.schema().get_field("master_id").unwrap();
+        let mut writer: IndexWriter<TantivyDocument> = index.writer(50_000_000).unwrap();
+        writer.delete_term(Term::from_field_text(master_id, "a0"));
+        writer.commit().unwrap();
+        drop(writer);
+        let before = index.searchable_segment_ids().unwrap();
+
+        gc_index(&index_dir, DEFAULT_DELETED_RATIO).unwrap();
+
+        assert_eq!(Index::open_in_dir(&index_dir).unwrap().searchable_segment_ids().unwrap(), before);
+    }
+}
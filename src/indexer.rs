@@ -1,29 +1,526 @@
-use crate::schema::build_schema;
+use crate::schema::{build_schema, exact_field_name, extra_column_name, extra_columns, normalize_exact_value, normalize_unicode, substring_field_name, STORED_ONLY_FIELDS, SUBSTRING_FIELDS};
+use crate::gc::dir_size;
+use crate::phone::{mark_canonical_phones, warn_raw_phones, PhoneNumber};
+use crate::ready::{clear_ready, force_unlock, lock_index_dir, mark_ready, open_writer};
+use crate::tokenizer::{has_tokenizer, register_tokenizers};
 use anyhow::Result;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::Path;
 use std::time::Instant;
-use tantivy::{Index, TantivyDocument};
-use csv::ReaderBuilder;
+use tantivy::schema::{Field, FieldType, Schema};
+use tantivy::{DateTime, Index, IndexSettings, TantivyDocument};
+use tantivy::store::{Compressor, ZstdCompressor};
+use csv::{Reader, ReaderBuilder, StringRecord};
+use std::fs::File;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
 
-pub fn build_index(csv_path: &str, index_dir: &str) -> Result<()> {
+/// Rows per batch handed from the CSV reader thread to the indexer
+const CSV_BATCH_SIZE: usize = 10_000;
+/// Batches allowed in flight; bounds parsed-but-unindexed rows to
+/// CSV_BATCH_SIZE * CSV_CHANNEL_CAPACITY
+const CSV_CHANNEL_CAPACITY: usize = 8;
+
+/// Schema field fed by each position of the fixed CSV layout
+pub const CSV_COLUMNS: [&str; 7] = ["master_id", "mobile", "fname", "name", "alt", "email", "address"];
+
+/// Header spellings read into a field besides its own name, as compared
+/// after `normalize_header`
+const HEADER_SYNONYMS: &[(&str, &str)] = &[
+    ("id", "master_id"),
+    ("mobileno", "mobile"),
+    ("mobilenumber", "mobile"),
+    ("phone", "mobile"),
+    ("phoneno", "mobile"),
+    ("phonenumber", "mobile"),
+    ("altmobile", "alt"),
+    ("altno", "alt"),
+    ("altphone", "alt"),
+    ("alternatemobile", "alt"),
+    ("alternatenumber", "alt"),
+    ("fullname", "name"),
+    ("emailid", "email"),
+    ("emailaddress", "email"),
+    ("mail", "email"),
+    ("addr", "address"),
+];
+
+/// Rows remembered for `--dedup` unless `--dedup-limit` says otherwise.
+/// Each is a 64-bit hash in a hash set, roughly 16 bytes with overhead,
+/// so the default costs up to ~800MB on huge datasets.
+pub const DEFAULT_DEDUP_LIMIT: usize = 50_000_000;
+
+/// What makes two rows duplicates for index-time dedup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DedupMode {
+    /// Same value in this field (the configured unique key)
+    Key(String),
+    /// Same value in every column
+    Content,
+}
+
+/// Row validation policy and store settings for index builds
+#[derive(Debug, Clone, Default)]
+pub struct IndexOptions {
+    /// Fields that must be non-empty in every row
+    pub required: Vec<String>,
+    /// Groups of fields where at least one per group must be non-empty
+    pub require_one_of: Vec<Vec<String>>,
+    /// Abort the build on the first invalid row instead of skipping it
+    pub strict: bool,
+    /// Doc store compressor for new indexes (tantivy's default lz4 when None).
+    /// Fixed at creation: appends keep the index's existing setting.
+    pub compression: Option<Compressor>,
+    /// Doc store block size in bytes for new indexes
+    pub block_size: Option<usize>,
+    /// Skip rows duplicating one already indexed in this build
+    pub dedup: Option<DedupMode>,
+    /// Most rows remembered for dedup; once full, later rows are indexed
+    /// without being checked (0 means DEFAULT_DEDUP_LIMIT)
+    pub dedup_limit: usize,
+    /// Tokenizer per TEXT field for new indexes, overriding the built-in
+    /// choice (see `tokenizer::register_custom_tokenizer`)
+    pub tokenizers: HashMap<String, String>,
+    /// CSV header => field, for headers `HEADER_SYNONYMS` doesn't cover
+    pub header_aliases: HashMap<String, String>,
+    /// Remove lock files left by a crashed writer first (see `ready::force_unlock`)
+    pub force_unlock: bool,
+    /// STRING fields given an ngram companion in new indexes, so they can be
+    /// matched by substring (see `schema::SUBSTRING_FIELDS`)
+    pub substring_fields: Vec<String>,
+}
+
+/// Parse a `--compression` value: `none`, `lz4`, `zstd` or `zstd:<level>`
+pub fn parse_compression(value: &str) -> Result<Compressor> {
+    let value = value.trim().to_lowercase();
+    match value.split_once(':') {
+        None if value == "none" => Ok(Compressor::None),
+        None if value == "lz4" => Ok(Compressor::Lz4),
+        None if value == "zstd" => Ok(Compressor::Zstd(ZstdCompressor::default())),
+        Some(("zstd", level)) => {
+            let level = level.parse()
+                .map_err(|_| anyhow::anyhow!("Invalid zstd compression level: {}", level))?;
+            Ok(Compressor::Zstd(ZstdCompressor { compression_level: Some(level) }))
+        }
+        _ => Err(anyhow::anyhow!("Unknown compression '{}', expected none, lz4, zstd or zstd:<level>", value)),
+    }
+}
+
+impl IndexOptions {
+    /// Resolve field names to `CSV_COLUMNS` positions, rejecting unknown
+    /// fields and required fields the CSV has no column for up front
+    fn resolve(&self, columns: &ColumnMap) -> Result<(Vec<usize>, Vec<Vec<usize>>)> {
+        let column = |field: &String| -> Result<usize> {
+            CSV_COLUMNS.iter().position(|c| c == field)
+                .ok_or_else(|| anyhow::anyhow!("Unknown field in row validation rules: {}", field))
+        };
+        let required: Vec<usize> = self.required.iter().map(column).collect::<Result<_>>()?;
+        if let Some(&pos) = required.iter().find(|&&pos| columns.fixed[pos].is_none()) {
+            return Err(anyhow::anyhow!("Required field {} has no column in the CSV", CSV_COLUMNS[pos]));
+        }
+        let groups = self.require_one_of.iter()
+            .map(|group| group.iter().map(column).collect::<Result<_>>())
+            .collect::<Result<_>>()?;
+        Ok((required, groups))
+    }
+
+    /// CSV column holding the dedup key, checked up front like the validation rules
+    fn dedup_column(&self, columns: &ColumnMap) -> Result<Option<usize>> {
+        match &self.dedup {
+            Some(DedupMode::Key(field)) => CSV_COLUMNS.iter().position(|c| c == field)
+                .and_then(|pos| columns.fixed[pos])
+                .map(Some)
+                .ok_or_else(|| anyhow::anyhow!("Dedup key '{}' is not a CSV column", field)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Header as compared when mapping columns: lowercase alphanumerics only,
+/// so `Mobile No.` and `mobile_no` are both `mobileno`
+fn normalize_header(header: &str) -> String {
+    header.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Where each field's values sit in a CSV, resolved from its header row
+#[derive(Debug)]
+pub struct ColumnMap {
+    /// Column of each fixed field, in `CSV_COLUMNS` order; None when the
+    /// CSV has none and the field is left empty
+    pub fixed: [Option<usize>; 7],
+    pub stored_only: Vec<(&'static str, usize)>,
+    /// Remaining columns with identifier-like headers, as extra fields
+    pub extra: Vec<(String, usize)>,
+    /// Fixed fields were located by header rather than by position
+    pub by_header: bool,
+}
+
+impl ColumnMap {
+    /// Print which header feeds each field
+    fn report(&self, headers: &StringRecord) {
+        println!("Column mapping (by {}):", if self.by_header { "header" } else { "position" });
+        for (field, col) in CSV_COLUMNS.iter().zip(&self.fixed) {
+            match col {
+                Some(col) => println!("  {:<13} <- '{}'", field, headers[*col].trim()),
+                None => println!("  {:<13} <- (no column, left empty)", field),
+            }
+        }
+        for (field, col) in &self.stored_only {
+            println!("  {:<13} <- '{}'", field, headers[*col].trim());
+        }
+        for (field, col) in &self.extra {
+            println!("  {:<13} <- '{}' (extra)", field, headers[*col].trim());
+        }
+    }
+}
+
+/// Resolve a CSV header row to fields. Headers are compared after
+/// `normalize_header` with `aliases`, the field names and `HEADER_SYNONYMS`,
+/// in that order, so `Mobile No.` or `Phone` feed `mobile`; the first column
+/// matching a field wins. When no header names a fixed field, the first
+/// seven columns are read in `CSV_COLUMNS` order instead.
+pub fn map_columns(headers: &StringRecord, aliases: &HashMap<String, String>) -> Result<ColumnMap> {
+    let mut alias_fields = HashMap::new();
+    for (header, field) in aliases {
+        if !CSV_COLUMNS.contains(&field.as_str()) && !STORED_ONLY_FIELDS.contains(&field.as_str()) {
+            return Err(anyhow::anyhow!("Header alias '{}' names '{}', which is not a CSV field", header, field));
+        }
+        alias_fields.insert(normalize_header(header), field.as_str());
+    }
+    let resolved: Vec<Option<&str>> = headers.iter()
+        .map(|header| {
+            let key = normalize_header(header);
+            alias_fields.get(&key).copied()
+                .or_else(|| CSV_COLUMNS.iter().chain(STORED_ONLY_FIELDS).find(|f| normalize_header(f) == key).copied())
+                .or_else(|| HEADER_SYNONYMS.iter().find(|(synonym, _)| *synonym == key).map(|(_, f)| *f))
+        })
+        .collect();
+
+    let by_header = resolved.iter().flatten().any(|field| CSV_COLUMNS.contains(field));
+    let mut map = ColumnMap { fixed: [None; 7], stored_only: Vec::new(), extra: Vec::new(), by_header };
+    let mut claimed = vec![false; headers.len()];
+    if by_header {
+        for (col, field) in resolved.iter().enumerate() {
+            let Some(pos) = field.and_then(|field| CSV_COLUMNS.iter().position(|c| *c == field)) else {
+                continue;
+            };
+            claimed[col] = true;
+            if map.fixed[pos].is_some() {
+                println!("[Warning] Column '{}' repeats field {} and is ignored", headers[col].trim(), CSV_COLUMNS[pos]);
+                continue;
+            }
+            map.fixed[pos] = Some(col);
+        }
+    } else {
+        for (pos, claimed) in claimed.iter_mut().take(CSV_COLUMNS.len()).enumerate() {
+            map.fixed[pos] = Some(pos);
+            *claimed = true;
+        }
+    }
+
+    for (col, header) in headers.iter().enumerate().filter(|(col, _)| !claimed[*col]) {
+        if let Some(name) = STORED_ONLY_FIELDS.iter().find(|name| resolved[col] == Some(**name)) {
+            if !map.stored_only.iter().any(|(n, _)| n == name) {
+                map.stored_only.push((name, col));
+            }
+        } else if let Some(name) = extra_column_name(header)
+            && !map.extra.iter().any(|(n, _)| *n == name)
+        {
+            map.extra.push((name, col));
+        }
+    }
+    Ok(map)
+}
+
+/// Rows seen so far in a build, remembered as 64-bit hashes. A hash
+/// collision (vanishingly rare below billions of rows) would drop a row.
+struct SeenRows {
+    hashes: HashSet<u64>,
+    limit: usize,
+    key_column: Option<usize>,
+}
+
+impl SeenRows {
+    fn new(limit: usize, key_column: Option<usize>) -> Self {
+        Self { hashes: HashSet::new(), limit, key_column }
+    }
+
+    /// Record a row, returning false if an identical one was seen. Rows with
+    /// an empty key are never duplicates.
+    fn insert(&mut self, row: &StringRecord) -> bool {
+        let mut hasher = DefaultHasher::new();
+        match self.key_column {
+            Some(col) => {
+                let key = row.get(col).unwrap_or("").trim();
+                if key.is_empty() {
+                    return true;
+                }
+                key.hash(&mut hasher);
+            }
+            None => row.iter().for_each(|value| value.hash(&mut hasher)),
+        }
+        let hash = hasher.finish();
+        if self.hashes.contains(&hash) {
+            return false;
+        }
+        if self.hashes.len() < self.limit {
+            if self.hashes.len() + 1 == self.limit {
+                println!("[Warning] Dedup limit of {} rows reached; later rows are not checked", self.limit);
+            }
+            self.hashes.insert(hash);
+        }
+        true
+    }
+}
+
+/// Describe why a row's fixed values (in `CSV_COLUMNS` order) break the
+/// validation rules, if they do
+fn row_violation(values: &[&str; 7], required: &[usize], groups: &[Vec<usize>]) -> Option<String> {
+    let is_empty = |col: usize| values[col].trim().is_empty();
+    if let Some(&col) = required.iter().find(|&&col| is_empty(col)) {
+        return Some(format!("missing required field {}", CSV_COLUMNS[col]));
+    }
+    if let Some(group) = groups.iter().find(|group| group.iter().all(|&col| is_empty(col))) {
+        let names: Vec<&str> = group.iter().map(|&col| CSV_COLUMNS[col]).collect();
+        return Some(format!("needs at least one of {}", names.join("/")));
+    }
+    None
+}
+
+pub fn build_index(csv_path: &str, index_dir: &str, options: &IndexOptions) -> Result<()> {
     println!("Starting index build...");
     println!("CSV file: {}", csv_path);
     println!("Index directory: {}", index_dir);
 
-    let start_time = Instant::now();
-    let schema = build_schema();
-    let index = Index::create_in_dir(Path::new(index_dir), schema.clone())?;
-    // Increased buffer to 1GB for faster ingestion (was 400MB)
-    // Larger buffer = fewer flushes = faster indexing
-    let mut writer = index.writer(1_000_000_000)?; // 1GB writer buffer
+    let mut settings = IndexSettings::default();
+    if let Some(compression) = options.compression {
+        settings.docstore_compression = compression;
+    }
+    if let Some(block_size) = options.block_size {
+        settings.docstore_blocksize = block_size;
+    }
+    println!(
+        "Doc store: {} compression, {} byte blocks",
+        serde_json::to_value(settings.docstore_compression)?.as_str().unwrap_or("unknown"),
+        settings.docstore_blocksize
+    );
+
+    // Columns not feeding a built-in field become fields of their own
+    let headers = ReaderBuilder::new().has_headers(true).from_path(csv_path)?.headers()?.clone();
+    let extra: Vec<String> = map_columns(&headers, &options.header_aliases)?
+        .extra
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    if !extra.is_empty() {
+        println!("Extra columns: {}", extra.join(", "));
+    }
+
+    // Only fields tokenized by default can take another tokenizer
+    let defaults = build_schema(&extra, &HashMap::new(), &[]);
+    for (field, tokenizer) in &options.tokenizers {
+        let is_text = defaults.get_field(field).ok()
+            .and_then(|f| match defaults.get_field_entry(f).field_type() {
+                FieldType::Str(o) => o.get_indexing_options().map(|i| i.tokenizer() != "raw"),
+                _ => None,
+            })
+            .unwrap_or(false);
+        if !is_text {
+            return Err(anyhow::anyhow!("--tokenizer names '{}', which is not a TEXT field", field));
+        }
+        if !has_tokenizer(tokenizer) {
+            return Err(anyhow::anyhow!("Unknown tokenizer '{}' for field '{}'", tokenizer, field));
+        }
+        println!("Field '{}' tokenized with '{}'", field, tokenizer);
+    }
+    for field in &options.substring_fields {
+        if !SUBSTRING_FIELDS.contains(&field.as_str()) {
+            return Err(anyhow::anyhow!(
+                "--substring-fields names '{}'; substring matching is available for {}",
+                field,
+                SUBSTRING_FIELDS.join(", ")
+            ));
+        }
+        println!("Field '{}' indexed for substring matches", field);
+    }
+    let schema = build_schema(&extra, &options.tokenizers, &options.substring_fields);
+    std::fs::create_dir_all(index_dir)
+        .map_err(|e| anyhow::anyhow!("Cannot create index directory {}: {}", index_dir, e))?;
+    if options.force_unlock {
+        force_unlock(index_dir)?;
+    }
+    let _lock = lock_index_dir(index_dir)?;
 
-    let master = schema.get_field("master_id").unwrap();
-    let mobile = schema.get_field("mobile").unwrap();
-    let alt    = schema.get_field("alt").unwrap();
-    let name   = schema.get_field("name").unwrap();
-    let fname  = schema.get_field("fname").unwrap();
-    let addr   = schema.get_field("address").unwrap();
-    let email  = schema.get_field("email").unwrap();
+    let index = Index::builder()
+        .schema(schema)
+        .settings(settings)
+        .create_in_dir(Path::new(index_dir))?;
+    mark_canonical_phones(index_dir)?;
+    register_tokenizers(&index);
+    let opstamp = index_csv(&index, index_dir, csv_path, options)?;
+    mark_ready(index_dir, opstamp)?;
+
+    println!("  Index size on disk: {} bytes", dir_size(index_dir));
+    Ok(())
+}
+
+/// Append the rows of a CSV to an existing index.
+/// Appended rows get their own `indexed_at` stamp, so they can be isolated
+/// with a query like `indexed_at:[<ts> TO *]`.
+pub fn append_index(csv_path: &str, index_dir: &str, options: &IndexOptions) -> Result<()> {
+    println!("Starting index append...");
+    println!("CSV file: {}", csv_path);
+    println!("Index directory: {}", index_dir);
+
+    let index = Index::open_in_dir(index_dir)?;
+    if options.force_unlock {
+        force_unlock(index_dir)?;
+    }
+    let _lock = lock_index_dir(index_dir)?;
+    register_tokenizers(&index);
+    // Appended rows are canonical; older rows of the index may not be
+    warn_raw_phones(&index.reader()?.searcher(), index_dir)?;
+    clear_ready(index_dir)?;
+    let opstamp = index_csv(&index, index_dir, csv_path, options)?;
+    mark_ready(index_dir, opstamp)
+}
+
+/// Turns the fixed columns of a record into an index document, the same for
+/// every ingestion path: phone canonicalization, exact-match and substring
+/// companions and the `indexed_at` stamp (plus `source_row` for CSV rows)
+pub struct DocumentBuilder {
+    master: Field,
+    mobile: Field,
+    fname: Field,
+    name: Field,
+    alt: Field,
+    email: Field,
+    addr: Field,
+    indexed_at: Field,
+    // Absent on indexes built before source rows were recorded
+    source_row: Option<Field>,
+    // Exact-match companions present in this schema, keyed by source CSV column
+    exact_fields: Vec<(Field, usize)>,
+    // Substring companions present in this schema, keyed by source CSV column
+    substring_fields: Vec<(Field, usize)>,
+    // Stored-only and extra column fields, set by name
+    other_fields: HashMap<String, Field>,
+}
+
+impl DocumentBuilder {
+    pub fn new(schema: &Schema) -> Result<Self> {
+        let field = |name: &str| schema.get_field(name).unwrap();
+        let exact_fields = [("name", 3), ("fname", 2), ("address", 6), ("email", 5)]
+            .into_iter()
+            .filter_map(|(base, col)| schema.get_field(&exact_field_name(base)).ok().map(|f| (f, col)))
+            .collect();
+        let substring_fields = SUBSTRING_FIELDS.iter()
+            .filter_map(|base| {
+                let col = CSV_COLUMNS.iter().position(|c| c == base)?;
+                schema.get_field(&substring_field_name(base)).ok().map(|f| (f, col))
+            })
+            .collect();
+        let indexed_at = schema.get_field("indexed_at")
+            .map_err(|_| anyhow::anyhow!("Index has no indexed_at field; rebuild it with the current schema"))?;
+        let other_fields = STORED_ONLY_FIELDS.iter()
+            .map(|name| name.to_string())
+            .chain(extra_columns(schema))
+            .filter_map(|name| Some((name.clone(), schema.get_field(&name).ok()?)))
+            .collect();
+        Ok(Self {
+            master: field("master_id"),
+            mobile: field("mobile"),
+            fname: field("fname"),
+            name: field("name"),
+            alt: field("alt"),
+            email: field("email"),
+            addr: field("address"),
+            indexed_at,
+            source_row: schema.get_field("source_row").ok(),
+            exact_fields,
+            substring_fields,
+            other_fields,
+        })
+    }
+
+    /// Document for the fixed columns, given in `CSV_COLUMNS` order
+    pub fn build(&self, values: [&str; 7], indexed_at: DateTime) -> TantivyDocument {
+        let mut doc = TantivyDocument::default();
+        let mobile = PhoneNumber::canonicalize(values[1]);
+        let alt = PhoneNumber::canonicalize(values[4]);
+        // CSV column order: id,mobile,fname,name,alt,email,address
+        doc.add_text(self.master, values[0]);  // id -> master_id
+        doc.add_text(self.mobile, &mobile);  // mobile -> mobile
+        doc.add_text(self.fname,  values[2]);  // fname -> fname
+        doc.add_text(self.name,   values[3]);  // name -> name
+        doc.add_text(self.alt,    &alt);  // alt -> alt
+        doc.add_text(self.email,  values[5]);  // email -> email
+        doc.add_text(self.addr,   values[6]);  // address -> address
+        doc.add_date(self.indexed_at, indexed_at);
+        for (field, col) in &self.exact_fields {
+            doc.add_text(*field, normalize_exact_value(values[*col]));
+        }
+        // Substrings of the value as indexed, phones canonical
+        for (field, col) in &self.substring_fields {
+            let value = match col {
+                1 => mobile.as_str(),
+                4 => alt.as_str(),
+                _ => values[*col],
+            };
+            doc.add_text(*field, value);
+        }
+        doc
+    }
+
+    /// Record the CSV line `doc` was read from, when the index keeps it
+    pub fn add_source_row(&self, doc: &mut TantivyDocument, line: u64) {
+        if let Some(field) = self.source_row {
+            doc.add_u64(field, line);
+        }
+    }
+
+    /// Document for a JSON record of field name => string (or number)
+    /// value. Missing fields are empty; unknown fields are rejected, as is a
+    /// record with nothing in any field.
+    pub fn build_json(&self, record: &serde_json::Value, indexed_at: DateTime) -> Result<TantivyDocument> {
+        let serde_json::Value::Object(record) = record else {
+            anyhow::bail!("record is not a JSON object");
+        };
+        let mut values: [Cow<str>; 7] = Default::default();
+        let mut others = Vec::new();
+        for (key, value) in record {
+            let value = match value {
+                serde_json::Value::String(s) => normalize_unicode(s).into_owned(),
+                serde_json::Value::Number(n) => n.to_string(),
+                serde_json::Value::Null => continue,
+                _ => anyhow::bail!("field '{}' must be a string or number", key),
+            };
+            if let Some(col) = CSV_COLUMNS.iter().position(|c| c == key) {
+                values[col] = Cow::Owned(value);
+            } else if let Some(field) = self.other_fields.get(key) {
+                others.push((*field, value));
+            } else {
+                anyhow::bail!("unknown field '{}'", key);
+            }
+        }
+        if values.iter().all(|v| v.trim().is_empty()) && others.iter().all(|(_, v)| v.trim().is_empty()) {
+            anyhow::bail!("record has no values");
+        }
+
+        let mut doc = self.build(std::array::from_fn(|col| values[col].as_ref()), indexed_at);
+        for (field, value) in others {
+            doc.add_text(field, value);
+        }
+        Ok(doc)
+    }
+}
+
+/// Index a CSV into `index`, returning the opstamp of the final commit
+fn index_csv(index: &Index, index_dir: &str, csv_path: &str, options: &IndexOptions) -> Result<u64> {
+    let start_time = Instant::now();
+    let schema = index.schema();
 
     // Optimize CSV reading: larger buffer, no trimming overhead
     let mut rdr = ReaderBuilder::new()
@@ -31,6 +528,55 @@ pub fn build_index(csv_path: &str, index_dir: &str) -> Result<()> {
         .buffer_capacity(1_048_576) // 1MB buffer for CSV reading
         .flexible(false) // Strict parsing for speed
         .from_path(csv_path)?;
+    let headers = rdr.headers()?.clone();
+    let columns = map_columns(&headers, &options.header_aliases)?;
+    columns.report(&headers);
+
+    let (required_cols, one_of_cols) = options.resolve(&columns)?;
+    let mut seen = match &options.dedup {
+        Some(mode) => {
+            println!("Skipping duplicate rows by {}", match mode {
+                DedupMode::Key(field) => field.as_str(),
+                DedupMode::Content => "content",
+            });
+            let limit = if options.dedup_limit == 0 { DEFAULT_DEDUP_LIMIT } else { options.dedup_limit };
+            Some(SeenRows::new(limit, options.dedup_column(&columns)?))
+        }
+        None => None,
+    };
+
+    // Every document in this batch shares one build timestamp
+    let indexed_at = DateTime::from_timestamp_secs(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64,
+    );
+    let indexed_at_secs = indexed_at.into_timestamp_secs();
+    println!("Build timestamp (indexed_at): {}", indexed_at_secs);
+
+    // Increased buffer to 1GB for faster ingestion (was 400MB)
+    // Larger buffer = fewer flushes = faster indexing
+    let mut writer = open_writer(index, index_dir, 1_000_000_000)?; // 1GB writer buffer
+
+    let builder = DocumentBuilder::new(&schema)?;
+
+    // Stored-only fields, for indexes built with them
+    let stored_only: Vec<(Field, usize)> = columns.stored_only
+        .iter()
+        .filter_map(|(name, col)| Some((schema.get_field(name).ok()?, *col)))
+        .collect();
+
+    // Extra columns the index was built with. Appends can't add fields, so
+    // columns the index lacks are skipped.
+    let index_extras = extra_columns(&schema);
+    let mut extras: Vec<(Field, usize)> = Vec::new();
+    for (name, col) in &columns.extra {
+        if index_extras.contains(name) {
+            extras.push((schema.get_field(name)?, *col));
+        } else {
+            println!("[Warning] Column '{}' is not in the index schema and is ignored", headers[*col].trim());
+        }
+    }
 
     let mut record_count = 0u64;
     let mut last_log_time = Instant::now();
@@ -39,18 +585,39 @@ pub fn build_index(csv_path: &str, index_dir: &str) -> Result<()> {
 
     println!("Reading CSV and indexing documents...");
 
-    for row in rdr.records() {
-        let row = row?;
+    // Parse CSV on a dedicated thread and hand rows over in batches through a
+    // bounded channel: parsing overlaps with indexing, and when the writer
+    // stalls on a flush the parser blocks instead of buffering the whole file.
+    let (batch_tx, batch_rx) = mpsc::sync_channel(CSV_CHANNEL_CAPACITY);
+    let csv_thread = thread::spawn(move || read_csv_batches(rdr, batch_tx));
 
-        let mut doc = TantivyDocument::default();
-        // CSV column order: id,mobile,fname,name,alt,email,address
-        doc.add_text(master, &row[0]);  // id -> master_id
-        doc.add_text(mobile, &row[1]);  // mobile -> mobile
-        doc.add_text(fname,  &row[2]);  // fname -> fname
-        doc.add_text(name,   &row[3]);  // name -> name
-        doc.add_text(alt,    &row[4]);  // alt -> alt
-        doc.add_text(email,  &row[5]);  // email -> email
-        doc.add_text(addr,   &row[6]);  // address -> address
+    let mut rejected_count = 0u64;
+    let mut duplicate_count = 0u64;
+
+    for row in batch_rx.into_iter().flatten() {
+        let values: [&str; 7] = std::array::from_fn(|pos| columns.fixed[pos].map_or("", |col| &row[col]));
+        if let Some(reason) = row_violation(&values, &required_cols, &one_of_cols) {
+            let line = row.position().map_or(0, |p| p.line());
+            if options.strict {
+                return Err(anyhow::anyhow!("Invalid row at line {}: {}", line, reason));
+            }
+            rejected_count += 1;
+            // Warn about the first few; the total is reported at the end
+            if rejected_count <= 10 {
+                println!("[Warning] Skipping row at line {}: {}", line, reason);
+            }
+            continue;
+        }
+        if seen.as_mut().is_some_and(|seen| !seen.insert(&row)) {
+            duplicate_count += 1;
+            continue;
+        }
+
+        let mut doc = builder.build(values, indexed_at);
+        builder.add_source_row(&mut doc, row.position().map_or(0, |p| p.line()));
+        for (field, col) in stored_only.iter().chain(&extras) {
+            doc.add_text(*field, &row[*col]);
+        }
 
         writer.add_document(doc)?;
 
@@ -59,7 +626,7 @@ pub fn build_index(csv_path: &str, index_dir: &str) -> Result<()> {
         // Periodic commits for large datasets (every 10M records) to prevent memory issues
         // This also makes progress visible if process is interrupted
         // Note: After commit(), the writer can continue to be used - no need to recreate
-        if record_count % 10_000_000 == 0 {
+        if record_count.is_multiple_of(10_000_000) {
             println!("[Checkpoint] Committing at {} records...", record_count);
             writer.commit()?;
             // Writer can continue to be used after commit - no recreation needed
@@ -67,7 +634,7 @@ pub fn build_index(csv_path: &str, index_dir: &str) -> Result<()> {
 
         // Log progress every N seconds or every N records
         let elapsed = last_log_time.elapsed().as_secs_f64();
-        if elapsed >= log_interval_secs || record_count % log_interval_records == 0 {
+        if elapsed >= log_interval_secs || record_count.is_multiple_of(log_interval_records) {
             let total_elapsed = start_time.elapsed().as_secs_f64();
             let records_per_sec = record_count as f64 / total_elapsed;
             let estimated_total_time = if records_per_sec > 0.0 {
@@ -85,18 +652,364 @@ pub fn build_index(csv_path: &str, index_dir: &str) -> Result<()> {
         }
     }
 
+    // Surface CSV parse errors before committing anything past the last checkpoint
+    csv_thread.join().map_err(|_| anyhow::anyhow!("CSV reader thread panicked"))??;
+
     println!("Committing index...");
-    writer.commit()?;
+    let opstamp = writer.commit()?;
 
     let total_elapsed = start_time.elapsed();
     let records_per_sec = record_count as f64 / total_elapsed.as_secs_f64();
 
     println!("✓ Indexing complete!");
     println!("  Total records indexed: {}", record_count);
+    println!("  Rows rejected by validation: {}", rejected_count);
+    if options.dedup.is_some() {
+        println!("  Duplicate rows skipped: {}", duplicate_count);
+    }
     println!("  Total time: {:.2} seconds ({:.2} minutes)",
              total_elapsed.as_secs_f64(),
              total_elapsed.as_secs_f64() / 60.0);
     println!("  Average speed: {:.0} records/second", records_per_sec);
+    println!("  Filter this batch with: indexed_at:[{} TO *]", indexed_at_secs);
+
+    Ok(opstamp)
+}
+
+/// NFC-normalize every value of a row. Rows that are already normalized
+/// (the common case) are passed through without copying.
+fn normalize_record(row: StringRecord) -> StringRecord {
+    if row.iter().all(|v| matches!(normalize_unicode(v), Cow::Borrowed(_))) {
+        return row;
+    }
+    let mut normalized: StringRecord = row.iter().map(normalize_unicode).collect();
+    normalized.set_position(row.position().cloned());
+    normalized
+}
 
+/// Read CSV rows in batches and send them to the indexing thread.
+/// Stops early if the receiver hangs up (the indexer failed).
+fn read_csv_batches(mut rdr: Reader<File>, batch_tx: SyncSender<Vec<StringRecord>>) -> Result<()> {
+    let mut batch = Vec::with_capacity(CSV_BATCH_SIZE);
+    for row in rdr.records() {
+        batch.push(normalize_record(row?));
+        if batch.len() == CSV_BATCH_SIZE {
+            let full = std::mem::replace(&mut batch, Vec::with_capacity(CSV_BATCH_SIZE));
+            if batch_tx.send(full).is_err() {
+                return Ok(());
+            }
+        }
+    }
+    if !batch.is_empty() {
+        let _ = batch_tx.send(batch);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_service::{SearchConfig, SearchOptions, SearchService};
+    use crate::test_support::write_csv;
+    use tantivy::time::OffsetDateTime;
+    use tantivy::time::format_description::well_known::Rfc3339;
+    use tempfile::TempDir;
+
+    fn master_ids(results: &[serde_json::Value]) -> Vec<String> {
+        let mut ids: Vec<String> = results.iter().map(|r| r["master_id"].as_str().unwrap().to_string()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn indexed_at_isolates_appended_rows() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        let first = write_csv(&dir, "first.csv", &["a1,9800000001,ravi,ravi kumar,,,delhi", "a2,9800000002,amit,amit shah,,,pune"]);
+        let second = write_csv(&dir, "second.csv", &["b1,9800000003,ravi,ravi verma,,,delhi"]);
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(&first, &index_dir, &IndexOptions::default()).unwrap();
+        // Stamps have second resolution
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        append_index(&second, &index_dir, &IndexOptions::default()).unwrap();
+
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let appended = service.search("master_id:b1", &SearchOptions::default()).unwrap();
+        let stamp = OffsetDateTime::parse(appended.results[0]["indexed_at"].as_str().unwrap(), &Rfc3339).unwrap().unix_timestamp();
+
+        let recent = service.search(&format!("indexed_at:[{} TO *]", stamp), &SearchOptions::default()).unwrap();
+        assert_eq!(master_ids(&recent.results), ["b1"]);
+        let older = service.search(&format!("indexed_at:[* TO {}}}", stamp), &SearchOptions::default()).unwrap();
+        assert_eq!(master_ids(&older.results), ["a1", "a2"]);
+        // The filter combines with ordinary clauses
+        let recent_delhi = service.search(&format!("address:delhi AND indexed_at:[{} TO *]", stamp), &SearchOptions::default()).unwrap();
+        assert_eq!(master_ids(&recent_delhi.results), ["b1"]);
+    }
+
+    #[test]
+    fn large_csv_is_indexed_completely() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        // Several reader batches, the last one partial
+        let rows: Vec<String> = (0..CSV_BATCH_SIZE * 5 + 123)
+            .map(|i| format!("m{i},98{i:08},f{i},name {i},,,house {i} delhi"))
+            .collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(&write_csv(&dir, "large.csv", &rows), &index_dir, &IndexOptions::default()).unwrap();
+
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let all = service.search("address:delhi", &SearchOptions::default()).unwrap();
+        assert_eq!(all.total_matches, rows.len());
+        let last = service.search(&format!("master_id:m{}", rows.len() - 1), &SearchOptions::default()).unwrap();
+        assert_eq!(last.results[0]["name"], format!("name {}", rows.len() - 1));
+    }
+
+    #[test]
+    fn csv_reader_blocks_once_the_channel_is_full() {
+        let dir = TempDir::new().unwrap();
+        let rows: Vec<String> = (0..CSV_BATCH_SIZE * (CSV_CHANNEL_CAPACITY + 4)).map(|i| format!("m{i},,,,,,")).collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let rdr = ReaderBuilder::new().has_headers(true).from_path(write_csv(&dir, "rows.csv", &rows)).unwrap();
+
+        let (batch_tx, batch_rx) = mpsc::sync_channel(CSV_CHANNEL_CAPACITY);
+        let reader = thread::spawn(move || read_csv_batches(rdr, batch_tx));
+        // Nothing consumed: the reader parks with CSV_CHANNEL_CAPACITY
+        // batches queued instead of reading on
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        assert!(!reader.is_finished());
+
+        let batches: Vec<Vec<StringRecord>> = batch_rx.into_iter().collect();
+        reader.join().unwrap().unwrap();
+        assert_eq!(batches.len(), CSV_CHANNEL_CAPACITY + 4);
+        assert!(batches.iter().all(|batch| batch.len() == CSV_BATCH_SIZE));
+    }
+
+    #[test]
+    fn rows_breaking_the_required_rules_are_skipped_or_abort_the_build() {
+        let dir = TempDir::new().unwrap();
+        let csv = write_csv(&dir, "people.csv", &[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            ",9800000002,amit,amit shah,,,pune",
+            "m3,,neha,neha gupta,,,delhi",
+            "m4,,zoe,zoe kumar,,zoe@example.org,delhi",
+        ]);
+        let rules = IndexOptions {
+            required: vec!["master_id".to_string()],
+            require_one_of: vec![vec!["mobile".to_string(), "email".to_string()]],
+            ..IndexOptions::default()
+        };
+
+        let lenient_dir = dir.path().join("lenient").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&lenient_dir).unwrap();
+        build_index(&csv, &lenient_dir, &rules).unwrap();
+        let service = SearchService::new(&lenient_dir, SearchConfig::default()).unwrap();
+        let kept = service.search("address:delhi OR address:pune", &SearchOptions::default()).unwrap();
+        assert_eq!(master_ids(&kept.results), ["m1", "m4"]);
+
+        let strict_dir = dir.path().join("strict").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&strict_dir).unwrap();
+        let err = build_index(&csv, &strict_dir, &IndexOptions { strict: true, ..rules }).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid row at line 3: missing required field master_id");
+    }
+
+    #[test]
+    fn decomposed_names_match_composed_queries() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        // "Jose\u{301}" is José with a combining acute accent
+        let csv = write_csv(&dir, "people.csv", &["m1,9800000001,jose\u{301},jose\u{301} garcia,,,madrid", "m2,9800000002,jose,jose garcia,,,madrid"]);
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let options = SearchOptions::default();
+        for query in ["fname:josé", "name=\"josé garcia\"", "fname:jose\u{301}"] {
+            let results = service.search(query, &options).unwrap();
+            assert_eq!(master_ids(&results.results), ["m1"], "{}", query);
+        }
+        assert_eq!(service.search("fname:josé", &options).unwrap().results[0]["fname"], "josé");
+    }
+
+    #[test]
+    fn compressed_index_returns_identical_documents() {
+        let dir = TempDir::new().unwrap();
+        let rows: Vec<String> = (0..500).map(|i| format!("m{i},98{i:08},ravi,ravi kumar {i},,r{i}@example.com,house {i} mg road delhi")).collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let csv = write_csv(&dir, "people.csv", &rows);
+        let plain_dir = dir.path().join("plain").to_str().unwrap().to_string();
+        let zstd_dir = dir.path().join("zstd").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&plain_dir).unwrap();
+        build_index(&csv, &plain_dir, &IndexOptions { compression: Some(Compressor::None), ..IndexOptions::default() }).unwrap();
+        let zstd = IndexOptions {
+            compression: Some(parse_compression("zstd:9").unwrap()),
+            block_size: Some(64 * 1024),
+            ..IndexOptions::default()
+        };
+        std::fs::create_dir_all(&zstd_dir).unwrap();
+        build_index(&csv, &zstd_dir, &zstd).unwrap();
+
+        let settings = Index::open_in_dir(&zstd_dir).unwrap().settings().clone();
+        assert_eq!(settings.docstore_compression, Compressor::Zstd(ZstdCompressor { compression_level: Some(9) }));
+        assert_eq!(settings.docstore_blocksize, 64 * 1024);
+        assert!(dir_size(&zstd_dir) < dir_size(&plain_dir));
+
+        let documents = |index_dir: &str| {
+            let service = SearchService::new(index_dir, SearchConfig::default()).unwrap();
+            let mut results = service.search("address:delhi", &SearchOptions::default()).unwrap().results;
+            for result in &mut results {
+                result.as_object_mut().unwrap().remove("indexed_at");
+            }
+            results.sort_by_key(|r| r["master_id"].as_str().unwrap().to_string());
+            results
+        };
+        let plain = documents(&plain_dir);
+        assert_eq!(plain.len(), rows.len());
+        assert_eq!(documents(&zstd_dir), plain);
+    }
+
+    #[test]
+    fn duplicate_rows_are_indexed_once_when_dedup_is_on() {
+        let dir = TempDir::new().unwrap();
+        let csv = write_csv(&dir, "rows.csv", &[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m1,9800000001,ravi,ravi kumar,,,noida",
+            ",9800000002,amit,amit shah,,,delhi",
+            ",9800000003,neha,neha gupta,,,delhi",
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            ",9800000002,amit,amit shah,,,delhi",
+        ]);
+        let indexed = |name: &str, dedup: Option<DedupMode>, dedup_limit: usize| {
+            let index_dir = dir.path().join(name).to_str().unwrap().to_string();
+            std::fs::create_dir_all(&index_dir).unwrap();
+            build_index(&csv, &index_dir, &IndexOptions { dedup, dedup_limit, ..IndexOptions::default() }).unwrap();
+            let index = Index::open_in_dir(&index_dir).unwrap();
+            index.reader().unwrap().searcher().num_docs()
+        };
+
+        assert_eq!(indexed("plain", None, 0), 7);
+        // Identical rows collapse; the noida row differs in one column
+        assert_eq!(indexed("content", Some(DedupMode::Content), 0), 4);
+        // Rows with an empty key are never duplicates
+        assert_eq!(indexed("key", Some(DedupMode::Key("master_id".to_string())), 0), 4);
+        // Past the limit rows are still checked against the remembered ones,
+        // but not remembered themselves
+        assert_eq!(indexed("bounded", Some(DedupMode::Content), 1), 5);
+
+        std::fs::create_dir_all(dir.path().join("bad").to_str().unwrap()).unwrap();
+        let err = build_index(&csv, dir.path().join("bad").to_str().unwrap(), &IndexOptions {
+            dedup: Some(DedupMode::Key("pincode".to_string())),
+            ..IndexOptions::default()
+        }).unwrap_err();
+        assert_eq!(err.to_string(), "Dedup key 'pincode' is not a CSV column");
+    }
+
+    #[test]
+    fn a_build_into_a_locked_directory_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        let csv = write_csv(&dir, "rows.csv", &["m1,9800000001,ravi,ravi kumar,,,delhi"]);
+        // Missing parents are created
+        let index_dir = dir.path().join("nested/index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+
+        // As if another build were running
+        let held = crate::ready::lock_index_dir(&index_dir).unwrap();
+        let err = append_index(&csv, &index_dir, &IndexOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), format!("Index {} is locked by another process", index_dir));
+        let fresh_dir = dir.path().join("fresh").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&fresh_dir).unwrap();
+        let _other = crate::ready::lock_index_dir(&fresh_dir).unwrap();
+        assert!(build_index(&csv, &fresh_dir, &IndexOptions::default()).unwrap_err().to_string().contains("is locked by another process"));
+
+        // Released with its holder
+        drop(held);
+        append_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+    }
+
+    #[test]
+    fn messy_headers_are_mapped_to_their_fields() {
+        let headers = StringRecord::from(vec!["Master ID", "Full Name", "Mobile No.", "E-Mail ID", "City", "Gender"]);
+        let aliases = HashMap::from([("city".to_string(), "address".to_string())]);
+        let columns = map_columns(&headers, &aliases).unwrap();
+        assert!(columns.by_header);
+        // master_id, mobile, fname, name, alt, email, address
+        assert_eq!(columns.fixed, [Some(0), Some(2), None, Some(1), None, Some(3), Some(4)]);
+        assert_eq!(columns.extra, [("gender".to_string(), 5)]);
+
+        let dir = TempDir::new().unwrap();
+        let csv = dir.path().join("messy.csv");
+        std::fs::write(&csv, "Master ID,Full Name,Mobile No.,E-Mail ID,City,Gender\n\
+            m1,Ravi Kumar,+91 98000 00001,Ravi@Example.com,Delhi,M\n").unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        let options = IndexOptions { header_aliases: aliases, ..IndexOptions::default() };
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(csv.to_str().unwrap(), &index_dir, &options).unwrap();
+
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let results = service.search("mobile:9800000001 AND address:delhi AND name:kumar", &SearchOptions::default()).unwrap();
+        assert_eq!(master_ids(&results.results), ["m1"]);
+        assert_eq!(results.results[0]["email"], "Ravi@Example.com");
+        assert_eq!(results.results[0]["gender"], "M");
+
+        let bad = HashMap::from([("city".to_string(), "town".to_string())]);
+        let err = map_columns(&headers, &bad).unwrap_err();
+        assert_eq!(err.to_string(), "Header alias 'city' names 'town', which is not a CSV field");
+    }
+
+    #[test]
+    fn results_carry_the_csv_line_they_were_read_from() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        // m2's quoted address spans two lines, so m3 starts on line 5
+        let csv = write_csv(&dir, "rows.csv", &[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m2,9800000002,amit,amit shah,,,\"flat 4\nmg road pune\"",
+            "m3,9800000003,sita,sita ram,,,delhi",
+        ]);
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let options = SearchOptions { source_row: true, ..SearchOptions::default() };
+        let rows: HashMap<String, u64> = service.search("name:ravi OR name:amit OR name:sita", &options).unwrap()
+            .results.iter()
+            .map(|r| (r["master_id"].as_str().unwrap().to_string(), r["source_row"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(rows, HashMap::from([("m1".to_string(), 2), ("m2".to_string(), 3), ("m3".to_string(), 5)]));
+
+        let plain = service.search("name:ravi", &SearchOptions::default()).unwrap();
+        assert!(plain.results[0].get("source_row").is_none());
+    }
+
+    #[test]
+    fn contains_finds_a_mobile_by_an_interior_substring() {
+        let dir = TempDir::new().unwrap();
+        let csv = write_csv(&dir, "rows.csv", &[
+            "m1,9812345678,ravi,ravi kumar,,,delhi",
+            "m2,9800000002,amit,amit shah,9812300000,,pune",
+        ]);
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        let options = IndexOptions { substring_fields: vec!["mobile".to_string()], ..IndexOptions::default() };
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(&csv, &index_dir, &options).unwrap();
+
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let search = |query: &str| service.search(query, &SearchOptions::default());
+        assert_eq!(master_ids(&search("mobile:contains:3456").unwrap().results), ["m1"]);
+        assert_eq!(master_ids(&search("mobile:contains:123").unwrap().results), ["m1"]);
+        // The STRING field itself stays exact
+        assert!(search("mobile:3456").unwrap().results.is_empty());
+        let code = |query: &str| search(query).unwrap_err().downcast::<crate::query_parser::QueryError>().unwrap().code;
+        assert_eq!(code("mobile:contains:34"), "VALUE_TOO_SHORT");
+        assert_eq!(code("alt:contains:123"), "SUBSTRING_NOT_INDEXED");
+
+        std::fs::create_dir_all(dir.path().join("other").to_str().unwrap()).unwrap();
+        let err = build_index(&csv, dir.path().join("other").to_str().unwrap(), &IndexOptions {
+            substring_fields: vec!["name".to_string()],
+            ..IndexOptions::default()
+        }).unwrap_err();
+        assert!(err.to_string().starts_with("--substring-fields names 'name'"), "{}", err);
+    }
+}
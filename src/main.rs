@@ -1,50 +1,253 @@
 mod schema;
+mod tokenizer;
 mod indexer;
 mod search;
 mod query_parser;
 mod dump;
+mod sample;
 mod search_service;
 mod api;
+mod gc;
+mod diff;
+mod reindex;
+mod ready;
+mod phone;
+mod proto;
+mod access;
+mod transform;
+mod compare;
+mod terms;
+#[cfg(test)]
+pub(crate) mod test_support;
 
 use anyhow::Result;
+use std::path::PathBuf;
 
 fn main() -> Result<()> {
     let args: Vec<String> = std::env::args().collect();
+    tokenizer::register_env_tokenizers()?;
 
     match args.get(1).map(String::as_str) {
         Some("index") => {
             let csv = &args[2];
             let index_dir = &args[3];
-            indexer::build_index(csv, index_dir)?;
+            indexer::build_index(csv, index_dir, &index_options(&args)?)?;
+        }
+        Some("append") => {
+            let csv = &args[2];
+            let index_dir = &args[3];
+            indexer::append_index(csv, index_dir, &index_options(&args)?)?;
+        }
+        Some("reindex") => {
+            let src_dir = &args[2];
+            let dst_dir = &args[3];
+            reindex::reindex(src_dir, dst_dir)?;
+        }
+        Some("merge-indexes") => {
+            let dst_dir = &args[2];
+            reindex::merge_indexes(dst_dir, &args[3..])?;
         }
         Some("search") => {
             let index_dir = &args[2];
             let query = &args[3];
-            search::search(index_dir, query)?;
+            let config = search_service::SearchConfig::from_env();
+            let highlight = has_flag(&args, "--highlight")
+                .then(|| search::Highlight::for_stdout(has_flag(&args, "--no-color")));
+            search::search(index_dir, query, &config.key_field, config.skip_failed_docs, highlight)?;
+        }
+        Some("export") => {
+            let index_dir = &args[2];
+            let query = &args[3];
+            let out_file = &args[4];
+            let config = search_service::SearchConfig::from_env();
+            search::export_results(index_dir, query, out_file, &config.key_field, config.skip_failed_docs)?;
+        }
+        Some("search-repl") => {
+            let index_dir = &args[2];
+            search::search_repl(index_dir, search_service::SearchConfig::from_env())?;
+        }
+        Some("search-range") => {
+            let base_dir = &args[2];
+            let from = &args[3];
+            let to = &args[4];
+            let query = &args[5];
+            let config = search_service::SearchConfig::from_env();
+            search::search_partitions(base_dir, from, to, query, config)?;
         }
         Some("dump") => {
             let index_dir = &args[2];
             let limit = args.get(3).and_then(|s| s.parse().ok()).unwrap_or(1000);
             dump::dump_index(index_dir, limit)?;
         }
-        Some("serve") => {
+        Some("sample") => {
+            let index_dir = &args[2];
+            let count = args.get(3).and_then(|s| s.parse().ok()).unwrap_or(10);
+            let seed = args.get(4).and_then(|s| s.parse().ok());
+            sample::sample_index(index_dir, count, seed)?;
+        }
+        Some("gc") => {
+            let index_dir = &args[2];
+            let ratio = args.get(3)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(gc::DEFAULT_DELETED_RATIO);
+            if has_flag(&args, "--force-unlock") {
+                ready::force_unlock(index_dir)?;
+            }
+            gc::gc_index(index_dir, ratio)?;
+        }
+        Some("diff") => {
+            let old_dir = &args[2];
+            let new_dir = &args[3];
+            let details = args.iter().skip(4).any(|a| a == "--details");
+            diff::diff_indexes(old_dir, new_dir, details)?;
+        }
+        Some("terms-export") => {
+            let index_dir = &args[2];
+            let field = &args[3];
+            let out_file = &args[4];
+            terms::export_terms(index_dir, field, out_file)?;
+        }
+        Some("schema") => {
             let index_dir = args.get(2).ok_or_else(|| anyhow::anyhow!("Missing index_dir"))?;
-            let host = args.get(3).map(String::as_str).unwrap_or("0.0.0.0");
-            let port = args.get(4)
+            schema::print_index_schema(index_dir)?;
+        }
+        Some("serve") => {
+            // Positional arguments come before any --flag
+            let positional: Vec<&String> = args.iter().skip(2).take_while(|a| !a.starts_with("--")).collect();
+            let index_dir = positional.first().ok_or_else(|| anyhow::anyhow!("Missing index_dir"))?;
+            let host = positional.get(1).map(|s| s.as_str()).unwrap_or("0.0.0.0");
+            let port = positional.get(2)
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(8080);
+            let warmup = flag_values(&args, "--warmup").last().map(|path| path.to_string());
+            let tls = tls_files(&args)?;
+            let limit_flag = |name: &str| flag_values(&args, name).last()
+                .map(|value| value.parse::<usize>().map_err(|_| anyhow::anyhow!("Invalid {}: {}", name, value)))
+                .transpose();
 
             let rt = tokio::runtime::Runtime::new()?;
-            rt.block_on(api::start_server(index_dir.clone(), host.to_string(), port))?;
+            let mut config = search_service::SearchConfig::from_env();
+            // Flags win over the environment
+            if let Some(default_limit) = limit_flag("--default-limit")? {
+                config.default_limit = default_limit;
+            }
+            if let Some(max_limit) = limit_flag("--max-limit")? {
+                config.max_limit = max_limit;
+            }
+            rt.block_on(api::start_server(index_dir.to_string(), host.to_string(), port, config, warmup, tls))?;
         }
         _ => {
             println!("Usage:");
-            println!("  cargo run --release index <csv> <index_dir>");
-            println!("  cargo run --release search <index_dir> \"query\"");
+            println!("  cargo run --release index <csv> <index_dir> [index options]   (index_dir is created if missing)");
+            println!("  cargo run --release append <csv> <index_dir> [index options]");
+            println!("  cargo run --release reindex <src_index_dir> <dst_index_dir>");
+            println!("  cargo run --release merge-indexes <out_dir> <in_dir1> <in_dir2> ...");
+            println!("  cargo run --release search <index_dir> \"query\" [--highlight [--no-color]]   (matched terms in color, or **marked**)");
+            println!("  cargo run --release export <index_dir> \"query\" <out_file>   (every match as NDJSON, beyond the result limit)");
+            println!("  cargo run --release search-repl <index_dir>   (one query per stdin line until EOF)");
+            println!("  cargo run --release search-range <base_dir> <from> <to> \"query\"   (daily indexes <base_dir>/YYYY-MM-DD)");
             println!("  cargo run --release dump <index_dir> [limit]");
-            println!("  cargo run --release serve <index_dir> [host] [port]");
+            println!("  cargo run --release sample <index_dir> [n] [seed]");
+            println!("  cargo run --release gc <index_dir> [min_deleted_ratio] [--force-unlock]");
+            println!("  cargo run --release diff <old_index_dir> <new_index_dir> [--details]");
+            println!("  cargo run --release terms-export <index_dir> <field> <out_file>   (.json/.jsonl/.ndjson for NDJSON, else TSV)");
+            println!("  cargo run --release schema <index_dir>");
+            println!("  cargo run --release serve <index_dir> [host] [port] [--warmup <queries_file>] [--default-limit <n>] [--max-limit <n>] [--tls-cert <pem> --tls-key <pem>]");
+            println!("  cargo run --release serve <index_dir> <socket_path>   (Unix domain socket, e.g. /run/knotorious.sock)");
+            println!();
+            println!("Index options:");
+            println!("  --required <field,...>       Skip rows where any of these fields is empty");
+            println!("  --require-one-of <field,...> Skip rows where all of these fields are empty (repeatable)");
+            println!("  --strict                     Abort on the first invalid row instead of skipping");
+            println!("  --compression <codec>        Doc store compression for new indexes: none, lz4, zstd, zstd:<level>");
+            println!("  --block-size <bytes>         Doc store block size for new indexes");
+            println!("  --dedup <key|content>        Skip rows repeating an earlier row's KEY_FIELD value or every column");
+            println!("  --dedup-limit <rows>         Rows remembered for --dedup (default {}, ~16 bytes each)", indexer::DEFAULT_DEDUP_LIMIT);
+            println!("  --tokenizer <field=name>     Tokenizer of a TEXT field for new indexes, built in or from CUSTOM_TOKENIZERS (repeatable)");
+            println!("  --substring-fields <field,...> Index mobile/alt/master_id of new indexes for field:contains:<substring> queries");
+            println!("  --force-unlock               Remove lock files left by a crashed writer, if no live process holds them");
+            println!("  --header-alias <header=field> Read the CSV column with this header into a field (repeatable);");
+            println!("                               headers match ignoring case and punctuation, common spellings built in");
         }
     }
 
     Ok(())
 }
+
+/// Values following every occurrence of `--name` in the arguments
+fn flag_values<'a>(args: &'a [String], name: &str) -> Vec<&'a str> {
+    args.windows(2)
+        .filter(|pair| pair[0] == name)
+        .map(|pair| pair[1].as_str())
+        .collect()
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == name)
+}
+
+fn split_fields(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|f| f.trim().to_lowercase())
+        .filter(|f| !f.is_empty())
+        .collect()
+}
+
+/// `--tls-cert` and `--tls-key`, each falling back to the `TLS_CERT` and
+/// `TLS_KEY` environment variables. Both or neither must be given.
+fn tls_files(args: &[String]) -> anyhow::Result<Option<api::TlsFiles>> {
+    let path = |flag: &str, var: &str| {
+        flag_values(args, flag).last().map(PathBuf::from)
+            .or_else(|| std::env::var_os(var).filter(|v| !v.is_empty()).map(PathBuf::from))
+    };
+    match (path("--tls-cert", "TLS_CERT"), path("--tls-key", "TLS_KEY")) {
+        (Some(cert), Some(key)) => Ok(Some(api::TlsFiles { cert, key })),
+        (None, None) => Ok(None),
+        (Some(_), None) => Err(anyhow::anyhow!("--tls-cert (TLS_CERT) needs --tls-key (TLS_KEY)")),
+        (None, Some(_)) => Err(anyhow::anyhow!("--tls-key (TLS_KEY) needs --tls-cert (TLS_CERT)")),
+    }
+}
+
+fn index_options(args: &[String]) -> anyhow::Result<indexer::IndexOptions> {
+    let compression = flag_values(args, "--compression").last()
+        .map(|value| indexer::parse_compression(value))
+        .transpose()?;
+    let block_size = flag_values(args, "--block-size").last()
+        .map(|value| value.parse().map_err(|_| anyhow::anyhow!("Invalid --block-size: {}", value)))
+        .transpose()?;
+    let dedup = flag_values(args, "--dedup").last()
+        .map(|mode| match *mode {
+            "key" => Ok(indexer::DedupMode::Key(search_service::SearchConfig::from_env().key_field)),
+            "content" => Ok(indexer::DedupMode::Content),
+            other => Err(anyhow::anyhow!("Unknown --dedup mode '{}', expected key or content", other)),
+        })
+        .transpose()?;
+    let dedup_limit = flag_values(args, "--dedup-limit").last()
+        .map(|value| value.parse().map_err(|_| anyhow::anyhow!("Invalid --dedup-limit: {}", value)))
+        .transpose()?
+        .unwrap_or(indexer::DEFAULT_DEDUP_LIMIT);
+    let tokenizers = flag_values(args, "--tokenizer").into_iter()
+        .map(|pair| pair.split_once('=')
+            .map(|(field, name)| (field.trim().to_lowercase(), name.trim().to_string()))
+            .ok_or_else(|| anyhow::anyhow!("Invalid --tokenizer '{}', expected field=name", pair)))
+        .collect::<anyhow::Result<_>>()?;
+    let header_aliases = flag_values(args, "--header-alias").into_iter()
+        .map(|pair| pair.split_once('=')
+            .map(|(header, field)| (header.trim().to_string(), field.trim().to_lowercase()))
+            .ok_or_else(|| anyhow::anyhow!("Invalid --header-alias '{}', expected header=field", pair)))
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(indexer::IndexOptions {
+        required: flag_values(args, "--required").into_iter().flat_map(split_fields).collect(),
+        require_one_of: flag_values(args, "--require-one-of").into_iter().map(split_fields).collect(),
+        strict: has_flag(args, "--strict"),
+        compression,
+        block_size,
+        dedup,
+        dedup_limit,
+        tokenizers,
+        header_aliases,
+        force_unlock: has_flag(args, "--force-unlock"),
+        substring_fields: flag_values(args, "--substring-fields").into_iter().flat_map(split_fields).collect(),
+    })
+}
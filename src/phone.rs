@@ -0,0 +1,141 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use tantivy::Searcher;
+
+/// Country calling code of the dataset; numbers carrying it are stored
+/// without it so `+91 98765 43210` and `98765 43210` are the same number
+pub const DEFAULT_COUNTRY_CODE: &str = "91";
+
+/// Digits in a national number for `DEFAULT_COUNTRY_CODE`
+const NATIONAL_NUMBER_LENGTH: usize = 10;
+
+/// File in the index directory marking that its `mobile` and `alt` terms are
+/// canonical. Written when build, reindex and merge create an index; indexes
+/// created before phone canonicalization don't have it.
+pub const CANONICAL_PHONES_MARKER: &str = "PHONES_CANONICAL";
+
+/// Phone fields stored as canonical `PhoneNumber`s
+const PHONE_FIELDS: &[&str] = &["mobile", "alt"];
+
+/// A phone number in canonical form: national digits only, without
+/// formatting, country code or trunk prefix.
+///
+/// Used for the `mobile` and `alt` fields at index time, query time and in
+/// fan-out, so every spelling of a number lands on the same term.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhoneNumber(String);
+
+impl PhoneNumber {
+    /// Parse a phone number written with optional `+`, spaces, dashes, dots
+    /// and parentheses. Returns None for anything else (letters, wildcards...).
+    ///
+    /// - `+91 98765-43210`, `0091 9876543210`, `919876543210` -> `9876543210`
+    /// - `09876543210` (trunk prefix) -> `9876543210`
+    /// - other lengths keep all their digits
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        if !raw.chars().all(|c| c.is_ascii_digit() || matches!(c, ' ' | '-' | '.' | '(' | ')' | '+')) {
+            return None;
+        }
+        let digits: String = raw.chars().filter(char::is_ascii_digit).collect();
+        if digits.is_empty() {
+            return None;
+        }
+
+        // International prefix written as 00 instead of +
+        let digits = digits.strip_prefix("00").unwrap_or(&digits);
+        let national = if digits.len() == NATIONAL_NUMBER_LENGTH + DEFAULT_COUNTRY_CODE.len() {
+            digits.strip_prefix(DEFAULT_COUNTRY_CODE).unwrap_or(digits)
+        } else if digits.len() == NATIONAL_NUMBER_LENGTH + 1 {
+            digits.strip_prefix('0').unwrap_or(digits)
+        } else {
+            digits
+        };
+        Some(Self(national.to_string()))
+    }
+
+    /// Canonical form of a phone value, falling back to the plain field
+    /// normalization (spaces removed, lowercased) for values that aren't
+    /// phone numbers so they still match themselves
+    pub fn canonicalize(raw: &str) -> String {
+        match Self::parse(raw) {
+            Some(phone) => phone.0,
+            None => raw.replace(' ', "").to_lowercase(),
+        }
+    }
+}
+
+/// Mark a newly created index as holding canonical phone terms
+pub fn mark_canonical_phones(index_dir: &str) -> Result<()> {
+    fs::write(Path::new(index_dir).join(CANONICAL_PHONES_MARKER), "")?;
+    Ok(())
+}
+
+/// For an index without the canonical phones marker: the first `mobile` or
+/// `alt` term not in canonical form, as `(field, term)`. Queries canonicalize
+/// phone values, so such rows are only found again once the index is
+/// rebuilt or reindexed. None when marked, or when every term is canonical
+/// anyway (then the whole term dictionary is read once).
+pub fn raw_phone_term(searcher: &Searcher, index_dir: &str) -> Result<Option<(String, String)>> {
+    if Path::new(index_dir).join(CANONICAL_PHONES_MARKER).exists() {
+        return Ok(None);
+    }
+    let schema = searcher.schema();
+    for name in PHONE_FIELDS {
+        let Ok(field) = schema.get_field(name) else {
+            continue;
+        };
+        for segment in searcher.segment_readers() {
+            let inverted = segment.inverted_index(field)?;
+            let mut stream = inverted.terms().stream()?;
+            while stream.advance() {
+                let term = String::from_utf8_lossy(stream.key());
+                if PhoneNumber::canonicalize(&term) != term {
+                    return Ok(Some((name.to_string(), term.into_owned())));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Warn when `raw_phone_term` finds the index still holds raw phone values
+pub fn warn_raw_phones(searcher: &Searcher, index_dir: &str) -> Result<()> {
+    if let Some((field, term)) = raw_phone_term(searcher, index_dir)? {
+        eprintln!(
+            "[WARN] Index {} was built before phone numbers were canonicalized ({} value {:?}): \
+             mobile/alt lookups will miss such rows until it is rebuilt or reindexed",
+            index_dir, field, term,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_forms_of_one_number_agree() {
+        for raw in ["9876543210", "+91 98765 43210", "+91-98765-43210", "0091 9876543210", "919876543210", "09876543210", "(98765) 43210", " 98765.43210 "] {
+            assert_eq!(PhoneNumber::canonicalize(raw), "9876543210", "{raw}");
+        }
+    }
+
+    #[test]
+    fn other_lengths_keep_their_digits() {
+        // 11 digits read as a trunk prefix plus a national number
+        assert_eq!(PhoneNumber::canonicalize("011 2345 6789"), "1123456789");
+        assert_eq!(PhoneNumber::canonicalize("12345"), "12345");
+        assert_eq!(PhoneNumber::canonicalize("+1 415 555 0100"), "14155550100");
+    }
+
+    #[test]
+    fn non_numbers_fall_back_to_plain_normalization() {
+        assert_eq!(PhoneNumber::parse("98765*"), None);
+        assert_eq!(PhoneNumber::parse(""), None);
+        assert_eq!(PhoneNumber::canonicalize("N A"), "na");
+        assert_eq!(PhoneNumber::canonicalize("98765*"), "98765*");
+    }
+}
@@ -0,0 +1,251 @@
+use crate::api::SearchResponse;
+use serde_json::Value;
+
+/// Media type of protobuf responses; JSON stays the default
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Whether an `Accept` header asks for protobuf (`application/x-protobuf`
+/// or `application/protobuf`); quality values are not weighed
+pub fn accepts_protobuf(accept: &str) -> bool {
+    accept.split(',').any(|media| {
+        let media = media.split(';').next().unwrap_or("").trim();
+        media.eq_ignore_ascii_case(PROTOBUF_CONTENT_TYPE) || media.eq_ignore_ascii_case("application/protobuf")
+    })
+}
+
+/// Encode a search response as the `SearchResponse` message of
+/// `proto/search.proto`. The messages are small and fixed, so the wire
+/// format is written directly rather than through generated code.
+pub fn encode_search_response(response: &SearchResponse) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(response.results.len() * 256);
+    let mut record = Vec::new();
+    let mut entry = Vec::new();
+    for result in &response.results {
+        record.clear();
+        if let Value::Object(fields) = result {
+            for (name, value) in fields {
+                let text = match value {
+                    Value::Null => continue,
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                entry.clear();
+                put_bytes(&mut entry, 1, name.as_bytes());
+                put_bytes(&mut entry, 2, text.as_bytes());
+                put_bytes(&mut record, 1, &entry);
+            }
+        }
+        put_bytes(&mut buf, 1, &record);
+    }
+
+    put_uint(&mut buf, 2, response.total_matches as u64);
+    put_uint(&mut buf, 3, response.results_returned as u64);
+    put_uint(&mut buf, 4, response.phrase_fallback as u64);
+    put_uint(&mut buf, 5, response.index_version);
+    put_double(&mut buf, 6, response.query_parse_time_ms);
+    put_double(&mut buf, 7, response.search_execution_time_ms);
+    put_double(&mut buf, 8, response.document_retrieval_time_ms);
+    put_double(&mut buf, 9, response.total_time_ms);
+    if let Some(warning) = &response.warning {
+        put_bytes(&mut buf, 10, warning.as_bytes());
+    }
+    if let Some(debug_query) = &response.debug_query {
+        put_bytes(&mut buf, 11, debug_query.as_bytes());
+    }
+    if let Some(stats) = &response.stats {
+        put_bytes(&mut buf, 12, stats.to_string().as_bytes());
+    }
+    for (field, count) in response.field_match_counts.iter().flatten() {
+        entry.clear();
+        put_bytes(&mut entry, 1, field.as_bytes());
+        put_uint(&mut entry, 2, *count as u64);
+        put_bytes(&mut buf, 13, &entry);
+    }
+    if let Some(duplicates_removed) = response.duplicates_removed {
+        put_uint(&mut buf, 14, duplicates_removed as u64);
+    }
+    if let Some(stored_bytes) = response.stored_bytes {
+        put_uint(&mut buf, 15, stored_bytes);
+    }
+    if let Some(failed_retrievals) = response.failed_retrievals {
+        put_uint(&mut buf, 16, failed_retrievals as u64);
+    }
+    if let Some(zero_reason) = response.zero_reason {
+        put_bytes(&mut buf, 17, zero_reason.as_bytes());
+    }
+    if !response.executed_query.is_empty() {
+        put_bytes(&mut buf, 18, response.executed_query.as_bytes());
+    }
+    if let Some(results_hash) = &response.results_hash {
+        put_bytes(&mut buf, 19, results_hash.as_bytes());
+    }
+    buf
+}
+
+// Wire types
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LEN: u64 = 2;
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_key(buf: &mut Vec<u8>, field: u64, wire_type: u64) {
+    put_varint(buf, (field << 3) | wire_type);
+}
+
+/// Scalars equal to their default are omitted, as proto3 encoders do
+fn put_uint(buf: &mut Vec<u8>, field: u64, value: u64) {
+    if value != 0 {
+        put_key(buf, field, VARINT);
+        put_varint(buf, value);
+    }
+}
+
+fn put_double(buf: &mut Vec<u8>, field: u64, value: f64) {
+    if value != 0.0 {
+        put_key(buf, field, FIXED64);
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn put_bytes(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_key(buf, field, LEN);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// Decoded field of a message: a varint, a fixed64 or length-delimited bytes
+    #[derive(Debug, PartialEq)]
+    enum Wire {
+        Uint(u64),
+        Fixed(f64),
+        Bytes(Vec<u8>),
+    }
+
+    fn read_varint(buf: &[u8], pos: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = buf[*pos];
+            *pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    /// (field number, value) of every field of a message, in wire order
+    fn decode(buf: &[u8]) -> Vec<(u64, Wire)> {
+        let mut fields = Vec::new();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let key = read_varint(buf, &mut pos);
+            let value = match key & 7 {
+                VARINT => Wire::Uint(read_varint(buf, &mut pos)),
+                FIXED64 => {
+                    pos += 8;
+                    Wire::Fixed(f64::from_le_bytes(buf[pos - 8..pos].try_into().unwrap()))
+                }
+                LEN => {
+                    let len = read_varint(buf, &mut pos) as usize;
+                    pos += len;
+                    Wire::Bytes(buf[pos - len..pos].to_vec())
+                }
+                wire_type => panic!("unexpected wire type {}", wire_type),
+            };
+            fields.push((key >> 3, value));
+        }
+        fields
+    }
+
+    fn bytes(wire: &Wire) -> &[u8] {
+        match wire {
+            Wire::Bytes(bytes) => bytes,
+            other => panic!("expected bytes, got {:?}", other),
+        }
+    }
+
+    /// `Record` messages back into field => value maps
+    fn records(fields: &[(u64, Wire)]) -> Vec<BTreeMap<String, String>> {
+        fields.iter()
+            .filter(|(number, _)| *number == 1)
+            .map(|(_, record)| {
+                decode(bytes(record)).iter()
+                    .map(|(_, entry)| {
+                        let entry = decode(bytes(entry));
+                        let text = |i: usize| String::from_utf8(bytes(&entry[i].1).to_vec()).unwrap();
+                        (text(0), text(1))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn search_response_decodes_back_into_its_records() {
+        let response = SearchResponse {
+            results: vec![
+                serde_json::json!({"master_id": "m1", "name": "ravi kumar", "score": 1.5, "email": null}),
+                serde_json::json!({"master_id": "m2", "name": "zoë", "address": ""}),
+            ],
+            total_matches: 300,
+            results_returned: 2,
+            phrase_fallback: false,
+            index_version: 7,
+            query_parse_time_ms: 0.25,
+            search_execution_time_ms: 0.0,
+            document_retrieval_time_ms: 0.0,
+            total_time_ms: 1.0,
+            warning: Some("limit reduced to the maximum of 2".to_string()),
+            debug_query: None,
+            stats: None,
+            field_match_counts: Some(BTreeMap::from([("name".to_string(), 2)])),
+            duplicates_removed: None,
+            stored_bytes: None,
+            failed_retrievals: None,
+            zero_reason: None,
+            executed_query: "name:ravi".to_string(),
+            results_hash: None,
+        };
+        let fields = decode(&encode_search_response(&response));
+
+        let expected: Vec<BTreeMap<String, String>> = vec![
+            [("master_id", "m1"), ("name", "ravi kumar"), ("score", "1.5")].map(|(k, v)| (k.to_string(), v.to_string())).into(),
+            [("address", ""), ("master_id", "m2"), ("name", "zoë")].map(|(k, v)| (k.to_string(), v.to_string())).into(),
+        ];
+        assert_eq!(records(&fields), expected);
+
+        let field = |number: u64| fields.iter().find(|(n, _)| *n == number).map(|(_, wire)| wire);
+        assert_eq!(field(2), Some(&Wire::Uint(300)));
+        assert_eq!(field(5), Some(&Wire::Uint(7)));
+        assert_eq!(field(6), Some(&Wire::Fixed(0.25)));
+        // Defaults are left out
+        assert_eq!(field(4), None);
+        assert_eq!(field(7), None);
+        assert_eq!(field(10).map(bytes), Some(&b"limit reduced to the maximum of 2"[..]));
+        let counts = decode(bytes(field(13).unwrap()));
+        assert_eq!(counts, [(1, Wire::Bytes(b"name".to_vec())), (2, Wire::Uint(2))]);
+        assert_eq!(field(18).map(bytes), Some(&b"name:ravi"[..]));
+    }
+
+    #[test]
+    fn protobuf_is_chosen_only_when_accepted() {
+        assert!(accepts_protobuf("application/x-protobuf"));
+        assert!(accepts_protobuf("application/json;q=0.5, Application/Protobuf"));
+        assert!(!accepts_protobuf("application/json"));
+        assert!(!accepts_protobuf("*/*"));
+    }
+}
@@ -1,58 +1,391 @@
+use crate::phone::PhoneNumber;
+use crate::schema::{exact_field_name, normalize_exact_value, normalize_unicode, substring_field_name, STORED_ONLY_FIELDS, SUBSTRING_FIELDS};
+use crate::tokenizer::{SUBSTRING_MAX_GRAM, SUBSTRING_MIN_GRAM};
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
-use tantivy::schema::{Field, Schema};
-use tantivy::{Index, Term};
-use tantivy::query::{Query, TermQuery, BooleanQuery, Occur, QueryParser};
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
+use tantivy::schema::{Field, FieldType, Schema};
+use tantivy::time::OffsetDateTime;
+use tantivy::time::format_description::well_known::Rfc3339;
+use tantivy::{DateTime, Index, Term};
+use tantivy::query::{Query, TermQuery, BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, PhraseQuery, QueryParser, RangeQuery, RegexQuery};
+use tantivy::tokenizer::TextAnalyzer;
 use tantivy::schema::IndexRecordOption;
 
+/// Client-facing query error with a stable machine-readable code.
+/// Carried inside `anyhow::Error`; the API downcasts it to answer with a 400.
+#[derive(Debug, Clone)]
+pub struct QueryError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl QueryError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+
+    /// A field value that is blank or has nothing searchable once normalized
+    pub fn empty_value(field: &str) -> Self {
+        Self::new("EMPTY_VALUE", format!("Field '{}' has an empty value after normalization", field))
+    }
+
+    /// A field value with fewer letters and digits than the field's minimum
+    pub fn value_too_short(field: &str, value: &str, minimum: usize) -> Self {
+        Self::new(
+            "VALUE_TOO_SHORT",
+            format!("Field '{}' value '{}' is too short, the minimum is {} letters or digits", field, value, minimum),
+        )
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Default cap on clauses per query, guarding against pathological
+/// queries that would build a huge `BooleanQuery`
+pub const DEFAULT_MAX_CLAUSES: usize = 64;
+
+/// Default budget for `estimate_cost`, in rough units of one term lookup
+pub const DEFAULT_MAX_QUERY_COST: u64 = 1_000;
+
+/// Cost of a pattern word with no literal prefix: its automaton has to walk
+/// the whole term dictionary. Each leading literal character halves it.
+const PATTERN_SCAN_COST: u64 = 1_024;
+/// Cost of a clause that may seed an identity fan-out (several extra searches)
+const FANOUT_COST: u64 = 50;
+/// Cost of an `indexed_at` range over the fast field
+const RANGE_COST: u64 = 10;
+
+/// Cost of each fuzzy word, whose automaton walks the term dictionary
+const FUZZY_COST: u64 = 20;
+
 #[derive(Debug, Clone)]
 pub enum QueryOp {
     And,
     Or,
 }
 
+/// How a clause value is matched against its field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// `field:value` - tokenized matching on TEXT fields, exact on STRING fields
+    Tokenized,
+    /// `field="value"` - the whole stored value must equal `value`
+    Exact,
+    /// `field~value` - tokens OR phrase OR whole value, ranked in reverse order
+    Blended,
+    /// `field|value` - any of the whitespace-separated words, each matched as with `field:word`
+    AnyToken,
+    /// `field:value~N` - every word of a TEXT field within N typos (edits);
+    /// a bare `~` allows one
+    Fuzzy(u8),
+    /// `field:contains:value` - `value` anywhere in a STRING field indexed
+    /// for substrings (`--substring-fields`), e.g. `mobile:contains:4321`
+    Contains,
+}
+
+/// Largest edit distance of a fuzzy clause
+pub const MAX_FUZZY_DISTANCE: u8 = 2;
+
+/// Score boosts of the stricter match modes in a blended clause
+const PHRASE_BOOST: f32 = 2.0;
+const EXACT_BOOST: f32 = 4.0;
+
+/// When tokenized TEXT clauses are matched as phrases (words adjacent and
+/// in order) rather than as a token AND (all words anywhere in the field)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PhraseMode {
+    /// Phrase only for a single-clause query, tokens otherwise
+    #[default]
+    Auto,
+    /// Always phrase
+    Phrase,
+    /// Always tokens
+    Tokens,
+    /// Phrase first; the search service retries with tokens on zero results
+    PhraseThenTokens,
+    /// Every tokenized TEXT clause blended as with `field~value`
+    Blend,
+}
+
 #[derive(Debug, Clone)]
 pub struct QueryClause {
     pub field: String,
     pub value: String,
+    pub mode: MatchMode,
+    pub boost: Option<f32>, // `value^N`: the clause's score multiplied by N
 }
 
 #[derive(Debug, Clone)]
 pub struct ParsedQuery {
     pub clauses: Vec<QueryClause>,
     pub ops: Vec<QueryOp>, // ops[i] connects clauses[i] and clauses[i+1]
+    pub excluded: Vec<QueryClause>, // `NOT` clauses: documents matching any are left out
+    pub dropped: Vec<QueryClause>, // clauses removed for having nothing searchable
+}
+
+/// Append a clause, recording the operator that joins it to the previous one
+/// (AND when none was written, e.g. `a:x b:y` or `a:x, b:y`)
+fn push_clause(clauses: &mut Vec<QueryClause>, ops: &mut Vec<QueryOp>, clause: QueryClause, op: Option<QueryOp>) {
+    if !clauses.is_empty() {
+        ops.push(op.unwrap_or(QueryOp::And));
+    }
+    clauses.push(clause);
 }
 
 pub struct CustomQueryParser {
     schema: Schema,
     index: Index,
     field_map: HashMap<String, Field>,
+    exact_fields: HashMap<String, Field>, // TEXT field name -> raw companion
+    substring_fields: HashMap<String, Field>, // STRING field name -> ngram companion
+    max_clauses: usize,
+    max_cost: u64,
+    boost_exact: bool, // rank whole-value and phrase matches above scattered tokens
+    allow_patterns: bool, // wildcard and regex clauses
+    query_analyzers: HashMap<String, TextAnalyzer>, // TEXT field name -> query-time analyzer
+    min_value_lengths: HashMap<String, usize>, // field name -> fewest letters and digits a value may have
+    unsearchable_fields: HashSet<String>, // returned with results, rejected in queries
 }
 
 impl CustomQueryParser {
     pub fn new(schema: Schema, index: Index) -> Self {
-        let mut field_map = HashMap::new();
-        field_map.insert("master_id".to_string(), schema.get_field("master_id").unwrap());
-        field_map.insert("mobile".to_string(), schema.get_field("mobile").unwrap());
-        field_map.insert("alt".to_string(), schema.get_field("alt").unwrap());
-        field_map.insert("name".to_string(), schema.get_field("name").unwrap());
-        field_map.insert("fname".to_string(), schema.get_field("fname").unwrap());
-        field_map.insert("address".to_string(), schema.get_field("address").unwrap());
-        field_map.insert("email".to_string(), schema.get_field("email").unwrap());
+        // Raw companions exist only for fields opted in at index build time
+        let exact_fields: HashMap<String, Field> = ["name", "fname", "address", "email"]
+            .into_iter()
+            .filter_map(|base| {
+                schema.get_field(&exact_field_name(base)).ok()
+                    .map(|f| (base.to_string(), f))
+            })
+            .collect();
+        let substring_fields: HashMap<String, Field> = SUBSTRING_FIELDS.iter()
+            .filter_map(|base| {
+                schema.get_field(&substring_field_name(base)).ok()
+                    .map(|f| (base.to_string(), f))
+            })
+            .collect();
+
+        // Every indexed field of the index is queryable by name, including
+        // extra CSV columns; indexes built before indexed_at existed simply
+        // don't expose the filter. Raw `_exact` companions are only reached
+        // through `field="value"`, ngram `_ngram` ones through `field:contains:`.
+        let field_map: HashMap<String, Field> = schema.fields()
+            .filter(|(_, entry)| entry.is_indexed())
+            .filter(|(field, _)| !exact_fields.values().chain(substring_fields.values()).any(|companion| companion == field))
+            .map(|(field, entry)| (entry.name().to_string(), field))
+            .collect();
 
         Self {
             schema,
             index,
             field_map,
+            exact_fields,
+            substring_fields,
+            max_clauses: DEFAULT_MAX_CLAUSES,
+            max_cost: DEFAULT_MAX_QUERY_COST,
+            boost_exact: true,
+            allow_patterns: true,
+            query_analyzers: HashMap::new(),
+            min_value_lengths: HashMap::new(),
+            unsearchable_fields: HashSet::new(),
         }
     }
 
+    /// Analyze TEXT clause values with these analyzers instead of the built-in
+    /// token handling, independently of the index-time tokenizer
+    /// (see `tokenizer::build_query_analyzer` for the mismatch risk)
+    pub fn with_query_analyzers(mut self, query_analyzers: HashMap<String, TextAnalyzer>) -> Self {
+        self.query_analyzers = query_analyzers;
+        self
+    }
+
+    /// Allow or forbid wildcard (`jo*`, `*son`) and regex (`/jo.n/`) clauses.
+    /// When forbidden they fail with FEATURE_DISABLED instead of running.
+    pub fn with_patterns(mut self, allow_patterns: bool) -> Self {
+        self.allow_patterns = allow_patterns;
+        self
+    }
+
+    /// Reject clause values of these fields with fewer letters and digits
+    /// than the given minimum (VALUE_TOO_SHORT), e.g. `name` => 3 so a one
+    /// or two letter name can't match half the index
+    pub fn with_min_value_lengths(mut self, min_value_lengths: HashMap<String, usize>) -> Self {
+        self.min_value_lengths = min_value_lengths;
+        self
+    }
+
+    /// Reject clauses on these fields with FIELD_NOT_SEARCHABLE, as for
+    /// stored-only fields, even though the index has them indexed
+    pub fn with_unsearchable_fields(mut self, unsearchable_fields: HashSet<String>) -> Self {
+        self.unsearchable_fields = unsearchable_fields;
+        self
+    }
+
+    /// Whether queries may target `field_name` (see `with_unsearchable_fields`)
+    pub fn is_searchable(&self, field_name: &str) -> bool {
+        !self.unsearchable_fields.contains(field_name)
+    }
+
+    /// Override the maximum number of clauses a query may contain
+    pub fn with_max_clauses(mut self, max_clauses: usize) -> Self {
+        self.max_clauses = max_clauses;
+        self
+    }
+
+    /// Boost exact and phrase matches of every tokenized TEXT clause, as
+    /// `field~value` does, without changing which documents match
+    pub fn with_exact_boost(mut self, boost_exact: bool) -> Self {
+        self.boost_exact = boost_exact;
+        self
+    }
+
+    /// Override the cost budget queries are checked against (see `estimate_cost`)
+    pub fn with_max_cost(mut self, max_cost: u64) -> Self {
+        self.max_cost = max_cost;
+        self
+    }
+
+    /// Rough cost of running a parsed query, summed over its clauses:
+    /// one unit per term, `PATTERN_SCAN_COST` halved per literal prefix
+    /// character for each wildcard word or regex, `FANOUT_COST` for clauses
+    /// that can seed a fan-out and `RANGE_COST` for date and master_id ranges.
+    pub fn estimate_cost(&self, parsed: &ParsedQuery) -> u64 {
+        parsed.clauses.iter().chain(&parsed.excluded).map(|clause| self.clause_cost(clause)).sum()
+    }
+
+    fn clause_cost(&self, clause: &QueryClause) -> u64 {
+        let value = clause.value.trim();
+        if is_pattern_value(value) {
+            // Disabled or bare patterns are rejected with their own error when built
+            if !self.allow_patterns {
+                return 0;
+            }
+            let pattern_cost = |literal_prefix: usize| PATTERN_SCAN_COST >> literal_prefix.min(7);
+            if let Some(regex) = value.strip_prefix('/').and_then(|v| v.strip_suffix('/')) {
+                return pattern_cost(regex.chars().take_while(|c| c.is_alphanumeric()).count());
+            }
+            return value.split_whitespace()
+                .map(|word| match word.find('*') {
+                    Some(_) if !word.chars().any(char::is_alphanumeric) => 0,
+                    Some(star) => pattern_cost(word[..star].chars().count()),
+                    None => 1,
+                })
+                .sum();
+        }
+
+        if clause.field == "indexed_at" || (clause.field == "master_id" && is_range_value(value)) {
+            return RANGE_COST;
+        }
+        let tokens = value.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()).count().max(1) as u64;
+        let terms = match clause.mode {
+            MatchMode::Exact => 1,
+            MatchMode::Tokenized | MatchMode::AnyToken => tokens,
+            // Tokens and phrase over the same terms, plus the exact companion
+            MatchMode::Blended => 2 * tokens + 1,
+            MatchMode::Fuzzy(_) => tokens * FUZZY_COST,
+            // One ngram term per window of the value
+            MatchMode::Contains => self.contains_value(clause).chars().count().saturating_sub(SUBSTRING_MAX_GRAM) as u64 + 1,
+        };
+        match clause.field.as_str() {
+            "mobile" | "email" if clause.mode != MatchMode::Contains => terms + FANOUT_COST,
+            _ => terms,
+        }
+    }
+
+    fn check_cost(&self, parsed: &ParsedQuery) -> Result<()> {
+        let cost = self.estimate_cost(parsed);
+        if cost > self.max_cost {
+            return Err(QueryError::new(
+                "QUERY_TOO_EXPENSIVE",
+                format!("Query cost {} exceeds the budget of {}", cost, self.max_cost),
+            ).into());
+        }
+        Ok(())
+    }
+
+    fn check_clause_count(&self, count: usize) -> Result<()> {
+        if count > self.max_clauses {
+            return Err(QueryError::new(
+                "TOO_MANY_CLAUSES",
+                format!("Query has more than {} clauses", self.max_clauses),
+            ).into());
+        }
+        Ok(())
+    }
+
+    /// Exclusions are exempt: a short NOT value only narrows the results
+    fn check_min_lengths(&self, parsed: &ParsedQuery) -> Result<()> {
+        for clause in &parsed.clauses {
+            let Some(&minimum) = self.min_value_lengths.get(&clause.field) else {
+                continue;
+            };
+            // Wildcards, separators and punctuation don't narrow a match
+            let length = clause.value.chars().filter(|c| c.is_alphanumeric()).count();
+            if length < minimum {
+                return Err(QueryError::value_too_short(&clause.field, &clause.value, minimum).into());
+            }
+        }
+        Ok(())
+    }
+
     /// Parse query string into clauses and operators
-    /// Supports: "field:value", "field:value AND field:value", "field:value OR field:value"
+    /// Supports: "field:value", "field:value AND field:value", "field:value OR field:value",
+    /// "field:value AND NOT field:value"
     pub fn parse(&self, query_str: &str) -> Result<ParsedQuery> {
+        let mut parsed = self.parse_clauses(query_str)?;
+        self.drop_empty_clauses(&mut parsed)?;
+        self.check_min_lengths(&parsed)?;
+        self.check_cost(&parsed)?;
+        Ok(parsed)
+    }
+
+    /// Parse a query template whose values hold `{0}`, `{1}`... placeholders
+    /// and fill them with `params`.
+    ///
+    /// The template's structure is parsed before substitution, so a parameter
+    /// only ever becomes (part of) a clause value: commas, AND/OR and field
+    /// separators in it are literal text. Wildcards and regexes are rejected,
+    /// since they would turn a term lookup into a dictionary scan.
+    pub fn parse_template(&self, template: &str, params: &[String]) -> Result<ParsedQuery> {
+        let invalid = |param: &str| QueryError::new(
+            "INVALID_PARAMETER",
+            format!("Template parameter '{}' cannot contain wildcard or regex syntax", param),
+        );
+        if let Some(param) = params.iter().find(|param| param.contains('*')) {
+            return Err(invalid(param).into());
+        }
+        let mut parsed = self.parse_clauses(template)?;
+        for clause in parsed.clauses.iter_mut().chain(&mut parsed.excluded) {
+            let filled = fill_placeholders(&clause.value, params)?;
+            // e.g. `/abc` and `x/` filled into `{0} {1}` would form a regex
+            if is_pattern_value(&filled) != is_pattern_value(&clause.value) {
+                return Err(invalid(&filled).into());
+            }
+            clause.value = filled;
+        }
+        self.drop_empty_clauses(&mut parsed)?;
+        self.check_min_lengths(&parsed)?;
+        self.check_cost(&parsed)?;
+        Ok(parsed)
+    }
+
+    /// Fields a query or template clause names, checking only its structure
+    /// (known fields, separators, clause count) and not its values
+    pub fn clause_fields(&self, query_str: &str) -> Result<Vec<String>> {
+        let parsed = self.parse_clauses(query_str)?;
+        Ok(parsed.clauses.into_iter().chain(parsed.excluded).map(|clause| clause.field).collect())
+    }
+
+    /// Split a query into clauses and operators, before any check on values
+    fn parse_clauses(&self, query_str: &str) -> Result<ParsedQuery> {
         let query_str = query_str.trim();
-        let mut clauses = Vec::new();
-        let mut ops = Vec::new();
+        let mut parsed = ParsedQuery { clauses: Vec::new(), ops: Vec::new(), excluded: Vec::new(), dropped: Vec::new() };
 
         // Handle comma-separated queries (treated as AND)
         // Also handle AND/OR operators
@@ -65,6 +398,7 @@ impl CustomQueryParser {
 
         let mut current_clause = String::new();
         let mut current_op: Option<QueryOp> = None;
+        let mut negated = false;
 
         for part in parts {
             let part = part.trim();
@@ -72,30 +406,21 @@ impl CustomQueryParser {
                 continue;
             }
 
-            // Check for AND/OR operators
-            if part.eq_ignore_ascii_case("AND") {
+            // Check for AND/OR/NOT operators
+            let is_and = part.eq_ignore_ascii_case("AND");
+            let is_or = part.eq_ignore_ascii_case("OR");
+            let is_not = part.eq_ignore_ascii_case("NOT");
+            if is_and || is_or || is_not {
                 if !current_clause.is_empty() {
-                    if let Some(clause) = self.parse_clause(&current_clause)? {
-                        clauses.push(clause);
-                        if let Some(op) = current_op.take() {
-                            ops.push(op);
-                        }
-                    }
+                    self.finish_clause(&mut parsed, &current_clause, current_op.take(), negated)?;
+                    negated = false;
                     current_clause.clear();
                 }
-                current_op = Some(QueryOp::And);
-                continue;
-            } else if part.eq_ignore_ascii_case("OR") {
-                if !current_clause.is_empty() {
-                    if let Some(clause) = self.parse_clause(&current_clause)? {
-                        clauses.push(clause);
-                        if let Some(op) = current_op.take() {
-                            ops.push(op);
-                        }
-                    }
-                    current_clause.clear();
+                if is_not {
+                    negated = true;
+                } else {
+                    current_op = Some(if is_and { QueryOp::And } else { QueryOp::Or });
                 }
-                current_op = Some(QueryOp::Or);
                 continue;
             }
 
@@ -110,19 +435,71 @@ impl CustomQueryParser {
 
         // Handle last clause
         if !current_clause.is_empty() {
-            if let Some(clause) = self.parse_clause(&current_clause)? {
-                clauses.push(clause);
-            }
+            self.finish_clause(&mut parsed, &current_clause, current_op.take(), negated)?;
+        } else if negated {
+            return Err(QueryError::new("INVALID_NOT", "NOT must be followed by a clause").into());
+        }
+        self.check_clause_count(parsed.clauses.len() + parsed.excluded.len())?;
+        if parsed.clauses.is_empty() && !parsed.excluded.is_empty() {
+            return Err(QueryError::new("INVALID_NOT", "A query needs at least one clause without NOT").into());
         }
 
-        // Default to AND if no operators specified
-        if clauses.len() > 1 && ops.is_empty() {
-            for _ in 0..clauses.len() - 1 {
-                ops.push(QueryOp::And);
-            }
+        Ok(parsed)
+    }
+
+    /// Add a parsed clause: to the exclusions after NOT, otherwise joined to
+    /// the previous clause by `op`
+    fn finish_clause(&self, parsed: &mut ParsedQuery, clause_str: &str, op: Option<QueryOp>, negated: bool) -> Result<()> {
+        let Some(clause) = self.parse_clause(clause_str)? else {
+            return Ok(());
+        };
+        if !negated {
+            push_clause(&mut parsed.clauses, &mut parsed.ops, clause, op);
+        } else if matches!(op, Some(QueryOp::Or)) {
+            // Exclusions apply to the whole query, so there is nothing to OR them with
+            return Err(QueryError::new("INVALID_NOT", "OR NOT is not supported; use AND NOT").into());
+        } else {
+            parsed.excluded.push(clause);
         }
+        self.check_clause_count(parsed.clauses.len() + parsed.excluded.len())
+    }
+
+    /// Remove clauses whose value has nothing searchable left after
+    /// normalization (e.g. `name:!!`) instead of failing the whole query.
+    ///
+    /// A dropped AND clause no longer constrains its group, as if it had not
+    /// been written; a dropped OR alternative matches nothing, so removing it
+    /// changes nothing. Only when every clause is empty does the query fail
+    /// with EMPTY_VALUE.
+    fn drop_empty_clauses(&self, parsed: &mut ParsedQuery) -> Result<()> {
+        // An empty exclusion excludes nothing
+        let (excluded, empty): (Vec<QueryClause>, Vec<QueryClause>) = std::mem::take(&mut parsed.excluded)
+            .into_iter()
+            .partition(|clause| self.normalize_clause_value(clause).is_ok());
+        parsed.excluded = excluded;
+        parsed.dropped.extend(empty);
 
-        Ok(ParsedQuery { clauses, ops })
+        let mut idx = 0;
+        while idx < parsed.clauses.len() {
+            if self.normalize_clause_value(&parsed.clauses[idx]).is_ok() {
+                idx += 1;
+                continue;
+            }
+            if parsed.clauses.len() == 1 {
+                return Err(QueryError::empty_value(&parsed.clauses[0].field).into());
+            }
+            // Remove the operator on the AND side, so the OR structure of
+            // the remaining clauses is kept
+            let before = idx.checked_sub(1).filter(|&op| matches!(parsed.ops[op], QueryOp::And));
+            let op = match (before, parsed.ops.get(idx)) {
+                (Some(before), _) => before,
+                (None, Some(_)) => idx,
+                (None, None) => idx - 1,
+            };
+            parsed.ops.remove(op);
+            parsed.dropped.push(parsed.clauses.remove(idx));
+        }
+        Ok(())
     }
 
     fn parse_clause(&self, clause_str: &str) -> Result<Option<QueryClause>> {
@@ -131,17 +508,84 @@ impl CustomQueryParser {
             return Ok(None);
         }
 
-        // Handle field:value format
-        if let Some((field_name, value)) = clause_str.split_once(':') {
-            let field_name = field_name.trim().to_lowercase();
-            let value = value.trim();
+        // Handle field:value (tokenized), field="value" (exact), field~value
+        // (blended) and field|value (any word) formats, whichever separator
+        // comes first, each optionally boosted with a trailing ^N
+        let separator = clause_str.find([':', '=', '~', '|']);
+        if let Some(pos) = separator {
+            let field_name = clause_str[..pos].trim().to_lowercase();
+            let (mut value, boost) = split_boost_suffix(clause_str[pos + 1..].trim())?;
+            let mode = if clause_str[pos..].starts_with('=') {
+                value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                MatchMode::Exact
+            } else if clause_str[pos..].starts_with('~') {
+                MatchMode::Blended
+            } else if clause_str[pos..].starts_with('|') {
+                MatchMode::AnyToken
+            } else if let Some(substring) = value.strip_prefix("contains:") {
+                value = substring.trim();
+                if is_pattern_value(value) {
+                    return Err(QueryError::new(
+                        "INVALID_CONTAINS",
+                        format!("Substring '{}' cannot contain wildcard or regex syntax", value),
+                    ).into());
+                }
+                MatchMode::Contains
+            } else if let Some((fuzzy_value, distance)) = split_fuzzy_suffix(value)? {
+                value = fuzzy_value;
+                if is_pattern_value(value) {
+                    return Err(QueryError::new(
+                        "INVALID_FUZZY",
+                        format!("Fuzzy value '{}' cannot contain wildcard or regex syntax", value),
+                    ).into());
+                }
+                MatchMode::Fuzzy(distance)
+            } else {
+                MatchMode::Tokenized
+            };
+
+            if STORED_ONLY_FIELDS.contains(&field_name.as_str()) {
+                return Err(QueryError::new(
+                    "FIELD_NOT_SEARCHABLE",
+                    format!("Field '{}' is stored-only and cannot be queried", field_name),
+                ).into());
+            }
+            if !self.is_searchable(&field_name) {
+                return Err(QueryError::new(
+                    "FIELD_NOT_SEARCHABLE",
+                    format!("Field '{}' is configured as not searchable and cannot be queried", field_name),
+                ).into());
+            }
+
+            if matches!(mode, MatchMode::Fuzzy(_)) && self.field_map.contains_key(&field_name) && !self.is_text_field(&field_name) {
+                return Err(QueryError::new(
+                    "INVALID_FUZZY",
+                    format!("Field '{}' is not a TEXT field and cannot be matched fuzzily", field_name),
+                ).into());
+            }
+
+            if mode == MatchMode::Contains && self.field_map.contains_key(&field_name) && !self.substring_fields.contains_key(&field_name) {
+                return Err(QueryError::new(
+                    "SUBSTRING_NOT_INDEXED",
+                    format!("Field '{}' is not indexed for substrings; build the index with --substring-fields {}", field_name, field_name),
+                ).into());
+            }
 
             if self.field_map.contains_key(&field_name) {
                 return Ok(Some(QueryClause {
                     field: field_name,
                     value: value.to_string(),
+                    mode,
+                    boost,
                 }));
             }
+            return Err(QueryError::new(
+                "UNKNOWN_FIELD",
+                format!("Field '{}' is not a searchable field of this index", field_name),
+            ).into());
         }
 
         // If no field specified, try to infer or use default fields
@@ -149,13 +593,21 @@ impl CustomQueryParser {
         Err(anyhow!("Invalid clause format: {}", clause_str))
     }
 
-    /// Normalize value: remove spaces, convert to lowercase for mobile/alt/master_id
+    /// Normalize value: canonical phone numbers for mobile/alt, spaces removed
+    /// and lowercased for master_id
     pub fn normalize_value(&self, field: &str, value: &str) -> String {
+        // Same Unicode form as the indexed values (see indexer)
+        let value = normalize_unicode(value);
         match field {
-            "mobile" | "alt" | "master_id" => {
+            "mobile" | "alt" => PhoneNumber::canonicalize(&value),
+            "master_id" => {
                 // Remove all spaces and convert to lowercase
                 value.replace(' ', "").to_lowercase()
             }
+            "indexed_at" => {
+                // Timestamps/ranges are case-sensitive (RFC 3339 'T' and 'Z')
+                value.trim().to_string()
+            }
             _ => {
                 // For text fields, just lowercase
                 value.to_lowercase()
@@ -163,28 +615,158 @@ impl CustomQueryParser {
         }
     }
 
+    /// Value of a `Contains` clause as its ngram terms are indexed: phone
+    /// digits as written (no country code handling, a substring may start
+    /// anywhere), other values with spaces removed, lowercased
+    fn contains_value(&self, clause: &QueryClause) -> String {
+        let value = normalize_unicode(&clause.value);
+        match clause.field.as_str() {
+            "mobile" | "alt" => value.chars().filter(char::is_ascii_alphanumeric).flat_map(char::to_lowercase).collect(),
+            _ => value.replace(' ', "").to_lowercase(),
+        }
+    }
+
+    /// Normalize a clause value, rejecting values with nothing searchable left.
+    /// Without this, e.g. `mobile:"   "` would run a silent no-op TermQuery.
+    pub fn normalize_clause_value(&self, clause: &QueryClause) -> Result<String> {
+        let normalized = self.normalize_value(&clause.field, &clause.value);
+        if clause.field != "indexed_at" && !normalized.chars().any(char::is_alphanumeric) {
+            return Err(QueryError::empty_value(&clause.field).into());
+        }
+        Ok(normalized)
+    }
+
+    /// Re-runnable query string of a parsed query as it executes: values
+    /// normalized, ignored clauses left out and NOT clauses at the end.
+    /// Commas, which would split a clause, become spaces.
+    pub fn to_query_string(&self, parsed: &ParsedQuery) -> String {
+        let mut query = String::new();
+        for (idx, clause) in parsed.clauses.iter().enumerate() {
+            if let Some(op) = idx.checked_sub(1).and_then(|op| parsed.ops.get(op)) {
+                query.push_str(match op {
+                    QueryOp::And => " AND ",
+                    QueryOp::Or => " OR ",
+                });
+            }
+            query.push_str(&self.clause_string(clause));
+        }
+        for clause in &parsed.excluded {
+            query.push_str(" AND NOT ");
+            query.push_str(&self.clause_string(clause));
+        }
+        query
+    }
+
+    fn clause_string(&self, clause: &QueryClause) -> String {
+        // Patterns, date ranges and exact values are matched as written; id
+        // range bounds are lowercased like single ids
+        let value = if clause.field == "master_id" && is_range_value(&clause.value) {
+            let range = clause.value.trim();
+            let parts: Vec<String> = range[1..range.len() - 1].split_whitespace()
+                .map(|part| self.normalize_value(&clause.field, part))
+                .collect();
+            match parts.as_slice() {
+                [lower, _, upper] => format!("{}{} TO {}{}", &range[..1], lower, upper, &range[range.len() - 1..]),
+                _ => range.to_string(),
+            }
+        } else if clause.mode == MatchMode::Exact || is_pattern_value(&clause.value) || is_range_value(&clause.value) {
+            clause.value.trim().to_string()
+        } else if clause.mode == MatchMode::Contains {
+            self.contains_value(clause)
+        } else {
+            self.normalize_value(&clause.field, &clause.value)
+        };
+        let value = value.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let clause_str = match clause.mode {
+            MatchMode::Tokenized => format!("{}:{}", clause.field, value),
+            MatchMode::Exact => format!("{}=\"{}\"", clause.field, value),
+            MatchMode::Blended => format!("{}~{}", clause.field, value),
+            MatchMode::AnyToken => format!("{}|{}", clause.field, value),
+            MatchMode::Fuzzy(distance) => format!("{}:{}~{}", clause.field, value, distance),
+            MatchMode::Contains => format!("{}:contains:{}", clause.field, value),
+        };
+        match clause.boost {
+            Some(boost) => format!("{}^{}", clause_str, boost),
+            None => clause_str,
+        }
+    }
+
     /// Build optimized Tantivy query from parsed query
     pub fn build_query(&self, parsed: &ParsedQuery) -> Result<Box<dyn Query>> {
+        self.build_query_with_phrase_mode(parsed, PhraseMode::Auto)
+    }
+
+    /// Build the query with an explicit phrase matching mode.
+    /// `PhraseThenTokens` builds the phrase variant; falling back is up to the caller.
+    pub fn build_query_with_phrase_mode(&self, parsed: &ParsedQuery, phrase_mode: PhraseMode) -> Result<Box<dyn Query>> {
         if parsed.clauses.is_empty() {
             return Err(anyhow!("No query clauses"));
         }
+        self.check_clause_count(parsed.clauses.len())?;
 
         // Build queries for each clause
-        let mut query_clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        // Clauses split into AND groups
+        let mut groups: Vec<Vec<Box<dyn Query>>> = vec![Vec::new()];
+
+        // TEXT clauses as phrases: in Auto mode only for a lone clause,
+        // tokens otherwise, unless the caller asked for phrases explicitly
+        let use_phrase = match phrase_mode {
+            PhraseMode::Auto => parsed.clauses.len() == 1,
+            PhraseMode::Phrase | PhraseMode::PhraseThenTokens => true,
+            PhraseMode::Tokens | PhraseMode::Blend => false,
+        };
 
         for (idx, clause) in parsed.clauses.iter().enumerate() {
-            let normalized_value = self.normalize_value(&clause.field, &clause.value);
+            let normalized_value = self.normalize_clause_value(clause)?;
             let field = self.field_map.get(&clause.field)
                 .ok_or_else(|| anyhow!("Unknown field: {}", clause.field))?;
 
             // Optimized query building based on field type
             let query: Box<dyn Query> = match clause.field.as_str() {
+                "indexed_at" => {
+                    // DATE field - exact timestamp or [from TO to] range
+                    self.build_date_query(*field, &normalized_value)?
+                }
+                _ if clause.mode == MatchMode::Contains => {
+                    // Substring via the ngram companion
+                    self.build_contains_query(clause)?
+                }
+                "master_id" if clause.mode == MatchMode::Tokenized && is_range_value(&clause.value) => {
+                    // Lexical range over the id terms
+                    self.build_term_range_query(clause, *field)?
+                }
+                _ if clause.mode == MatchMode::AnyToken => {
+                    // Words OR-ed instead of AND-ed
+                    self.build_any_token_query(clause)?
+                }
+                _ if clause.mode == MatchMode::Tokenized && is_pattern_value(&clause.value) => {
+                    // Wildcard/regex - matched against the field's indexed terms
+                    self.build_pattern_query(clause, *field)?
+                }
                 "mobile" | "alt" | "master_id" => {
                     // STRING fields - use TermQuery (fastest for exact matches)
                     let term = Term::from_field_text(*field, &normalized_value);
                     Box::new(TermQuery::new(term, IndexRecordOption::Basic))
                 }
-                "name" | "fname" | "address" | "email" => {
+                _ if clause.mode == MatchMode::Exact && self.is_text_field(&clause.field) => {
+                    // Exact whole-value match via the raw companion field
+                    let exact_field = self.exact_fields.get(&clause.field)
+                        .ok_or_else(|| anyhow!("Exact matching is not enabled for field: {}", clause.field))?;
+                    let term = Term::from_field_text(*exact_field, &normalize_exact_value(&clause.value));
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+                }
+                _ if let MatchMode::Fuzzy(distance) = clause.mode => {
+                    // Typo-tolerant TEXT words
+                    self.build_fuzzy_query(clause, *field, &normalized_value, distance)?
+                }
+                _ if self.query_analyzers.contains_key(&clause.field) && self.is_text_field(&clause.field) => {
+                    // TEXT fields with a query-time analyzer override
+                    self.build_analyzed_query(clause, *field, &normalized_value, use_phrase)?
+                }
+                _ if self.is_text_field(&clause.field) => {
                     // TEXT fields - handle special characters and punctuation properly
                     let field_vec = vec![*field];
                     let parser = QueryParser::for_index(&self.index, field_vec);
@@ -206,11 +788,10 @@ impl CustomQueryParser {
                             // - At least 2 characters, OR
                             // - Single character that's alphanumeric (like "y" in "block y")
                             // - Contains digits (like "1550", "83", "110044")
-                            if trimmed.len() >= 2 {
-                                Some(trimmed.to_lowercase())
-                            } else if trimmed.len() == 1 && trimmed.chars().next().map_or(false, |c| c.is_alphanumeric()) {
-                                Some(trimmed.to_lowercase())
-                            } else if trimmed.chars().any(|c| c.is_ascii_digit()) {
+                            if trimmed.len() >= 2
+                                || (trimmed.len() == 1 && trimmed.chars().next().is_some_and(|c| c.is_alphanumeric()))
+                                || trimmed.chars().any(|c| c.is_ascii_digit())
+                            {
                                 Some(trimmed.to_lowercase())
                             } else {
                                 None
@@ -219,7 +800,7 @@ impl CustomQueryParser {
                         .collect();
 
                     if tokens.is_empty() {
-                        return Err(anyhow!("Query value too short after filtering"));
+                        return Err(QueryError::empty_value(&clause.field).into());
                     }
 
                     // Strategy 1: Try phrase query first for exact matching (preserves order and structure)
@@ -232,19 +813,21 @@ impl CustomQueryParser {
                     let phrase_query_result = parser.parse_query(&phrase_query_str);
 
                     // Strategy 2: Use token-based query (more flexible for combining with other clauses)
-                    // If we have only one clause total, we can use phrase query
-                    // Otherwise, use token-based approach so we can properly combine with AND/OR
-                    let use_phrase = parsed.clauses.len() == 1;
-
-                    if use_phrase {
-                        // Single clause - can use phrase query for exact matching
-                        if let Ok(phrase_query) = phrase_query_result {
-                            return Ok(phrase_query);
-                        }
-                    }
+                    // Blending keeps the phrase aside to combine with the tokens below;
+                    // exact boosting blends every token clause the same way
+                    let blend = clause.mode == MatchMode::Blended
+                        || phrase_mode == PhraseMode::Blend
+                        || (self.boost_exact && !use_phrase);
+                    let (phrase_query, blend_phrase) = match phrase_query_result.ok() {
+                        phrase if blend => (None, phrase),
+                        phrase if use_phrase => (phrase, None),
+                        _ => (None, None),
+                    };
 
                     // Token-based approach (works better for multi-clause queries)
-                    if tokens.len() == 1 {
+                    let clause_query: Box<dyn Query> = if let Some(phrase_query) = phrase_query {
+                        phrase_query
+                    } else if tokens.len() == 1 {
                         // Single token - use exact term query
                         let token = &tokens[0];
                         let query_str = format!("{}:{}", clause.field, token);
@@ -252,9 +835,11 @@ impl CustomQueryParser {
                             // Fallback: try with quotes
                             let query_str = format!("{}:\"{}\"", clause.field, token);
                             parser.parse_query(&query_str).unwrap_or_else(|_| {
-                                // Last resort: direct term query
-                                let term = Term::from_field_text(*field, token);
-                                Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+                                // Last resort: the token as the field indexes it
+                                self.index_word_query(*field, token).unwrap_or_else(|| {
+                                    let term = Term::from_field_text(*field, token);
+                                    Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+                                })
                             })
                         })
                     } else {
@@ -273,10 +858,9 @@ impl CustomQueryParser {
                                 let single_token_query = format!("{}:{}", clause.field, token);
                                 if let Ok(q) = parser.parse_query(&single_token_query) {
                                     term_queries.push((Occur::Must, q));
-                                } else {
-                                    // Direct term query as fallback
-                                    let term = Term::from_field_text(*field, token);
-                                    term_queries.push((Occur::Must, Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as Box<dyn Query>));
+                                } else if let Some(q) = self.index_word_query(*field, token) {
+                                    // The token as the field indexes it, split the same way
+                                    term_queries.push((Occur::Must, q));
                                 }
                             }
 
@@ -288,37 +872,906 @@ impl CustomQueryParser {
                                 Box::new(BooleanQuery::new(term_queries))
                             }
                         })
+                    };
+
+                    // A phrase clause only gets the exact boost on top
+                    if blend || self.boost_exact {
+                        self.blend_match_modes(clause, clause_query, blend_phrase)
+                    } else {
+                        clause_query
                     }
                 }
                 _ => {
                     return Err(anyhow!("Unsupported field: {}", clause.field));
                 }
             };
+            let query = match clause.boost {
+                Some(boost) => Box::new(BoostQuery::new(query, boost)),
+                None => query,
+            };
 
-            // Determine Occur based on operator
-            let occur = if idx == 0 {
-                Occur::Must // First clause is always Must
-            } else {
-                match parsed.ops.get(idx - 1) {
-                    Some(QueryOp::And) => Occur::Must,
-                    Some(QueryOp::Or) => Occur::Should,
-                    None => Occur::Must, // Default to AND
+            // AND binds tighter than OR: each OR starts a new group of Must clauses
+            if idx > 0 && matches!(parsed.ops.get(idx - 1), Some(QueryOp::Or)) {
+                groups.push(Vec::new());
+            }
+            groups.last_mut().unwrap().push(query);
+        }
+
+        let mut group_queries: Vec<Box<dyn Query>> = groups
+            .into_iter()
+            .map(|mut group| -> Box<dyn Query> {
+                if group.len() == 1 {
+                    return group.pop().unwrap();
                 }
+                Box::new(BooleanQuery::new(
+                    group.into_iter().map(|q| (Occur::Must, q)).collect(),
+                ))
+            })
+            .collect();
+
+        // Build BooleanQuery: any one AND group matching is enough
+        let query: Box<dyn Query> = if group_queries.len() == 1 {
+            group_queries.pop().unwrap()
+        } else {
+            Box::new(BooleanQuery::new(
+                group_queries.into_iter().map(|q| (Occur::Should, q)).collect(),
+            ))
+        };
+        if parsed.excluded.is_empty() {
+            return Ok(query);
+        }
+
+        // NOT clauses remove their matches from the whole query
+        Ok(Box::new(BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (Occur::MustNot, self.build_exclusion_query(parsed)?),
+        ])))
+    }
+
+    /// Documents matching any of the query's NOT clauses, each clause
+    /// matched on its tokens
+    pub fn build_exclusion_query(&self, parsed: &ParsedQuery) -> Result<Box<dyn Query>> {
+        let exclusions = ParsedQuery {
+            clauses: parsed.excluded.clone(),
+            ops: vec![QueryOp::Or; parsed.excluded.len().saturating_sub(1)],
+            excluded: Vec::new(),
+            dropped: Vec::new(),
+        };
+        self.build_query_with_phrase_mode(&exclusions, PhraseMode::Tokens)
+    }
+
+    /// AND of the value's words, each matching indexed terms within
+    /// `distance` edits (a swap of adjacent letters counting as one). The
+    /// words are split by the field's own tokenizer so they line up with the
+    /// indexed terms. Words no longer than the distance must match exactly,
+    /// as any term that short would otherwise match.
+    fn build_fuzzy_query(&self, clause: &QueryClause, field: Field, value: &str, distance: u8) -> Result<Box<dyn Query>> {
+        let words = self.index_terms(field, value)?;
+        if words.is_empty() {
+            return Err(QueryError::empty_value(&clause.field).into());
+        }
+
+        let mut word_queries: Vec<(Occur, Box<dyn Query>)> = words.into_iter()
+            .map(|word| {
+                let exact = word.chars().count() <= distance as usize;
+                let term = Term::from_field_text(field, &word);
+                let query: Box<dyn Query> = if exact {
+                    Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs))
+                } else {
+                    Box::new(FuzzyTermQuery::new(term, distance, true))
+                };
+                (Occur::Must, query)
+            })
+            .collect();
+        if word_queries.len() == 1 {
+            return Ok(word_queries.pop().unwrap().1);
+        }
+        Ok(Box::new(BooleanQuery::new(word_queries)))
+    }
+
+    /// Terms the field's own tokenizer indexes for `text`
+    fn index_terms(&self, field: Field, text: &str) -> Result<Vec<String>> {
+        let mut analyzer = self.index.tokenizer_for_field(field)?;
+        let mut terms = Vec::new();
+        let mut stream = analyzer.token_stream(text);
+        while stream.advance() {
+            terms.push(stream.token().text.clone());
+        }
+        Ok(terms)
+    }
+
+    /// Query for a single word as the field indexes it: its one term, or the
+    /// phrase of the terms a word like `jean-paul` or `9.5` is split into
+    fn index_word_query(&self, field: Field, word: &str) -> Option<Box<dyn Query>> {
+        let mut terms: Vec<Term> = self.index_terms(field, word).ok()?
+            .iter()
+            .map(|term| Term::from_field_text(field, term))
+            .collect();
+        match terms.len() {
+            0 => None,
+            1 => Some(Box::new(TermQuery::new(terms.pop().unwrap(), IndexRecordOption::WithFreqs))),
+            _ => Some(Box::new(PhraseQuery::new(terms))),
+        }
+    }
+
+    /// OR of the clause's whitespace-separated words, each built as its own
+    /// tokenized clause so patterns, analyzers and STRING fields behave as usual
+    fn build_any_token_query(&self, clause: &QueryClause) -> Result<Box<dyn Query>> {
+        let mut word_queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for word in clause.value.split_whitespace() {
+            let word_clause = QueryClause {
+                field: clause.field.clone(),
+                value: word.to_string(),
+                mode: MatchMode::Tokenized,
+                boost: None,
             };
+            // Stray punctuation between the words is not a word
+            if self.normalize_clause_value(&word_clause).is_err() {
+                continue;
+            }
+            let parsed = ParsedQuery { clauses: vec![word_clause], ops: Vec::new(), excluded: Vec::new(), dropped: Vec::new() };
+            word_queries.push((Occur::Should, self.build_query_with_phrase_mode(&parsed, PhraseMode::Tokens)?));
+        }
+        if word_queries.is_empty() {
+            return Err(QueryError::empty_value(&clause.field).into());
+        }
+        Ok(Box::new(BooleanQuery::new(word_queries)))
+    }
+
+    /// OR the token query of a TEXT clause with its phrase and, when the field
+    /// has an exact companion, its whole-value match, boosted so that exact
+    /// beats phrase beats scattered tokens. Both stricter forms imply the token
+    /// match, so recall is exactly that of the tokens.
+    fn blend_match_modes(&self, clause: &QueryClause, token_query: Box<dyn Query>, phrase_query: Option<Box<dyn Query>>) -> Box<dyn Query> {
+        let mut subqueries: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Should, token_query)];
+        if let Some(phrase_query) = phrase_query {
+            subqueries.push((Occur::Should, Box::new(BoostQuery::new(phrase_query, PHRASE_BOOST))));
+        }
+        if let Some(exact_field) = self.exact_fields.get(&clause.field) {
+            let term = Term::from_field_text(*exact_field, &normalize_exact_value(&clause.value));
+            let exact_query = Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+            subqueries.push((Occur::Should, Box::new(BoostQuery::new(exact_query, EXACT_BOOST))));
+        }
+        Box::new(BooleanQuery::new(subqueries))
+    }
+
+    /// Build a TEXT clause query from the terms of its query-time analyzer.
+    /// Phrases keep the analyzer's positions, so a removed stop word leaves a
+    /// gap instead of pulling its neighbours together.
+    fn build_analyzed_query(&self, clause: &QueryClause, field: Field, value: &str, use_phrase: bool) -> Result<Box<dyn Query>> {
+        let mut analyzer = self.query_analyzers[&clause.field].clone();
+        let mut stream = analyzer.token_stream(value);
+        let mut terms: Vec<(usize, Term)> = Vec::new();
+        while stream.advance() {
+            let token = stream.token();
+            terms.push((token.position, Term::from_field_text(field, &token.text)));
+        }
+
+        match terms.len() {
+            0 => Err(QueryError::new(
+                "EMPTY_VALUE",
+                format!("Field '{}' has no terms left after query-time analysis", clause.field),
+            ).into()),
+            1 => Ok(Box::new(TermQuery::new(terms.pop().unwrap().1, IndexRecordOption::WithFreqs))),
+            _ if use_phrase => Ok(Box::new(PhraseQuery::new_with_offset(terms))),
+            _ => Ok(Box::new(BooleanQuery::new(
+                terms.into_iter()
+                    .map(|(_, term)| -> (Occur, Box<dyn Query>) {
+                        (Occur::Must, Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs)))
+                    })
+                    .collect(),
+            ))),
+        }
+    }
 
-            query_clauses.push((occur, query));
+    /// Build a wildcard or regex query.
+    /// `/pattern/` is a regex over whole indexed terms, used verbatim. Otherwise
+    /// each word must match a term, `*` standing for any run of characters.
+    /// TEXT terms are lowercased tokens, so patterns match single words there,
+    /// and a word the field's tokenizer splits (`jean-pa*`) matches by parts.
+    fn build_pattern_query(&self, clause: &QueryClause, field: Field) -> Result<Box<dyn Query>> {
+        if !self.allow_patterns {
+            return Err(QueryError::new(
+                "FEATURE_DISABLED",
+                format!("Wildcard and regex queries are disabled (field '{}')", clause.field),
+            ).into());
+        }
+        let regex_query = |pattern: &str| -> Result<Box<dyn Query>> {
+            let query = RegexQuery::from_pattern(pattern, field).map_err(|e| {
+                QueryError::new("INVALID_PATTERN", format!("Invalid pattern for field '{}': {}", clause.field, e))
+            })?;
+            Ok(Box::new(query))
+        };
+
+        let value = clause.value.trim();
+        if let Some(pattern) = value.strip_prefix('/').and_then(|v| v.strip_suffix('/')) {
+            return regex_query(pattern);
+        }
+
+        let normalized = self.normalize_value(&clause.field, value);
+        let mut word_queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for word in normalized.split_whitespace() {
+            // The field's tokenizer splits `jean-paul` or `9.5` into several
+            // terms, so such a word has its parts matched separately
+            let literal = word.replace('*', "");
+            let parts: Vec<&str> = if word.contains('*') && !self.index_terms(field, &literal)?.contains(&literal) {
+                word.split(|c: char| !c.is_alphanumeric() && c != '*')
+                    .filter(|part| part.chars().any(char::is_alphanumeric))
+                    .collect()
+            } else {
+                vec![word]
+            };
+            if parts.is_empty() {
+                // A bare `*` would enumerate the whole term dictionary
+                return Err(QueryError::new(
+                    "INVALID_PATTERN",
+                    format!("Wildcard for field '{}' needs at least one literal character", clause.field),
+                ).into());
+            }
+            for part in parts {
+                let query = if part.contains('*') {
+                    if !part.chars().any(char::is_alphanumeric) {
+                        return Err(QueryError::new(
+                            "INVALID_PATTERN",
+                            format!("Wildcard for field '{}' needs at least one literal character", clause.field),
+                        ).into());
+                    }
+                    regex_query(&glob_to_regex(part))?
+                } else {
+                    Box::new(TermQuery::new(Term::from_field_text(field, part), IndexRecordOption::WithFreqs))
+                };
+                word_queries.push((Occur::Must, query));
+            }
         }
 
-        // Build BooleanQuery
-        if query_clauses.len() == 1 {
-            Ok(query_clauses.into_iter().next().unwrap().1)
+        if word_queries.len() == 1 {
+            Ok(word_queries.pop().unwrap().1)
         } else {
-            Ok(Box::new(BooleanQuery::new(query_clauses)))
+            Ok(Box::new(BooleanQuery::new(word_queries)))
         }
     }
 
+    /// Build a query on a DATE field.
+    /// Accepts a single timestamp or a range `[from TO to]`, where bounds are
+    /// RFC 3339 timestamps or unix seconds and `*` leaves a side open.
+    /// `[`/`]` are inclusive bounds, `{`/`}` exclusive.
+    fn build_date_query(&self, field: Field, value: &str) -> Result<Box<dyn Query>> {
+        if !matches!(self.schema.get_field_entry(field).field_type(), FieldType::Date(_)) {
+            return Err(anyhow!("Field is not a date field: {}", self.schema.get_field_name(field)));
+        }
+
+        let Some((lower, upper)) = split_range(value)? else {
+            let term = Term::from_field_date(field, parse_timestamp(value)?);
+            return Ok(Box::new(TermQuery::new(term, IndexRecordOption::Basic)));
+        };
+        let term = |raw: &str| -> Result<Term> { Ok(Term::from_field_date(field, parse_timestamp(raw)?)) };
+        Ok(Box::new(RangeQuery::new(map_bound(lower, term)?, map_bound(upper, term)?)))
+    }
+
+    /// Build a substring match on a field's ngram companion: a single term
+    /// when the value fits in one gram, otherwise every `SUBSTRING_MAX_GRAM`
+    /// window of it (all windows present is taken as the substring present)
+    fn build_contains_query(&self, clause: &QueryClause) -> Result<Box<dyn Query>> {
+        let field = *self.substring_fields.get(&clause.field)
+            .ok_or_else(|| anyhow!("Substring matching is not enabled for field: {}", clause.field))?;
+        let value = self.contains_value(clause);
+        let chars: Vec<char> = value.chars().collect();
+        if chars.len() < SUBSTRING_MIN_GRAM {
+            return Err(QueryError::value_too_short(&clause.field, &clause.value, SUBSTRING_MIN_GRAM).into());
+        }
+
+        let term_query = |gram: String| -> Box<dyn Query> {
+            Box::new(TermQuery::new(Term::from_field_text(field, &gram), IndexRecordOption::Basic))
+        };
+        if chars.len() <= SUBSTRING_MAX_GRAM {
+            return Ok(term_query(value));
+        }
+        Ok(Box::new(BooleanQuery::new(
+            chars.windows(SUBSTRING_MAX_GRAM)
+                .map(|window| (Occur::Must, term_query(window.iter().collect())))
+                .collect(),
+        )))
+    }
+
+    /// Build a lexical `[from TO to]` range on a STRING field. Bounds are
+    /// normalized like a single value of the field (see `normalize_value`),
+    /// so a range finds what lookups of its bounds find, and compared with
+    /// the indexed terms lexically: `[DEL00000 TO DEL09999]` matches
+    /// `DEL00420` but not `DEL1`.
+    fn build_term_range_query(&self, clause: &QueryClause, field: Field) -> Result<Box<dyn Query>> {
+        let (lower, upper) = split_range(clause.value.trim())?
+            .ok_or_else(|| anyhow!("Invalid range, expected [from TO to]: {}", clause.value))?;
+        let term = |raw: &str| -> Result<Term> { Ok(Term::from_field_text(field, &self.normalize_value(&clause.field, raw))) };
+        Ok(Box::new(RangeQuery::new(map_bound(lower, term)?, map_bound(upper, term)?)))
+    }
+
+    /// Whether a queryable field is tokenized TEXT (built-in or an extra
+    /// CSV column) rather than an exact STRING or DATE field
+    pub fn is_text_field(&self, field_name: &str) -> bool {
+        self.field_map.get(field_name).is_some_and(|field| {
+            match self.schema.get_field_entry(*field).field_type() {
+                FieldType::Str(options) => options.get_indexing_options()
+                    .is_some_and(|indexing| indexing.tokenizer() != "raw"),
+                _ => false,
+            }
+        })
+    }
+
     /// Get field reference by name
     pub fn get_field(&self, field_name: &str) -> Option<Field> {
         self.field_map.get(field_name).copied()
     }
 }
+
+/// Split a trailing `^N` boost off a clause value; the value is returned
+/// unchanged when it has none
+fn split_boost_suffix(value: &str) -> Result<(&str, Option<f32>)> {
+    let Some(caret) = value.rfind('^') else {
+        return Ok((value, None));
+    };
+    let (rest, suffix) = (&value[..caret], &value[caret + 1..]);
+    if suffix.is_empty() || !suffix.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return Ok((value, None));
+    }
+    let boost = suffix.parse::<f32>().ok().filter(|b| b.is_finite() && *b > 0.0).ok_or_else(|| QueryError::new(
+        "INVALID_BOOST",
+        format!("Boost '{}' must be a positive number", suffix),
+    ))?;
+    Ok((rest.trim_end(), Some(boost)))
+}
+
+/// Split a trailing `~` or `~N` fuzzy marker off a `field:value` value.
+/// None when the value has no marker.
+fn split_fuzzy_suffix(value: &str) -> Result<Option<(&str, u8)>> {
+    let Some(tilde) = value.rfind('~') else {
+        return Ok(None);
+    };
+    let (rest, suffix) = (&value[..tilde], &value[tilde + 1..]);
+    if !suffix.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+    let distance = match suffix {
+        "" => 1,
+        digits => digits.parse().ok().filter(|d| *d <= MAX_FUZZY_DISTANCE).ok_or_else(|| QueryError::new(
+            "INVALID_FUZZY",
+            format!("Fuzzy distance '{}' must be between 0 and {}", digits, MAX_FUZZY_DISTANCE),
+        ))?,
+    };
+    Ok(Some((rest.trim_end(), distance)))
+}
+
+/// Whether a clause value uses wildcard (`*`) or regex (`/.../`) syntax.
+/// The `*` of an open range bound is not a wildcard.
+pub fn is_pattern_value(value: &str) -> bool {
+    let value = value.trim();
+    !is_range_value(value) && (value.contains('*') || (value.len() > 2 && value.starts_with('/') && value.ends_with('/')))
+}
+
+/// Whether a clause value is a `[from TO to]` range (see `split_range`)
+pub fn is_range_value(value: &str) -> bool {
+    matches!(split_range(value.trim()), Ok(Some(_)))
+}
+
+/// Split a `[from TO to]` range into its bounds: `[`/`]` are inclusive,
+/// `{`/`}` exclusive and `*` leaves a side open. None when the value is not
+/// bracketed; an error when it is but isn't a well-formed range.
+fn split_range(value: &str) -> Result<Option<(Bound<&str>, Bound<&str>)>> {
+    let lower_inclusive = match value.chars().next() {
+        Some('[') => true,
+        Some('{') => false,
+        _ => return Ok(None),
+    };
+    let upper_inclusive = match value.chars().last() {
+        Some(']') if value.len() > 1 => true,
+        Some('}') if value.len() > 1 => false,
+        _ => return Err(anyhow!("Unterminated range: {}", value)),
+    };
+
+    let body = &value[1..value.len() - 1];
+    let parts: Vec<&str> = body.split_whitespace().collect();
+    if parts.len() != 3 || !parts[1].eq_ignore_ascii_case("TO") {
+        return Err(anyhow!("Invalid range, expected [from TO to]: {}", value));
+    }
+
+    let bound = |raw, inclusive| match raw {
+        "*" => Bound::Unbounded,
+        raw if inclusive => Bound::Included(raw),
+        raw => Bound::Excluded(raw),
+    };
+    Ok(Some((bound(parts[0], lower_inclusive), bound(parts[2], upper_inclusive))))
+}
+
+/// Turn a range bound's text into a term, keeping its kind
+fn map_bound(bound: Bound<&str>, term: impl Fn(&str) -> Result<Term>) -> Result<Bound<Term>> {
+    Ok(match bound {
+        Bound::Included(raw) => Bound::Included(term(raw)?),
+        Bound::Excluded(raw) => Bound::Excluded(term(raw)?),
+        Bound::Unbounded => Bound::Unbounded,
+    })
+}
+
+/// Replace the `{N}` placeholders of a template value in a single pass, so
+/// braces inside a parameter are never expanded themselves. Control
+/// characters in parameters become spaces, as in request fields.
+fn fill_placeholders(value: &str, params: &[String]) -> Result<String> {
+    let mut filled = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}').map(|close| open + close) else {
+            break;
+        };
+        let Ok(idx) = rest[open + 1..close].parse::<usize>() else {
+            filled.push_str(&rest[..=open]);
+            rest = &rest[open + 1..];
+            continue;
+        };
+        let param = params.get(idx).ok_or_else(|| QueryError::new(
+            "MISSING_PARAMETER",
+            format!("Template needs parameter {{{}}} but only {} were given", idx, params.len()),
+        ))?;
+        filled.push_str(&rest[..open]);
+        filled.extend(param.chars().map(|c| if c.is_control() { ' ' } else { c }));
+        rest = &rest[close + 1..];
+    }
+    filled.push_str(rest);
+    Ok(filled.trim().to_string())
+}
+
+/// Translate a `*` wildcard into an anchored term regex, escaping the rest
+fn glob_to_regex(word: &str) -> String {
+    let mut regex = String::with_capacity(word.len() + 8);
+    for c in word.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            c if "\\.+?()|[]{}^$#&-~".contains(c) => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            c => regex.push(c),
+        }
+    }
+    regex
+}
+
+/// Parse a timestamp given as unix seconds or RFC 3339
+pub fn parse_timestamp(raw: &str) -> Result<DateTime> {
+    if let Ok(secs) = raw.parse::<i64>() {
+        return Ok(DateTime::from_timestamp_secs(secs));
+    }
+    let parsed = OffsetDateTime::parse(raw, &Rfc3339)
+        .map_err(|e| anyhow!("Invalid timestamp '{}': {}", raw, e))?;
+    Ok(DateTime::from_utc(parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{build_schema, exact_field_name, normalize_exact_value};
+    use crate::tokenizer::register_tokenizers;
+    use tantivy::collector::{Count, DocSetCollector};
+    use tantivy::query::EnableScoring;
+    use tantivy::{DocSet, TantivyDocument};
+
+    const COLUMNS: [&str; 7] = ["master_id", "mobile", "fname", "name", "alt", "email", "address"];
+
+    /// In-memory index of rows in CSV column order
+    fn index(rows: &[[&str; 7]]) -> Index {
+        let index = Index::create_in_ram(build_schema(&[], &HashMap::new(), &[]));
+        register_tokenizers(&index);
+        let schema = index.schema();
+        let mut writer = index.writer(15_000_000).unwrap();
+        for row in rows {
+            let mut doc = TantivyDocument::default();
+            for (column, value) in COLUMNS.iter().zip(row) {
+                doc.add_text(schema.get_field(column).unwrap(), value);
+                if let Ok(exact) = schema.get_field(&exact_field_name(column)) {
+                    doc.add_text(exact, normalize_exact_value(value));
+                }
+            }
+            writer.add_document(doc).unwrap();
+        }
+        writer.commit().unwrap();
+        index
+    }
+
+    fn parser(index: &Index) -> CustomQueryParser {
+        CustomQueryParser::new(index.schema(), index.clone())
+    }
+
+    fn count(index: &Index, parser: &CustomQueryParser, query_str: &str) -> usize {
+        let query = parser.build_query(&parser.parse(query_str).unwrap()).unwrap();
+        index.reader().unwrap().searcher().search(query.as_ref(), &Count).unwrap()
+    }
+
+    // Must clauses are not reordered by selectivity: Tantivy's intersection
+    // already sorts its docsets by size_hint and leapfrogs from the smallest,
+    // so the clause order written in the query doesn't matter
+    #[test]
+    fn selective_clause_drives_the_intersection_in_either_order() {
+        let ids: Vec<String> = (0..500).map(|i| format!("m{}", i)).collect();
+        let mut rows: Vec<[&str; 7]> = ids.iter().map(|id| [id.as_str(), "", "ravi", "ravi", "", "", "delhi"]).collect();
+        rows[250][1] = "9876543210";
+        let index = index(&rows);
+        let parser = parser(&index);
+        let searcher = index.reader().unwrap().searcher();
+
+        for query_str in ["address:delhi AND mobile:9876543210", "mobile:9876543210 AND address:delhi"] {
+            let query = parser.build_query(&parser.parse(query_str).unwrap()).unwrap();
+            let weight = query.weight(EnableScoring::disabled_from_searcher(&searcher)).unwrap();
+            let mut scorer = weight.scorer(searcher.segment_reader(0), 1.0).unwrap();
+            // Sized by the one-doc mobile postings, not the 500 delhi ones
+            assert!(scorer.size_hint() <= 1, "{query_str}: {}", scorer.size_hint());
+            let mut visited = 0;
+            while scorer.doc() != tantivy::TERMINATED {
+                visited += 1;
+                scorer.advance();
+            }
+            assert_eq!(visited, 1, "{query_str}");
+            assert_eq!(count(&index, &parser, query_str), 1);
+        }
+    }
+
+    #[test]
+    fn exact_matches_the_whole_value_where_a_phrase_matches_part_of_it() {
+        let index = index(&[
+            ["m1", "", "", "John  Smith", "", "", ""],
+            ["m2", "", "", "john smith jr", "", "", ""],
+            ["m3", "", "", "smith john", "", "", ""],
+        ]);
+        let parser = parser(&index);
+        // A single tokenized clause runs as a phrase: words adjacent, in order
+        assert_eq!(count(&index, &parser, "name:john smith"), 2);
+        // Case and runs of whitespace don't matter, extra words do
+        assert_eq!(count(&index, &parser, "name=\"john smith\""), 1);
+        assert_eq!(count(&index, &parser, "name=\"JOHN SMITH JR\""), 1);
+        assert_eq!(count(&index, &parser, "name=\"john\""), 0);
+    }
+
+    #[test]
+    fn exact_matching_is_opt_in_per_field() {
+        let index = index(&[["m1", "", "", "", "", "", "delhi"]]);
+        let parser = parser(&index);
+        let err = parser.build_query(&parser.parse("address=\"delhi\"").unwrap()).unwrap_err();
+        assert!(err.to_string().contains("Exact matching is not enabled for field: address"), "{err}");
+    }
+
+    fn error_code(parser: &CustomQueryParser, query_str: &str) -> &'static str {
+        let err = parser.parse(query_str).and_then(|parsed| parser.build_query(&parsed)).unwrap_err();
+        err.downcast_ref::<QueryError>().unwrap_or_else(|| panic!("{query_str}: {err}")).code
+    }
+
+    #[test]
+    fn blank_and_punctuation_only_values_are_empty_value_errors() {
+        let index = index(&[["m1", "9876543210", "", "ravi", "", "", "delhi"]]);
+        let parser = parser(&index);
+        for query_str in ["mobile:\"   \"", "mobile:--", "name:!!!", "address:... ;", "master_id:\"\"", "name=\"  \""] {
+            assert_eq!(error_code(&parser, query_str), "EMPTY_VALUE", "{query_str}");
+        }
+        let clause = QueryClause { field: "mobile".into(), value: "   ".into(), mode: MatchMode::Tokenized, boost: None };
+        assert!(parser.normalize_clause_value(&clause).is_err());
+    }
+
+    #[test]
+    fn email_matches_by_full_address_local_part_and_domain() {
+        let index = index(&[
+            ["m1", "", "", "", "", "John.Doe@gmail.com", ""],
+            ["m2", "", "", "", "", "jane@gmail.com", ""],
+            ["m3", "", "", "", "", "john.doe@yahoo.co.in", ""],
+        ]);
+        let parser = parser(&index);
+        assert_eq!(count(&index, &parser, "email:john.doe@gmail.com"), 1);
+        assert_eq!(count(&index, &parser, "email:JOHN.DOE@GMAIL.COM"), 1);
+        assert_eq!(count(&index, &parser, "email:john.doe"), 2);
+        assert_eq!(count(&index, &parser, "email:gmail.com"), 2);
+        assert_eq!(count(&index, &parser, "email:gmail"), 2);
+        assert_eq!(count(&index, &parser, "email:yahoo.co.in"), 1);
+        // A local part is not split on its dots
+        assert_eq!(count(&index, &parser, "email:doe"), 0);
+    }
+
+    #[test]
+    fn queries_past_the_clause_limit_are_rejected() {
+        let index = index(&[["m1", "", "ravi", "", "", "", ""]]);
+        let parser = parser(&index).with_max_clauses(3);
+        let query = |n: usize| (0..n).map(|i| format!("fname:ravi{}", i)).collect::<Vec<_>>().join(" OR ");
+
+        assert!(parser.parse(&query(3)).is_ok());
+        assert_eq!(error_code(&parser, &query(4)), "TOO_MANY_CLAUSES");
+        assert_eq!(error_code(&parser, "fname:a, fname:b, fname:c, fname:d"), "TOO_MANY_CLAUSES");
+        assert_eq!(count(&index, &parser, "fname:ravi OR fname:x OR fname:y"), 1);
+    }
+
+    #[test]
+    fn wildcard_and_regex_clauses_are_rejected_only_when_disabled() {
+        let index = index(&[
+            ["m1", "", "ravi", "ravi kumar", "", "", ""],
+            ["m2", "", "ravindra", "ravindra shukla", "", "", ""],
+            ["m3", "", "amit", "amit kumar", "", "", ""],
+        ]);
+        // A leading wildcard scans every term, more than the default cost budget
+        let enabled = parser(&index).with_max_cost(10 * PATTERN_SCAN_COST);
+        assert_eq!(count(&index, &enabled, "name:rav*"), 2);
+        assert_eq!(count(&index, &enabled, "name:*mar"), 2);
+        assert_eq!(count(&index, &enabled, "name:/ra.i/"), 1);
+        assert_eq!(count(&index, &enabled, "fname:ravi"), 1);
+
+        let disabled = parser(&index).with_patterns(false);
+        for query_str in ["name:rav*", "name:*mar", "name:/ra.i/", "fname:ravi AND name:k*"] {
+            assert_eq!(error_code(&disabled, query_str), "FEATURE_DISABLED", "{query_str}");
+        }
+        // Plain clauses are unaffected
+        assert_eq!(count(&index, &disabled, "name:kumar"), 2);
+    }
+
+    #[test]
+    fn query_time_stop_words_change_matches_without_reindexing() {
+        let index = index(&[
+            ["m1", "", "", "", "", "", "mg road delhi"],
+            ["m2", "", "", "", "", "", "the mall road"],
+        ]);
+        let plain = parser(&index);
+        assert_eq!(count(&index, &plain, "address:the mg road"), 0);
+        assert_eq!(count(&index, &plain, "address:the mg road, address:delhi"), 0);
+
+        let analyzers = HashMap::from([("address".to_string(), crate::tokenizer::build_query_analyzer("stopwords(english)").unwrap())]);
+        let analyzed = parser(&index).with_query_analyzers(analyzers);
+        assert_eq!(count(&index, &analyzed, "address:the mg road"), 1);
+        assert_eq!(count(&index, &analyzed, "address:the mg road, address:delhi"), 1);
+        // The index still holds the stop words
+        assert_eq!(count(&index, &plain, "address:the mall road"), 1);
+        assert_eq!(error_code(&analyzed, "address:the"), "EMPTY_VALUE");
+    }
+
+    /// (master_id, score) of every match, best first
+    fn ranked(index: &Index, parser: &CustomQueryParser, query_str: &str) -> Vec<(String, f32)> {
+        let query = parser.build_query(&parser.parse(query_str).unwrap()).unwrap();
+        let searcher = index.reader().unwrap().searcher();
+        let master_id = index.schema().get_field("master_id").unwrap();
+        searcher.search(query.as_ref(), &tantivy::collector::TopDocs::with_limit(10)).unwrap()
+            .into_iter()
+            .map(|(score, addr)| {
+                let doc: tantivy::TantivyDocument = searcher.doc(addr).unwrap();
+                (doc.get_first(master_id).and_then(|v| tantivy::schema::Value::as_str(&v)).unwrap().to_string(), score)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blended_clause_ranks_exact_over_phrase_over_scattered_tokens() {
+        let index = index(&[
+            ["m1", "", "", "kumar ravi singh", "", "", "delhi"],
+            ["m2", "", "", "ravi kumar singh", "", "", "delhi"],
+            ["m3", "", "", "ravi kumar", "", "", "delhi"],
+            ["m4", "", "", "ravi singh", "", "", "delhi"],
+        ]);
+        let parser = parser(&index).with_exact_boost(false);
+
+        let blended = ranked(&index, &parser, "name~ravi kumar, address:delhi");
+        let order: Vec<&str> = blended.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(order, ["m3", "m2", "m1"]);
+        // Same recall as tokens, where the two three-word names tie
+        let tokens = ranked(&index, &parser, "name:ravi kumar, address:delhi");
+        assert_eq!(tokens.len(), 3);
+        let score = |results: &[(String, f32)], id: &str| results.iter().find(|(i, _)| i == id).unwrap().1;
+        assert_eq!(score(&tokens, "m1"), score(&tokens, "m2"));
+        assert!(score(&blended, "m2") > score(&blended, "m1"));
+    }
+
+    #[test]
+    fn broad_wildcards_are_too_expensive_and_plain_lookups_are_not() {
+        let index = index(&[["m1", "9876543210", "ravi", "ravi kumar", "", "", "delhi"]]);
+        let unchecked = parser(&index).with_max_cost(u64::MAX);
+        let parser = parser(&index);
+        let cost = |query_str: &str| parser.estimate_cost(&unchecked.parse(query_str).unwrap());
+
+        assert_eq!(cost("name:ravi kumar"), 2);
+        assert_eq!(cost("name:ravi kumar AND address:delhi"), 3);
+        assert_eq!(cost("mobile:9876543210"), 1 + FANOUT_COST);
+        // Each literal prefix character halves a wildcard's scan
+        assert_eq!(cost("name:*vi"), PATTERN_SCAN_COST);
+        assert_eq!(cost("name:r*"), PATTERN_SCAN_COST / 2);
+        assert_eq!(cost("name:ravi*"), PATTERN_SCAN_COST / 16);
+
+        assert_eq!(count(&index, &parser, "name:ravi kumar AND address:delhi"), 1);
+        assert_eq!(count(&index, &parser, "name:ra*"), 1);
+        assert_eq!(error_code(&parser, "name:*vi"), "QUERY_TOO_EXPENSIVE");
+        assert_eq!(error_code(&parser, "name:r* AND address:d*"), "QUERY_TOO_EXPENSIVE");
+        // The budget is configurable
+        assert_eq!(count(&index, &parser.with_max_cost(2 * PATTERN_SCAN_COST), "name:*vi"), 1);
+    }
+
+    #[test]
+    fn a_fields_words_are_anded_by_default_and_ored_with_a_bar() {
+        let index = index(&[
+            ["m1", "", "john", "john smith", "", "", "delhi"],
+            ["m2", "", "jane", "jane doe", "", "", "delhi"],
+            ["m3", "", "john", "john jane", "", "", "pune"],
+            ["m4", "", "amit", "amit shah", "", "", "delhi"],
+        ]);
+        let parser = parser(&index);
+        let ids = |query_str: &str| {
+            let mut ids: Vec<String> = ranked(&index, &parser, query_str).into_iter().map(|(id, _)| id).collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids("name:john jane"), ["m3"]);
+        assert_eq!(ids("name|john jane"), ["m1", "m2", "m3"]);
+        assert_eq!(ids("name|john jane AND address:delhi"), ["m1", "m2"]);
+        assert_eq!(ids("name:john jane, address:delhi"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn clauses_with_nothing_searchable_are_dropped_and_the_rest_still_run() {
+        let index = index(&[
+            ["m1", "", "ravi", "ravi kumar", "", "", "delhi"],
+            ["m2", "", "amit", "amit shah", "", "", "pune"],
+            ["m3", "", "ravi", "ravi verma", "", "", "agra"],
+        ]);
+        let parser = parser(&index);
+
+        let parsed = parser.parse("fname:ravi AND address:!!!").unwrap();
+        assert_eq!(parsed.dropped.iter().map(|c| c.field.as_str()).collect::<Vec<_>>(), ["address"]);
+        assert_eq!(parsed.clauses.len(), 1);
+        assert_eq!(count(&index, &parser, "fname:ravi AND address:!!!"), 2);
+        assert_eq!(count(&index, &parser, "name:-- OR address:pune"), 1);
+        // The OR is kept when the AND side of it is dropped
+        let parsed = parser.parse("fname:ravi AND name:?? OR address:pune").unwrap();
+        assert!(matches!(parsed.ops.as_slice(), [QueryOp::Or]), "{:?}", parsed.ops);
+        assert_eq!(count(&index, &parser, "fname:ravi AND name:?? OR address:pune"), 3);
+
+        assert_eq!(error_code(&parser, "name:!! AND address:??"), "EMPTY_VALUE");
+    }
+
+    #[test]
+    fn exact_name_ranks_first_by_default() {
+        let index = index(&[
+            ["m1", "", "", "ravi ravi", "", "", "delhi"],
+            ["m2", "", "", "Ravi", "", "", "delhi"],
+            ["m3", "", "", "ravi kumar", "", "", "delhi"],
+        ]);
+        let first = |parser: &CustomQueryParser, query_str: &str| ranked(&index, parser, query_str)[0].0.clone();
+
+        // Term frequency alone favours the repeated word
+        let unboosted = parser(&index).with_exact_boost(false);
+        assert_eq!(first(&unboosted, "name:ravi"), "m1");
+        let boosted = parser(&index);
+        assert_eq!(first(&boosted, "name:ravi"), "m2");
+        assert_eq!(first(&boosted, "name:ravi, address:delhi"), "m2");
+        assert_eq!(first(&boosted, "name:ravi kumar"), "m3");
+        // Boosting reorders but never adds matches
+        assert_eq!(count(&index, &boosted, "name:ravi kumar"), count(&index, &unboosted, "name:ravi kumar"));
+    }
+
+    #[test]
+    fn template_parameters_fill_values_without_adding_clauses() {
+        let index = index(&[
+            ["m1", "9800000001", "", "ravi kumar", "", "", "pune"],
+            ["m2", "9800000002", "", "amit shah", "9800000001", "", "delhi"],
+        ]);
+        let parser = parser(&index);
+        let params = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let template_count = |template: &str, values: &[&str]| {
+            let query = parser.build_query(&parser.parse_template(template, &params(values)).unwrap()).unwrap();
+            index.reader().unwrap().searcher().search(query.as_ref(), &Count).unwrap()
+        };
+        let template_error = |template: &str, values: &[&str]| {
+            parser.parse_template(template, &params(values)).unwrap_err().downcast::<QueryError>().unwrap().code
+        };
+
+        assert_eq!(template_count("mobile:{0} OR alt:{0}", &["9800000001"]), 2);
+        assert_eq!(template_count("name:{0}", &["ravi"]), 1);
+
+        // Operators and field separators in a parameter stay inside its value
+        let parsed = parser.parse_template("name:{0}", &params(&["ravi OR address:delhi"])).unwrap();
+        assert_eq!(parsed.clauses.len(), 1);
+        assert_eq!(parsed.clauses[0].value, "ravi OR address:delhi");
+        assert_eq!(template_count("name:{0}", &["ravi OR address:delhi"]), 0);
+        // A placeholder inside a parameter is not expanded again
+        let parsed = parser.parse_template("name:{0}", &params(&["{1}", "ravi"])).unwrap();
+        assert_eq!(parsed.clauses[0].value, "{1}");
+
+        assert_eq!(template_error("name:{0}", &["ra*"]), "INVALID_PARAMETER");
+        assert_eq!(template_error("name:{0} {1}", &["/rav", "i/"]), "INVALID_PARAMETER");
+        assert_eq!(template_error("mobile:{0} OR alt:{1}", &["9800000001"]), "MISSING_PARAMETER");
+    }
+
+    #[test]
+    fn executed_query_shows_the_normalized_query_and_reruns_as_is() {
+        let index = index(&[
+            ["m1", "9800000001", "ravi", "ravi kumar", "", "", "delhi"],
+            ["m2", "9800000002", "ravi", "ravi shah", "", "", "pune"],
+        ]);
+        let parser = parser(&index);
+        let executed = |query_str: &str| parser.to_query_string(&parser.parse(query_str).unwrap());
+
+        let query = executed("NAME:Ravi AND NOT address:PUNE AND mobile:+91 98000-00001");
+        assert_eq!(query, "name:ravi AND mobile:9800000001 AND NOT address:pune");
+        assert_eq!(count(&index, &parser, &query), 1);
+        // Exact values keep their case, ignored clauses are left out
+        assert_eq!(executed("name=\"Ravi Kumar\" OR address:!!!"), "name=\"Ravi Kumar\"");
+        assert_eq!(executed("name|Ravi   Amit"), "name|ravi amit");
+        assert_eq!(executed("name:Ravy~"), "name:ravy~1");
+        assert_eq!(count(&index, &parser, &executed("name:Ravy~")), 2);
+    }
+
+    /// The general path already runs a lone master_id/alt lookup as one term
+    /// query, so a dedicated fast path would save only the parse. Measured by
+    /// `point_lookup_timing` in search_service.rs, that is about 0.7us of a
+    /// 14.6us search, too little to keep a second code path for.
+    #[test]
+    fn point_lookups_match_a_direct_term_query() {
+        let index = index(&[
+            ["m1", "9800000001", "ravi", "ravi kumar", "9811111111", "", "delhi"],
+            ["m2", "9800000002", "amit", "amit shah", "+91 98111 11111", "", "pune"],
+            ["m3", "9800000003", "sita", "sita ram", "", "", "delhi"],
+        ]);
+        let parser = parser(&index);
+        let searcher = index.reader().unwrap().searcher();
+        let docs = |query: &dyn Query| searcher.search(query, &DocSetCollector).unwrap();
+
+        for (query_str, hits) in [("master_id:m1", 1), ("MASTER_ID:M1", 1), ("alt:9811111111", 1), ("alt:+919811111111", 1), ("master_id:m9", 0)] {
+            let parsed = parser.parse(query_str).unwrap();
+            let clause = &parsed.clauses[0];
+            let term = Term::from_field_text(parser.field_map[&clause.field], &parser.normalize_clause_value(clause).unwrap());
+            let direct = docs(&TermQuery::new(term, IndexRecordOption::Basic));
+            assert_eq!(docs(parser.build_query(&parsed).unwrap().as_ref()), direct, "{}", query_str);
+            assert_eq!(direct.len(), hits, "{}", query_str);
+        }
+    }
+
+    #[test]
+    fn master_id_ranges_return_only_ids_in_the_lexical_range() {
+        let index = index(&[
+            ["del00000", "9800000001", "ravi", "ravi kumar", "", "", "delhi"],
+            ["del05000", "9800000002", "amit", "amit shah", "", "", "delhi"],
+            ["del09999", "9800000003", "sita", "sita ram", "", "", "delhi"],
+            ["del10000", "9800000004", "ravi", "ravi verma", "", "", "delhi"],
+            ["mum00001", "9800000005", "ravi", "ravi iyer", "", "", "mumbai"],
+        ]);
+        let parser = parser(&index);
+        let ids = |query_str: &str| {
+            let mut ids: Vec<String> = ranked(&index, &parser, query_str).into_iter().map(|(id, _)| id).collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids("master_id:[DEL00000 TO DEL09999]"), ["del00000", "del05000", "del09999"]);
+        // Bounds are lowercased like single ids
+        assert_eq!(ids("master_id:[Del00000 TO DEL09999]"), ["del00000", "del05000", "del09999"]);
+        assert_eq!(ids("master_id:{DEL00000 TO DEL09999}"), ["del05000"]);
+        assert_eq!(ids("master_id:[* TO DEL05000]"), ["del00000", "del05000"]);
+        assert_eq!(ids("master_id:[DEL10000 TO *]"), ["del10000", "mum00001"]);
+        assert_eq!(ids("master_id:[DEL00000 TO DEL99999] AND name:ravi"), ["del00000", "del10000"]);
+    }
+
+    #[test]
+    fn hyphenated_and_dotted_words_match_whole_and_by_parts() {
+        let index = index(&[
+            ["m1", "9800000001", "jean-paul", "jean-paul sartre", "", "jp.sartre@example.fr", "flat 9.5 rue de paris"],
+            ["m2", "9800000002", "jean", "jean dupont", "", "", "lyon"],
+            ["m3", "9800000003", "paul", "paul martin", "", "", "paris"],
+            ["m4", "9800000004", "paul", "paul jean", "", "", "nice"],
+        ]);
+        let parser = parser(&index);
+        let ids = |query_str: &str| {
+            let mut ids: Vec<String> = ranked(&index, &parser, query_str).into_iter().map(|(id, _)| id).collect();
+            ids.sort();
+            ids
+        };
+
+        // As a whole: the parts in order, so "paul jean" is no match
+        assert_eq!(ids("name:jean-paul"), ["m1"]);
+        assert_eq!(ids("name:Jean-Paul Sartre"), ["m1"]);
+        assert_eq!(ids("fname:jean-paul"), ["m1"]);
+        // And by parts, in any order for a pattern
+        assert_eq!(ids("name:jean-pa*"), ["m1", "m4"]);
+        assert_eq!(ids("name:jean"), ["m1", "m2", "m4"]);
+        assert_eq!(ids("name:paul AND name:sartre"), ["m1"]);
+        assert_eq!(ids("name|dupont jean-paul"), ["m1", "m2"]);
+        assert_eq!(ids("address:9.5"), ["m1"]);
+        assert_eq!(ids("address:9"), ["m1"]);
+        assert_eq!(ids("email:jp.sartre"), ["m1"]);
+        assert_eq!(ids("email:jp.sartre@example.fr"), ["m1"]);
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{Result, anyhow};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use tantivy::directory::INDEX_WRITER_LOCK;
+use tantivy::directory::error::LockError;
+use tantivy::schema::document::Document;
+use tantivy::{Index, IndexWriter, TantivyError};
+
+/// File in the index directory holding the opstamp of the last complete build
+pub const READY_MARKER: &str = "READY";
+
+/// File in the index directory locked while a build writes to it
+pub const BUILD_LOCK: &str = ".build.lock";
+
+/// Take the build lock of an index directory, so two builds or appends never
+/// write the same index at once. It is an OS file lock held while the
+/// returned file is open, so it goes away when the build finishes, panics or
+/// is killed; the lock file itself stays behind.
+pub fn lock_index_dir(index_dir: &str) -> Result<File> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(Path::new(index_dir).join(BUILD_LOCK))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(std::fs::TryLockError::WouldBlock) => Err(anyhow!("Index {} is locked by another process", index_dir)),
+        Err(std::fs::TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// Tantivy's writer lock file of an index directory
+fn writer_lock_path(index_dir: &str) -> PathBuf {
+    Path::new(index_dir).join(&INDEX_WRITER_LOCK.filepath)
+}
+
+/// Open an index writer, explaining a busy writer lock rather than passing
+/// on tantivy's bare lock error
+pub fn open_writer<D: Document>(index: &Index, index_dir: &str, memory_budget: usize) -> Result<IndexWriter<D>> {
+    index.writer(memory_budget).map_err(|e| match e {
+        TantivyError::LockFailure(LockError::LockBusy, _) => anyhow!(
+            "Index {} already has a writer open (lock file {}), e.g. a running build, append, gc or a \
+             server taking /index/bulk. Wait for it to finish; if nothing is writing the index, clear \
+             the stale lock with --force-unlock",
+            index_dir,
+            writer_lock_path(index_dir).display(),
+        ),
+        e => e.into(),
+    })
+}
+
+/// Remove the writer and build lock files a dead process left in an index
+/// directory. Each is locked first to confirm no live process holds it; a
+/// lock still held fails instead of being removed.
+pub fn force_unlock(index_dir: &str) -> Result<()> {
+    for path in [writer_lock_path(index_dir), Path::new(index_dir).join(BUILD_LOCK)] {
+        let file = match OpenOptions::new().write(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(anyhow!("Cannot open lock file {}: {}", path.display(), e)),
+        };
+        match file.try_lock() {
+            Ok(()) => {
+                fs::remove_file(&path)?;
+                println!("Removed stale lock {}", path.display());
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(anyhow!("Lock {} is held by a running process and was not removed", path.display()));
+            }
+            Err(TryLockError::Error(e)) => return Err(anyhow!("Cannot check lock {}: {}", path.display(), e)),
+        }
+    }
+    Ok(())
+}
+
+/// Remove the marker before a build starts writing, so checkpoint commits made
+/// mid-build are never mistaken for a finished generation
+pub fn clear_ready(index_dir: &str) -> Result<()> {
+    match fs::remove_file(Path::new(index_dir).join(READY_MARKER)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Record `opstamp` as fully committed. Written to a temp file and renamed
+/// over the marker, so readers see either the old or the new marker, never
+/// a partial one.
+pub fn mark_ready(index_dir: &str, opstamp: u64) -> Result<()> {
+    let dir = Path::new(index_dir);
+    let tmp = dir.join(format!("{}.tmp", READY_MARKER));
+    fs::write(&tmp, opstamp.to_string())?;
+    fs::rename(&tmp, dir.join(READY_MARKER))?;
+    Ok(())
+}
+
+/// Opstamp of the last complete build, or None while a build is in progress
+/// (or for indexes built before markers existed)
+pub fn ready_opstamp(index_dir: &str) -> Result<Option<u64>> {
+    match fs::read_to_string(Path::new(index_dir).join(READY_MARKER)) {
+        Ok(content) => Ok(content.trim().parse().ok()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::build;
+    use tantivy::TantivyDocument;
+    use tempfile::TempDir;
+
+    #[test]
+    fn stale_locks_are_removed_and_held_ones_explained() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = build(&dir, "index", &["m1,9800000001,ravi,ravi kumar,,,delhi"]);
+        let index = Index::open_in_dir(&index_dir).unwrap();
+
+        // A crashed writer leaves its lock files behind, held by no one
+        let build_lock = Path::new(&index_dir).join(BUILD_LOCK);
+        fs::write(writer_lock_path(&index_dir), "").unwrap();
+        fs::write(&build_lock, "").unwrap();
+        force_unlock(&index_dir).unwrap();
+        assert!(!writer_lock_path(&index_dir).exists());
+        assert!(!build_lock.exists());
+
+        let writer: IndexWriter<TantivyDocument> = open_writer(&index, &index_dir, 15_000_000).unwrap();
+        let err = open_writer::<TantivyDocument>(&index, &index_dir, 15_000_000).err().unwrap();
+        assert!(err.to_string().contains("already has a writer open"), "{}", err);
+        assert!(err.to_string().contains("--force-unlock"), "{}", err);
+        let err = force_unlock(&index_dir).unwrap_err();
+        assert!(err.to_string().contains("is held by a running process"), "{}", err);
+        assert!(writer_lock_path(&index_dir).exists());
+
+        drop(writer);
+        force_unlock(&index_dir).unwrap();
+        open_writer::<TantivyDocument>(&index, &index_dir, 15_000_000).unwrap();
+    }
+}
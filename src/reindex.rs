@@ -0,0 +1,298 @@
+use crate::schema::{build_schema, exact_field_name, extra_columns, substring_field_name, substring_fields, tokenizer_overrides, normalize_exact_value, normalize_unicode, EXACT_MATCH_FIELDS, SUBSTRING_FIELDS};
+use crate::phone::{mark_canonical_phones, PhoneNumber};
+use crate::ready::{lock_index_dir, mark_ready, open_writer};
+use crate::tokenizer::register_tokenizers;
+use anyhow::{Result, anyhow};
+use std::path::Path;
+use std::time::Instant;
+use tantivy::schema::{Schema, Value};
+use tantivy::{DateTime, Index, IndexWriter, ReloadPolicy, TantivyDocument};
+
+/// Rebuild an index from the stored fields of an existing one using the
+/// current schema, e.g. after changing tokenizer settings in `schema.rs`.
+/// The original CSV is not needed since every searchable field is STORED.
+pub fn reindex(src_dir: &str, dst_dir: &str) -> Result<()> {
+    println!("Starting reindex...");
+    println!("Source index: {}", src_dir);
+    println!("Destination index: {}", dst_dir);
+
+    let start_time = Instant::now();
+    let source = Index::open_in_dir(src_dir)?;
+    std::fs::create_dir_all(dst_dir)
+        .map_err(|e| anyhow::anyhow!("Cannot create index directory {}: {}", dst_dir, e))?;
+    let _lock = lock_index_dir(dst_dir)?;
+
+    // Extra CSV columns, tokenizer choices and substring companions of the source carry over
+    let source_schema = source.schema();
+    let schema = build_schema(&extra_columns(&source_schema), &tokenizer_overrides(&source_schema), &substring_fields(&source_schema));
+    let target = Index::create_in_dir(Path::new(dst_dir), schema.clone())?;
+    mark_canonical_phones(dst_dir)?;
+    register_tokenizers(&target);
+    let mut writer: IndexWriter<TantivyDocument> = open_writer(&target, dst_dir, 1_000_000_000)?;
+
+    let copied = copy_documents(&source, &schema, &mut writer)?;
+
+    println!("Committing index...");
+    let opstamp = writer.commit()?;
+    mark_ready(dst_dir, opstamp)?;
+
+    let total_elapsed = start_time.elapsed();
+    println!("✓ Reindex complete!");
+    println!("  Total records reindexed: {}", copied);
+    println!("  Total time: {:.2} seconds", total_elapsed.as_secs_f64());
+
+    Ok(())
+}
+
+/// Combine several indexes sharing one schema (e.g. per-shard builds) into a
+/// new index at `dst_dir`, copying their live stored documents the way
+/// `reindex` does. Sources whose schemas differ are rejected before anything
+/// is written.
+pub fn merge_indexes(dst_dir: &str, src_dirs: &[String]) -> Result<()> {
+    println!("Starting index merge...");
+    println!("Destination index: {}", dst_dir);
+    let start_time = Instant::now();
+
+    let (first_dir, rest) = src_dirs.split_first()
+        .ok_or_else(|| anyhow!("No source indexes to merge"))?;
+    let first = Index::open_in_dir(first_dir)?;
+    let schema = first.schema();
+    let mut sources = vec![(first_dir, first)];
+    for src_dir in rest {
+        let source = Index::open_in_dir(src_dir)?;
+        if source.schema() != schema {
+            let field = schema_difference(&schema, &source.schema()).unwrap_or_default();
+            return Err(anyhow!("Index {} has a different schema from {} (field '{}')", src_dir, first_dir, field));
+        }
+        sources.push((src_dir, source));
+    }
+
+    std::fs::create_dir_all(dst_dir)
+        .map_err(|e| anyhow!("Cannot create index directory {}: {}", dst_dir, e))?;
+    let _lock = lock_index_dir(dst_dir)?;
+    let target = Index::create_in_dir(Path::new(dst_dir), schema.clone())?;
+    // Copies canonicalize phone numbers whatever the sources held
+    mark_canonical_phones(dst_dir)?;
+    register_tokenizers(&target);
+    let mut writer: IndexWriter<TantivyDocument> = open_writer(&target, dst_dir, 1_000_000_000)?;
+
+    let mut total = 0;
+    for (src_dir, source) in &sources {
+        let copied = copy_documents(source, &schema, &mut writer)?;
+        println!("  {}: {} records", src_dir, copied);
+        total += copied;
+    }
+
+    println!("Committing index...");
+    let opstamp = writer.commit()?;
+    mark_ready(dst_dir, opstamp)?;
+
+    println!("✓ Merge complete!");
+    println!("  Indexes merged: {}", sources.len());
+    println!("  Total records merged: {}", total);
+    println!("  Total time: {:.2} seconds", start_time.elapsed().as_secs_f64());
+
+    Ok(())
+}
+
+/// Name of the first field the two schemas define differently or only one has
+fn schema_difference(a: &Schema, b: &Schema) -> Option<String> {
+    let differs = |x: &Schema, y: &Schema| x.fields().find_map(|(_, entry)| {
+        let other = y.get_field(entry.name()).ok().map(|field| y.get_field_entry(field));
+        (other != Some(entry)).then(|| entry.name().to_string())
+    });
+    differs(a, b).or_else(|| differs(b, a))
+}
+
+/// Copy every live document of `source` into `writer`, mapping stored fields
+/// by name onto `schema`. Fields that aren't stored (exact-match and
+/// substring companions) are re-derived, text is NFC-normalized, phone numbers canonicalized, and documents predating `indexed_at` get stamped now.
+/// Returns the number of documents copied.
+pub fn copy_documents(source: &Index, schema: &Schema, writer: &mut IndexWriter<TantivyDocument>) -> Result<u64> {
+    let source_schema = source.schema();
+    let reader = source.reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()?;
+    let searcher = reader.searcher();
+
+    // (source field, target field) for every stored field both schemas share
+    let stored_fields: Vec<_> = schema.fields()
+        .filter(|(_, entry)| entry.is_stored())
+        .filter_map(|(field, entry)| {
+            source_schema.get_field(entry.name()).ok().map(|src| (src, field))
+        })
+        .collect();
+    let exact_fields: Vec<_> = EXACT_MATCH_FIELDS.iter()
+        .filter_map(|base| {
+            let src = source_schema.get_field(base).ok()?;
+            let dst = schema.get_field(&exact_field_name(base)).ok()?;
+            Some((src, dst))
+        })
+        .collect();
+    // Derived from the copied (canonicalized) value, as at index time
+    let substring_companions: Vec<_> = SUBSTRING_FIELDS.iter()
+        .filter_map(|base| {
+            let dst_base = schema.get_field(base).ok()?;
+            let dst = schema.get_field(&substring_field_name(base)).ok()?;
+            Some((dst_base, dst))
+        })
+        .collect();
+    let phone_fields: Vec<_> = ["mobile", "alt"].iter()
+        .filter_map(|name| schema.get_field(name).ok())
+        .collect();
+    let indexed_at_field = schema.get_field("indexed_at").ok();
+    let source_has_indexed_at = source_schema.get_field("indexed_at").is_ok();
+    let now = DateTime::from_timestamp_secs(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64,
+    );
+
+    let mut copied = 0u64;
+    for segment in searcher.segment_readers() {
+        let store = segment.get_store_reader(64)?;
+        for doc in store.iter::<TantivyDocument>(segment.alive_bitset()) {
+            let doc = doc?;
+            let mut new_doc = TantivyDocument::default();
+
+            for (src, dst) in &stored_fields {
+                for value in doc.get_all(*src) {
+                    if let Some(text) = value.as_str() {
+                        let text = normalize_unicode(text);
+                        if phone_fields.contains(dst) {
+                            new_doc.add_text(*dst, PhoneNumber::canonicalize(&text));
+                        } else {
+                            new_doc.add_text(*dst, text);
+                        }
+                    } else if let Some(date) = value.as_datetime() {
+                        new_doc.add_date(*dst, date);
+                    } else if let Some(number) = value.as_u64() {
+                        new_doc.add_u64(*dst, number);
+                    }
+                }
+            }
+            for (src, dst) in &exact_fields {
+                let value = doc.get_first(*src).and_then(|v| v.as_str()).unwrap_or("");
+                new_doc.add_text(*dst, normalize_exact_value(value));
+            }
+            for (base, dst) in &substring_companions {
+                let value = new_doc.get_first(*base).and_then(|v| v.as_str()).unwrap_or("").to_string();
+                new_doc.add_text(*dst, value);
+            }
+            if let Some(field) = indexed_at_field
+                && !source_has_indexed_at
+            {
+                new_doc.add_date(field, now);
+            }
+
+            writer.add_document(new_doc)?;
+            copied += 1;
+            if copied.is_multiple_of(100_000) {
+                println!("[Progress] Copied {} records", copied);
+            }
+        }
+    }
+
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::{build_index, IndexOptions};
+    use crate::phone::CANONICAL_PHONES_MARKER;
+    use crate::search_service::{SearchConfig, SearchOptions, SearchService};
+    use crate::test_support::write_csv;
+    use tempfile::TempDir;
+
+    /// Stored text fields of every live document, sorted, keyed by field name
+    fn stored_records(index_dir: &str) -> Vec<Vec<(String, String)>> {
+        let index = Index::open_in_dir(index_dir).unwrap();
+        let schema = index.schema();
+        let searcher = index.reader().unwrap().searcher();
+        let mut records = Vec::new();
+        for segment in searcher.segment_readers() {
+            let store = segment.get_store_reader(1).unwrap();
+            for doc in store.iter::<TantivyDocument>(segment.alive_bitset()) {
+                let doc = doc.unwrap();
+                let mut fields: Vec<(String, String)> = doc.field_values()
+                    .filter_map(|(field, value)| {
+                        let text = value.as_str()?;
+                        Some((schema.get_field_name(field).to_string(), text.to_string()))
+                    })
+                    .collect();
+                fields.sort();
+                records.push(fields);
+            }
+        }
+        records.sort();
+        records
+    }
+
+    #[test]
+    fn reindex_preserves_every_record_and_keeps_it_searchable() {
+        let dir = TempDir::new().unwrap();
+        let csv = write_csv(&dir, "people.csv", &[
+            "m1,9800000001,ravi,ravi kumar,9800000009,ravi@example.com,mg road delhi",
+            "m2,9800000002,amit,amit shah,,,pune",
+            "m3,,zoe,zoë kumar,,zoe@example.org,",
+        ]);
+        let src = dir.path().join("src").to_str().unwrap().to_string();
+        let dst = dir.path().join("dst").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&src).unwrap();
+        std::fs::create_dir_all(&dst).unwrap();
+        build_index(&csv, &src, &IndexOptions::default()).unwrap();
+
+        reindex(&src, &dst).unwrap();
+
+        let records = stored_records(&dst);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records, stored_records(&src));
+
+        let service = SearchService::new(&dst, SearchConfig::default()).unwrap();
+        let options = SearchOptions::default();
+        let kumar = service.search("name:kumar", &options).unwrap();
+        let mut ids: Vec<&str> = kumar.results.iter().map(|r| r["master_id"].as_str().unwrap()).collect();
+        ids.sort();
+        assert_eq!(ids, ["m1", "m3"]);
+        assert_eq!(service.search("name=\"amit shah\"", &options).unwrap().results.len(), 1);
+        assert_eq!(service.search("email:example.org", &options).unwrap().results.len(), 1);
+    }
+
+    #[test]
+    fn merged_indexes_search_as_their_union() {
+        let dir = TempDir::new().unwrap();
+        let build = |name: &str, csv: &str| {
+            let path = dir.path().join(format!("{}.csv", name));
+            std::fs::write(&path, csv).unwrap();
+            let index_dir = dir.path().join(name).to_str().unwrap().to_string();
+            std::fs::create_dir_all(&index_dir).unwrap();
+            build_index(path.to_str().unwrap(), &index_dir, &IndexOptions::default()).unwrap();
+            index_dir
+        };
+        let north = build("north", "master_id,mobile,fname,name,alt,email,address\n\
+            n1,9800000001,ravi,ravi kumar,,,delhi\n\
+            n2,9800000002,amit,amit shah,,,delhi\n");
+        let south = build("south", "master_id,mobile,fname,name,alt,email,address\n\
+            s1,9800000003,ravi,ravi iyer,,,chennai\n");
+        let merged = dir.path().join("merged").to_str().unwrap().to_string();
+
+        merge_indexes(&merged, &[north.clone(), south]).unwrap();
+
+        let service = SearchService::new(&merged, SearchConfig::default()).unwrap();
+        let options = SearchOptions::default();
+        let ravi = service.search("name:ravi", &options).unwrap();
+        let mut ids: Vec<&str> = ravi.results.iter().map(|r| r["master_id"].as_str().unwrap()).collect();
+        ids.sort();
+        assert_eq!(ids, ["n1", "s1"]);
+        assert_eq!(stored_records(&merged).len(), 3);
+        assert!(Path::new(&merged).join(CANONICAL_PHONES_MARKER).exists());
+
+        let gendered = build("gendered", "master_id,mobile,fname,name,alt,email,address,gender\n\
+            g1,9800000004,sita,sita ram,,,pune,f\n");
+        let err = merge_indexes(dir.path().join("mixed").to_str().unwrap(), &[north, gendered])
+            .unwrap_err();
+        assert!(err.to_string().contains("different schema"), "{}", err);
+        assert!(err.to_string().contains("'gender'"), "{}", err);
+    }
+}
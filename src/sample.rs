@@ -0,0 +1,159 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use tantivy::{DocAddress, Index, ReloadPolicy, Searcher, TantivyDocument};
+use tantivy::schema::Value;
+use serde_json::json;
+
+/// Print `count` pseudo-random live documents as JSON lines.
+///
+/// Segments are picked with probability proportional to their size and a doc
+/// id drawn uniformly within the segment, retrying deleted or already chosen
+/// docs. The same `seed` over the same index always yields the same sample.
+pub fn sample_index(index_dir: &str, count: usize, seed: Option<u64>) -> Result<()> {
+    println!("Opening index from: {}", index_dir);
+    let index = Index::open_in_dir(index_dir)?;
+    let schema = index.schema();
+
+    let reader = index.reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()?;
+    let searcher = reader.searcher();
+
+    let seed = seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    });
+    println!("Seed: {} (pass it again to repeat this sample)", seed);
+
+    let live_docs = searcher.num_docs() as usize;
+    println!("Sampling {} of {} records...\n", count.min(live_docs), live_docs);
+    let sample = sample_addresses(&searcher, count, seed);
+
+    let master_id_field = schema.get_field("master_id").unwrap();
+    let mobile_field = schema.get_field("mobile").unwrap();
+    let alt_field = schema.get_field("alt").unwrap();
+    let name_field = schema.get_field("name").unwrap();
+    let fname_field = schema.get_field("fname").unwrap();
+    let address_field = schema.get_field("address").unwrap();
+    let email_field = schema.get_field("email").unwrap();
+
+    for (idx, addr) in sample.iter().enumerate() {
+        let doc: TantivyDocument = searcher.doc(*addr)?;
+
+        let extract_first = |field| -> String {
+            doc.get_first(field)
+                .and_then(|v| Value::as_str(&v))
+                .unwrap_or("")
+                .to_string()
+        };
+
+        let json_obj = json!({
+            "row": idx + 1,
+            "master_id": extract_first(master_id_field),
+            "mobile": extract_first(mobile_field),
+            "alt": extract_first(alt_field),
+            "name": extract_first(name_field),
+            "fname": extract_first(fname_field),
+            "address": extract_first(address_field),
+            "email": extract_first(email_field),
+        });
+
+        println!("{}", serde_json::to_string(&json_obj)?);
+    }
+
+    println!("\nTotal records sampled: {}", sample.len());
+    Ok(())
+}
+
+/// `count` distinct live doc addresses (fewer if the index is smaller),
+/// drawn as described on `sample_index`
+fn sample_addresses(searcher: &Searcher, count: usize, seed: u64) -> Vec<DocAddress> {
+    let segments = searcher.segment_readers();
+    let max_docs: Vec<u64> = segments.iter().map(|s| s.max_doc() as u64).collect();
+    let total_max_doc: u64 = max_docs.iter().sum();
+    let live_docs = searcher.num_docs() as usize;
+    let count = count.min(live_docs);
+
+    let mut rng = SplitMix64(seed);
+    let mut chosen: HashSet<DocAddress> = HashSet::with_capacity(count);
+    let mut sample = Vec::with_capacity(count);
+    while sample.len() < count {
+        // A uniform draw over all doc slots weights segments by their size
+        let mut slot = rng.next() % total_max_doc;
+        let mut segment_ord = 0;
+        while slot >= max_docs[segment_ord] {
+            slot -= max_docs[segment_ord];
+            segment_ord += 1;
+        }
+        let doc_id = slot as u32;
+        if !segments[segment_ord].is_deleted(doc_id) {
+            let addr = DocAddress::new(segment_ord as u32, doc_id);
+            if chosen.insert(addr) {
+                sample.push(addr);
+            }
+        }
+    }
+
+    sample
+}
+
+/// Small seeded PRNG; sampling doesn't need more than this
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::build_schema;
+    use crate::tokenizer::register_tokenizers;
+    use std::collections::HashMap;
+    use tantivy::{TantivyDocument, Term};
+
+    /// Two-segment in-memory index of 60 rows, m0..m59, with m0..m9 deleted
+    fn searcher() -> Searcher {
+        let index = Index::create_in_ram(build_schema(&[], &HashMap::new(), &[]));
+        register_tokenizers(&index);
+        let master_id = index.schema().get_field("master_id").unwrap();
+        let mut writer = index.writer(15_000_000).unwrap();
+        for batch in [0..30, 30..60] {
+            for i in batch {
+                let mut doc = TantivyDocument::default();
+                doc.add_text(master_id, format!("m{}", i));
+                writer.add_document(doc).unwrap();
+            }
+            writer.commit().unwrap();
+        }
+        for i in 0..10 {
+            writer.delete_term(Term::from_field_text(master_id, &format!("m{}", i)));
+        }
+        writer.commit().unwrap();
+        index.reader().unwrap().searcher()
+    }
+
+    #[test]
+    fn seeded_sample_is_repeatable_distinct_and_live() {
+        let searcher = searcher();
+        assert_eq!(searcher.segment_readers().len(), 2);
+
+        let sample = sample_addresses(&searcher, 20, 42);
+        assert_eq!(sample.len(), 20);
+        assert_eq!(sample_addresses(&searcher, 20, 42), sample);
+        assert_ne!(sample_addresses(&searcher, 20, 43), sample);
+        assert_eq!(sample.iter().collect::<HashSet<_>>().len(), 20);
+        assert!(sample.iter().all(|addr| !searcher.segment_reader(addr.segment_ord).is_deleted(addr.doc_id)));
+
+        // Asking for more than the index holds returns every live doc once
+        assert_eq!(sample_addresses(&searcher, 100, 7).len(), 50);
+    }
+}
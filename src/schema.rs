@@ -1,6 +1,126 @@
+use crate::tokenizer::{EMAIL_TOKENIZER, SUBSTRING_TOKENIZER};
+use icu_normalizer::ComposingNormalizerBorrowed;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use tantivy::schema::*;
 
-pub fn build_schema() -> Schema {
+/// TEXT fields that get an untokenized `<field>_exact` companion for
+/// whole-value matching (`name="john smith"`). Opt-in per field because
+/// every companion adds another term dictionary to the index.
+pub const EXACT_MATCH_FIELDS: &[&str] = &["name", "fname"];
+
+/// STRING fields that can get an ngram `<field>_ngram` companion for
+/// substring matching (`mobile:contains:4321`). Opt-in per index build
+/// (`--substring-fields`): every value adds dozens of terms.
+pub const SUBSTRING_FIELDS: &[&str] = &["mobile", "alt", "master_id"];
+
+/// Extra CSV columns kept for display only: STORED but not indexed, so they
+/// come back with results without costing any term dictionary space.
+/// Populated from the CSV column with the same header name, when present.
+pub const STORED_ONLY_FIELDS: &[&str] = &["notes", "source_system"];
+
+/// Fields every index has, fed by the fixed seven-column CSV layout or
+/// derived by the indexer
+const BUILTIN_FIELDS: &[&str] = &["master_id", "mobile", "alt", "name", "fname", "address", "email", "indexed_at", "source_row"];
+
+/// Whether `name` is part of the built-in schema rather than an extra CSV
+/// column (see `build_schema`)
+pub fn is_builtin_field(name: &str) -> bool {
+    BUILTIN_FIELDS.contains(&name)
+        || STORED_ONLY_FIELDS.contains(&name)
+        || EXACT_MATCH_FIELDS.iter().any(|base| exact_field_name(base) == name)
+        || SUBSTRING_FIELDS.iter().any(|base| substring_field_name(base) == name)
+}
+
+/// Field name for an extra CSV column header: trimmed and lowercased, and
+/// only when it is a plain identifier (letters, digits, `_`, starting with
+/// a letter) that the query syntax can address
+pub fn extra_column_name(header: &str) -> Option<String> {
+    let name = header.trim().to_lowercase();
+    let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    (valid && !is_builtin_field(&name)).then_some(name)
+}
+
+/// Extra CSV columns an index was built with, in schema order
+pub fn extra_columns(schema: &Schema) -> Vec<String> {
+    schema.fields()
+        .map(|(_, entry)| entry.name())
+        .filter(|name| !is_builtin_field(name))
+        .map(str::to_string)
+        .collect()
+}
+
+/// TEXT fields of an index whose tokenizer differs from the one
+/// `build_schema` gives them by default, as `build_schema` overrides
+pub fn tokenizer_overrides(schema: &Schema) -> HashMap<String, String> {
+    let tokenizer = |entry: &FieldEntry| match entry.field_type() {
+        FieldType::Str(options) => options.get_indexing_options().map(|o| o.tokenizer().to_string()),
+        _ => None,
+    };
+    let defaults = build_schema(&extra_columns(schema), &HashMap::new(), &[]);
+    schema.fields()
+        .filter_map(|(_, entry)| {
+            let default = defaults.get_field(entry.name()).ok()?;
+            let used = tokenizer(entry)?;
+            (tokenizer(defaults.get_field_entry(default)).as_ref() != Some(&used))
+                .then(|| (entry.name().to_string(), used))
+        })
+        .collect()
+}
+
+/// Name of the raw companion field backing exact matches on `field`
+pub fn exact_field_name(field: &str) -> String {
+    format!("{}_exact", field)
+}
+
+/// Name of the ngram companion backing substring matches on `field`
+pub fn substring_field_name(field: &str) -> String {
+    format!("{}_ngram", field)
+}
+
+/// Fields of `SUBSTRING_FIELDS` an index was built with a substring companion for
+pub fn substring_fields(schema: &Schema) -> Vec<String> {
+    SUBSTRING_FIELDS.iter()
+        .filter(|base| schema.get_field(&substring_field_name(base)).is_ok())
+        .map(|base| base.to_string())
+        .collect()
+}
+
+/// Unicode NFC form of a value, so composed ("José") and decomposed
+/// ("Jose" + U+0301) spellings index and query to the same terms.
+/// Applied to every CSV value at index time and to every query value.
+pub fn normalize_unicode(value: &str) -> Cow<'_, str> {
+    ComposingNormalizerBorrowed::new_nfc().normalize(value)
+}
+
+/// Canonical form of a whole value for exact matching:
+/// NFC, lowercased, with runs of whitespace collapsed to a single space.
+/// Applied identically at index and query time.
+pub fn normalize_exact_value(value: &str) -> String {
+    normalize_unicode(value).split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Print the schema an existing index was built with as JSON: one entry per
+/// field with its name, type and indexing options. Only the JSON goes to
+/// stdout so the output can be piped.
+pub fn print_index_schema(index_dir: &str) -> anyhow::Result<()> {
+    println!("{}", index_schema_json(index_dir)?);
+    Ok(())
+}
+
+/// Pretty-printed JSON schema of the index in `index_dir`
+fn index_schema_json(index_dir: &str) -> anyhow::Result<String> {
+    let index = tantivy::Index::open_in_dir(index_dir)?;
+    Ok(serde_json::to_string_pretty(&index.schema())?)
+}
+
+/// The built-in schema plus one TEXT field per extra CSV column, so columns
+/// beyond the fixed layout (`gender`, `pincode`...) are searchable and
+/// returned like `address` without code changes. `tokenizers` overrides the
+/// tokenizer of TEXT fields by name; `substring_fields` (from
+/// `SUBSTRING_FIELDS`) get an ngram companion.
+pub fn build_schema(extra_columns: &[String], tokenizers: &HashMap<String, String>, substring_fields: &[String]) -> Schema {
     let mut schema_builder = Schema::builder();
 
     // STRING + FAST fields for exact matches (mobile, alt, master_id)
@@ -11,22 +131,90 @@ pub fn build_schema() -> Schema {
     schema_builder.add_text_field("mobile", STRING | STORED | FAST);
     schema_builder.add_text_field("alt", STRING | STORED | FAST);
 
-    // TEXT fields for partial/prefix matches (name, fname, address, email)
+    // TEXT fields for partial/prefix matches (name, fname, address)
     // - TEXT: Tokenized for partial matching
     // - Default tokenizer: case-insensitive, handles partial matches
     // - STORED: Store original value for retrieval
-    let text_options = TextOptions::default()
+    let text_options = |field: &str, tokenizer: &str| TextOptions::default()
         .set_stored()
         .set_indexing_options(
             TextFieldIndexing::default()
-                .set_tokenizer("default") // Case-insensitive tokenizer
+                .set_tokenizer(tokenizers.get(field).map_or(tokenizer, String::as_str))
                 .set_index_option(IndexRecordOption::WithFreqsAndPositions)
         );
 
-    schema_builder.add_text_field("name", text_options.clone());
-    schema_builder.add_text_field("fname", text_options.clone());
-    schema_builder.add_text_field("address", text_options.clone());
-    schema_builder.add_text_field("email", text_options);
+    // Case-insensitive default tokenizer
+    schema_builder.add_text_field("name", text_options("name", "default"));
+    schema_builder.add_text_field("fname", text_options("fname", "default"));
+    schema_builder.add_text_field("address", text_options("address", "default"));
+
+    // Email uses the email-aware tokenizer: whole address, local part, domain
+    // and domain labels each become a term (see tokenizer.rs)
+    schema_builder.add_text_field("email", text_options("email", EMAIL_TOKENIZER));
+
+    // STORED-only fields: retrievable, never searchable
+    for field in STORED_ONLY_FIELDS {
+        schema_builder.add_text_field(field, STORED);
+    }
+
+    // STRING companions for exact whole-value matches on selected TEXT fields
+    // - Not STORED: the original value is already stored on the TEXT field
+    for field in EXACT_MATCH_FIELDS {
+        schema_builder.add_text_field(&exact_field_name(field), STRING);
+    }
+
+    // Ngram companions for substring matches on opted-in STRING fields
+    // - Not STORED, no positions: only term lookups of a substring
+    for field in substring_fields {
+        let options = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(SUBSTRING_TOKENIZER)
+                .set_index_option(IndexRecordOption::Basic)
+        );
+        schema_builder.add_text_field(&substring_field_name(field), options);
+    }
+
+    // DATE field stamped once per build/append batch by the indexer
+    // - INDEXED: Enables range filters like indexed_at:[<ts> TO *]
+    // - FAST + STORED: Cheap filtering and retrieval of the build time
+    schema_builder.add_date_field("indexed_at", INDEXED | STORED | FAST);
+
+    // U64 field holding the CSV line each record was read from, for audits
+    // - STORED only: returned on request, never searched
+    schema_builder.add_u64_field("source_row", STORED);
+
+    // Extra CSV columns, tokenized and stored like the built-in TEXT fields
+    for column in extra_columns {
+        schema_builder.add_text_field(column, text_options(column, "default"));
+    }
 
     schema_builder.build()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::{build_index, IndexOptions};
+    use tempfile::TempDir;
+
+    #[test]
+    fn printed_schema_matches_the_built_one() {
+        let dir = TempDir::new().unwrap();
+        let csv = dir.path().join("people.csv");
+        std::fs::write(&csv, "master_id,mobile,fname,name,alt,email,address,PinCode\nm1,9800000001,ravi,ravi kumar,,,delhi,110001\n").unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(csv.to_str().unwrap(), &index_dir, &IndexOptions::default()).unwrap();
+
+        let printed: serde_json::Value = serde_json::from_str(&index_schema_json(&index_dir).unwrap()).unwrap();
+        let expected = build_schema(&["pincode".to_string()], &HashMap::new(), &[]);
+        assert_eq!(printed, serde_json::to_value(&expected).unwrap());
+
+        let fields = printed.as_array().unwrap();
+        let entry = |name: &str| fields.iter().find(|f| f["name"] == name).unwrap();
+        assert_eq!(entry("mobile")["type"], "text");
+        assert_eq!(entry("mobile")["options"]["indexing"]["tokenizer"], "raw");
+        assert_eq!(entry("indexed_at")["type"], "date");
+        assert_eq!(entry("pincode")["options"]["stored"], true);
+    }
+}
@@ -1,23 +1,63 @@
-use crate::query_parser::CustomQueryParser;
-use anyhow::Result;
-use std::collections::HashSet;
+use crate::phone::warn_raw_phones;
+use crate::query_parser::{CustomQueryParser, MatchMode, ParsedQuery, is_pattern_value};
+use crate::search_service::{
+    exact_key_field, is_email_fanout_value, mark_matches, match_offsets, read_document, SearchConfig,
+    SearchOptions, SearchService, EMAIL_FANOUT_FIELDS,
+};
+use crate::schema::{is_builtin_field, STORED_ONLY_FIELDS};
+use crate::tokenizer::register_tokenizers;
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufWriter, IsTerminal, Write};
+use std::path::Path;
 use std::time::Instant;
 use tantivy::{
-    Index, TantivyDocument, collector::TopDocs,
-    ReloadPolicy, DocAddress, Term
+    Index, TantivyDocument, collector::{DocSetCollector, TopDocs},
+    ReloadPolicy, DocAddress, DocSet, Term, TERMINATED
 };
-use tantivy::query::{Query, BooleanQuery, Occur, TermQuery};
+use tantivy::query::{Query, BooleanQuery, EnableScoring, Occur, TermQuery};
 use tantivy::schema::{Value, IndexRecordOption};
+use tantivy::time::format_description::well_known::Rfc3339;
 use serde_json::json;
 
 const MAX_RESULTS: usize = 10_000;
 
-pub fn search(index_dir: &str, query_str: &str) -> Result<()> {
+/// Longest date range `search_partitions` accepts
+const MAX_PARTITION_DAYS: i64 = 366;
+
+/// How `search --highlight` marks matched terms of TEXT fields in the
+/// printed values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    /// Bold red terminal color
+    Color,
+    /// `**term**`, for `--no-color`, `NO_COLOR` or output that isn't a terminal
+    Markers,
+}
+
+impl Highlight {
+    pub fn for_stdout(no_color: bool) -> Self {
+        if no_color || std::env::var_os("NO_COLOR").is_some() || !std::io::stdout().is_terminal() {
+            Highlight::Markers
+        } else {
+            Highlight::Color
+        }
+    }
+}
+
+// Stand-ins for the color codes while the record is serialized, which
+// would escape the codes themselves
+const HIGHLIGHT_OPEN: char = '\u{E000}';
+const HIGHLIGHT_CLOSE: char = '\u{E001}';
+
+pub fn search(index_dir: &str, query_str: &str, key_field: &str, skip_failed_docs: bool, highlight: Option<Highlight>) -> Result<()> {
     let search_start = Instant::now();
 
     println!("Opening index from: {}", index_dir);
     let open_start = Instant::now();
     let index = Index::open_in_dir(index_dir)?;
+    register_tokenizers(&index);
 
     // Use the actual schema from the index (not build_schema)
     let schema = index.schema();
@@ -28,6 +68,7 @@ pub fn search(index_dir: &str, query_str: &str) -> Result<()> {
         .try_into()?;
 
     let searcher = reader.searcher();
+    warn_raw_phones(&searcher, index_dir)?;
     let open_time = open_start.elapsed();
     println!("Index opened in {:.3}s", open_time.as_secs_f64());
 
@@ -36,27 +77,25 @@ pub fn search(index_dir: &str, query_str: &str) -> Result<()> {
     println!("Parsing query: {}", query_str);
     let parse_start = Instant::now();
     let parsed_query = query_parser.parse(query_str)?;
+    for clause in &parsed_query.dropped {
+        println!("Ignoring clause on '{}': nothing searchable in its value", clause.field);
+    }
     let parse_time = parse_start.elapsed();
     println!("Query parsed in {:.3}ms", parse_time.as_secs_f64() * 1000.0);
 
     println!("Executing search...");
     let execute_start = Instant::now();
 
-    // Check if this is a mobile search (needs fan-out)
-    let is_mobile_search = parsed_query.clauses.len() == 1
-        && parsed_query.clauses[0].field == "mobile";
-
-    let all_doc_addresses = if is_mobile_search {
-        // Mobile fan-out logic
-        let mobile_value = query_parser.normalize_value("mobile", &parsed_query.clauses[0].value);
-        execute_mobile_fanout(&searcher, &schema, &query_parser, &mobile_value)?
-    } else {
-        // Regular query execution
-        let query = query_parser.build_query(&parsed_query)?;
-        searcher.search(&*query, &TopDocs::with_limit(MAX_RESULTS))?
-            .into_iter()
-            .map(|(_score, addr)| addr)
-            .collect()
+    let all_doc_addresses = match fanout_addresses(&searcher, &schema, key_field, &query_parser, &parsed_query, skip_failed_docs)? {
+        Some(addresses) => addresses,
+        None => {
+            // Regular query execution
+            let query = query_parser.build_query(&parsed_query)?;
+            searcher.search(&*query, &TopDocs::with_limit(MAX_RESULTS))?
+                .into_iter()
+                .map(|(_score, addr)| addr)
+                .collect()
+        }
     };
 
     let execute_time = execute_start.elapsed();
@@ -75,9 +114,12 @@ pub fn search(index_dir: &str, query_str: &str) -> Result<()> {
     let mut results: Vec<TantivyDocument> = Vec::new();
 
     // Return all results without deduplication for maximum speed
+    let mut failed_retrievals = 0;
     for addr in all_doc_addresses.iter().take(MAX_RESULTS) {
-        let retrieved: TantivyDocument = searcher.doc(*addr)?;
-        results.push(retrieved);
+        match read_document(&searcher, *addr, skip_failed_docs)? {
+            Some(retrieved) => results.push(retrieved),
+            None => failed_retrievals += 1,
+        }
     }
 
     let retrieve_time = retrieve_start.elapsed();
@@ -85,7 +127,13 @@ pub fn search(index_dir: &str, query_str: &str) -> Result<()> {
     // Output results in JSON format
     for doc in &results {
         let json_doc = document_to_json(doc, &schema)?;
-        println!("{}", json_doc);
+        match highlight {
+            Some(highlight) => {
+                let offsets = match_offsets(&index, &query_parser, &parsed_query, doc)?;
+                println!("{}", highlight_record(&json_doc, &offsets, highlight)?);
+            }
+            None => println!("{}", json_doc),
+        }
     }
 
     let total_time = search_start.elapsed();
@@ -94,6 +142,9 @@ pub fn search(index_dir: &str, query_str: &str) -> Result<()> {
     println!("Summary:");
     println!("  Total matches found: {}", total_results);
     println!("  Results returned: {}", actual_results_count);
+    if skip_failed_docs {
+        println!("  Unreadable results skipped: {}", failed_retrievals);
+    }
     println!("  Document retrieval time: {:.3}ms", retrieve_time.as_secs_f64() * 1000.0);
     println!("  Total time: {:.3}ms", total_time.as_secs_f64() * 1000.0);
     println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
@@ -101,37 +152,325 @@ pub fn search(index_dir: &str, query_str: &str) -> Result<()> {
     Ok(())
 }
 
+/// Open the index once and run each line of stdin as a query until EOF,
+/// printing every result as JSON followed by a summary. A failing query
+/// prints its error and the loop carries on.
+pub fn search_repl(index_dir: &str, config: SearchConfig) -> Result<()> {
+    let service = SearchService::new(index_dir, config)?;
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprintln!("Enter one query per line, Ctrl-D to quit");
+    }
+    run_queries(&service, stdin.lock(), &mut std::io::stdout().lock())
+}
+
+/// Run each non-blank line of `input` as a query, writing its results and
+/// summary to `out`
+fn run_queries(service: &SearchService, input: impl BufRead, out: &mut impl Write) -> Result<()> {
+    let options = SearchOptions::default();
+    for line in input.lines() {
+        let line = line?;
+        let query = line.trim();
+        if query.is_empty() {
+            continue;
+        }
+        writeln!(out, "Query: {}", query)?;
+        let results = match service.search(query, &options) {
+            Ok(results) => results,
+            Err(e) => {
+                writeln!(out, "Error: {}", e)?;
+                writeln!(out, "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━")?;
+                continue;
+            }
+        };
+        for result in &results.results {
+            writeln!(out, "{}", serde_json::to_string(result)?)?;
+        }
+        writeln!(out, "Summary:")?;
+        writeln!(out, "  Total matches found: {}", results.total_matches)?;
+        writeln!(out, "  Results returned: {}", results.results_returned)?;
+        if results.executed_query != query {
+            writeln!(out, "  Executed query: {}", results.executed_query)?;
+        }
+        if let Some(failed_retrievals) = results.failed_retrievals {
+            writeln!(out, "  Unreadable results skipped: {}", failed_retrievals)?;
+        }
+        writeln!(out, "  Total time: {:.3}ms", results.total_time_ms)?;
+        writeln!(out, "━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Write every match of a query to `out_file` as NDJSON, in index order.
+/// Each record is written as soon as it is read, so exports far beyond
+/// MAX_RESULTS never hold the result set in memory. Fan-out queries are
+/// exported as `search` runs them.
+pub fn export_results(index_dir: &str, query_str: &str, out_file: &str, key_field: &str, skip_failed_docs: bool) -> Result<()> {
+    let export_start = Instant::now();
+
+    println!("Opening index from: {}", index_dir);
+    let index = Index::open_in_dir(index_dir)?;
+    register_tokenizers(&index);
+    let schema = index.schema();
+    let reader = index.reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()?;
+    let searcher = reader.searcher();
+    warn_raw_phones(&searcher, index_dir)?;
+
+    let query_parser = CustomQueryParser::new(schema.clone(), index.clone());
+    let parsed_query = query_parser.parse(query_str)?;
+    for clause in &parsed_query.dropped {
+        println!("Ignoring clause on '{}': nothing searchable in its value", clause.field);
+    }
+
+    let mut out = BufWriter::new(File::create(out_file)
+        .map_err(|e| anyhow!("Cannot create {}: {}", out_file, e))?);
+    let mut exported = 0u64;
+    let mut failed_retrievals = 0u64;
+    let mut export_doc = |addr: DocAddress| -> Result<()> {
+        match read_document(&searcher, addr, skip_failed_docs)? {
+            Some(doc) => {
+                writeln!(out, "{}", document_to_json(&doc, &schema)?)?;
+                exported += 1;
+            }
+            None => failed_retrievals += 1,
+        }
+        Ok(())
+    };
+
+    match fanout_addresses(&searcher, &schema, key_field, &query_parser, &parsed_query, skip_failed_docs)? {
+        Some(addresses) => {
+            for addr in addresses {
+                export_doc(addr)?;
+            }
+        }
+        None => {
+            // Walk each segment's matches directly instead of collecting them
+            let query = query_parser.build_query(&parsed_query)?;
+            let weight = query.weight(EnableScoring::disabled_from_searcher(&searcher))?;
+            for (segment_ord, segment) in searcher.segment_readers().iter().enumerate() {
+                let mut scorer = weight.scorer(segment, 1.0)?;
+                let mut doc = scorer.doc();
+                while doc != TERMINATED {
+                    if !segment.is_deleted(doc) {
+                        export_doc(DocAddress::new(segment_ord as u32, doc))?;
+                    }
+                    doc = scorer.advance();
+                }
+            }
+        }
+    }
+    out.flush()?;
+
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("Summary:");
+    println!("  Records exported: {} to {}", exported, out_file);
+    if skip_failed_docs {
+        println!("  Unreadable results skipped: {}", failed_retrievals);
+    }
+    println!("  Total time: {:.3}ms", export_start.elapsed().as_secs_f64() * 1000.0);
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    Ok(())
+}
+
+/// Search the per-day indexes `<base_dir>/<YYYY-MM-DD>` of every day from
+/// `from` to `to` inclusive and print the merged results, newest day first,
+/// each tagged with its `partition`. Days without an index directory are
+/// skipped; a range with none fails.
+pub fn search_partitions(base_dir: &str, from: &str, to: &str, query: &str, config: SearchConfig) -> Result<()> {
+    let search_start = Instant::now();
+    let PartitionResults { partitions, results } = partition_results(base_dir, from, to, query, config)?;
+    for (partition, matches) in &partitions {
+        println!("Partition {}: {} matches", partition, matches);
+    }
+    for result in &results {
+        println!("{}", serde_json::to_string(result)?);
+    }
+
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("Summary:");
+    println!("  Partitions searched: {}", partitions.len());
+    println!("  Total matches found: {}", partitions.iter().map(|(_, matches)| matches).sum::<usize>());
+    println!("  Results returned: {}", results.len());
+    println!("  Total time: {:.3}ms", search_start.elapsed().as_secs_f64() * 1000.0);
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    Ok(())
+}
+
+/// What `partition_results` found over a date range
+#[derive(Debug)]
+struct PartitionResults {
+    /// Searched days, newest first, with their match counts
+    partitions: Vec<(String, usize)>,
+    /// Up to `default_limit` results tagged with their `partition`
+    results: Vec<serde_json::Value>,
+}
+
+/// Run a query on each daily index of a date range, for `search_partitions`
+fn partition_results(base_dir: &str, from: &str, to: &str, query: &str, config: SearchConfig) -> Result<PartitionResults> {
+    let day_format = tantivy::time::format_description::parse("[year]-[month]-[day]")?;
+    let parse_day = |day: &str| tantivy::time::Date::parse(day, &day_format)
+        .map_err(|_| anyhow!("Invalid date '{}', expected YYYY-MM-DD", day));
+    let (from, to) = (parse_day(from)?, parse_day(to)?);
+    if from > to {
+        return Err(anyhow!("Date range starts after it ends"));
+    }
+    if (to - from).whole_days() >= MAX_PARTITION_DAYS {
+        return Err(anyhow!("Date range is longer than {} days", MAX_PARTITION_DAYS));
+    }
+
+    let options = SearchOptions::default();
+    let limit = config.default_limit;
+    let mut partitions = Vec::new();
+    let mut merged = Vec::new();
+    let mut day = Some(to);
+    while let Some(date) = day.filter(|date| *date >= from) {
+        day = date.previous_day();
+        let partition = date.format(&day_format)?;
+        let index_dir = Path::new(base_dir).join(&partition);
+        if !index_dir.is_dir() {
+            println!("Skipping {}: no index", partition);
+            continue;
+        }
+
+        let service = SearchService::new(&index_dir.to_string_lossy(), config.clone())?;
+        let results = service.search(query, &options)?;
+        for mut result in results.results.into_iter().take(limit - merged.len()) {
+            if let Some(record) = result.as_object_mut() {
+                record.insert("partition".to_string(), json!(partition));
+            }
+            merged.push(result);
+        }
+        partitions.push((partition, results.total_matches));
+    }
+    if partitions.is_empty() {
+        return Err(anyhow!("No daily index found under {} for the date range", base_dir));
+    }
+    Ok(PartitionResults { partitions, results: merged })
+}
+
+/// Rows of a query that fans out (a lone mobile clause or whole email
+/// address), its NOT clauses applied; None for any other query
+fn fanout_addresses(
+    searcher: &tantivy::Searcher,
+    schema: &tantivy::schema::Schema,
+    key_field: &str,
+    query_parser: &CustomQueryParser,
+    parsed_query: &ParsedQuery,
+    skip_failed: bool,
+) -> Result<Option<HashSet<DocAddress>>> {
+    let [clause] = parsed_query.clauses.as_slice() else {
+        return Ok(None);
+    };
+    // Check if this is a mobile search (needs fan-out)
+    let is_mobile_search = clause.field == "mobile"
+        && !matches!(clause.mode, MatchMode::AnyToken | MatchMode::Contains)
+        && !is_pattern_value(&clause.value);
+    // A single whole email address fans out the same way
+    let is_email_search = clause.field == "email"
+        && !matches!(clause.mode, MatchMode::Fuzzy(_))
+        && is_email_fanout_value(&clause.value);
+
+    let mut addresses = if is_mobile_search {
+        let mobile_value = query_parser.normalize_clause_value(clause)?;
+        execute_mobile_fanout(searcher, schema, key_field, &mobile_value, skip_failed)?
+    } else if is_email_search {
+        let email_value = query_parser.normalize_clause_value(clause)?;
+        execute_email_fanout(searcher, schema, key_field, &email_value, skip_failed)?
+    } else {
+        return Ok(None);
+    };
+    if !parsed_query.excluded.is_empty() {
+        // Fan-outs bypass the built query, so apply its NOT clauses here
+        let exclusion = query_parser.build_exclusion_query(parsed_query)?;
+        let excluded = searcher.search(&*exclusion, &DocSetCollector)?;
+        addresses.retain(|addr| !excluded.contains(addr));
+    }
+    Ok(Some(addresses))
+}
+
 /// Execute mobile fan-out search:
 /// 1. Find all rows where mobile = X
-/// 2. Extract master_id from those rows
-/// 3. Find all rows with those master_id values
+/// 2. Extract the identity key (master_id by default) from those rows
+/// 3. Find all rows with those key values
 /// 4. Find all rows where alt = X
 /// 5. Return union of all results
 fn execute_mobile_fanout(
     searcher: &tantivy::Searcher,
     schema: &tantivy::schema::Schema,
-    _query_parser: &CustomQueryParser,
+    key_field: &str,
     mobile_value: &str,
+    skip_failed: bool,
 ) -> Result<HashSet<DocAddress>> {
-    let mut all_addresses: HashSet<DocAddress> = HashSet::new();
-
     let mobile_field = schema.get_field("mobile").unwrap();
-    let master_id_field = schema.get_field("master_id").unwrap();
     let alt_field = schema.get_field("alt").unwrap();
 
-    // Step 1: Find all rows where mobile = X
+    // Steps 1-3: rows where mobile = X and every row sharing their key
     // Use TermQuery for STRING field - fastest for exact matches
     let mobile_term = Term::from_field_text(mobile_field, mobile_value);
     let mobile_query = TermQuery::new(mobile_term, IndexRecordOption::Basic);
-    let mobile_docs = searcher.search(&mobile_query, &TopDocs::with_limit(MAX_RESULTS))?;
+    let mut all_addresses = fan_out_from(searcher, schema, key_field, &mobile_query, skip_failed)?;
+
+    // Step 4: Find all rows where alt = X (only if mobile_value is not empty)
+    // Use TermQuery for STRING field - fastest for exact matches
+    if !mobile_value.trim().is_empty() {
+        let alt_term = Term::from_field_text(alt_field, mobile_value);
+        let alt_query = TermQuery::new(alt_term, IndexRecordOption::Basic);
+        let alt_docs = searcher.search(&alt_query, &TopDocs::with_limit(MAX_RESULTS))?;
+        for (_score, addr) in &alt_docs {
+            all_addresses.insert(*addr);
+        }
+    }
+
+    Ok(all_addresses)
+}
+
+/// Execute email fan-out search: rows holding the address in any of
+/// `EMAIL_FANOUT_FIELDS`, expanded to every row sharing their identity key
+fn execute_email_fanout(
+    searcher: &tantivy::Searcher,
+    schema: &tantivy::schema::Schema,
+    key_field: &str,
+    email_value: &str,
+    skip_failed: bool,
+) -> Result<HashSet<DocAddress>> {
+    // The email tokenizer indexes the whole address as one of its terms
+    let email_queries: Vec<(Occur, Box<dyn Query>)> = EMAIL_FANOUT_FIELDS
+        .iter()
+        .filter_map(|name| schema.get_field(name).ok())
+        .map(|field| {
+            let term = Term::from_field_text(field, email_value);
+            (Occur::Should, Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as Box<dyn Query>)
+        })
+        .collect();
+    fan_out_from(searcher, schema, key_field, &BooleanQuery::new(email_queries), skip_failed)
+}
+
+/// Rows matching `seed` plus all rows sharing an identity key with any of them
+fn fan_out_from(
+    searcher: &tantivy::Searcher,
+    schema: &tantivy::schema::Schema,
+    key_field: &str,
+    seed: &dyn Query,
+    skip_failed: bool,
+) -> Result<HashSet<DocAddress>> {
+    let mut all_addresses: HashSet<DocAddress> = HashSet::new();
+    let master_id_field = exact_key_field(schema, key_field)?;
+
+    // Step 1: Find all rows matching the seed
+    let seed_docs = searcher.search(seed, &TopDocs::with_limit(MAX_RESULTS))?;
 
     let mut master_ids: HashSet<String> = HashSet::new();
 
-    for (_score, addr) in &mobile_docs {
+    for (_score, addr) in &seed_docs {
         all_addresses.insert(*addr);
 
         // Extract master_id (skip empty values)
-        let doc: TantivyDocument = searcher.doc(*addr)?;
+        let Some(doc) = read_document(searcher, *addr, skip_failed)? else {
+            continue;
+        };
         if let Some(master_id_val) = doc.get_first(master_id_field)
             .and_then(|v| Value::as_str(&v))
         {
@@ -172,18 +511,28 @@ fn execute_mobile_fanout(
         }
     }
 
-    // Step 4: Find all rows where alt = X (only if mobile_value is not empty)
-    // Use TermQuery for STRING field - fastest for exact matches
-    if !mobile_value.trim().is_empty() {
-        let alt_term = Term::from_field_text(alt_field, mobile_value);
-        let alt_query = TermQuery::new(alt_term, IndexRecordOption::Basic);
-        let alt_docs = searcher.search(&alt_query, &TopDocs::with_limit(MAX_RESULTS))?;
-        for (_score, addr) in &alt_docs {
-            all_addresses.insert(*addr);
+    Ok(all_addresses)
+}
+
+/// A record printed by `search` with its matched terms marked
+fn highlight_record(json_doc: &str, offsets: &BTreeMap<String, Vec<[usize; 2]>>, highlight: Highlight) -> Result<String> {
+    let mut record: serde_json::Value = serde_json::from_str(json_doc)?;
+    let (open, close) = match highlight {
+        Highlight::Color => (HIGHLIGHT_OPEN.to_string(), HIGHLIGHT_CLOSE.to_string()),
+        Highlight::Markers => ("**".to_string(), "**".to_string()),
+    };
+    for (field, spans) in offsets {
+        if let Some(serde_json::Value::String(value)) = record.get_mut(field) {
+            *value = mark_matches(value, spans, &open, &close);
         }
     }
-
-    Ok(all_addresses)
+    let printed = serde_json::to_string(&record)?;
+    Ok(match highlight {
+        Highlight::Color => printed
+            .replace(HIGHLIGHT_OPEN, "\x1b[1;31m")
+            .replace(HIGHLIGHT_CLOSE, "\x1b[0m"),
+        Highlight::Markers => printed,
+    })
 }
 
 /// Convert TantivyDocument to JSON format
@@ -195,6 +544,7 @@ fn document_to_json(doc: &TantivyDocument, schema: &tantivy::schema::Schema) ->
     let fname_field = schema.get_field("fname").unwrap();
     let address_field = schema.get_field("address").unwrap();
     let email_field = schema.get_field("email").unwrap();
+    let indexed_at_field = schema.get_field("indexed_at").ok();
 
     let extract_values = |field: tantivy::schema::Field| -> Vec<String> {
         doc.get_all(field)
@@ -202,7 +552,7 @@ fn document_to_json(doc: &TantivyDocument, schema: &tantivy::schema::Schema) ->
             .collect()
     };
 
-    let json_obj = json!({
+    let mut json_obj = json!({
         "master_id": extract_values(master_id_field).first().cloned().unwrap_or_default(),
         "mobile": extract_values(mobile_field).first().cloned().unwrap_or_default(),
         "alt": extract_values(alt_field).first().cloned().unwrap_or_default(),
@@ -210,7 +560,146 @@ fn document_to_json(doc: &TantivyDocument, schema: &tantivy::schema::Schema) ->
         "fname": extract_values(fname_field).first().cloned().unwrap_or_default(),
         "address": extract_values(address_field).first().cloned().unwrap_or_default(),
         "email": extract_values(email_field).first().cloned().unwrap_or_default(),
+        "indexed_at": indexed_at_field
+            .and_then(|field| doc.get_first(field))
+            .and_then(|v| Value::as_datetime(&v))
+            .and_then(|dt| dt.into_utc().format(&Rfc3339).ok())
+            .unwrap_or_default(),
     });
 
+    // Stored-only fields, for indexes built with them
+    for name in STORED_ONLY_FIELDS {
+        if let Ok(field) = schema.get_field(name) {
+            json_obj[*name] = json!(extract_values(field).first().cloned().unwrap_or_default());
+        }
+    }
+
+    // Extra CSV columns the index was built with
+    for (field, entry) in schema.fields() {
+        if entry.is_stored() && !is_builtin_field(entry.name()) {
+            json_obj[entry.name()] = json!(extract_values(field).first().cloned().unwrap_or_default());
+        }
+    }
+
     Ok(serde_json::to_string(&json_obj)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::{build_index, IndexOptions};
+    use crate::test_support::{build, HEADER};
+    use tempfile::TempDir;
+
+    #[test]
+    fn repl_answers_every_stdin_query_from_one_open_index() {
+        let dir = TempDir::new().unwrap();
+        let csv = dir.path().join("rows.csv");
+        std::fs::write(&csv, "master_id,mobile,fname,name,alt,email,address\n\
+            m1,9800000001,ravi,ravi kumar,,,delhi\n\
+            m2,9800000002,amit,amit shah,,,pune\n").unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(csv.to_str().unwrap(), &index_dir, &IndexOptions::default()).unwrap();
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+
+        let input = "name:ravi\n\n  address:pune  \nnmae:ravi\naddress:delhi OR address:pune\n";
+        let mut out = Vec::new();
+        run_queries(&service, input.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let answers: Vec<&str> = out.split("Query: ").skip(1).collect();
+        assert_eq!(answers.len(), 4);
+        assert!(answers[0].starts_with("name:ravi\n{") && answers[0].contains("\"master_id\":\"m1\""), "{}", answers[0]);
+        assert!(answers[1].starts_with("address:pune\n") && answers[1].contains("\"master_id\":\"m2\""), "{}", answers[1]);
+        // A bad query reports its error and the next one still runs
+        assert!(answers[2].starts_with("nmae:ravi\nError: "), "{}", answers[2]);
+        assert!(answers[3].contains("Total matches found: 2"), "{}", answers[3]);
+    }
+
+    #[test]
+    fn date_range_search_merges_the_daily_indexes_it_finds() {
+        let dir = TempDir::new().unwrap();
+        for (day, rows) in [
+            ("2024-03-01", "m1,9800000001,ravi,ravi kumar,,,delhi\nm2,9800000002,amit,amit shah,,,delhi"),
+            ("2024-03-03", "m3,9800000003,ravi,ravi verma,,,delhi"),
+            ("2024-03-09", "m4,9800000004,ravi,ravi gupta,,,delhi"),
+        ] {
+            let csv = dir.path().join(format!("{}.csv", day));
+            std::fs::write(&csv, format!("master_id,mobile,fname,name,alt,email,address\n{}\n", rows)).unwrap();
+            std::fs::create_dir_all(dir.path().join(day).to_str().unwrap()).unwrap();
+            build_index(csv.to_str().unwrap(), dir.path().join(day).to_str().unwrap(), &IndexOptions::default()).unwrap();
+        }
+        let base_dir = dir.path().to_str().unwrap();
+
+        // 03-02 has no index and 03-09 is out of range
+        let PartitionResults { partitions, results } = partition_results(base_dir, "2024-03-01", "2024-03-05", "name:ravi", SearchConfig::default()).unwrap();
+        assert_eq!(partitions, [("2024-03-03".to_string(), 1), ("2024-03-01".to_string(), 1)]);
+        let tagged: Vec<(&str, &str)> = results.iter()
+            .map(|r| (r["master_id"].as_str().unwrap(), r["partition"].as_str().unwrap()))
+            .collect();
+        assert_eq!(tagged, [("m3", "2024-03-03"), ("m1", "2024-03-01")]);
+
+        let config = SearchConfig { default_limit: 1, ..SearchConfig::default() };
+        let PartitionResults { partitions, results } = partition_results(base_dir, "2024-03-01", "2024-03-09", "address:delhi", config).unwrap();
+        assert_eq!(partitions.iter().map(|(_, matches)| matches).sum::<usize>(), 4);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["master_id"], "m4");
+
+        let err = partition_results(base_dir, "2024-04-01", "2024-04-02", "name:ravi", SearchConfig::default()).unwrap_err();
+        assert!(err.to_string().starts_with("No daily index found"), "{err}");
+        assert!(partition_results(base_dir, "2024-03-05", "2024-03-01", "name:ravi", SearchConfig::default()).is_err());
+    }
+
+    #[test]
+    fn export_writes_every_match_beyond_the_result_limit() {
+        let dir = TempDir::new().unwrap();
+        let csv = dir.path().join("rows.csv");
+        let mut rows = format!("{}\n", HEADER);
+        for i in 0..MAX_RESULTS + 5 {
+            rows.push_str(&format!("m{},98{:08},ravi,ravi kumar,,,delhi\n", i, i));
+        }
+        rows.push_str("x1,9700000001,amit,amit shah,,,pune\n");
+        std::fs::write(&csv, rows).unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(csv.to_str().unwrap(), &index_dir, &IndexOptions::default()).unwrap();
+
+        let out_file = dir.path().join("export.ndjson");
+        export_results(&index_dir, "name:ravi", out_file.to_str().unwrap(), "master_id", false).unwrap();
+
+        let exported = std::fs::read_to_string(&out_file).unwrap();
+        let ids: HashSet<String> = exported.lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["master_id"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(exported.lines().count(), MAX_RESULTS + 5);
+        assert_eq!(ids.len(), MAX_RESULTS + 5);
+        assert!(!ids.contains("x1"));
+    }
+
+    #[test]
+    fn highlight_marks_the_matched_terms_of_text_fields() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = build(&dir, "index", &["m1,9800000001,ravi,Ravi Kumar,,,MG Road Delhi"]);
+        let index = Index::open_in_dir(&index_dir).unwrap();
+        register_tokenizers(&index);
+        let schema = index.schema();
+        let query_parser = CustomQueryParser::new(schema.clone(), index.clone());
+        let searcher = index.reader().unwrap().searcher();
+        let doc: TantivyDocument = searcher.doc(DocAddress::new(0, 0)).unwrap();
+
+        let parsed = query_parser.parse("name:kumar AND address:delhi AND master_id:m1").unwrap();
+        let offsets = match_offsets(&index, &query_parser, &parsed, &doc).unwrap();
+        let json_doc = document_to_json(&doc, &schema).unwrap();
+
+        let marked: serde_json::Value = serde_json::from_str(&highlight_record(&json_doc, &offsets, Highlight::Markers).unwrap()).unwrap();
+        assert_eq!(marked["name"], "Ravi **Kumar**");
+        assert_eq!(marked["address"], "MG Road **Delhi**");
+        // STRING fields are printed as stored
+        assert_eq!(marked["master_id"], "m1");
+
+        let colored = highlight_record(&json_doc, &offsets, Highlight::Color).unwrap();
+        assert!(colored.contains("Ravi \x1b[1;31mKumar\x1b[0m"), "{}", colored);
+        assert!(!colored.contains("**"));
+    }
+}
@@ -1,94 +1,874 @@
-use crate::query_parser::CustomQueryParser;
+use crate::compare::Profile;
+use crate::indexer::DocumentBuilder;
+use crate::query_parser::{CustomQueryParser, MatchMode, ParsedQuery, PhraseMode, QueryClause, QueryError, QueryOp, DEFAULT_MAX_CLAUSES, DEFAULT_MAX_QUERY_COST, is_pattern_value};
+use crate::schema::{is_builtin_field, normalize_exact_value, STORED_ONLY_FIELDS};
+use crate::phone::{warn_raw_phones, PhoneNumber};
+use crate::ready::{mark_ready, open_writer, ready_opstamp};
+use crate::tokenizer::{build_query_analyzer, register_tokenizers};
+use crate::transform::{MaskDigits, PassThrough, ResultTransform};
 use anyhow::Result;
-use std::collections::HashSet;
-use std::sync::Arc;
-use std::time::Instant;
+use std::cmp::Reverse;
+use std::ops::Bound;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use tantivy::{
-    Index, IndexReader, TantivyDocument, collector::{TopDocs, Count},
-    ReloadPolicy, DocAddress, Term
+    Index, IndexReader, IndexWriter, TantivyDocument, collector::{Collector, SegmentCollector, TopDocs, Count, DocSetCollector},
+    DateTime, IndexMeta, ReloadPolicy, DocAddress, DocId, Score, Searcher, SegmentOrdinal, SegmentReader, Term
 };
-use tantivy::query::{Query, BooleanQuery, Occur, TermQuery};
-use tantivy::schema::{Value, IndexRecordOption};
+use tantivy::columnar::Column;
+use tantivy::query::{Query, BooleanQuery, EnableScoring, Occur, RangeQuery, Scorer, TermQuery, Weight};
+use tantivy::DocSet;
+use tantivy::schema::{Field, FieldType, Value, IndexRecordOption};
+use tantivy::termdict::TermMerger;
+use tantivy::time::format_description::well_known::Rfc3339;
+use rayon::prelude::*;
 use serde_json::json;
 
 const MAX_RESULTS: usize = 10_000;
 
+/// Most matches a `results_hash` is computed over; broader queries get none
+/// rather than holding every match in memory
+pub const RESULTS_HASH_MAX_MATCHES: usize = MAX_RESULTS;
+
+/// Indexing buffer of the writer behind bulk indexing requests
+const BULK_WRITER_MEMORY: usize = 200_000_000;
+
+/// Tantivy's own default for the per-segment doc store block cache
+const DEFAULT_DOC_STORE_CACHE_BLOCKS: usize = 100;
+
+/// Tunables for the search service.
+/// `Default` preserves the original behavior; `from_env` lets deployments
+/// override individual settings without recompiling.
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    /// Threads used to search segments in parallel (1 = single-threaded)
+    pub search_threads: usize,
+    /// Maximum characters accepted in a single request field value
+    pub max_field_length: usize,
+    /// Per-field overrides of `max_field_length`
+    pub field_length_limits: HashMap<String, usize>,
+    /// Fewest letters and digits a clause value of these fields may have
+    pub min_value_lengths: HashMap<String, usize>,
+    /// Fields still returned with results but rejected in queries with
+    /// FIELD_NOT_SEARCHABLE (e.g. internal notes kept in an indexed column)
+    pub unsearchable_fields: HashSet<String>,
+    /// Maximum clauses in a single query
+    pub max_clauses: usize,
+    /// Budget for the estimated cost of a query (see `CustomQueryParser::estimate_cost`)
+    pub max_query_cost: u64,
+    /// Rank exact and phrase matches of TEXT clauses above partial token matches
+    pub boost_exact_matches: bool,
+    /// Query-time analyzer specs per TEXT field (see `build_query_analyzer`)
+    pub query_analyzers: HashMap<String, String>,
+    /// Accept wildcard and regex clauses (FEATURE_DISABLED otherwise)
+    pub allow_patterns: bool,
+    /// Identity key grouping a person's rows for fan-out, /context and /records
+    pub key_field: String,
+    /// Only reload generations the indexer marked as complete (see `ready.rs`)
+    pub require_ready_marker: bool,
+    /// Results returned when a request omits `limit`
+    pub default_limit: usize,
+    /// Hard cap on a request's `limit`; larger values are clamped
+    pub max_limit: usize,
+    /// Read result documents in store order instead of rank order
+    pub batched_retrieval: bool,
+    /// Log and skip result documents that fail to read instead of failing
+    /// the search; the response counts the skipped ones
+    pub skip_failed_docs: bool,
+    /// Search only this many of the most recently indexed segments (by
+    /// newest `indexed_at`). Faster on heavily appended indexes, but matches
+    /// in older segments are silently missed and counts cover the searched
+    /// segments only. Fan-outs still see every segment. All when None.
+    pub max_search_segments: Option<usize>,
+    /// Decompressed doc store blocks cached per segment
+    pub doc_store_cache_blocks: usize,
+    /// Threads for fan-out doc reads and key searches (1 = sequential)
+    pub fanout_threads: usize,
+    /// Drop the index reader and its caches after this long without searches
+    /// and reopen it on the next one (never released when None). The served
+    /// generation stays pinned meanwhile, so a build in progress is not
+    /// picked up by the reopen.
+    pub reader_idle_timeout: Option<Duration>,
+    /// Check for a new committed generation this often and reload when
+    /// there is one, as `POST /reload` would (only on request when None)
+    pub reload_interval: Option<Duration>,
+    /// Named query templates with `{0}`, `{1}`... placeholders, run through
+    /// `search_template` (e.g. `phone_lookup` => `mobile:{0} OR alt:{0}`)
+    pub query_templates: HashMap<String, String>,
+    /// Longest snippet fragment, in characters
+    pub snippet_max_chars: usize,
+    /// Most snippet fragments per field
+    pub snippet_max_fragments: usize,
+    /// Applied to every result record before it is returned
+    pub result_transform: Arc<dyn ResultTransform>,
+    /// Accept records over `POST /index/bulk`. The server then holds the
+    /// index writer lock, so CLI appends to the index fail while it runs.
+    pub bulk_indexing: bool,
+    /// Commit bulk-indexed records every this many (and at the end of
+    /// each request)
+    pub bulk_commit_every: usize,
+    /// Log searches taking longer than this many milliseconds, with their
+    /// query and timing breakdown (none logged when None)
+    pub slow_query_ms: Option<f64>,
+    /// Searches allowed to run at once; the API turns away the rest with a
+    /// 503 instead of slowing every search down (unlimited when None)
+    pub max_in_flight_searches: Option<usize>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            search_threads: 1,
+            max_field_length: 256,
+            field_length_limits: HashMap::new(),
+            min_value_lengths: HashMap::new(),
+            unsearchable_fields: HashSet::new(),
+            max_clauses: DEFAULT_MAX_CLAUSES,
+            max_query_cost: DEFAULT_MAX_QUERY_COST,
+            boost_exact_matches: true,
+            query_analyzers: HashMap::new(),
+            allow_patterns: true,
+            key_field: "master_id".to_string(),
+            require_ready_marker: true,
+            default_limit: MAX_RESULTS,
+            max_limit: MAX_RESULTS,
+            batched_retrieval: true,
+            skip_failed_docs: false,
+            max_search_segments: None,
+            doc_store_cache_blocks: DEFAULT_DOC_STORE_CACHE_BLOCKS,
+            fanout_threads: 1,
+            reader_idle_timeout: None,
+            reload_interval: None,
+            query_templates: HashMap::new(),
+            snippet_max_chars: 150,
+            snippet_max_fragments: 3,
+            result_transform: Arc::new(PassThrough),
+            bulk_indexing: false,
+            bulk_commit_every: 10_000,
+            slow_query_ms: None,
+            max_in_flight_searches: None,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Build a config from environment variables, falling back to defaults:
+    /// - `SEARCH_THREADS`: segment search thread count
+    /// - `MAX_FIELD_LENGTH`: default per-field value length limit
+    /// - `FIELD_LENGTH_LIMITS`: overrides as `field=len,...` (e.g. `address=512`)
+    /// - `MIN_VALUE_LENGTHS`: minimum value lengths as `field=len,...` (e.g. `name=3`)
+    /// - `UNSEARCHABLE_FIELDS`: `field,...` returned with results but never queryable
+    /// - `MAX_QUERY_CLAUSES`: maximum clauses per query
+    /// - `MAX_QUERY_COST`: estimated cost budget per query (QUERY_TOO_EXPENSIVE above it)
+    /// - `BOOST_EXACT_MATCHES`: `false` scores TEXT clauses on their tokens alone
+    /// - `QUERY_ANALYZERS`: `field=spec,...` (e.g. `address=stopwords(house|no)`)
+    /// - `ALLOW_WILDCARDS`: `false` rejects wildcard and regex clauses
+    /// - `KEY_FIELD`: identity key field (must be an exact-match STRING field)
+    /// - `REQUIRE_READY_MARKER`: `false` reloads any committed generation
+    /// - `DEFAULT_PAGE_SIZE` / `MAX_PAGE_SIZE`: default and maximum result `limit`
+    ///   (also read as `DEFAULT_RESULT_LIMIT` / `MAX_RESULT_LIMIT`)
+    /// - `BATCHED_RETRIEVAL`: `false` reads result documents one by one in rank order
+    /// - `SKIP_FAILED_DOCS`: `true` returns partial results when some documents can't be read
+    /// - `MAX_SEARCH_SEGMENTS`: search only the N newest segments, trading recall for speed (0 = all)
+    /// - `DOC_STORE_CACHE_BLOCKS`: doc store block cache size per segment
+    /// - `FANOUT_THREADS`: threads for fan-out doc reads and key searches
+    /// - `READER_IDLE_TIMEOUT_SECS`: release the reader after this many idle seconds (0 = never)
+    /// - `RELOAD_INTERVAL_SECS`: reload new committed generations every N seconds (0 = off)
+    /// - `QUERY_TEMPLATES_FILE`: JSON object of named query templates
+    /// - `SNIPPET_MAX_CHARS` / `SNIPPET_MAX_FRAGMENTS`: snippet fragment length and count per field
+    /// - `MASK_FIELDS`: `field,...` whose digits are masked in results but for the last four
+    /// - `BULK_INDEXING`: `true` enables `POST /index/bulk`
+    /// - `BULK_COMMIT_EVERY`: records between commits of bulk-indexed records
+    /// - `SLOW_QUERY_MS`: log searches slower than this many milliseconds
+    /// - `MAX_IN_FLIGHT_SEARCHES`: concurrent searches before requests get a 503 (0 = unlimited)
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        if let Some(threads) = env_parse("SEARCH_THREADS") {
+            config.search_threads = threads;
+        }
+        if let Some(max_len) = env_parse("MAX_FIELD_LENGTH") {
+            config.max_field_length = max_len;
+        }
+        if let Some(max_clauses) = env_parse("MAX_QUERY_CLAUSES") {
+            config.max_clauses = max_clauses;
+        }
+        if let Some(max_query_cost) = env_parse("MAX_QUERY_COST") {
+            config.max_query_cost = max_query_cost;
+        }
+        if let Some(boost_exact_matches) = env_parse("BOOST_EXACT_MATCHES") {
+            config.boost_exact_matches = boost_exact_matches;
+        }
+        if let Ok(analyzers) = std::env::var("QUERY_ANALYZERS") {
+            for (field, spec) in analyzers.split(',').filter_map(|pair| pair.split_once('=')) {
+                config.query_analyzers.insert(field.trim().to_lowercase(), spec.trim().to_string());
+            }
+        }
+        if let Some(allow_patterns) = env_parse("ALLOW_WILDCARDS") {
+            config.allow_patterns = allow_patterns;
+        }
+        if let Ok(key_field) = std::env::var("KEY_FIELD") {
+            config.key_field = key_field.trim().to_lowercase();
+        }
+        if let Some(require_ready_marker) = env_parse("REQUIRE_READY_MARKER") {
+            config.require_ready_marker = require_ready_marker;
+        }
+        if let Some(default_limit) = env_parse("DEFAULT_PAGE_SIZE").or_else(|| env_parse("DEFAULT_RESULT_LIMIT")) {
+            config.default_limit = default_limit;
+        }
+        if let Some(max_limit) = env_parse("MAX_PAGE_SIZE").or_else(|| env_parse("MAX_RESULT_LIMIT")) {
+            config.max_limit = max_limit;
+        }
+        if let Some(batched_retrieval) = env_parse("BATCHED_RETRIEVAL") {
+            config.batched_retrieval = batched_retrieval;
+        }
+        if let Some(skip_failed_docs) = env_parse("SKIP_FAILED_DOCS") {
+            config.skip_failed_docs = skip_failed_docs;
+        }
+        if let Some(max_segments) = env_parse::<usize>("MAX_SEARCH_SEGMENTS") {
+            config.max_search_segments = (max_segments > 0).then_some(max_segments);
+        }
+        if let Some(cache_blocks) = env_parse("DOC_STORE_CACHE_BLOCKS") {
+            config.doc_store_cache_blocks = cache_blocks;
+        }
+        if let Some(fanout_threads) = env_parse("FANOUT_THREADS") {
+            config.fanout_threads = fanout_threads;
+        }
+        if let Some(secs) = env_parse::<u64>("READER_IDLE_TIMEOUT_SECS") {
+            config.reader_idle_timeout = (secs > 0).then(|| Duration::from_secs(secs));
+        }
+        if let Some(secs) = env_parse::<u64>("RELOAD_INTERVAL_SECS") {
+            config.reload_interval = (secs > 0).then(|| Duration::from_secs(secs));
+        }
+        if let Ok(path) = std::env::var("QUERY_TEMPLATES_FILE") {
+            match load_query_templates(&path) {
+                Ok(templates) => config.query_templates = templates,
+                Err(e) => eprintln!("Ignoring QUERY_TEMPLATES_FILE {}: {}", path, e),
+            }
+        }
+        if let Some(max_chars) = env_parse("SNIPPET_MAX_CHARS") {
+            config.snippet_max_chars = max_chars;
+        }
+        if let Some(max_fragments) = env_parse("SNIPPET_MAX_FRAGMENTS") {
+            config.snippet_max_fragments = max_fragments;
+        }
+        if let Ok(fields) = std::env::var("MASK_FIELDS") {
+            let fields: Vec<String> = fields.split(',')
+                .map(|f| f.trim().to_lowercase())
+                .filter(|f| !f.is_empty())
+                .collect();
+            if !fields.is_empty() {
+                config.result_transform = Arc::new(MaskDigits::new(fields));
+            }
+        }
+        if let Some(bulk_indexing) = env_parse("BULK_INDEXING") {
+            config.bulk_indexing = bulk_indexing;
+        }
+        if let Some(commit_every) = env_parse::<usize>("BULK_COMMIT_EVERY") {
+            config.bulk_commit_every = commit_every.max(1);
+        }
+        if let Some(slow_query_ms) = env_parse("SLOW_QUERY_MS") {
+            config.slow_query_ms = Some(slow_query_ms);
+        }
+        if let Some(max_in_flight) = env_parse::<usize>("MAX_IN_FLIGHT_SEARCHES") {
+            config.max_in_flight_searches = (max_in_flight > 0).then_some(max_in_flight);
+        }
+        if let Ok(limits) = std::env::var("FIELD_LENGTH_LIMITS") {
+            for (field, len) in limits.split(',').filter_map(|pair| pair.split_once('=')) {
+                if let Ok(len) = len.trim().parse() {
+                    config.field_length_limits.insert(field.trim().to_lowercase(), len);
+                }
+            }
+        }
+        if let Ok(minimums) = std::env::var("MIN_VALUE_LENGTHS") {
+            for (field, len) in minimums.split(',').filter_map(|pair| pair.split_once('=')) {
+                if let Ok(len) = len.trim().parse() {
+                    config.min_value_lengths.insert(field.trim().to_lowercase(), len);
+                }
+            }
+        }
+        if let Ok(fields) = std::env::var("UNSEARCHABLE_FIELDS") {
+            config.unsearchable_fields = fields.split(',')
+                .map(|f| f.trim().to_lowercase())
+                .filter(|f| !f.is_empty())
+                .collect();
+        }
+        config
+    }
+
+    /// Effective result limit for a request and whether it had to be clamped.
+    /// The default is clamped too, in case it was configured above the cap.
+    pub fn resolve_limit(&self, requested: Option<usize>) -> (usize, bool) {
+        let limit = requested.unwrap_or(self.default_limit);
+        (limit.min(self.max_limit), limit > self.max_limit)
+    }
+
+    /// Length limit for a request field value
+    pub fn field_length_limit(&self, field: &str) -> usize {
+        self.field_length_limits.get(field).copied().unwrap_or(self.max_field_length)
+    }
+}
+
+/// Read query templates from a JSON object of name => template
+fn load_query_templates(path: &str) -> Result<HashMap<String, String>> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// Parse an environment variable, ignoring it when unset or malformed
+fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T> {
+    std::env::var(key).ok().and_then(|v| v.trim().parse().ok())
+}
+
+/// The index reader, shared with the idle-release thread when one runs
+struct ReaderSlot {
+    reader: RwLock<Option<IndexReader>>, // None while released
+    // While released: the served generation without its block cache, as a
+    // reopened reader could only see the latest commit
+    pinned: Mutex<Option<IndexReader>>,
+    loaded_opstamp: AtomicU64, // commit the reader currently serves
+    last_used: Mutex<Instant>,
+}
+
 /// Search service that keeps the index reader open for fast repeated searches
 pub struct SearchService {
     index: Arc<Index>,
-    reader: IndexReader,
+    index_dir: String,
+    reader: Arc<ReaderSlot>,
+    key_field: Field,
     query_parser: CustomQueryParser,
+    fanout_pool: Option<rayon::ThreadPool>, // only with fanout_threads > 1
+    writer: Mutex<Option<IndexWriter>>, // opened by the first bulk indexing request
+    in_flight: AtomicUsize, // searches holding a SearchPermit
+    config: SearchConfig,
+}
+
+/// A slot among the `max_in_flight_searches` concurrent searches, freed on drop
+pub struct SearchPermit<'a>(&'a AtomicUsize);
+
+impl Drop for SearchPermit<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
 }
 
 impl SearchService {
     /// Create a new search service with an open index reader
-    pub fn new(index_dir: &str) -> Result<Self> {
+    pub fn new(index_dir: &str, config: SearchConfig) -> Result<Self> {
         let open_start = Instant::now();
-        let index = Arc::new(Index::open_in_dir(index_dir)?);
+        let mut index = Index::open_in_dir(index_dir)?;
+        register_tokenizers(&index);
+
+        // Broad queries over multi-segment indexes parallelize across segments
+        if config.search_threads > 1 {
+            index.set_multithread_executor(config.search_threads)?;
+            eprintln!("Searching segments with {} threads", config.search_threads);
+        }
+
+        let index = Arc::new(index);
         let schema = index.schema();
 
         // Use Manual reload policy - we'll reload manually if needed
         // For HTTP server, the reader stays open and segments are cached
-        let reader = index.reader_builder()
-            .reload_policy(ReloadPolicy::Manual)
-            .try_into()?;
+        let reader = open_reader(&index, &config)?;
+        warn_raw_phones(&reader.searcher(), index_dir)?;
+        let reader = Arc::new(ReaderSlot {
+            loaded_opstamp: AtomicU64::new(index.load_metas()?.opstamp),
+            reader: RwLock::new(Some(reader)),
+            pinned: Mutex::new(None),
+            last_used: Mutex::new(Instant::now()),
+        });
+        if let Some(timeout) = config.reader_idle_timeout {
+            spawn_idle_release(Arc::downgrade(&reader), (*index).clone(), timeout);
+        }
+
+        let query_parser = CustomQueryParser::new(schema.clone(), (*index).clone())
+            .with_max_clauses(config.max_clauses)
+            .with_min_value_lengths(config.min_value_lengths.clone())
+            .with_unsearchable_fields(config.unsearchable_fields.clone())
+            .with_max_cost(config.max_query_cost)
+            .with_exact_boost(config.boost_exact_matches)
+            .with_patterns(config.allow_patterns)
+            .with_query_analyzers(
+                config.query_analyzers.iter()
+                    .map(|(field, spec)| Ok((field.clone(), build_query_analyzer(spec)?)))
+                    .collect::<Result<_>>()?,
+            );
+
+        let key_field = exact_key_field(&schema, &config.key_field)?;
+
+        for (name, template) in &config.query_templates {
+            query_parser.clause_fields(template)
+                .map_err(|e| anyhow::anyhow!("Invalid query template '{}': {}", name, e))?;
+        }
 
-        let query_parser = CustomQueryParser::new(schema.clone(), (*index).clone());
+        let fanout_pool = if config.fanout_threads > 1 {
+            eprintln!("Fanning out with {} threads", config.fanout_threads);
+            Some(rayon::ThreadPoolBuilder::new()
+                .num_threads(config.fanout_threads)
+                .thread_name(|i| format!("fanout-{}", i))
+                .build()?)
+        } else {
+            None
+        };
 
         let open_time = open_start.elapsed();
         eprintln!("Index opened in {:.3}s", open_time.as_secs_f64());
 
         Ok(Self {
             index,
+            index_dir: index_dir.to_string(),
             reader,
+            key_field,
             query_parser,
+            fanout_pool,
+            writer: Mutex::new(None),
+            in_flight: AtomicUsize::new(0),
+            config,
         })
     }
 
+    /// Claim a slot for a search, or None when `max_in_flight_searches`
+    /// are already running
+    pub fn try_begin_search(&self) -> Option<SearchPermit<'_>> {
+        let running = self.in_flight.fetch_add(1, Ordering::AcqRel);
+        let permit = SearchPermit(&self.in_flight);
+        if self.config.max_in_flight_searches.is_some_and(|max| running >= max) {
+            return None;
+        }
+        Some(permit)
+    }
+
+    /// Builder for bulk-indexed documents; FEATURE_DISABLED unless
+    /// `bulk_indexing` is on
+    pub fn bulk_builder(&self) -> Result<DocumentBuilder> {
+        if !self.config.bulk_indexing {
+            return Err(QueryError::new("FEATURE_DISABLED", "Bulk indexing is not enabled on this server").into());
+        }
+        DocumentBuilder::new(&self.index.schema())
+    }
+
+    /// Add documents through the server's index writer, opening it (and
+    /// taking the index lock) on first use. Not visible until `bulk_commit`.
+    pub fn bulk_add(&self, docs: Vec<TantivyDocument>) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        if writer.is_none() {
+            *writer = Some(open_writer(&self.index, &self.index_dir, BULK_WRITER_MEMORY)?);
+            eprintln!("Index writer opened for bulk indexing");
+        }
+        let writer = writer.as_mut().unwrap();
+        for doc in docs {
+            writer.add_document(doc)?;
+        }
+        Ok(())
+    }
+
+    /// Commit bulk-added documents, mark the commit ready and reload so
+    /// searches see them
+    pub fn bulk_commit(&self) -> Result<ReloadStatus> {
+        {
+            let mut writer = self.writer.lock().unwrap();
+            let Some(writer) = writer.as_mut() else {
+                return self.reload();
+            };
+            let opstamp = writer.commit()?;
+            mark_ready(&self.index_dir, opstamp)?;
+        }
+        self.reload()
+    }
+
+    /// Run every query in `path` once and discard the results, so the first
+    /// real searches of those patterns find the term dictionaries, postings
+    /// and doc store blocks already in the page cache. One query per line;
+    /// blank lines and `#` comments are skipped. A failing query is only
+    /// reported. Returns how many queries ran.
+    pub fn warm_up(&self, path: &str) -> Result<usize> {
+        let start = Instant::now();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read warmup file {}: {}", path, e))?;
+        let options = SearchOptions {
+            limit: Some(self.config.default_limit),
+            ..SearchOptions::default()
+        };
+
+        let mut count = 0;
+        for query in content.lines().map(str::trim).filter(|q| !q.is_empty() && !q.starts_with('#')) {
+            if let Err(e) = self.search(query, &options) {
+                eprintln!("[Warning] Warmup query {:?} failed: {}", query, e);
+            }
+            count += 1;
+        }
+        eprintln!("Warmed up with {} queries in {:.3}s", count, start.elapsed().as_secs_f64());
+        Ok(count)
+    }
+
+    /// Names of the index's fields
+    pub fn field_names(&self) -> Vec<String> {
+        self.index.schema().fields().map(|(_, entry)| entry.name().to_string()).collect()
+    }
+
+    /// Reject stored-only fields and those configured unsearchable: neither
+    /// may be queried, nor ordered or grouped on, which would reveal their
+    /// values just the same
+    fn check_searchable(&self, field: &str) -> Result<()> {
+        if STORED_ONLY_FIELDS.contains(&field) {
+            return Err(QueryError::new(
+                "FIELD_NOT_SEARCHABLE",
+                format!("Field '{}' is stored-only and cannot be queried", field),
+            ).into());
+        }
+        if !self.query_parser.is_searchable(field) {
+            return Err(QueryError::new(
+                "FIELD_NOT_SEARCHABLE",
+                format!("Field '{}' is configured as not searchable and cannot be queried", field),
+            ).into());
+        }
+        Ok(())
+    }
+
+    /// Fields the clauses of a query (or query template) search on
+    pub fn query_fields(&self, query_str: &str) -> Result<Vec<String>> {
+        self.query_parser.clause_fields(query_str)
+    }
+
+    /// A value as a query clause on `field` would search it: canonical
+    /// phone numbers, compacted identity keys, lowercased text. None when
+    /// nothing searchable is left, as such a clause would be ignored.
+    pub fn normalize(&self, field: &str, value: &str) -> Result<Option<String>> {
+        self.check_searchable(field)?;
+        if self.query_parser.get_field(field).is_none() {
+            return Err(QueryError::new(
+                "UNKNOWN_FIELD",
+                format!("Field '{}' is not a searchable field of this index", field),
+            ).into());
+        }
+        let clause = QueryClause { field: field.to_string(), value: value.to_string(), mode: MatchMode::Tokenized, boost: None };
+        Ok(self.query_parser.normalize_clause_value(&clause).ok())
+    }
+
+    /// Configuration this service was created with
+    pub fn config(&self) -> &SearchConfig {
+        &self.config
+    }
+
+    /// Switch the reader to the latest committed generation.
+    ///
+    /// With `require_ready_marker`, a generation is only picked up once the
+    /// indexer has written the ready marker for exactly that commit, so
+    /// checkpoint commits of a build still in progress are never served. A
+    /// commit landing between the marker check and the swap is caught by
+    /// `swap_reader`, which keeps the served generation.
+    pub fn reload(&self) -> Result<ReloadStatus> {
+        let metas = self.index.load_metas()?;
+        let committed = metas.opstamp;
+        if committed == self.index_version() {
+            return Ok(ReloadStatus::Unchanged(committed));
+        }
+        if self.config.require_ready_marker {
+            let ready = ready_opstamp(&self.index_dir)?;
+            if ready != Some(committed) {
+                return Ok(ReloadStatus::NotReady { committed, ready });
+            }
+        }
+        if !self.swap_reader(&metas)? {
+            // The marker is for `committed`, not for what landed since
+            let latest = self.index.load_metas()?.opstamp;
+            return Ok(ReloadStatus::NotReady { committed: latest, ready: Some(committed) });
+        }
+        Ok(ReloadStatus::Reloaded(committed))
+    }
+
+    /// Serve the commit `metas` describes. The new reader is opened beside
+    /// the served one, which is only replaced if the reader really loaded
+    /// that commit; with `require_ready_marker`, false when a later one
+    /// landed in the meantime, leaving the served generation in place.
+    fn swap_reader(&self, metas: &IndexMeta) -> Result<bool> {
+        if self.reader.reader.read().unwrap().is_none() {
+            // A released reader has nothing to swap: it reopens at the latest
+            // commit, and the old generation it held need not be kept any more
+            drop(self.reader.pinned.lock().unwrap().take());
+        } else {
+            let reader = open_reader(&self.index, &self.config)?;
+            if self.config.require_ready_marker && !is_generation_of(&reader.searcher(), metas) {
+                return Ok(false);
+            }
+            *self.reader.reader.write().unwrap() = Some(reader);
+        }
+        self.reader.loaded_opstamp.store(metas.opstamp, Ordering::SeqCst);
+        Ok(true)
+    }
+
+    /// Searcher over the served generation, reopening the reader if it was
+    /// released while idle. A reopened reader always sees the latest commit,
+    /// so with `require_ready_marker` a commit other than the served one must
+    /// be marked ready, as for a reload; until it is, the generation pinned
+    /// at release keeps being served.
+    fn searcher(&self) -> Result<Searcher> {
+        *self.reader.last_used.lock().unwrap() = Instant::now();
+        if let Some(reader) = self.reader.reader.read().unwrap().as_ref() {
+            return Ok(reader.searcher());
+        }
+
+        let mut slot = self.reader.reader.write().unwrap();
+        if slot.is_none() {
+            let metas = self.index.load_metas()?;
+            let committed = metas.opstamp;
+            let pinned = || match self.reader.pinned.lock().unwrap().as_ref() {
+                Some(pinned) => Ok(pinned.searcher()),
+                None => Err(anyhow::anyhow!("Index reader was released and commit {} is not marked ready yet", committed)),
+            };
+            if committed != self.index_version()
+                && self.config.require_ready_marker
+                && ready_opstamp(&self.index_dir)? != Some(committed)
+            {
+                return pinned();
+            }
+            let reopen_start = Instant::now();
+            let reader = open_reader(&self.index, &self.config)?;
+            // A commit landing since the marker check isn't ready either
+            if self.config.require_ready_marker && !is_generation_of(&reader.searcher(), &metas) {
+                return pinned();
+            }
+            *slot = Some(reader);
+            self.reader.pinned.lock().unwrap().take();
+            self.reader.loaded_opstamp.store(committed, Ordering::SeqCst);
+            eprintln!("Index reader reopened in {:.3}s", reopen_start.elapsed().as_secs_f64());
+        }
+        Ok(slot.as_ref().unwrap().searcher())
+    }
+
+    /// Opstamp of the commit currently served. Changes whenever a reload
+    /// picks up a new generation, so clients can key cached results on it.
+    pub fn index_version(&self) -> u64 {
+        self.reader.loaded_opstamp.load(Ordering::SeqCst)
+    }
+
+    /// Parse and build a query without executing it, returning the parsed
+    /// clauses and the `Debug` form of the query that would run
+    pub fn validate(&self, query_str: &str, phrase_mode: PhraseMode) -> Result<(ParsedQuery, String, u64)> {
+        let parsed_query = self.query_parser.parse(query_str)?;
+        let query = self.query_parser.build_query_with_phrase_mode(&parsed_query, phrase_mode)?;
+        let cost = self.query_parser.estimate_cost(&parsed_query);
+        Ok((parsed_query, format!("{:?}", query), cost))
+    }
+
     /// Execute a search query and return results
-    pub fn search(&self, query_str: &str) -> Result<SearchResults> {
+    pub fn search(&self, query_str: &str, options: &SearchOptions) -> Result<SearchResults> {
+        self.execute(|parser| parser.parse(query_str), options)
+    }
+
+    /// Run the named query template with its parameters filled in (see
+    /// `CustomQueryParser::parse_template`). Filled values get the same
+    /// length limits as request fields.
+    pub fn search_template(&self, name: &str, params: &[String], options: &SearchOptions) -> Result<SearchResults> {
+        let template = self.config.query_templates.get(name).ok_or_else(|| QueryError::new(
+            "UNKNOWN_TEMPLATE",
+            format!("No query template named '{}'", name),
+        ))?;
+        self.execute(|parser| {
+            let parsed = parser.parse_template(template, params)?;
+            for clause in &parsed.clauses {
+                let limit = self.config.field_length_limit(&clause.field);
+                let length = clause.value.chars().count();
+                if length > limit {
+                    return Err(QueryError::new(
+                        "FIELD_TOO_LONG",
+                        format!("Field '{}' is {} characters long, the limit is {}", clause.field, length, limit),
+                    ).into());
+                }
+            }
+            Ok(parsed)
+        }, options)
+    }
+
+    fn execute(
+        &self,
+        parse: impl FnOnce(&CustomQueryParser) -> Result<ParsedQuery>,
+        options: &SearchOptions,
+    ) -> Result<SearchResults> {
         let search_start = Instant::now();
-        let searcher = self.reader.searcher();
+        let searcher = self.searcher()?;
+        let index_version = self.index_version();
+        let limit = options.limit.unwrap_or(MAX_RESULTS);
+        let dedup_fields = self.dedup_fields(&options.dedup_key)?;
+        let sort_fields = self.sort_fields(&options.sort)?;
+        // Sorting applies to every match (up to MAX_RESULTS), not just the
+        // best-ranked page
+        let fetch_limit = if sort_fields.is_empty() { limit } else { MAX_RESULTS };
 
         // Parse query
         let parse_start = Instant::now();
-        let parsed_query = self.query_parser.parse(query_str)?;
+        let parsed_query = parse(&self.query_parser)?;
         let parse_time = parse_start.elapsed();
 
         // Execute search
         let execute_start = Instant::now();
         let is_mobile_search = parsed_query.clauses.len() == 1
-            && parsed_query.clauses[0].field == "mobile";
+            && parsed_query.clauses[0].field == "mobile"
+            && !matches!(parsed_query.clauses[0].mode, MatchMode::AnyToken | MatchMode::Contains)
+            && !is_pattern_value(&parsed_query.clauses[0].value);
+        let is_email_search = parsed_query.clauses.len() == 1
+            && parsed_query.clauses[0].field == "email"
+            && !matches!(parsed_query.clauses[0].mode, MatchMode::Fuzzy(_))
+            && is_email_fanout_value(&parsed_query.clauses[0].value);
+        let fanout_seed = fanout_seed(&parsed_query);
 
         // Build the query for counting and searching
-        let query = if is_mobile_search {
+        let query = if is_mobile_search || is_email_search || fanout_seed.is_some() {
             // For mobile search, we need to count differently (fan-out logic)
             // We'll count after getting addresses
             None
         } else {
-            Some(self.query_parser.build_query(&parsed_query)?)
+            let query = self.query_parser.build_query_with_phrase_mode(&parsed_query, options.phrase_mode)?;
+            Some(self.restrict_indexed_since(query, options.indexed_since)?)
         };
+        let mut phrase_fallback = false;
+        // Matches existed but every one scored below min_score
+        let mut below_min_score = false;
+        // Description of what actually ran, when requested
+        let mut debug_query = None;
+        // How each row of a fan-out was reached
+        let mut fanout_tiers: Option<HashMap<DocAddress, FanoutTier>> = None;
+        // Every match, beyond the limit too, when a results hash was requested
+        // and there are at most RESULTS_HASH_MAX_MATCHES
+        let hash_matches = |total: usize| options.results_hash && total <= RESULTS_HASH_MAX_MATCHES;
+        let mut matched: Option<Vec<DocAddress>> = None;
 
         // Get results and total count
         let (all_doc_addresses, total_matches) = if is_mobile_search {
             // Mobile fan-out logic - get addresses and count
-            let mobile_value = self.query_parser.normalize_value("mobile", &parsed_query.clauses[0].value);
-            let addresses = self.execute_mobile_fanout(&searcher, &mobile_value)?;
+            let mobile_value = self.query_parser.normalize_clause_value(&parsed_query.clauses[0])?;
+            // Fan-out results are unscored, so min_score does not apply here
+            let fanout = self.execute_mobile_fanout(&searcher, &mobile_value)?;
+            let mut addresses: HashSet<DocAddress> = fanout.keys().copied().collect();
+            self.retain_indexed_since(&searcher, &mut addresses, options.indexed_since)?;
+            self.remove_excluded(&searcher, &parsed_query, &mut addresses)?;
+            let addresses = tier_order(addresses, &fanout);
+            fanout_tiers = Some(fanout);
+            if hash_matches(addresses.len()) {
+                matched = Some(addresses.clone());
+            }
+            if options.debug_query {
+                debug_query = Some(format!("MobileFanout({:?})", mobile_value));
+            }
             let total = addresses.len();
-            (addresses, total)
+            (addresses.into_iter().take(fetch_limit).collect(), total)
+        } else if is_email_search {
+            // Email fan-out: same identity expansion, seeded by the address
+            let email_value = self.query_parser.normalize_clause_value(&parsed_query.clauses[0])?;
+            let fanout = self.execute_email_fanout(&searcher, &email_value)?;
+            let mut addresses: HashSet<DocAddress> = fanout.keys().copied().collect();
+            self.retain_indexed_since(&searcher, &mut addresses, options.indexed_since)?;
+            self.remove_excluded(&searcher, &parsed_query, &mut addresses)?;
+            let addresses = tier_order(addresses, &fanout);
+            fanout_tiers = Some(fanout);
+            if hash_matches(addresses.len()) {
+                matched = Some(addresses.clone());
+            }
+            if options.debug_query {
+                debug_query = Some(format!("EmailFanout({:?})", email_value));
+            }
+            let total = addresses.len();
+            (addresses.into_iter().take(fetch_limit).collect(), total)
+        } else if let Some(seed) = fanout_seed {
+            // Mobile AND attribute clauses: keep the fanned-out rows matching
+            // the attributes, ranked by how well they match them
+            let mobile_value = self.query_parser.normalize_clause_value(&parsed_query.clauses[seed])?;
+            let tiers = self.execute_mobile_fanout(&searcher, &mobile_value)?;
+            let mut fanout: HashSet<DocAddress> = tiers.keys().copied().collect();
+            self.retain_indexed_since(&searcher, &mut fanout, options.indexed_since)?;
+            fanout_tiers = Some(tiers);
+
+            let mut attributes = parsed_query.clone();
+            attributes.clauses.remove(seed);
+            attributes.ops.truncate(attributes.clauses.len() - 1);
+            // The user wrote several clauses, so Auto means tokens as elsewhere
+            let phrase_mode = match options.phrase_mode {
+                PhraseMode::Auto => PhraseMode::Tokens,
+                mode => mode,
+            };
+            let attribute_query = self.query_parser.build_query_with_phrase_mode(&attributes, phrase_mode)?;
+            if options.debug_query {
+                debug_query = Some(format!("MobileFanout({:?}) scored by {:?}", mobile_value, attribute_query));
+            }
+
+            let mut scored = self.score_addresses(&searcher, attribute_query.as_ref(), fanout)?;
+            if let Some(half_life) = options.recency_half_life {
+                let columns = self.indexed_at_columns(&searcher)?;
+                let now = now_secs();
+                for (score, addr) in &mut scored {
+                    let indexed_at = columns[addr.segment_ord as usize].first(addr.doc_id);
+                    *score *= recency_decay(indexed_at, now, half_life);
+                }
+            }
+            let scored_matches = scored.len();
+            scored.retain(|(score, _)| options.min_score.is_none_or(|min| *score >= min));
+            below_min_score = scored_matches > 0 && scored.is_empty();
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+            if hash_matches(scored.len()) {
+                matched = Some(scored.iter().map(|(_score, addr)| *addr).collect());
+            }
+            let total = scored.len();
+            (scored.into_iter().take(fetch_limit).map(|(_score, addr)| addr).collect(), total)
         } else {
             // Regular query execution
-            let q = query.as_ref().unwrap();
+            let mut q = query.unwrap();
+            let scope = self.segment_scope(&searcher)?;
 
             // Get total count FIRST using Count collector (fast, doesn't retrieve docs)
-            let total = searcher.search(q.as_ref(), &Count)?;
+            let mut total = searcher.search(q.as_ref(), &SegmentSubset::new(Count, scope.as_ref()))?;
+
+            // Nothing matched the strict phrases: broaden to token AND
+            if total == 0 && options.phrase_mode == PhraseMode::PhraseThenTokens {
+                q = self.query_parser.build_query_with_phrase_mode(&parsed_query, PhraseMode::Tokens)?;
+                q = self.restrict_indexed_since(q, options.indexed_since)?;
+                total = searcher.search(q.as_ref(), &SegmentSubset::new(Count, scope.as_ref()))?;
+                phrase_fallback = true;
+            }
+            if options.debug_query {
+                debug_query = Some(format!("{:?}", q));
+            }
+
+            // Then get limited results, best score first
+            let top_docs = |limit: usize| -> Result<Vec<(f32, DocAddress)>> {
+                Ok(match options.recency_half_life {
+                    Some(half_life) => self.search_with_recency(&searcher, q.as_ref(), limit.max(1), half_life, scope.as_ref())?,
+                    None => {
+                        let collector = SegmentSubset::new(TopDocs::with_limit(limit.max(1)), scope.as_ref());
+                        searcher.search(q.as_ref(), &collector)?
+                    }
+                })
+            };
+            if hash_matches(total) {
+                // Scores only matter when some matches fall below min_score
+                matched = Some(match options.min_score {
+                    Some(min) => top_docs(total)?.into_iter()
+                        .filter(|(score, _)| *score >= min)
+                        .map(|(_score, addr)| addr)
+                        .collect(),
+                    None => searcher.search(q.as_ref(), &SegmentSubset::new(DocSetCollector, scope.as_ref()))?
+                        .into_iter()
+                        .collect(),
+                });
+            }
+            let top_docs = top_docs(fetch_limit)?;
+            let collected = top_docs.len();
 
-            // Then get limited results
-            let addresses: HashSet<DocAddress> = searcher.search(q.as_ref(), &TopDocs::with_limit(MAX_RESULTS))?
+            // Drop the weak tail before paying for document retrieval
+            let addresses: Vec<DocAddress> = top_docs
                 .into_iter()
+                .filter(|(score, _)| options.min_score.is_none_or(|min| *score >= min))
                 .map(|(_score, addr)| addr)
                 .collect();
 
+            // TopDocs is score-ordered: if the threshold cut into the collected
+            // docs, nothing beyond them can pass either, so the count is exact
+            if addresses.len() < collected {
+                total = addresses.len();
+            }
+            below_min_score = collected > 0 && addresses.is_empty();
+
             (addresses, total)
         };
 
@@ -96,27 +876,128 @@ impl SearchService {
 
         // Retrieve documents
         let retrieve_start = Instant::now();
-        let schema = searcher.schema();
-        let mut results: Vec<TantivyDocument> = Vec::new();
+        let schema = self.index.schema();
+        let skip_failed = self.config.skip_failed_docs;
+        let candidates = &all_doc_addresses[..all_doc_addresses.len().min(fetch_limit)];
+        let fetched = if self.config.batched_retrieval {
+            fetch_documents(&searcher, candidates, skip_failed)?
+        } else {
+            candidates.iter()
+                .map(|addr| read_document(&searcher, *addr, skip_failed))
+                .collect::<Result<Vec<_>>>()?
+        };
+        let mut results: Vec<TantivyDocument> = Vec::with_capacity(fetched.len());
+        let mut addresses: Vec<DocAddress> = Vec::with_capacity(fetched.len());
+        for (addr, doc) in candidates.iter().zip(fetched) {
+            if let Some(doc) = doc {
+                results.push(doc);
+                addresses.push(*addr);
+            }
+        }
+        let failed_retrievals = skip_failed.then(|| candidates.len() - results.len());
 
-        for addr in all_doc_addresses.iter().take(MAX_RESULTS) {
-            let retrieved: TantivyDocument = searcher.doc(*addr)?;
-            results.push(retrieved);
+        let mut duplicates_removed = None;
+        if !dedup_fields.is_empty() {
+            let before = results.len();
+            (results, addresses) = dedup_documents(results, addresses, &dedup_fields);
+            duplicates_removed = Some(before - results.len());
+        }
+        if !sort_fields.is_empty() {
+            (results, addresses) = sort_documents(results, addresses, &sort_fields);
+            results.truncate(limit);
+            addresses.truncate(limit);
         }
 
         let retrieve_time = retrieve_start.elapsed();
         let total_time = search_start.elapsed();
 
         // Convert to JSON
-        let json_results: Vec<serde_json::Value> = results.iter()
-            .filter_map(|doc| document_to_json(doc, &schema).ok())
-            .filter_map(|json_str| serde_json::from_str(&json_str).ok())
-            .collect();
+        let mut json_results: Vec<serde_json::Value> = Vec::with_capacity(results.len());
+        for (doc, addr) in results.iter().zip(&addresses) {
+            let Some(mut json_doc) = document_to_json(doc, &schema).ok()
+                .and_then(|json_str| serde_json::from_str::<serde_json::Value>(&json_str).ok())
+            else {
+                continue;
+            };
+            if (options.match_offsets || options.snippets)
+                && let Some(obj) = json_doc.as_object_mut()
+            {
+                let offsets = self.match_offsets(&parsed_query, doc)?;
+                if options.snippets {
+                    obj.insert("snippets".to_string(), self.snippets(&offsets, doc));
+                }
+                if options.match_offsets {
+                    obj.insert("match_offsets".to_string(), json!(offsets));
+                }
+            }
+            if options.found_via
+                && let Some(obj) = json_doc.as_object_mut()
+            {
+                // Every row of a query that doesn't fan out matched it directly
+                let tier = fanout_tiers.as_ref().map_or(Some(FanoutTier::Direct), |tiers| tiers.get(addr).copied());
+                obj.insert("found_via".to_string(), json!(tier.map(FanoutTier::as_str)));
+            }
+            if options.source_row
+                && let Some(obj) = json_doc.as_object_mut()
+            {
+                obj.insert("source_row".to_string(), json!(source_row(doc, &schema)));
+            }
+            if options.omit_empty
+                && let Some(obj) = json_doc.as_object_mut()
+            {
+                obj.retain(|_, value| value.as_str() != Some(""));
+            }
+            self.config.result_transform.apply(&mut json_doc);
+            json_results.push(json_doc);
+        }
+
+        let stats = options.stats.then(|| result_stats(&results, &schema));
+        let results_hash = matched.map(|matched| self.results_hash(&searcher, &matched)).transpose()?;
+        let stored_bytes = options.stored_bytes.then(|| stored_bytes(&results, &schema));
+        let field_match_counts = if options.field_match_counts {
+            // Same phrase handling the main query ended up with
+            let phrase_mode = match options.phrase_mode {
+                _ if phrase_fallback => PhraseMode::Tokens,
+                PhraseMode::Auto if parsed_query.clauses.len() > 1 => PhraseMode::Tokens,
+                mode => mode,
+            };
+            Some(self.field_match_counts(&searcher, &parsed_query, phrase_mode, &addresses)?)
+        } else {
+            None
+        };
+
+        let zero_reason = results.is_empty().then(|| {
+            if failed_retrievals.is_some_and(|failed| failed > 0) {
+                "unreadable_documents"
+            } else if below_min_score {
+                "below_min_score"
+            } else if total_matches > 0 {
+                "limit"
+            } else if parsed_query.dropped.iter().any(|clause| !clause.value.trim().is_empty()) {
+                "all_tokens_filtered"
+            } else if !parsed_query.dropped.is_empty() {
+                "empty_value"
+            } else {
+                "no_match"
+            }
+        });
 
         Ok(SearchResults {
             results: json_results,
             total_matches,
             results_returned: results.len(),
+            phrase_fallback,
+            dropped_clauses: parsed_query.dropped.iter().map(|clause| clause.field.clone()).collect(),
+            executed_query: self.query_parser.to_query_string(&parsed_query),
+            zero_reason,
+            debug_query,
+            stats,
+            field_match_counts,
+            stored_bytes,
+            duplicates_removed,
+            failed_retrievals,
+            index_version,
+            results_hash,
             query_parse_time_ms: parse_time.as_secs_f64() * 1000.0,
             search_execution_time_ms: execute_time.as_secs_f64() * 1000.0,
             document_retrieval_time_ms: retrieve_time.as_secs_f64() * 1000.0,
@@ -124,103 +1005,1314 @@ impl SearchService {
         })
     }
 
-    /// Execute mobile fan-out search
-    fn execute_mobile_fanout(
+    fn indexed_at_field(&self) -> Result<Field> {
+        self.index.schema().get_field("indexed_at").map_err(|_| QueryError::new(
+            "FEATURE_DISABLED",
+            "Index has no indexed_at field; rebuild it to filter by indexed_since",
+        ).into())
+    }
+
+    /// Restrict a query to documents indexed strictly after `since`
+    fn restrict_indexed_since(&self, query: Box<dyn Query>, since: Option<DateTime>) -> Result<Box<dyn Query>> {
+        let Some(since) = since else {
+            return Ok(query);
+        };
+        let field = self.indexed_at_field()?;
+        let range = RangeQuery::new(Bound::Excluded(Term::from_field_date(field, since)), Bound::Unbounded);
+        Ok(Box::new(BooleanQuery::new(vec![(Occur::Must, query), (Occur::Must, Box::new(range))])))
+    }
+
+    /// Keep the fan-out rows indexed strictly after `since`, read from the
+    /// `indexed_at` fast field
+    fn retain_indexed_since(
         &self,
         searcher: &tantivy::Searcher,
-        mobile_value: &str,
-    ) -> Result<HashSet<DocAddress>> {
-        let mut all_addresses: HashSet<DocAddress> = HashSet::new();
-        let schema = self.index.schema();
+        addresses: &mut HashSet<DocAddress>,
+        since: Option<DateTime>,
+    ) -> Result<()> {
+        let Some(since) = since else {
+            return Ok(());
+        };
+        let columns = self.indexed_at_columns(searcher)?;
+        addresses.retain(|addr| {
+            columns[addr.segment_ord as usize].first(addr.doc_id).is_some_and(|indexed_at| indexed_at > since)
+        });
+        Ok(())
+    }
 
-        let mobile_field = schema.get_field("mobile").unwrap();
-        let master_id_field = schema.get_field("master_id").unwrap();
-        let alt_field = schema.get_field("alt").unwrap();
+    /// Drop fan-out rows matching the query's NOT clauses. Scored fan-outs
+    /// need no filtering: their attribute query carries the exclusions.
+    fn remove_excluded(
+        &self,
+        searcher: &tantivy::Searcher,
+        parsed: &ParsedQuery,
+        addresses: &mut HashSet<DocAddress>,
+    ) -> Result<()> {
+        if parsed.excluded.is_empty() {
+            return Ok(());
+        }
+        let exclusion = self.query_parser.build_exclusion_query(parsed)?;
+        for (_, addr) in self.score_addresses(searcher, exclusion.as_ref(), addresses.clone())? {
+            addresses.remove(&addr);
+        }
+        Ok(())
+    }
 
-        // Step 1: Find all rows where mobile = X
-        let mobile_term = Term::from_field_text(mobile_field, mobile_value);
-        let mobile_query = TermQuery::new(mobile_term, IndexRecordOption::Basic);
-        let mobile_docs = searcher.search(&mobile_query, &TopDocs::with_limit(MAX_RESULTS))?;
+    /// `indexed_at` fast field column of each segment, by segment ordinal
+    fn indexed_at_columns(&self, searcher: &tantivy::Searcher) -> Result<Vec<Column<DateTime>>> {
+        self.indexed_at_field()?;
+        Ok(searcher.segment_readers().iter()
+            .map(|segment| segment.fast_fields().date("indexed_at"))
+            .collect::<tantivy::Result<Vec<_>>>()?)
+    }
+
+    /// Top `limit` matches of `query` by relevance scaled with `recency_decay`
+    fn search_with_recency(
+        &self,
+        searcher: &tantivy::Searcher,
+        query: &dyn Query,
+        limit: usize,
+        half_life: Duration,
+        scope: Option<&HashSet<SegmentOrdinal>>,
+    ) -> Result<Vec<(f32, DocAddress)>> {
+        self.indexed_at_field()?;
+        let now = now_secs();
+        let collector = TopDocs::with_limit(limit).tweak_score(move |segment: &SegmentReader| {
+            let column = segment.fast_fields().date("indexed_at").ok();
+            move |doc: DocId, score: Score| {
+                let indexed_at = column.as_ref().and_then(|column| column.first(doc));
+                score * recency_decay(indexed_at, now, half_life)
+            }
+        });
+        Ok(searcher.search(query, &SegmentSubset::new(collector, scope))?)
+    }
+
+    /// Segments a regular query may search under `max_search_segments`: the
+    /// ones holding the newest `indexed_at`, or the last ones for indexes
+    /// without it. None when every segment is searched.
+    fn segment_scope(&self, searcher: &tantivy::Searcher) -> Result<Option<HashSet<SegmentOrdinal>>> {
+        let segments = searcher.segment_readers();
+        let Some(max_segments) = self.config.max_search_segments.filter(|&max| max < segments.len()) else {
+            return Ok(None);
+        };
+        let mut newest: Vec<(i64, SegmentOrdinal)> = Vec::with_capacity(segments.len());
+        for (ord, segment) in segments.iter().enumerate() {
+            let indexed_at = match self.indexed_at_field() {
+                Ok(_) => segment.fast_fields().date("indexed_at")?.max_value().into_timestamp_secs(),
+                Err(_) => 0,
+            };
+            newest.push((indexed_at, ord as SegmentOrdinal));
+        }
+        newest.sort_by(|a, b| b.cmp(a));
+        Ok(Some(newest.into_iter().take(max_segments).map(|(_, ord)| ord).collect()))
+    }
 
-        let mut master_ids: HashSet<String> = HashSet::new();
+    /// Identity graph behind a fan-out from `field` = `value` (mobile, email
+    /// or the identity key): each fanned-out row links its identity key to
+    /// its `GRAPH_LINK_FIELDS` values. Mobile and alt numbers share one
+    /// `phone` node, so a number that is one key's mobile and another's alt
+    /// joins both keys. Edge weights count the rows behind a link.
+    pub fn fanout_graph(&self, field: &str, value: &str) -> Result<FanoutGraph> {
+        let searcher = self.searcher()?;
+        let clause = QueryClause { field: field.to_string(), value: value.to_string(), mode: MatchMode::Exact, boost: None };
+        let normalized = self.query_parser.normalize_clause_value(&clause)?;
+        let key_name = self.index.schema().get_field_name(self.key_field).to_string();
 
-        for (_score, addr) in &mobile_docs {
-            all_addresses.insert(*addr);
+        let addresses = match field {
+            "mobile" => self.execute_mobile_fanout(&searcher, &normalized)?.into_keys().collect::<HashSet<_>>(),
+            "email" => self.execute_email_fanout(&searcher, &normalized)?.into_keys().collect(),
+            _ if field == key_name => {
+                // Keys are matched as stored, as /context does
+                let term = Term::from_field_text(self.key_field, value.trim());
+                self.fan_out_from(&searcher, &TermQuery::new(term, IndexRecordOption::Basic))?.into_keys().collect()
+            }
+            _ => return Err(QueryError::new(
+                "INVALID_REQUEST",
+                format!("Graphs start from mobile, email or {}, not '{}'", key_name, field),
+            ).into()),
+        };
 
-            // Extract master_id
-            let doc: TantivyDocument = searcher.doc(*addr)?;
-            if let Some(master_id_val) = doc.get_first(master_id_field)
+        let link_fields: Vec<(&str, Field)> = GRAPH_LINK_FIELDS.iter()
+            .filter_map(|name| Some((*name, self.query_parser.get_field(name)?)))
+            .collect();
+        let mut graph = FanoutGraph::default();
+        let mut addresses: Vec<DocAddress> = addresses.into_iter().collect();
+        addresses.sort();
+        for doc in fetch_documents(&searcher, &addresses, self.config.skip_failed_docs)?.into_iter().flatten() {
+            let stored = |field: Field| doc.get_first(field)
                 .and_then(|v| Value::as_str(&v))
-            {
-                let master_id = master_id_val.trim();
-                if !master_id.is_empty() {
-                    master_ids.insert(master_id.to_string());
+                .map(str::trim)
+                .filter(|v| !v.is_empty());
+            let Some(key) = stored(self.key_field) else {
+                continue;
+            };
+            let key_node = graph.add_node(&key_name, key);
+            for (name, field) in &link_fields {
+                if let Some(linked) = stored(*field) {
+                    let node = graph.add_node(GRAPH_PHONE_NODE, linked);
+                    *graph.edges.entry((key_node.clone(), node, name.to_string())).or_default() += 1;
                 }
             }
         }
+        Ok(graph)
+    }
 
-        // Step 2 & 3: Find all rows with those master_id values
-        if !master_ids.is_empty() {
-            let mut master_id_queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
-            for master_id in &master_ids {
-                let master_id = master_id.trim();
-                if master_id.is_empty() {
-                    continue;
-                }
-                let master_id_term = Term::from_field_text(master_id_field, master_id);
-                let master_id_query = TermQuery::new(master_id_term, IndexRecordOption::Basic);
-                master_id_queries.push((Occur::Should, Box::new(master_id_query)));
+    /// Resolve sort keys to stored fields, rejecting unknown or unstored ones
+    /// and those that may not be searched
+    fn sort_fields(&self, sort: &[SortKey]) -> Result<Vec<(Field, bool)>> {
+        let schema = self.index.schema();
+        sort.iter()
+            .map(|key| {
+                let name = key.field.trim().to_lowercase();
+                self.check_searchable(&name)?;
+                schema.get_field(&name).ok()
+                    .filter(|field| schema.get_field_entry(*field).is_stored())
+                    .map(|field| (field, key.descending))
+                    .ok_or_else(|| QueryError::new(
+                        "INVALID_SORT",
+                        format!("Sort field '{}' is not a stored field", name),
+                    ).into())
+            })
+            .collect()
+    }
+
+    /// Resolve a dedup key to stored fields, rejecting unknown or unstored
+    /// ones and those that may not be searched
+    fn dedup_fields(&self, dedup_key: &[String]) -> Result<Vec<Field>> {
+        let schema = self.index.schema();
+        dedup_key.iter()
+            .map(|name| {
+                let name = name.trim().to_lowercase();
+                self.check_searchable(&name)?;
+                schema.get_field(&name).ok()
+                    .filter(|field| schema.get_field_entry(*field).is_stored())
+                    .ok_or_else(|| QueryError::new(
+                        "INVALID_DEDUP_KEY",
+                        format!("Dedup key field '{}' is not a stored field", name),
+                    ).into())
+            })
+            .collect()
+    }
+
+    /// Investigative view around one master_id: its own rows, the rows linked
+    /// to them through mobile fan-out, and up to `neighbors` master_ids on each
+    /// side in lexicographic order (looked up by term ordinal, no dictionary scan).
+    pub fn search_context(&self, master_id: &str, neighbors: usize) -> Result<ContextResults> {
+        let searcher = self.searcher()?;
+        let master_id = master_id.trim();
+        let master_id_field = self.key_field; // identity key, master_id by default
+        let mobile_field = self.query_parser.get_field("mobile").unwrap();
+
+        let term = Term::from_field_text(master_id_field, master_id);
+        let match_docs = searcher.search(
+            &TermQuery::new(term, IndexRecordOption::Basic),
+            &TopDocs::with_limit(MAX_RESULTS),
+        )?;
+        let match_addresses: HashSet<DocAddress> = match_docs.iter().map(|(_, addr)| *addr).collect();
+
+        // Siblings: everything the matched rows' mobiles fan out to
+        let mut sibling_addresses: HashSet<DocAddress> = HashSet::new();
+        for addr in &match_addresses {
+            let Some(doc) = read_document(&searcher, *addr, self.config.skip_failed_docs)? else {
+                continue;
+            };
+            if let Some(mobile) = doc.get_first(mobile_field).and_then(|v| Value::as_str(&v))
+                && !mobile.trim().is_empty()
+            {
+                sibling_addresses.extend(self.execute_mobile_fanout(&searcher, &PhoneNumber::canonicalize(mobile))?.into_keys());
             }
+        }
+        sibling_addresses.retain(|addr| !match_addresses.contains(addr));
+
+        // Neighbors: nearest master_ids before and after, merged across segments
+        let mut before: BTreeSet<Vec<u8>> = BTreeSet::new();
+        let mut after: BTreeSet<Vec<u8>> = BTreeSet::new();
+        if neighbors > 0 {
+            for segment in searcher.segment_readers() {
+                let inverted = segment.inverted_index(master_id_field)?;
+                let terms = inverted.terms();
+                // Ordinal of the first term >= master_id (num_terms when none)
+                let mut stream = terms.range().ge(master_id.as_bytes()).into_stream()?;
+                let pivot = if stream.advance() { stream.term_ord() } else { terms.num_terms() as u64 };
 
-            if master_id_queries.len() == 1 {
-                let master_id_docs = searcher.search(master_id_queries[0].1.as_ref(), &TopDocs::with_limit(MAX_RESULTS))?;
-                for (_score, addr) in &master_id_docs {
-                    all_addresses.insert(*addr);
+                let mut key = Vec::new();
+                for ord in pivot.saturating_sub(neighbors as u64)..pivot {
+                    if terms.ord_to_term(ord, &mut key)? {
+                        before.insert(key.clone());
+                    }
                 }
-            } else if !master_id_queries.is_empty() {
-                let master_id_bool_query = BooleanQuery::new(master_id_queries);
-                let master_id_docs = searcher.search(&master_id_bool_query, &TopDocs::with_limit(MAX_RESULTS))?;
-                for (_score, addr) in &master_id_docs {
-                    all_addresses.insert(*addr);
+
+                let mut stream = terms.range().gt(master_id.as_bytes()).into_stream()?;
+                let mut taken = 0;
+                while taken < neighbors && stream.advance() {
+                    after.insert(stream.key().to_vec());
+                    taken += 1;
                 }
             }
         }
+        // Keep only the nearest across all segments
+        while before.len() > neighbors {
+            before.pop_first();
+        }
 
-        // Step 4: Find all rows where alt = X
-        if !mobile_value.trim().is_empty() {
-            let alt_term = Term::from_field_text(alt_field, mobile_value);
-            let alt_query = TermQuery::new(alt_term, IndexRecordOption::Basic);
-            let alt_docs = searcher.search(&alt_query, &TopDocs::with_limit(MAX_RESULTS))?;
-            for (_score, addr) in &alt_docs {
-                all_addresses.insert(*addr);
+        let mut neighbor_results = Vec::new();
+        let nearest_after = after.into_iter().take(neighbors);
+        for (position, key) in before.into_iter().map(|k| ("before", k)).chain(nearest_after.map(|k| ("after", k))) {
+            let neighbor_id = String::from_utf8_lossy(&key).to_string();
+            let term = Term::from_field_text(master_id_field, &neighbor_id);
+            let docs = searcher.search(&TermQuery::new(term, IndexRecordOption::Basic), &TopDocs::with_limit(MAX_RESULTS))?;
+            let addresses: Vec<DocAddress> = docs.into_iter().map(|(_, addr)| addr).collect();
+            // Terms of fully deleted docs linger until merge; skip them
+            if addresses.is_empty() {
+                continue;
             }
+            neighbor_results.push(json!({
+                "master_id": neighbor_id,
+                "position": position,
+                "records": self.docs_to_json(&searcher, addresses.iter())?,
+            }));
         }
 
-        Ok(all_addresses)
+        Ok(ContextResults {
+            matches: self.docs_to_json(&searcher, match_addresses.iter())?,
+            siblings: self.docs_to_json(&searcher, sibling_addresses.iter())?,
+            neighbors: neighbor_results,
+        })
     }
-}
 
-/// Search results with timing information
-#[derive(Debug)]
-pub struct SearchResults {
-    pub results: Vec<serde_json::Value>,
-    pub total_matches: usize,
-    pub results_returned: usize,
-    pub query_parse_time_ms: f64,
-    pub search_execution_time_ms: f64,
-    pub document_retrieval_time_ms: f64,
-    pub total_time_ms: f64,
-}
+    /// Distinct terms of a queryable field with their document frequencies,
+    /// most frequent first (ties broken by term). Frequencies are read from the
+    /// term dictionaries, so deleted docs still count until segments merge.
+    pub fn field_terms(&self, field_name: &str, limit: usize) -> Result<Vec<(String, u64)>> {
+        let field = self.query_parser.get_field(field_name).ok_or_else(|| {
+            QueryError::new("UNKNOWN_FIELD", format!("Field '{}' is not an indexed field", field_name))
+        })?;
+        if !matches!(self.index.schema().get_field_entry(field).field_type(), FieldType::Str(_)) {
+            return Err(QueryError::new(
+                "FIELD_NOT_SEARCHABLE",
+                format!("Field '{}' has no text terms to list", field_name),
+            ).into());
+        }
 
-/// Convert TantivyDocument to JSON format
-fn document_to_json(doc: &TantivyDocument, schema: &tantivy::schema::Schema) -> Result<String> {
-    let master_id_field = schema.get_field("master_id").unwrap();
-    let mobile_field = schema.get_field("mobile").unwrap();
-    let alt_field = schema.get_field("alt").unwrap();
+        let searcher = self.searcher()?;
+        let inverted = searcher.segment_readers()
+            .iter()
+            .map(|segment| segment.inverted_index(field))
+            .collect::<tantivy::Result<Vec<_>>>()?;
+        let streams = inverted.iter()
+            .map(|inv| inv.terms().stream())
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let mut merger = TermMerger::new(streams);
+
+        // Min-heap of the `limit` most frequent terms seen so far
+        let mut top: BinaryHeap<Reverse<(u64, Reverse<Vec<u8>>)>> = BinaryHeap::with_capacity(limit + 1);
+        while merger.advance() {
+            let doc_freq: u64 = merger.current_segment_ords_and_term_infos()
+                .map(|(_, info)| info.doc_freq as u64)
+                .sum();
+            top.push(Reverse((doc_freq, Reverse(merger.key().to_vec()))));
+            if top.len() > limit {
+                top.pop();
+            }
+        }
+
+        Ok(top.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((doc_freq, Reverse(key)))| (String::from_utf8_lossy(&key).to_string(), doc_freq))
+            .collect())
+    }
+
+    /// Profile of an identity key for record comparison: names, addresses,
+    /// phones and emails of its rows, plus the keys its phones fan out to.
+    /// None when no row has the key.
+    pub fn record_profile(&self, key: &str) -> Result<Option<Profile>> {
+        let searcher = self.searcher()?;
+        let key = key.trim();
+        let term = Term::from_field_text(self.key_field, key);
+        let addresses = searcher.search(&TermQuery::new(term, IndexRecordOption::Basic), &DocSetCollector)?;
+        if addresses.is_empty() {
+            return Ok(None);
+        }
+
+        let field = |name: &str| self.query_parser.get_field(name).unwrap();
+        let (name, fname, address, mobile, alt, email) =
+            (field("name"), field("fname"), field("address"), field("mobile"), field("alt"), field("email"));
+        let mut profile = Profile { key: key.to_string(), ..Profile::default() };
+        for addr in addresses {
+            let Some(doc) = read_document(&searcher, addr, self.config.skip_failed_docs)? else {
+                continue;
+            };
+            let stored = |field: Field| doc.get_first(field)
+                .and_then(|v| Value::as_str(&v))
+                .map(str::trim)
+                .filter(|v| !v.is_empty());
+            stored(name).into_iter().chain(stored(fname)).for_each(|v| profile.add_name(v));
+            stored(address).into_iter().for_each(|v| profile.add_address(v));
+            profile.phones.extend([stored(mobile), stored(alt)].into_iter().flatten().map(PhoneNumber::canonicalize));
+            profile.emails.extend(stored(email).map(str::to_lowercase));
+        }
+
+        // Siblings through fan-out, recorded by key
+        let mut siblings: HashSet<DocAddress> = HashSet::new();
+        for phone in &profile.phones {
+            siblings.extend(self.execute_mobile_fanout(&searcher, phone)?.into_keys());
+        }
+        for addr in siblings {
+            let Some(doc) = read_document(&searcher, addr, self.config.skip_failed_docs)? else {
+                continue;
+            };
+            if let Some(linked) = doc.get_first(self.key_field).and_then(|v| Value::as_str(&v))
+                && linked.trim() != key
+            {
+                profile.linked_keys.insert(linked.trim().to_string());
+            }
+        }
+        Ok(Some(profile))
+    }
+
+    /// Fetch the records of a batch of master_ids with a single query.
+    /// Returns the records grouped by master_id and the ids with no live record.
+    /// Ids are matched verbatim after trimming, as stored at index time.
+    pub fn lookup_records(&self, master_ids: &[String]) -> Result<RecordLookup> {
+        let searcher = self.searcher()?;
+        let master_id_field = self.key_field; // identity key, master_id by default
+
+        let ids: BTreeSet<&str> = master_ids.iter().map(|id| id.trim()).filter(|id| !id.is_empty()).collect();
+        let term_queries: Vec<(Occur, Box<dyn Query>)> = ids.iter()
+            .map(|id| -> (Occur, Box<dyn Query>) {
+                let term = Term::from_field_text(master_id_field, id);
+                (Occur::Should, Box::new(TermQuery::new(term, IndexRecordOption::Basic)))
+            })
+            .collect();
+        let mut addresses: Vec<DocAddress> = searcher.search(&BooleanQuery::new(term_queries), &DocSetCollector)?
+            .into_iter()
+            .collect();
+        addresses.sort();
+
+        let schema = self.index.schema();
+        let mut records: BTreeMap<String, Vec<serde_json::Value>> = BTreeMap::new();
+        for addr in &addresses {
+            let Some(doc) = read_document(&searcher, *addr, self.config.skip_failed_docs)? else {
+                continue;
+            };
+            let master_id = doc.get_first(master_id_field)
+                .and_then(|v| Value::as_str(&v))
+                .unwrap_or("")
+                .to_string();
+            let mut json_doc = serde_json::from_str(&document_to_json(&doc, &schema)?)?;
+            self.config.result_transform.apply(&mut json_doc);
+            records.entry(master_id).or_default().push(json_doc);
+        }
+
+        let not_found = ids.into_iter()
+            .filter(|id| !records.contains_key(*id))
+            .map(str::to_string)
+            .collect();
+        Ok(RecordLookup { records, not_found })
+    }
+
+    /// Score `addresses` against `query`, dropping those it doesn't match.
+    /// Each segment's scorer only seeks through the candidate docs in doc order
+    /// instead of enumerating every match of `query`.
+    fn score_addresses(
+        &self,
+        searcher: &tantivy::Searcher,
+        query: &dyn Query,
+        addresses: HashSet<DocAddress>,
+    ) -> Result<Vec<(f32, DocAddress)>> {
+        let weight = query.weight(EnableScoring::enabled_from_searcher(searcher))?;
+        let mut addresses: Vec<DocAddress> = addresses.into_iter().collect();
+        addresses.sort();
+
+        let mut scored = Vec::new();
+        for segment_addresses in addresses.chunk_by(|a, b| a.segment_ord == b.segment_ord) {
+            let segment = searcher.segment_reader(segment_addresses[0].segment_ord);
+            let mut scorer = weight.scorer(segment, 1.0)?;
+            for addr in segment_addresses {
+                if scorer.doc() < addr.doc_id {
+                    scorer.seek(addr.doc_id);
+                }
+                if scorer.doc() == addr.doc_id {
+                    scored.push((scorer.score(), *addr));
+                }
+            }
+        }
+        Ok(scored)
+    }
+
+    /// For each field the query has clauses on, how many of `addresses` match
+    /// those clauses (OR-ed when the field appears more than once). Matches
+    /// are direct: rows only reached through fan-out count for no field.
+    fn field_match_counts(
+        &self,
+        searcher: &tantivy::Searcher,
+        parsed: &ParsedQuery,
+        phrase_mode: PhraseMode,
+        addresses: &[DocAddress],
+    ) -> Result<BTreeMap<String, usize>> {
+        let mut counts = BTreeMap::new();
+        for clause in &parsed.clauses {
+            if counts.contains_key(&clause.field) {
+                continue;
+            }
+            let clauses: Vec<QueryClause> = parsed.clauses.iter()
+                .filter(|c| c.field == clause.field)
+                .cloned()
+                .collect();
+            let ops = vec![QueryOp::Or; clauses.len() - 1];
+            let query = self.query_parser.build_query_with_phrase_mode(&ParsedQuery { clauses, ops, excluded: Vec::new(), dropped: Vec::new() }, phrase_mode)?;
+            let matched = self.score_addresses(searcher, query.as_ref(), addresses.iter().copied().collect())?;
+            counts.insert(clause.field.clone(), matched.len());
+        }
+        Ok(counts)
+    }
+
+    /// Retrieve documents and convert them to JSON
+    fn docs_to_json<'a>(
+        &self,
+        searcher: &tantivy::Searcher,
+        addresses: impl Iterator<Item = &'a DocAddress>,
+    ) -> Result<Vec<serde_json::Value>> {
+        let schema = self.index.schema();
+        let mut json_docs = Vec::new();
+        for addr in addresses {
+            let Some(doc) = read_document(searcher, *addr, self.config.skip_failed_docs)? else {
+                continue;
+            };
+            let mut json_doc = serde_json::from_str(&document_to_json(&doc, &schema)?)?;
+            self.config.result_transform.apply(&mut json_doc);
+            json_docs.push(json_doc);
+        }
+        Ok(json_docs)
+    }
+
+    fn match_offsets(&self, parsed_query: &ParsedQuery, doc: &TantivyDocument) -> Result<BTreeMap<String, Vec<[usize; 2]>>> {
+        match_offsets(&self.index, &self.query_parser, parsed_query, doc)
+    }
+
+    /// Snippets of the matched TEXT fields of `doc`, keyed by field: at most
+    /// `snippet_max_fragments` fragments of `snippet_max_chars` characters
+    /// each, with matches in `<b>` tags and cuts marked by an ellipsis
+    fn snippets(&self, offsets: &BTreeMap<String, Vec<[usize; 2]>>, doc: &TantivyDocument) -> serde_json::Value {
+        let mut snippets = serde_json::Map::new();
+        for (field_name, spans) in offsets {
+            let field = self.query_parser.get_field(field_name).unwrap();
+            if let Some(stored) = doc.get_first(field).and_then(|v| Value::as_str(&v)) {
+                let fragments = snippet_fragments(
+                    stored,
+                    spans,
+                    self.config.snippet_max_chars,
+                    self.config.snippet_max_fragments,
+                );
+                snippets.insert(field_name.clone(), json!(fragments));
+            }
+        }
+        serde_json::Value::Object(snippets)
+    }
+
+    /// Execute mobile fan-out search, tagging each row with how it was reached
+    fn execute_mobile_fanout(
+        &self,
+        searcher: &tantivy::Searcher,
+        mobile_value: &str,
+    ) -> Result<HashMap<DocAddress, FanoutTier>> {
+        let mobile_field = self.query_parser.get_field("mobile").unwrap();
+        let alt_field = self.query_parser.get_field("alt").unwrap();
+
+        // Steps 1-3: rows where mobile = X and every row sharing their master_id
+        let mobile_term = Term::from_field_text(mobile_field, mobile_value);
+        let mobile_query = TermQuery::new(mobile_term, IndexRecordOption::Basic);
+        let mut all_addresses = self.fan_out_from(searcher, &mobile_query)?;
+
+        // Step 4: Find all rows where alt = X
+        if !mobile_value.trim().is_empty() {
+            let alt_term = Term::from_field_text(alt_field, mobile_value);
+            let alt_query = TermQuery::new(alt_term, IndexRecordOption::Basic);
+            let alt_docs = searcher.search(&alt_query, &TopDocs::with_limit(MAX_RESULTS))?;
+            for (_score, addr) in &alt_docs {
+                all_addresses.entry(*addr).or_insert(FanoutTier::AltLinked);
+            }
+        }
+
+        Ok(all_addresses)
+    }
+
+    /// Execute email fan-out search: rows holding the address in any of
+    /// `EMAIL_FANOUT_FIELDS`, expanded to every row sharing their master_id
+    fn execute_email_fanout(
+        &self,
+        searcher: &tantivy::Searcher,
+        email_value: &str,
+    ) -> Result<HashMap<DocAddress, FanoutTier>> {
+        // The email tokenizer indexes the whole address as one of its terms
+        let email_queries: Vec<(Occur, Box<dyn Query>)> = EMAIL_FANOUT_FIELDS
+            .iter()
+            .filter_map(|name| self.query_parser.get_field(name))
+            .map(|field| {
+                let term = Term::from_field_text(field, email_value);
+                (Occur::Should, Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as Box<dyn Query>)
+            })
+            .collect();
+        self.fan_out_from(searcher, &BooleanQuery::new(email_queries))
+    }
+
+    /// Rows matching `seed` (`Direct`) plus all rows sharing an identity key
+    /// (master_id by default) with any of them (`KeyLinked`)
+    fn fan_out_from(&self, searcher: &tantivy::Searcher, seed: &dyn Query) -> Result<HashMap<DocAddress, FanoutTier>> {
+        // Step 1: Find all rows matching the seed
+        let seed_addresses: Vec<DocAddress> = searcher.search(seed, &TopDocs::with_limit(MAX_RESULTS))?
+            .into_iter()
+            .map(|(_score, addr)| addr)
+            .collect();
+
+        // Extract master_ids, reading the stored docs on the fan-out pool when configured
+        let keys: Vec<Option<String>> = match &self.fanout_pool {
+            Some(pool) => pool.install(|| {
+                seed_addresses.par_iter().map(|addr| self.stored_key(searcher, *addr)).collect::<Result<_>>()
+            })?,
+            None => seed_addresses.iter().map(|addr| self.stored_key(searcher, *addr)).collect::<Result<_>>()?,
+        };
+        let master_ids: Vec<String> = keys.into_iter().flatten().collect::<HashSet<_>>().into_iter().collect();
+
+        // Step 2 & 3: Find all rows with those master_id values, one OR query
+        // per chunk of ids; with a pool, one chunk per thread searched in parallel
+        let chunk_size = match &self.fanout_pool {
+            Some(pool) => master_ids.len().div_ceil(pool.current_num_threads()),
+            None => master_ids.len(),
+        }.max(1);
+        let search_chunk = |chunk: &[String]| -> Result<Vec<DocAddress>> {
+            let master_id_queries: Vec<(Occur, Box<dyn Query>)> = chunk.iter()
+                .map(|master_id| {
+                    let master_id_term = Term::from_field_text(self.key_field, master_id);
+                    (Occur::Should, Box::new(TermQuery::new(master_id_term, IndexRecordOption::Basic)) as Box<dyn Query>)
+                })
+                .collect();
+            let master_id_docs = searcher.search(&BooleanQuery::new(master_id_queries), &TopDocs::with_limit(MAX_RESULTS))?;
+            Ok(master_id_docs.into_iter().map(|(_score, addr)| addr).collect())
+        };
+        let key_addresses: Vec<Vec<DocAddress>> = match &self.fanout_pool {
+            Some(pool) => pool.install(|| master_ids.par_chunks(chunk_size).map(search_chunk).collect::<Result<_>>())?,
+            None => master_ids.chunks(chunk_size).map(search_chunk).collect::<Result<_>>()?,
+        };
+
+        // Union assembled here, after the parallel parts have finished
+        let mut all_addresses: HashMap<DocAddress, FanoutTier> = seed_addresses.into_iter()
+            .map(|addr| (addr, FanoutTier::Direct))
+            .collect();
+        for addr in key_addresses.into_iter().flatten() {
+            all_addresses.entry(addr).or_insert(FanoutTier::KeyLinked);
+        }
+        Ok(all_addresses)
+    }
+
+    /// Hash of the sorted, distinct identity keys (master_id by default) of
+    /// `addresses`, as 16 hex digits. Identical queries over unchanged
+    /// matches give the same hash, across restarts too, so polling clients
+    /// can compare it instead of the results. Keys are read from their fast
+    /// column when the key field has one, from the stored rows otherwise.
+    fn results_hash(&self, searcher: &tantivy::Searcher, addresses: &[DocAddress]) -> Result<String> {
+        let columns = searcher.segment_readers().iter()
+            .map(|segment| segment.fast_fields().str(&self.config.key_field))
+            .collect::<tantivy::Result<Vec<_>>>()?;
+        let mut keys = BTreeSet::new();
+        for addr in addresses {
+            let Some(column) = &columns[addr.segment_ord as usize] else {
+                keys.extend(self.stored_key(searcher, *addr)?);
+                continue;
+            };
+            for ord in column.term_ords(addr.doc_id) {
+                let mut key = String::new();
+                column.ord_to_str(ord, &mut key)?;
+                keys.insert(key);
+            }
+        }
+
+        // FNV-1a, which unlike `DefaultHasher` is fixed across Rust releases;
+        // each key is terminated so ["ab", "c"] and ["a", "bc"] differ
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for key in &keys {
+            for byte in key.bytes().chain([0]) {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        Ok(format!("{:016x}", hash))
+    }
+
+    /// Trimmed identity key stored on a row, None when missing or blank (or
+    /// unreadable, with `skip_failed_docs`)
+    fn stored_key(&self, searcher: &tantivy::Searcher, addr: DocAddress) -> Result<Option<String>> {
+        let Some(doc) = read_document(searcher, addr, self.config.skip_failed_docs)? else {
+            return Ok(None);
+        };
+        Ok(doc.get_first(self.key_field)
+            .and_then(|v| Value::as_str(&v).map(|key| key.trim().to_string()))
+            .filter(|key| !key.is_empty()))
+    }
+}
+
+/// Open a reader on `index` with the service's reader settings.
+/// Manual reload policy: generations are switched by `reload` only.
+fn open_reader(index: &Index, config: &SearchConfig) -> Result<IndexReader> {
+    Ok(index.reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .doc_store_cache_num_blocks(config.doc_store_cache_blocks)
+        .try_into()?)
+}
+
+/// Whether `searcher` serves the segments of the commit `metas` describes,
+/// each with its deletes as of that commit
+fn is_generation_of(searcher: &Searcher, metas: &IndexMeta) -> bool {
+    let committed: BTreeMap<_, _> = metas.segments.iter().map(|segment| (segment.id(), segment.delete_opstamp())).collect();
+    searcher.generation().segments() == &committed
+}
+
+/// Drop the reader in `slot` once it has gone `timeout` without a search,
+/// keeping the served generation pinned (see `pin_generation`). While the
+/// latest commit is not the served one, the release waits: a pin can only be
+/// opened on the latest commit. The thread ends with the service.
+fn spawn_idle_release(slot: Weak<ReaderSlot>, index: Index, timeout: Duration) {
+    let check_interval = (timeout / 4).max(Duration::from_millis(100));
+    std::thread::spawn(move || loop {
+        std::thread::sleep(check_interval);
+        let Some(slot) = slot.upgrade() else {
+            break;
+        };
+        if slot.last_used.lock().unwrap().elapsed() < timeout {
+            continue;
+        }
+        let mut reader = slot.reader.write().unwrap();
+        if reader.is_none() {
+            continue;
+        }
+        match pin_generation(&index, slot.loaded_opstamp.load(Ordering::SeqCst)) {
+            Ok(Some(pinned)) => {
+                *slot.pinned.lock().unwrap() = Some(pinned);
+                reader.take();
+                eprintln!("Index reader released after {}s idle", timeout.as_secs());
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("[WARN] Index reader not released: {}", e),
+        }
+    });
+}
+
+/// Reader on generation `served` without a doc store block cache, keeping
+/// the generation's files open so it can still be served if a build commits
+/// unready checkpoints meanwhile. None when `served` is no longer the latest
+/// commit, as a reader always opens the latest.
+fn pin_generation(index: &Index, served: u64) -> Result<Option<IndexReader>> {
+    if index.load_metas()?.opstamp != served {
+        return Ok(None);
+    }
+    let pinned: IndexReader = index.reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .doc_store_cache_num_blocks(0)
+        .try_into()?;
+    // A commit landing while it opened may have been picked up instead
+    if index.load_metas()?.opstamp != served {
+        return Ok(None);
+    }
+    Ok(Some(pinned))
+}
+
+/// Fetch documents for `addresses`, keeping their order, but reading them
+/// grouped by segment in doc id order: the store is compressed in blocks of
+/// consecutive docs, so neighbours come from the same cached block instead of
+/// decompressing a block per hit when results are in rank order.
+fn fetch_documents(
+    searcher: &tantivy::Searcher,
+    addresses: &[DocAddress],
+    skip_failed: bool,
+) -> Result<Vec<Option<TantivyDocument>>> {
+    let mut read_order: Vec<usize> = (0..addresses.len()).collect();
+    read_order.sort_unstable_by_key(|&idx| addresses[idx]);
+
+    let mut docs: Vec<Option<TantivyDocument>> = (0..addresses.len()).map(|_| None).collect();
+    for idx in read_order {
+        docs[idx] = read_document(searcher, addresses[idx], skip_failed)?;
+    }
+    Ok(docs)
+}
+
+/// Read one document. With `skip_failed` a failed read is logged and gives
+/// None, so one unreadable document doesn't fail the whole request.
+pub fn read_document(searcher: &tantivy::Searcher, addr: DocAddress, skip_failed: bool) -> Result<Option<TantivyDocument>> {
+    match searcher.doc(addr) {
+        Ok(doc) => Ok(Some(doc)),
+        Err(e) if skip_failed => {
+            eprintln!("[Warning] Skipping unreadable document {:?}: {}", addr, e);
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Fields holding email addresses; an email fan-out matches the address in any of them
+pub const EMAIL_FANOUT_FIELDS: &[&str] = &["email"];
+
+/// Whether a single `email` clause names one whole address and so seeds a
+/// fan-out. Domains, local parts and patterns stay ordinary searches.
+pub fn is_email_fanout_value(value: &str) -> bool {
+    let value = value.trim();
+    !is_pattern_value(value) && value.contains('@') && !value.contains(char::is_whitespace)
+}
+
+/// Index of the mobile clause when a query is exactly one mobile clause ANDed
+/// with attribute clauses; such queries fan out from the mobile first
+fn fanout_seed(parsed: &ParsedQuery) -> Option<usize> {
+    if parsed.clauses.len() < 2 || parsed.ops.iter().any(|op| matches!(op, QueryOp::Or)) {
+        return None;
+    }
+    let mut mobiles = parsed.clauses.iter().enumerate().filter(|(_, c)| c.field == "mobile");
+    if parsed.clauses.iter().any(|c| c.field == "mobile" && (matches!(c.mode, MatchMode::AnyToken | MatchMode::Contains) || is_pattern_value(&c.value))) {
+        return None;
+    }
+    match (mobiles.next(), mobiles.next()) {
+        (Some((idx, _)), None) => Some(idx),
+        _ => None,
+    }
+}
+
+/// Runs `inner` on the allowed segments only; the others are skipped before
+/// any scoring (see `SearchConfig::max_search_segments`). Every segment is
+/// searched when `allowed` is None.
+struct SegmentSubset<'a, C> {
+    inner: C,
+    allowed: Option<&'a HashSet<SegmentOrdinal>>,
+}
+
+impl<'a, C> SegmentSubset<'a, C> {
+    fn new(inner: C, allowed: Option<&'a HashSet<SegmentOrdinal>>) -> Self {
+        Self { inner, allowed }
+    }
+}
+
+impl<C: Collector> Collector for SegmentSubset<'_, C> {
+    type Fruit = C::Fruit;
+    type Child = C::Child;
+
+    fn for_segment(&self, segment_ord: SegmentOrdinal, segment: &SegmentReader) -> tantivy::Result<C::Child> {
+        self.inner.for_segment(segment_ord, segment)
+    }
+
+    fn requires_scoring(&self) -> bool {
+        self.inner.requires_scoring()
+    }
+
+    fn merge_fruits(&self, fruits: Vec<<C::Child as SegmentCollector>::Fruit>) -> tantivy::Result<C::Fruit> {
+        self.inner.merge_fruits(fruits)
+    }
+
+    fn collect_segment(
+        &self,
+        weight: &dyn Weight,
+        segment_ord: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<<C::Child as SegmentCollector>::Fruit> {
+        if self.allowed.is_none_or(|allowed| allowed.contains(&segment_ord)) {
+            self.inner.collect_segment(weight, segment_ord, segment)
+        } else {
+            Ok(self.inner.for_segment(segment_ord, segment)?.harvest())
+        }
+    }
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Score multiplier for a record indexed at `indexed_at`: 1 when new, halving
+/// with every `half_life` of age. Records without a timestamp count as new.
+fn recency_decay(indexed_at: Option<DateTime>, now_secs: i64, half_life: Duration) -> f32 {
+    let Some(indexed_at) = indexed_at else {
+        return 1.0;
+    };
+    let age = (now_secs - indexed_at.into_timestamp_secs()).max(0) as f64;
+    0.5f64.powf(age / half_life.as_secs_f64().max(1.0)) as f32
+}
+
+/// Per-request switches for optional, off-by-default response extras
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// Include byte offsets of matched tokens in TEXT fields for client-side highlighting
+    pub match_offsets: bool,
+    /// Include bounded highlighted fragments of the matched TEXT fields
+    pub snippets: bool,
+    /// Drop scored matches below this relevance score (no threshold when None)
+    pub min_score: Option<f32>,
+    /// Phrase vs token matching for TEXT clauses
+    pub phrase_mode: PhraseMode,
+    /// Maximum results to return (10,000 when None)
+    pub limit: Option<usize>,
+    /// Report the executed Tantivy query structure
+    pub debug_query: bool,
+    /// Summarize the returned documents (see `result_stats`)
+    pub stats: bool,
+    /// Count the returned documents matching each queried field
+    pub field_match_counts: bool,
+    /// Total the stored bytes of the returned documents per field
+    pub stored_bytes: bool,
+    /// Leave fields with empty values out of result records instead of
+    /// returning them as `""`
+    pub omit_empty: bool,
+    /// Label each result with how it was found (`found_via`): `direct`,
+    /// or for fan-out rows `key_linked` / `alt_linked`
+    pub found_via: bool,
+    /// Add each result's `source_row`: the line of the source CSV it was
+    /// indexed from (null for bulk-indexed records and older indexes)
+    pub source_row: bool,
+    /// Report `results_hash` (see `results_hash`)
+    pub results_hash: bool,
+    /// Only return documents indexed strictly after this watermark (the
+    /// `indexed_at` of the newest record a polling client has seen)
+    pub indexed_since: Option<DateTime>,
+    /// Order results by these fields, the first deciding and the rest breaking
+    /// ties, instead of by relevance (relevance order when empty)
+    pub sort: Vec<SortKey>,
+    /// Fields whose combined values identify duplicates; only the best-ranked
+    /// result per key is returned (no deduplication when empty)
+    pub dedup_key: Vec<String>,
+    /// Scale relevance scores by `0.5^(age / half-life)`, age measured from
+    /// each record's `indexed_at`, so newer records rank higher (no recency
+    /// boost when None)
+    pub recency_half_life: Option<Duration>,
+}
+
+/// One level of a result ordering
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+}
+
+/// How a fan-out reached a row. Unsorted fan-out results come in this
+/// order: direct matches first, then their identity-key siblings, then rows
+/// holding the number as alt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FanoutTier {
+    /// Holds the searched value itself
+    Direct,
+    /// Shares an identity key with a direct match
+    KeyLinked,
+    /// Holds the searched mobile as its alt number
+    AltLinked,
+}
+
+impl FanoutTier {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FanoutTier::Direct => "direct",
+            FanoutTier::KeyLinked => "key_linked",
+            FanoutTier::AltLinked => "alt_linked",
+        }
+    }
+}
+
+/// Fan-out rows ordered by tier, then index order so pages are stable
+fn tier_order(addresses: HashSet<DocAddress>, tiers: &HashMap<DocAddress, FanoutTier>) -> Vec<DocAddress> {
+    let mut addresses: Vec<DocAddress> = addresses.into_iter().collect();
+    addresses.sort_by_key(|addr| (tiers.get(addr).copied(), *addr));
+    addresses
+}
+
+/// Fields linked to a row's identity key in fan-out graphs
+pub const GRAPH_LINK_FIELDS: &[&str] = &["mobile", "alt"];
+
+/// Node kind of the numbers in `GRAPH_LINK_FIELDS`
+const GRAPH_PHONE_NODE: &str = "phone";
+
+/// Identity graph of a fan-out: nodes are `kind:value` ids, edges join an
+/// identity key to a linked number through one field, with the number of
+/// rows carrying both
+#[derive(Debug, Default)]
+pub struct FanoutGraph {
+    pub nodes: BTreeMap<String, (String, String)>, // id -> (kind, value)
+    pub edges: BTreeMap<(String, String, String), usize>, // (key node, phone node, field) -> rows
+}
+
+impl FanoutGraph {
+    fn add_node(&mut self, field: &str, value: &str) -> String {
+        let id = format!("{}:{}", field, value);
+        self.nodes.entry(id.clone()).or_insert_with(|| (field.to_string(), value.to_string()));
+        id
+    }
+
+    /// Neighbors of every node, in both directions
+    pub fn adjacency(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut adjacency: BTreeMap<&str, Vec<&str>> = self.nodes.keys().map(|id| (id.as_str(), Vec::new())).collect();
+        for (from, to, _) in self.edges.keys() {
+            adjacency.entry(from).or_default().push(to);
+            adjacency.entry(to).or_default().push(from);
+        }
+        // A number can be both mobile and alt of one key
+        for neighbors in adjacency.values_mut() {
+            neighbors.sort_unstable();
+            neighbors.dedup();
+        }
+        adjacency
+    }
+}
+
+/// Search results with timing information
+#[derive(Debug)]
+pub struct SearchResults {
+    pub results: Vec<serde_json::Value>,
+    pub total_matches: usize,
+    pub results_returned: usize,
+    /// Phrase matching found nothing and the token AND query was used instead
+    pub phrase_fallback: bool,
+    /// Fields of clauses ignored for having nothing searchable in their value
+    pub dropped_clauses: Vec<String>,
+    /// The query as it ran, after normalization and dropping clauses
+    /// (see `CustomQueryParser::to_query_string`)
+    pub executed_query: String,
+    /// Why nothing was returned, when nothing was: `no_match`,
+    /// `all_tokens_filtered` or `empty_value` (a clause's value had nothing
+    /// searchable and was ignored), `below_min_score`, `unreadable_documents`
+    /// or `limit` (matches exist but the limit was 0)
+    pub zero_reason: Option<&'static str>,
+    /// `Debug` form of the executed query when `debug_query` was requested
+    pub debug_query: Option<String>,
+    /// Summary of the returned documents when `stats` was requested
+    pub stats: Option<serde_json::Value>,
+    /// Results collapsed as duplicates of a better-ranked one, when a dedup key was given
+    pub duplicates_removed: Option<usize>,
+    /// Results left out because their document failed to read, when
+    /// `skip_failed_docs` is on
+    pub failed_retrievals: Option<usize>,
+    /// Returned documents matching each queried field, when requested
+    pub field_match_counts: Option<BTreeMap<String, usize>>,
+    /// Stored bytes of the returned documents per field, when requested
+    pub stored_bytes: Option<BTreeMap<String, u64>>,
+    /// Opstamp of the generation the results came from
+    pub index_version: u64,
+    /// Hash of every match's identity key, when requested and there are
+    /// at most `RESULTS_HASH_MAX_MATCHES` matches
+    pub results_hash: Option<String>,
+    pub query_parse_time_ms: f64,
+    pub search_execution_time_ms: f64,
+    pub document_retrieval_time_ms: f64,
+    pub total_time_ms: f64,
+}
+
+/// Records surrounding one master_id, grouped by how they relate to it
+#[derive(Debug)]
+pub struct ContextResults {
+    pub matches: Vec<serde_json::Value>,
+    pub siblings: Vec<serde_json::Value>,
+    pub neighbors: Vec<serde_json::Value>,
+}
+
+/// Resolve the identity key field, which must be indexed untokenized so a
+/// key value is a single exact term
+pub fn exact_key_field(schema: &tantivy::schema::Schema, name: &str) -> Result<Field> {
+    let field = schema.get_field(name)
+        .map_err(|_| anyhow::anyhow!("Key field '{}' is not in the index schema", name))?;
+    match schema.get_field_entry(field).field_type() {
+        FieldType::Str(options) if options.get_indexing_options().is_some_and(|o| o.tokenizer() == "raw") => Ok(field),
+        _ => Err(anyhow::anyhow!("Key field '{}' must be an exact-match (STRING) field", name)),
+    }
+}
+
+/// Values reported per FAST field in `result_stats`
+const STATS_TOP_VALUES: usize = 10;
+
+/// Sort documents by the `(field, descending)` keys in turn. Text compares
+/// in its exact-match form (case and spacing ignored), dates by time.
+/// Documents missing a value sort after those having it in either direction,
+/// and full ties keep their relevance order.
+fn sort_documents(
+    docs: Vec<TantivyDocument>,
+    addresses: Vec<DocAddress>,
+    keys: &[(Field, bool)],
+) -> (Vec<TantivyDocument>, Vec<DocAddress>) {
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    enum SortValue {
+        Date(i64),
+        Text(String),
+    }
+    let sort_value = |doc: &TantivyDocument, field: Field| -> Option<SortValue> {
+        let value = doc.get_first(field)?;
+        if let Some(date) = Value::as_datetime(&value) {
+            return Some(SortValue::Date(date.into_timestamp_micros()));
+        }
+        Value::as_str(&value)
+            .map(normalize_exact_value)
+            .filter(|text| !text.is_empty())
+            .map(SortValue::Text)
+    };
+
+    let mut rows: Vec<(Vec<Option<SortValue>>, TantivyDocument, DocAddress)> = docs.into_iter()
+        .zip(addresses)
+        .map(|(doc, addr)| (keys.iter().map(|(field, _)| sort_value(&doc, *field)).collect(), doc, addr))
+        .collect();
+    // Stable, so equal keys keep the incoming (relevance) order
+    rows.sort_by(|a, b| {
+        keys.iter().zip(a.0.iter().zip(&b.0))
+            .map(|((_, descending), pair)| match pair {
+                (Some(x), Some(y)) if *descending => y.cmp(x),
+                (Some(x), Some(y)) => x.cmp(y),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    rows.into_iter().map(|(_, doc, addr)| (doc, addr)).unzip()
+}
+
+/// Keep the first (best-ranked) of the documents sharing the same values of
+/// `key` fields, compared in their exact-match form (case and spacing
+/// ignored). A document with any key field empty is never collapsed: a
+/// missing mobile says nothing about two same-named people being one.
+fn dedup_documents(
+    docs: Vec<TantivyDocument>,
+    addresses: Vec<DocAddress>,
+    key: &[Field],
+) -> (Vec<TantivyDocument>, Vec<DocAddress>) {
+    let mut seen: HashSet<Vec<String>> = HashSet::with_capacity(docs.len());
+    docs.into_iter()
+        .zip(addresses)
+        .filter(|(doc, _)| {
+            let values: Vec<String> = key.iter()
+                .map(|field| {
+                    doc.get_first(*field)
+                        .and_then(|v| Value::as_str(&v))
+                        .map(normalize_exact_value)
+                        .unwrap_or_default()
+                })
+                .collect();
+            values.iter().any(String::is_empty) || seen.insert(values)
+        })
+        .unzip()
+}
+
+/// Byte offsets `[start, end]` of query tokens inside the stored TEXT values
+/// of `doc`, keyed by field. Both sides go through the field's own
+/// tokenizer, so offsets line up with what the index actually matched.
+pub fn match_offsets(
+    index: &Index,
+    query_parser: &CustomQueryParser,
+    parsed_query: &ParsedQuery,
+    doc: &TantivyDocument,
+) -> Result<BTreeMap<String, Vec<[usize; 2]>>> {
+    let mut query_tokens: HashMap<&str, HashSet<String>> = HashMap::new();
+    for clause in &parsed_query.clauses {
+        if !query_parser.is_text_field(&clause.field) {
+            continue;
+        }
+        let field = query_parser.get_field(&clause.field).unwrap();
+        let mut analyzer = index.tokenizer_for_field(field)?;
+        let tokens = query_tokens.entry(clause.field.as_str()).or_default();
+        analyzer.token_stream(&clause.value).process(&mut |token| {
+            tokens.insert(token.text.clone());
+        });
+    }
+
+    let mut offsets = BTreeMap::new();
+    for (field_name, tokens) in &query_tokens {
+        let field = query_parser.get_field(field_name).unwrap();
+        let Some(stored) = doc.get_first(field).and_then(|v| Value::as_str(&v)) else {
+            continue;
+        };
+        let mut analyzer = index.tokenizer_for_field(field)?;
+        let mut spans: Vec<[usize; 2]> = Vec::new();
+        analyzer.token_stream(stored).process(&mut |token| {
+            if tokens.contains(&token.text) {
+                spans.push([token.offset_from, token.offset_to]);
+            }
+        });
+        if !spans.is_empty() {
+            offsets.insert(field_name.to_string(), spans);
+        }
+    }
+
+    Ok(offsets)
+}
+
+/// `text` with each matched byte span (sorted, as `match_offsets` gives
+/// them) wrapped in `open` and `close`
+pub fn mark_matches(text: &str, spans: &[[usize; 2]], open: &str, close: &str) -> String {
+    let mut marked = String::with_capacity(text.len() + spans.len() * (open.len() + close.len()));
+    let mut pos = 0;
+    for &[from, to] in spans {
+        let from = from.max(pos);
+        if from >= to || to > text.len() {
+            continue;
+        }
+        marked.push_str(&text[pos..from]);
+        marked.push_str(open);
+        marked.push_str(&text[from..to]);
+        marked.push_str(close);
+        pos = to;
+    }
+    marked.push_str(&text[pos..]);
+    marked
+}
+
+/// Cut `text` into at most `max_fragments` fragments of at most `max_chars`
+/// characters around the matched byte `spans` (sorted, as tokenizers emit
+/// them). Matches are wrapped in `<b>` tags and the text HTML-escaped; an
+/// ellipsis marks where a fragment cuts the value.
+fn snippet_fragments(text: &str, spans: &[[usize; 2]], max_chars: usize, max_fragments: usize) -> Vec<String> {
+    // Byte offset of every char boundary, so cuts never split a character
+    let bounds: Vec<usize> = text.char_indices().map(|(i, _)| i).chain([text.len()]).collect();
+    let total_chars = bounds.len() - 1;
+    let char_index = |byte: usize| bounds.partition_point(|&b| b < byte);
+
+    let mut fragments = Vec::new();
+    let mut next = 0; // first span not shown yet
+    let mut shown_until = 0; // char index where the previous fragment ended
+    while next < spans.len() && fragments.len() < max_fragments && max_chars > 0 {
+        // Some context before the first match, more when the value ends
+        // early, without repeating shown text
+        let start = char_index(spans[next][0])
+            .saturating_sub(max_chars / 4)
+            .min(total_chars.saturating_sub(max_chars))
+            .max(shown_until);
+        let mut end = (start + max_chars).min(total_chars);
+        let (start_byte, mut end_byte) = (bounds[start], bounds[end]);
+
+        let mut fragment = String::new();
+        if start > 0 {
+            fragment.push('…');
+        }
+        let mut pos = start_byte;
+        while next < spans.len() && spans[next][0] < end_byte {
+            // A match that doesn't fit starts the next fragment instead
+            if spans[next][1] > end_byte && pos > start_byte {
+                end_byte = spans[next][0];
+                end = char_index(end_byte);
+                break;
+            }
+            let from = spans[next][0].max(pos);
+            let to = spans[next][1].min(end_byte);
+            if from < to {
+                push_escaped(&mut fragment, &text[pos..from]);
+                fragment.push_str("<b>");
+                push_escaped(&mut fragment, &text[from..to]);
+                fragment.push_str("</b>");
+                pos = to;
+            }
+            next += 1;
+        }
+        push_escaped(&mut fragment, &text[pos..end_byte]);
+        if end < total_chars {
+            fragment.push('…');
+        }
+        fragments.push(fragment);
+        shown_until = end;
+    }
+    fragments
+}
+
+fn push_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Approximate stored size of documents per field: the byte length of text
+/// values and 8 bytes for anything else, before doc store compression
+fn stored_bytes(docs: &[TantivyDocument], schema: &tantivy::schema::Schema) -> BTreeMap<String, u64> {
+    let mut bytes: BTreeMap<String, u64> = BTreeMap::new();
+    for doc in docs {
+        for (field, value) in doc.field_values() {
+            let entry = schema.get_field_entry(field);
+            if !entry.is_stored() {
+                continue;
+            }
+            let len = Value::as_str(&value).map_or(8, str::len) as u64;
+            *bytes.entry(entry.name().to_string()).or_default() += len;
+        }
+    }
+    bytes
+}
+
+/// Summary of the returned documents: how many have each stored text field
+/// filled in, and the most frequent values of each FAST text field. Computed
+/// over the retrieved results only (up to `limit`), not every match.
+fn result_stats(docs: &[TantivyDocument], schema: &tantivy::schema::Schema) -> serde_json::Value {
+    let mut non_empty = serde_json::Map::new();
+    let mut top_values = serde_json::Map::new();
+
+    for (field, entry) in schema.fields() {
+        if !entry.is_stored() || !matches!(entry.field_type(), FieldType::Str(_)) {
+            continue;
+        }
+        let values: Vec<&str> = docs.iter()
+            .filter_map(|doc| doc.get_first(field).and_then(|v| Value::as_str(&v)))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .collect();
+        non_empty.insert(entry.name().to_string(), json!(values.len()));
+
+        if entry.is_fast() {
+            let mut counts: HashMap<&str, usize> = HashMap::new();
+            for value in &values {
+                *counts.entry(value).or_default() += 1;
+            }
+            let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+            let top: Vec<_> = counts.into_iter()
+                .take(STATS_TOP_VALUES)
+                .map(|(value, count)| json!({"value": value, "count": count}))
+                .collect();
+            top_values.insert(entry.name().to_string(), json!(top));
+        }
+    }
+
+    json!({
+        "documents": docs.len(),
+        "non_empty": non_empty,
+        "top_values": top_values,
+    })
+}
+
+/// Outcome of `SearchService::reload`, carrying commit opstamps
+#[derive(Debug)]
+pub enum ReloadStatus {
+    /// Now serving this commit
+    Reloaded(u64),
+    /// Already serving the latest commit
+    Unchanged(u64),
+    /// The latest commit isn't marked ready (a build is in progress)
+    NotReady { committed: u64, ready: Option<u64> },
+}
+
+/// Records of a master_id batch lookup
+#[derive(Debug)]
+pub struct RecordLookup {
+    pub records: BTreeMap<String, Vec<serde_json::Value>>,
+    pub not_found: Vec<String>,
+}
+
+/// CSV line a document was indexed from, when it was recorded
+fn source_row(doc: &TantivyDocument, schema: &tantivy::schema::Schema) -> Option<u64> {
+    let field = schema.get_field("source_row").ok()?;
+    doc.get_first(field).and_then(|v| Value::as_u64(&v))
+}
+
+/// Convert TantivyDocument to JSON format
+fn document_to_json(doc: &TantivyDocument, schema: &tantivy::schema::Schema) -> Result<String> {
+    let master_id_field = schema.get_field("master_id").unwrap();
+    let mobile_field = schema.get_field("mobile").unwrap();
+    let alt_field = schema.get_field("alt").unwrap();
     let name_field = schema.get_field("name").unwrap();
     let fname_field = schema.get_field("fname").unwrap();
     let address_field = schema.get_field("address").unwrap();
     let email_field = schema.get_field("email").unwrap();
+    let indexed_at_field = schema.get_field("indexed_at").ok();
 
     let extract_values = |field: tantivy::schema::Field| -> Vec<String> {
         doc.get_all(field)
@@ -228,7 +2320,7 @@ fn document_to_json(doc: &TantivyDocument, schema: &tantivy::schema::Schema) ->
             .collect()
     };
 
-    let json_obj = json!({
+    let mut json_obj = json!({
         "master_id": extract_values(master_id_field).first().cloned().unwrap_or_default(),
         "mobile": extract_values(mobile_field).first().cloned().unwrap_or_default(),
         "alt": extract_values(alt_field).first().cloned().unwrap_or_default(),
@@ -236,7 +2328,1121 @@ fn document_to_json(doc: &TantivyDocument, schema: &tantivy::schema::Schema) ->
         "fname": extract_values(fname_field).first().cloned().unwrap_or_default(),
         "address": extract_values(address_field).first().cloned().unwrap_or_default(),
         "email": extract_values(email_field).first().cloned().unwrap_or_default(),
+        "indexed_at": indexed_at_field
+            .and_then(|field| doc.get_first(field))
+            .and_then(|v| Value::as_datetime(&v))
+            .and_then(|dt| dt.into_utc().format(&Rfc3339).ok())
+            .unwrap_or_default(),
     });
 
+    // Stored-only fields, for indexes built with them
+    for name in STORED_ONLY_FIELDS {
+        if let Ok(field) = schema.get_field(name) {
+            json_obj[*name] = json!(extract_values(field).first().cloned().unwrap_or_default());
+        }
+    }
+
+    // Extra CSV columns the index was built with
+    for (field, entry) in schema.fields() {
+        if entry.is_stored() && !is_builtin_field(entry.name()) {
+            json_obj[entry.name()] = json!(extract_values(field).first().cloned().unwrap_or_default());
+        }
+    }
+
     Ok(serde_json::to_string(&json_obj)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::{append_index, build_index, IndexOptions};
+    use crate::test_support::{index_batches, write_csv};
+    use tempfile::TempDir;
+
+
+    fn ids(results: &SearchResults) -> Vec<&str> {
+        results.results.iter().map(|r| r["master_id"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn threaded_segment_search_matches_single_threaded() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[
+            &["a1,9800000001,ravi,ravi kumar,,,delhi", "a2,9800000002,amit,amit shah,,,pune"],
+            &["b1,9800000003,ravi,ravi verma,,,delhi", "b2,9800000004,sita,sita ram,,,delhi"],
+            &["c1,9800000005,ravi,ravi kumar,,,mumbai"],
+        ]);
+        let single = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let threaded = SearchService::new(&index_dir, SearchConfig { search_threads: 4, ..SearchConfig::default() }).unwrap();
+        assert_eq!(threaded.searcher().unwrap().segment_readers().len(), 3);
+
+        for query in ["address:delhi", "name:ravi", "name:ravi AND address:delhi", "fname:ravi"] {
+            let expected = single.search(query, &SearchOptions::default()).unwrap();
+            let actual = threaded.search(query, &SearchOptions::default()).unwrap();
+            assert!(expected.total_matches > 1, "{query}");
+            // Hits are collected into a set, so compare them in sorted order
+            let (mut actual_ids, mut expected_ids) = (ids(&actual), ids(&expected));
+            actual_ids.sort();
+            expected_ids.sort();
+            assert_eq!(actual_ids, expected_ids, "{query}");
+            assert_eq!(actual.total_matches, expected.total_matches, "{query}");
+        }
+    }
+
+    /// Median of one server-reported timing of `query` over `rounds` runs,
+    /// after a few warm-up runs
+    fn median_ms(
+        service: &SearchService,
+        query: &str,
+        options: &SearchOptions,
+        rounds: usize,
+        timing: fn(&SearchResults) -> f64,
+    ) -> f64 {
+        for _ in 0..3 {
+            service.search(query, options).unwrap();
+        }
+        let mut times: Vec<f64> = (0..rounds)
+            .map(|_| timing(&service.search(query, options).unwrap()))
+            .collect();
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        times[rounds / 2]
+    }
+
+    /// 800k-row index in 4 segments for the timing tests: 600k rows in
+    /// delhi, names of two words w0..w388 so `name: w58` is broad and
+    /// `name: w58 w380` selective
+    fn timing_index(dir: &TempDir) -> String {
+        let batches: Vec<Vec<String>> = (0..4)
+            .map(|batch| {
+                (batch * 200_000..(batch + 1) * 200_000)
+                    .map(|i| {
+                        let address = if i % 4 == 0 { "pune" } else { "delhi" };
+                        format!("m{i},98{i:08},f{i},w{} w{},,,{address}", i % 227, i % 389)
+                    })
+                    .collect()
+            })
+            .collect();
+        let batches: Vec<Vec<&str>> = batches.iter().map(|rows| rows.iter().map(String::as_str).collect()).collect();
+        let batches: Vec<&[&str]> = batches.iter().map(Vec::as_slice).collect();
+        index_batches(dir, &batches)
+    }
+
+    /// Reproduces the SEARCH_THREADS figures: one broad and two selective
+    /// queries over `timing_index`. On a single core the pool only adds
+    /// dispatch overhead (6.59 vs 6.68 ms, 0.20 vs 0.34 ms, 0.19 vs
+    /// 0.29 ms), which is why the default stays at 1. Run with
+    /// `cargo test --release -- --ignored --nocapture search_threads_timing`
+    #[test]
+    #[ignore]
+    fn search_threads_timing() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = timing_index(&dir);
+        let options = SearchOptions::default();
+        let execution = |results: &SearchResults| results.search_execution_time_ms;
+
+        let single = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let threaded = SearchService::new(&index_dir, SearchConfig { search_threads: 4, ..SearchConfig::default() }).unwrap();
+        println!("{:<16} {:>8} {:>12} {:>12}", "query", "matches", "threads=1", "threads=4");
+        for query in ["address: delhi", "name: w58", "name: w58 w380"] {
+            let matches = single.search(query, &options).unwrap().total_matches;
+            println!(
+                "{:<16} {:>8} {:>9.2} ms {:>9.2} ms",
+                query,
+                matches,
+                median_ms(&single, query, &options, 30, execution),
+                median_ms(&threaded, query, &options, 30, execution),
+            );
+        }
+    }
+
+    /// Reproduces the batched retrieval figures over `timing_index`: with the
+    /// block cache, store order is on par with rank order (13.3 vs 12.6 ms
+    /// for 10000 rows, 4.2 vs 4.3 ms for 1000), while batching without the
+    /// cache is up to 3.5x slower (43.9 ms) since every read decompresses
+    /// its block again. Run with
+    /// `cargo test --release -- --ignored --nocapture batched_retrieval_timing`
+    #[test]
+    #[ignore]
+    fn batched_retrieval_timing() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = timing_index(&dir);
+        let retrieval = |results: &SearchResults| results.document_retrieval_time_ms;
+
+        let services: Vec<SearchService> = [
+            SearchConfig { batched_retrieval: false, ..SearchConfig::default() },
+            SearchConfig { doc_store_cache_blocks: 0, ..SearchConfig::default() },
+            SearchConfig::default(),
+        ]
+        .into_iter()
+        .map(|config| SearchService::new(&index_dir, config).unwrap())
+        .collect();
+        println!("{:<20} {:>8} {:>12} {:>20} {:>12}", "query", "returned", "unbatched", "batched, no cache", "batched");
+        for (query, limit) in [("address: delhi", 10_000), ("name: w58", 10_000), ("name: w58", 1000)] {
+            let options = SearchOptions { limit: Some(limit), ..SearchOptions::default() };
+            let returned = services[0].search(query, &options).unwrap().results_returned;
+            let times: Vec<f64> = services.iter().map(|service| median_ms(service, query, &options, 20, retrieval)).collect();
+            println!(
+                "{:<20} {:>8} {:>9.2} ms {:>17.2} ms {:>9.2} ms",
+                format!("{query} ({limit})"),
+                returned,
+                times[0],
+                times[1],
+                times[2],
+            );
+        }
+    }
+
+    /// Reproduces the point-lookup figures behind dropping the fast path:
+    /// 30k pseudo-random master_id lookups over a 200k-row index, timing the
+    /// general parse, build and execute against a hand-made term query (2.2
+    /// vs 1.5 us) and the whole search around them (about 14.6 us). The parse
+    /// and build are all a fast path could skip. Run with
+    /// `cargo test --release -- --ignored --nocapture point_lookup_timing`
+    #[test]
+    #[ignore]
+    fn point_lookup_timing() {
+        let dir = TempDir::new().unwrap();
+        let rows: Vec<String> = (0..200_000).map(|i| format!("m{i},98{i:08},f{i},w{} w{},,,delhi", i % 227, i % 389)).collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let index_dir = index_batches(&dir, &[&rows]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let searcher = service.searcher().unwrap();
+        let parser = &service.query_parser;
+        let master_id = searcher.schema().get_field("master_id").unwrap();
+
+        // Fixed LCG so every run looks up the same ids
+        let mut seed = 0x2545_f491_u64;
+        let ids: Vec<u64> = (0..30_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+                (seed >> 33) % 200_000
+            })
+            .collect();
+        let per_lookup_us = |run: &dyn Fn(u64)| {
+            let start = Instant::now();
+            for &id in &ids {
+                run(id);
+            }
+            start.elapsed().as_secs_f64() * 1e6 / ids.len() as f64
+        };
+
+        let general = per_lookup_us(&|id| {
+            let query = parser.build_query(&parser.parse(&format!("master_id:m{id}")).unwrap()).unwrap();
+            assert_eq!(searcher.search(&*query, &TopDocs::with_limit(10)).unwrap().len(), 1);
+        });
+        let direct = per_lookup_us(&|id| {
+            let query = TermQuery::new(Term::from_field_text(master_id, &format!("m{id}")), IndexRecordOption::Basic);
+            assert_eq!(searcher.search(&query, &TopDocs::with_limit(10)).unwrap().len(), 1);
+        });
+        let whole = per_lookup_us(&|id| {
+            assert_eq!(service.search(&format!("master_id:m{id}"), &SearchOptions::default()).unwrap().total_matches, 1);
+        });
+        println!("parse + build + execute: {general:.2} us");
+        println!("direct term query:       {direct:.2} us");
+        println!("whole search:            {whole:.2} us");
+    }
+
+    #[test]
+    fn match_offsets_point_at_the_matched_tokens() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["m1,9800000001,zoë,Zoë Kumar,,,\"12, MG Road, New Delhi\""]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+
+        let options = SearchOptions { match_offsets: true, ..SearchOptions::default() };
+        let results = service.search("name:kumar AND address:DELHI", &options).unwrap();
+        let result = &results.results[0];
+        let offsets = &result["match_offsets"];
+        assert_eq!(offsets["name"], json!([[5, 10]]));
+        for (field, expected) in [("name", "Kumar"), ("address", "Delhi")] {
+            let stored = result[field].as_str().unwrap();
+            for span in offsets[field].as_array().unwrap() {
+                let (from, to) = (span[0].as_u64().unwrap() as usize, span[1].as_u64().unwrap() as usize);
+                assert_eq!(&stored[from..to], expected);
+            }
+        }
+        // STRING clauses aren't tokenized, so they have no offsets
+        let results = service.search("mobile:9800000001", &options).unwrap();
+        assert_eq!(results.results[0]["match_offsets"], json!({}));
+
+        // Off by default
+        let results = service.search("name:kumar", &SearchOptions::default()).unwrap();
+        assert!(results.results[0].get("match_offsets").is_none());
+    }
+
+    #[test]
+    fn stored_only_fields_are_returned_but_not_searchable() {
+        let dir = TempDir::new().unwrap();
+        let csv = dir.path().join("notes.csv");
+        std::fs::write(&csv, "master_id,mobile,fname,name,alt,email,address,notes,source_system\n\
+                              m1,9800000001,ravi,ravi kumar,,,delhi,called twice,crm\n").unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(csv.to_str().unwrap(), &index_dir, &IndexOptions::default()).unwrap();
+        let schema = Index::open_in_dir(&index_dir).unwrap().schema();
+        assert!(!schema.get_field_entry(schema.get_field("notes").unwrap()).is_indexed());
+
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let results = service.search("name:ravi", &SearchOptions::default()).unwrap();
+        assert_eq!(results.results[0]["notes"], "called twice");
+        assert_eq!(results.results[0]["source_system"], "crm");
+
+        for query in ["notes:called", "name:ravi AND source_system:crm"] {
+            let err = service.search(query, &SearchOptions::default()).unwrap_err();
+            assert_eq!(err.downcast_ref::<QueryError>().unwrap().code, "FIELD_NOT_SEARCHABLE", "{query}");
+        }
+    }
+
+    #[test]
+    fn min_score_trims_the_weak_tail() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,,ravi kumar singh,,,delhi",
+            "m2,9800000002,,ravi kumar,,,delhi",
+            "m3,9800000003,,ravi,,,delhi",
+            "m4,9800000004,,amit singh,,,delhi",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let query_str = "name:ravi OR name:kumar OR name:singh";
+
+        // Matches with the scores the search ranks them by
+        let query = service.query_parser.build_query(&service.query_parser.parse(query_str).unwrap()).unwrap();
+        let searcher = service.searcher().unwrap();
+        let master_id = service.query_parser.get_field("master_id").unwrap();
+        let scored: Vec<(f32, String)> = searcher.search(query.as_ref(), &TopDocs::with_limit(10)).unwrap()
+            .into_iter()
+            .map(|(score, addr)| {
+                let doc: TantivyDocument = searcher.doc(addr).unwrap();
+                (score, doc.get_first(master_id).and_then(|v| Value::as_str(&v)).unwrap().to_string())
+            })
+            .collect();
+        assert_eq!(scored.len(), 4);
+        // Each threshold below trims a different tail
+        assert!(scored.windows(2).all(|pair| pair[0].0 > pair[1].0));
+
+        let search = |min_score: Option<f32>| {
+            let results = service.search(query_str, &SearchOptions { min_score, ..SearchOptions::default() }).unwrap();
+            let mut ids: Vec<String> = results.results.iter().map(|r| r["master_id"].as_str().unwrap().to_string()).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(search(None).len(), 4);
+        for (threshold, _) in &scored {
+            let mut expected: Vec<String> = scored.iter().filter(|(score, _)| score >= threshold).map(|(_, id)| id.clone()).collect();
+            expected.sort();
+            assert_eq!(search(Some(*threshold)), expected);
+        }
+        assert_eq!(search(Some(scored[0].0)), [scored[0].1.clone()]);
+        assert!(search(Some(scored[0].0 + 1.0)).is_empty());
+    }
+
+    #[test]
+    fn context_labels_matches_siblings_and_neighbors() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[
+            &[
+                "m1,9800000001,ravi,ravi kumar,,,delhi",
+                "m0,9800000010,amit,amit shah,,,pune",
+                "p7,9800000001,ravi,r kumar,,,delhi",
+                "q9,9800000009,sita,sita ram,9800000001,,agra",
+            ],
+            &["m1,9800000002,ravi,ravi kumar,,,noida", "m2,9800000020,gita,gita das,,,goa", "m3,9800000030,john,john doe,,,goa"],
+        ]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let key = |record: &serde_json::Value| record["master_id"].as_str().unwrap().to_string();
+        let sorted = |records: &[serde_json::Value]| {
+            let mut keys: Vec<String> = records.iter().map(key).collect();
+            keys.sort();
+            keys
+        };
+
+        let context = service.search_context("m1", 1).unwrap();
+        assert_eq!(sorted(&context.matches), ["m1", "m1"]);
+        // Linked by the shared mobile and by holding it as alt
+        assert_eq!(sorted(&context.siblings), ["p7", "q9"]);
+        let neighbors: Vec<(String, &str, usize)> = context.neighbors.iter()
+            .map(|n| (key(n), n["position"].as_str().unwrap(), n["records"].as_array().unwrap().len()))
+            .collect();
+        assert_eq!(neighbors, [("m0".to_string(), "before", 1), ("m2".to_string(), "after", 1)]);
+
+        let context = service.search_context("m3", 2).unwrap();
+        assert!(context.siblings.is_empty());
+        let positions: Vec<(String, &str)> = context.neighbors.iter().map(|n| (key(n), n["position"].as_str().unwrap())).collect();
+        assert_eq!(positions, [("m1".to_string(), "before"), ("m2".to_string(), "before"), ("p7".to_string(), "after"), ("q9".to_string(), "after")]);
+    }
+
+    #[test]
+    fn phrase_then_tokens_falls_back_only_when_no_phrase_matches() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m2,9800000002,ravi,kumar singh ravi,,,delhi",
+            "m3,9800000003,ravi,ravi kumar,,,pune",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let search = |query: &str, phrase_mode: PhraseMode| {
+            let results = service.search(query, &SearchOptions { phrase_mode, ..SearchOptions::default() }).unwrap();
+            let mut keys: Vec<String> = ids(&results).into_iter().map(String::from).collect();
+            keys.sort();
+            (keys, results.phrase_fallback)
+        };
+
+        // Some phrase match: kept as is, no broadening
+        assert_eq!(search("name:ravi kumar, address:delhi", PhraseMode::PhraseThenTokens), (vec!["m1".to_string()], false));
+        // Auto uses tokens for a multi-clause query
+        assert_eq!(search("name:ravi kumar, address:delhi", PhraseMode::Auto), (vec!["m1".to_string(), "m2".to_string()], false));
+        // No phrase match: retried as tokens
+        assert_eq!(search("name:kumar ravi, address:delhi", PhraseMode::PhraseThenTokens), (vec!["m1".to_string(), "m2".to_string()], true));
+        assert_eq!(search("name:kumar ravi, address:delhi", PhraseMode::Phrase), (Vec::new(), false));
+        // Nothing to fall back to
+        assert_eq!(search("name:ravi gupta", PhraseMode::PhraseThenTokens), (Vec::new(), true));
+    }
+
+    #[test]
+    fn fanout_results_are_ranked_by_the_attribute_clauses() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,ravi,ravi kumar singh verma,,,delhi",
+            "m1,9800000002,ravi,ravi kumar singh,,,delhi",
+            "m1,9800000003,amit,amit shah,,,delhi",
+            "m1,9800000004,ravi,ravi,,,delhi",
+            "m2,9800000005,ravi,ravi,,,delhi",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+
+        let results = service.search("mobile:9800000001 AND name:ravi", &SearchOptions::default()).unwrap();
+        let names: Vec<&str> = results.results.iter().map(|r| r["name"].as_str().unwrap()).collect();
+        // All of m1's rows naming ravi, shortest (best BM25) name first; m2 isn't fanned out to
+        assert_eq!(names, ["ravi", "ravi kumar singh", "ravi kumar singh verma"]);
+        assert_eq!(ids(&results), ["m1", "m1", "m1"]);
+        assert_eq!(results.total_matches, 3);
+    }
+
+    #[test]
+    fn reload_refuses_a_commit_not_marked_ready() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["m1,9800000001,ravi,ravi kumar,,,delhi"]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+
+        // A build mid-way: marker cleared, a checkpoint commit made
+        crate::ready::clear_ready(&index_dir).unwrap();
+        let index = Index::open_in_dir(&index_dir).unwrap();
+        register_tokenizers(&index);
+        let mut writer: tantivy::IndexWriter<TantivyDocument> = index.writer(15_000_000).unwrap();
+        let mut doc = TantivyDocument::default();
+        doc.add_text(index.schema().get_field("master_id").unwrap(), "m2");
+        writer.add_document(doc).unwrap();
+        let checkpoint = writer.commit().unwrap();
+
+        let reload = service.reload().unwrap();
+        assert!(matches!(reload, ReloadStatus::NotReady { committed, ready: None } if committed == checkpoint), "{reload:?}");
+        assert!(service.search("master_id:m2", &SearchOptions::default()).unwrap().results.is_empty());
+        // A stale marker from an earlier generation doesn't count either
+        mark_ready(&index_dir, checkpoint - 1).unwrap();
+        assert!(matches!(service.reload().unwrap(), ReloadStatus::NotReady { ready: Some(_), .. }));
+
+        mark_ready(&index_dir, checkpoint).unwrap();
+        assert!(matches!(service.reload().unwrap(), ReloadStatus::Reloaded(opstamp) if opstamp == checkpoint));
+        assert_eq!(ids(&service.search("master_id:m2", &SearchOptions::default()).unwrap()), ["m2"]);
+        assert!(matches!(service.reload().unwrap(), ReloadStatus::Unchanged(_)));
+    }
+
+    #[test]
+    fn a_commit_landing_after_the_ready_check_is_not_swapped_in() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["m1,9800000001,ravi,ravi kumar,,,delhi"]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let index = Index::open_in_dir(&index_dir).unwrap();
+        register_tokenizers(&index);
+        let master_id = index.schema().get_field("master_id").unwrap();
+        let mut writer: tantivy::IndexWriter<TantivyDocument> = index.writer(15_000_000).unwrap();
+        let mut commit = |id: &str| {
+            let mut doc = TantivyDocument::default();
+            doc.add_text(master_id, id);
+            writer.add_document(doc).unwrap();
+            writer.commit().unwrap()
+        };
+
+        // As if a build committed again between reload's marker check and its swap
+        let ready = commit("m2");
+        mark_ready(&index_dir, ready).unwrap();
+        let checked = index.load_metas().unwrap();
+        commit("m3");
+        assert!(!service.swap_reader(&checked).unwrap());
+        assert_ne!(service.index_version(), ready);
+        assert!(service.search("master_id:m2", &SearchOptions::default()).unwrap().results.is_empty());
+
+        // Without the race the checked commit is served
+        let ready = commit("m4");
+        mark_ready(&index_dir, ready).unwrap();
+        assert!(matches!(service.reload().unwrap(), ReloadStatus::Reloaded(opstamp) if opstamp == ready));
+        assert_eq!(ids(&service.search("master_id:m3", &SearchOptions::default()).unwrap()), ["m3"]);
+    }
+
+    #[test]
+    fn debug_query_shows_the_executed_query_structure() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["m1,9800000001,ravi,ravi kumar,,,delhi"]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let debug = |query: &str| {
+            let options = SearchOptions { debug_query: true, ..SearchOptions::default() };
+            service.search(query, &options).unwrap().debug_query.unwrap()
+        };
+        let schema = service.index.schema();
+        let term = |field: &str, text: &str| format!("Term(field={}, type=Str, {:?})", schema.get_field(field).unwrap().field_id(), text);
+
+        let phrase = debug("name:ravi kumar");
+        assert!(phrase.contains(&format!("PhraseQuery {{ field: Field({}), phrase_terms: [(0, {}), (1, {})], slop: 0 }}",
+            schema.get_field("name").unwrap().field_id(), term("name", "ravi"), term("name", "kumar"))), "{phrase}");
+        let and = debug("fname:ravi AND address:delhi");
+        assert_eq!(and.matches("(Must, ").count(), 2, "{and}");
+        assert!(and.contains(&term("fname", "ravi")) && and.contains(&term("address", "delhi")), "{and}");
+        assert_eq!(debug("name=\"ravi kumar\""), format!("TermQuery({})", term("name_exact", "ravi kumar")));
+        assert_eq!(debug("mobile:9800000001"), "MobileFanout(\"9800000001\")");
+
+        assert!(service.search("name:ravi", &SearchOptions::default()).unwrap().debug_query.is_none());
+    }
+
+    #[test]
+    fn stats_count_filled_fields_and_top_fast_values_of_the_results() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,ravi,ravi kumar,,ravi@example.com,delhi",
+            "m1,9800000002,ravi,ravi kumar,9800000009,,delhi",
+            "m2,9800000003,amit,amit shah,,amit@example.com,delhi",
+            "m3,9800000004,neha,neha gupta,,neha@example.com,delhi",
+            "m4,9800000005,sita,sita ram,,sita@example.com,pune",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+
+        let results = service.search("address:delhi", &SearchOptions { stats: true, ..SearchOptions::default() }).unwrap();
+        let stats = results.stats.unwrap();
+        assert_eq!(stats["documents"], 4);
+        assert_eq!(stats["non_empty"]["email"], 3);
+        assert_eq!(stats["non_empty"]["alt"], 1);
+        assert_eq!(stats["non_empty"]["name"], 4);
+        assert_eq!(stats["top_values"]["master_id"], json!([
+            {"value": "m1", "count": 2},
+            {"value": "m2", "count": 1},
+            {"value": "m3", "count": 1},
+        ]));
+        // TEXT fields aren't FAST, so only their fill counts are reported
+        assert!(stats["top_values"].get("address").is_none());
+
+        assert!(service.search("address:delhi", &SearchOptions::default()).unwrap().stats.is_none());
+    }
+
+    #[test]
+    fn fanout_follows_a_configured_key_field() {
+        let dir = TempDir::new().unwrap();
+        // Rows grouped by their alt number as well as by master_id
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,ravi,ravi kumar,9811111111,,delhi",
+            "m2,9800000002,sita,sita kumar,9811111111,,delhi",
+            "m1,9800000003,ravi,ravi kumar,9822222222,,noida",
+            "m3,9800000004,amit,amit shah,9822222222,,pune",
+        ]]);
+        fn sorted_ids(results: &SearchResults) -> Vec<&str> {
+            let mut keys = ids(results);
+            keys.sort();
+            keys
+        }
+
+        let by_master_id = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        assert_eq!(sorted_ids(&by_master_id.search("mobile:9800000001", &SearchOptions::default()).unwrap()), ["m1", "m1"]);
+
+        let by_alt = SearchService::new(&index_dir, SearchConfig { key_field: "alt".to_string(), ..SearchConfig::default() }).unwrap();
+        let results = by_alt.search("mobile:9800000001", &SearchOptions::default()).unwrap();
+        assert_eq!(sorted_ids(&results), ["m1", "m2"]);
+        assert!(results.results.iter().all(|r| r["alt"] == "9811111111"));
+
+        // The key must be a single exact term
+        let err = SearchService::new(&index_dir, SearchConfig { key_field: "address".to_string(), ..SearchConfig::default() }).err().unwrap();
+        assert_eq!(err.to_string(), "Key field 'address' must be an exact-match (STRING) field");
+    }
+
+    #[test]
+    fn email_fanout_links_records_sharing_only_an_email() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,ravi,ravi kumar,,ravi@example.com,delhi",
+            "m1,9800000002,ravi,ravi kumar,,,noida",
+            "m2,9800000003,r,r kumar,,Ravi@Example.com,pune",
+            "m3,9800000004,amit,amit shah,,amit@example.com,agra",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let sorted_ids = |query: &str| {
+            let results = service.search(query, &SearchOptions::default()).unwrap();
+            let mut keys: Vec<String> = ids(&results).into_iter().map(String::from).collect();
+            keys.sort();
+            keys
+        };
+
+        // m2 shares only the address with m1; m1's email-less row comes along by master_id
+        assert_eq!(sorted_ids("email:ravi@example.com"), ["m1", "m1", "m2"]);
+        assert_eq!(sorted_ids("email:RAVI@EXAMPLE.COM"), ["m1", "m1", "m2"]);
+        // A domain is an ordinary search, not a fan-out seed
+        assert_eq!(sorted_ids("email:example.com"), ["m1", "m2", "m3"]);
+    }
+
+    #[test]
+    fn batched_retrieval_returns_the_same_results_in_rank_order() {
+        let dir = TempDir::new().unwrap();
+        let batch = |offset: usize, city: &str| -> Vec<String> {
+            (offset..offset + 300).map(|i| format!("m{i},98{i:08},ravi,ravi {} kumar,,,{city} {}", "x ".repeat(i % 7), i % 3)).collect()
+        };
+        let (first, second) = (batch(0, "delhi"), batch(300, "delhi"));
+        let first: Vec<&str> = first.iter().map(String::as_str).collect();
+        let second: Vec<&str> = second.iter().map(String::as_str).collect();
+        let index_dir = index_batches(&dir, &[&first, &second]);
+
+        let one_by_one = SearchService::new(&index_dir, SearchConfig { batched_retrieval: false, ..SearchConfig::default() }).unwrap();
+        let batched = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let uncached = SearchService::new(&index_dir, SearchConfig { doc_store_cache_blocks: 0, ..SearchConfig::default() }).unwrap();
+        for query in ["address:delhi", "name:kumar", "address:2 OR name:x", "mobile:9800000301"] {
+            let expected = one_by_one.search(query, &SearchOptions::default()).unwrap();
+            assert!(!expected.results.is_empty(), "{query}");
+            for service in [&batched, &uncached] {
+                assert_eq!(service.search(query, &SearchOptions::default()).unwrap().results, expected.results, "{query}");
+            }
+        }
+    }
+
+    #[test]
+    fn field_match_counts_split_an_or_across_its_fields() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m2,9800000002,amit,amit kumar,,,delhi",
+            "m3,9800000003,ravi,ravi shah,,,pune",
+            "m4,9800000004,neha,neha gupta,,,agra",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let options = SearchOptions { field_match_counts: true, ..SearchOptions::default() };
+
+        let results = service.search("fname:ravi OR address:delhi", &options).unwrap();
+        assert_eq!(results.results.len(), 3);
+        let counts = results.field_match_counts.unwrap();
+        assert_eq!(counts, BTreeMap::from([("address".to_string(), 2), ("fname".to_string(), 2)]));
+        // m1 matches both, so the counts overlap by exactly the rows matching both clauses
+        let both = service.search("fname:ravi AND address:delhi", &SearchOptions::default()).unwrap().results.len();
+        assert_eq!(counts.values().sum::<usize>() - both, results.results.len());
+
+        assert!(service.search("fname:ravi OR address:delhi", &SearchOptions::default()).unwrap().field_match_counts.is_none());
+    }
+
+    #[test]
+    fn idle_reader_is_released_and_reopened_on_the_next_search() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["m1,9800000001,ravi,ravi kumar,,,delhi"]]);
+        let config = SearchConfig { reader_idle_timeout: Some(Duration::from_millis(200)), ..SearchConfig::default() };
+        let service = SearchService::new(&index_dir, config).unwrap();
+        let released = || service.reader.reader.read().unwrap().is_none();
+        let wait_for_release = || {
+            let start = Instant::now();
+            while !released() {
+                assert!(start.elapsed() < Duration::from_secs(5), "reader never released");
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        };
+
+        assert_eq!(ids(&service.search("master_id:m1", &SearchOptions::default()).unwrap()), ["m1"]);
+        assert!(!released());
+        wait_for_release();
+        // The next search reopens it
+        assert_eq!(ids(&service.search("master_id:m1", &SearchOptions::default()).unwrap()), ["m1"]);
+        assert!(!released());
+
+        // A generation marked ready while released is what the reopened reader serves
+        wait_for_release();
+        let csv = dir.path().join("more.csv");
+        std::fs::write(&csv, "master_id,mobile,fname,name,alt,email,address\nm2,9800000002,amit,amit shah,,,delhi\n").unwrap();
+        append_index(csv.to_str().unwrap(), &index_dir, &IndexOptions::default()).unwrap();
+        let results = service.search("address:delhi", &SearchOptions::default()).unwrap();
+        assert_eq!(results.total_matches, 2);
+        assert_eq!(results.index_version, ready_opstamp(&index_dir).unwrap().unwrap());
+    }
+
+    #[test]
+    fn parallel_fanout_gives_the_same_union() {
+        let dir = TempDir::new().unwrap();
+        // 40 keys holding the hot mobile, each with two more rows; plus unrelated rows
+        let batch = |segment: usize| -> Vec<String> {
+            (0..20).flat_map(|i| {
+                let key = format!("k{}", segment * 20 + i);
+                [
+                    format!("{key},9800000001,ravi,ravi kumar,,,delhi"),
+                    format!("{key},97{:08},ravi,ravi kumar,,,noida", segment * 20 + i),
+                    format!("{key},96{:08},ravi,ravi kumar,9800000001,,agra", segment * 20 + i),
+                    format!("u{},95{:08},amit,amit shah,,,pune", segment * 20 + i, segment * 20 + i),
+                ]
+            }).collect()
+        };
+        let (first, second) = (batch(0), batch(1));
+        let first: Vec<&str> = first.iter().map(String::as_str).collect();
+        let second: Vec<&str> = second.iter().map(String::as_str).collect();
+        let index_dir = index_batches(&dir, &[&first, &second]);
+
+        let serial = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let parallel = SearchService::new(&index_dir, SearchConfig { fanout_threads: 4, ..SearchConfig::default() }).unwrap();
+        let rows = |service: &SearchService, query: &str| {
+            let results = service.search(query, &SearchOptions::default()).unwrap();
+            let mut rows: Vec<String> = results.results.iter().map(|r| r.to_string()).collect();
+            rows.sort();
+            (results.total_matches, rows)
+        };
+        for query in ["mobile:9800000001", "mobile:9800000001 AND address:noida"] {
+            let expected = rows(&serial, query);
+            assert_eq!(rows(&parallel, query), expected, "{query}");
+        }
+        assert_eq!(rows(&serial, "mobile:9800000001").0, 120);
+    }
+
+    #[test]
+    fn extra_csv_columns_are_queryable_and_returned() {
+        let dir = TempDir::new().unwrap();
+        let csv = dir.path().join("people.csv");
+        std::fs::write(&csv, "master_id,mobile,fname,name,alt,email,address,gender,pincode\n\
+            m1,9800000001,ravi,ravi kumar,,,delhi,Male,110001\n\
+            m2,9800000002,sita,sita kumar,,,delhi,Female,110001\n\
+            m3,9800000003,amit,amit shah,,,pune,Male,411001\n").unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(csv.to_str().unwrap(), &index_dir, &IndexOptions::default()).unwrap();
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        assert!(service.field_names().iter().any(|f| f == "pincode"));
+
+        let results = service.search("gender:female", &SearchOptions::default()).unwrap();
+        assert_eq!(ids(&results), ["m2"]);
+        assert_eq!(results.results[0]["gender"], "Female");
+        assert_eq!(results.results[0]["pincode"], "110001");
+
+        let results = service.search("pincode:110001 AND gender:male", &SearchOptions::default()).unwrap();
+        assert_eq!(ids(&results), ["m1"]);
+        assert_eq!(service.search("pincode:411001 OR name:sita", &SearchOptions::default()).unwrap().total_matches, 2);
+    }
+
+    #[test]
+    fn snippets_of_a_long_matched_field_are_bounded() {
+        let address = format!("{}delhi {}delhi {}delhi {}delhi", "lane ".repeat(40), "road ".repeat(40), "gali ".repeat(40), "marg ".repeat(40));
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[&format!("m1,9800000001,ravi,ravi kumar,,,{}", address)]]);
+        let service = SearchService::new(&index_dir, SearchConfig { snippet_max_chars: 40, snippet_max_fragments: 2, ..SearchConfig::default() }).unwrap();
+
+        let results = service.search("address:delhi", &SearchOptions { snippets: true, ..SearchOptions::default() }).unwrap();
+        let fragments = results.results[0]["snippets"]["address"].as_array().unwrap();
+        assert_eq!(fragments.len(), 2);
+        for fragment in fragments {
+            let fragment = fragment.as_str().unwrap();
+            assert!(fragment.contains("<b>delhi</b>"), "{fragment}");
+            assert!(fragment.starts_with('…') && fragment.ends_with('…'), "{fragment}");
+            let text = fragment.replace("<b>", "").replace("</b>", "").replace('…', "");
+            assert!(text.chars().count() <= 40, "{fragment}");
+        }
+
+        // Short values are shown whole, escaped, without an ellipsis
+        assert_eq!(snippet_fragments("a<b> delhi", &[[5, 10]], 150, 3), ["a&lt;b&gt; <b>delhi</b>"]);
+        assert!(snippet_fragments("delhi", &[[0, 5]], 150, 0).is_empty());
+    }
+
+    #[test]
+    fn warm_up_runs_each_listed_query_once() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["m1,9800000001,ravi,ravi kumar,,,delhi"]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let warmup = dir.path().join("warmup.txt");
+        std::fs::write(&warmup, "# common lookups\nname:ravi\n\n  address:delhi AND name:ravi  \nnmae:ravi\n").unwrap();
+
+        // The query naming an unknown field is reported, not fatal
+        assert_eq!(service.warm_up(warmup.to_str().unwrap()).unwrap(), 3);
+        let err = service.warm_up(dir.path().join("missing.txt").to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().starts_with("Cannot read warmup file"), "{err}");
+    }
+
+    #[test]
+    fn composite_dedup_key_collapses_rows_sharing_every_key_value() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m2,9800000001,ravi,Ravi  Kumar,,,noida",
+            "m3,9800000002,ravi,ravi kumar,,,delhi",
+            "m4,,ravi,ravi kumar,,,pune",
+            "m5,,ravi,ravi kumar,,,agra",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let dedup = |key: &[&str]| SearchOptions { dedup_key: key.iter().map(|f| f.to_string()).collect(), ..SearchOptions::default() };
+
+        let results = service.search("name:ravi kumar", &SearchOptions::default()).unwrap();
+        assert_eq!(results.results.len(), 5);
+        assert_eq!(results.duplicates_removed, None);
+
+        // m1 and m2 share both values once case and spacing are ignored;
+        // rows without a mobile are never collapsed
+        let results = service.search("name:ravi kumar", &dedup(&["name", "mobile"])).unwrap();
+        let mut kept = ids(&results);
+        kept.sort();
+        assert_eq!(results.duplicates_removed, Some(1));
+        assert_eq!(kept.len(), 4);
+        assert!(kept.contains(&"m3") && kept.contains(&"m4") && kept.contains(&"m5"), "{kept:?}");
+
+        let results = service.search("name:ravi kumar", &dedup(&["name"])).unwrap();
+        assert_eq!(results.results.len(), 1);
+        assert_eq!(results.duplicates_removed, Some(4));
+
+        let err = service.search("name:ravi", &dedup(&["name", "phone"])).unwrap_err();
+        assert_eq!(err.downcast::<QueryError>().unwrap().code, "INVALID_DEDUP_KEY");
+    }
+
+    #[test]
+    fn indexed_since_returns_only_rows_appended_after_the_watermark() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["a1,9800000001,ravi,ravi kumar,,,delhi", "a2,9800000002,amit,amit shah,,,delhi"]]);
+        // Stamps have second resolution
+        std::thread::sleep(Duration::from_millis(1100));
+        let csv = write_csv(&dir, "delta.csv", &["b1,9800000001,ravi,ravi verma,,,delhi"]);
+        append_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+
+        let old = service.search("master_id:a1", &SearchOptions::default()).unwrap();
+        let watermark = crate::query_parser::parse_timestamp(old.results[0]["indexed_at"].as_str().unwrap()).unwrap();
+        let delta = SearchOptions { indexed_since: Some(watermark), ..SearchOptions::default() };
+
+        assert_eq!(service.search("address:delhi", &SearchOptions::default()).unwrap().total_matches, 3);
+        assert_eq!(ids(&service.search("address:delhi", &delta).unwrap()), ["b1"]);
+        // Mobile fan-out reaches a1 through the shared number, but it is older
+        assert_eq!(ids(&service.search("mobile:9800000001", &delta).unwrap()), ["b1"]);
+        let later = SearchOptions { indexed_since: Some(DateTime::from_timestamp_secs(watermark.into_timestamp_secs() + 3600)), ..SearchOptions::default() };
+        assert_eq!(service.search("address:delhi", &later).unwrap().total_matches, 0);
+    }
+
+    #[test]
+    fn sort_ties_on_the_first_key_are_broken_by_the_next() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m3,9800000003,ravi,ravi kumar,,,delhi",
+            "m1,9800000001,ravi,Ravi Kumar,,,delhi",
+            "m4,9800000004,amit,amit shah,,,delhi",
+            "m2,9800000002,ravi,ravi  kumar,,,delhi",
+            "m5,9800000005,,,,,delhi",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let sorted = |keys: &[(&str, bool)]| {
+            let sort = keys.iter().map(|(field, descending)| SortKey { field: field.to_string(), descending: *descending }).collect();
+            let results = service.search("address:delhi", &SearchOptions { sort, ..SearchOptions::default() }).unwrap();
+            ids(&results).into_iter().map(String::from).collect::<Vec<_>>()
+        };
+
+        // The three "ravi kumar" spellings tie on name; rows without a name go last
+        assert_eq!(sorted(&[("name", false), ("master_id", false)]), ["m4", "m1", "m2", "m3", "m5"]);
+        assert_eq!(sorted(&[("name", false), ("master_id", true)]), ["m4", "m3", "m2", "m1", "m5"]);
+        assert_eq!(sorted(&[("name", true), ("master_id", false)]), ["m1", "m2", "m3", "m4", "m5"]);
+        assert_eq!(sorted(&[("fname", true), ("master_id", false)]), ["m1", "m2", "m3", "m4", "m5"]);
+    }
+
+    #[test]
+    fn fanout_graph_links_keys_through_shared_numbers() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,ravi,ravi kumar,9800000009,,delhi",
+            "m1,9800000002,ravi,ravi kumar,,,delhi",
+            "m1,9800000002,ravi,ravi kumar,,,noida",
+            "m2,9800000003,sita,sita kumar,9800000001,,delhi",
+            "m3,9800000004,amit,amit shah,,,pune",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+
+        let graph = service.fanout_graph("mobile", "9800000001").unwrap();
+        let edges: Vec<(&str, &str, &str, usize)> = graph.edges.iter()
+            .map(|((source, target, field), rows)| (source.as_str(), target.as_str(), field.as_str(), *rows))
+            .collect();
+        assert_eq!(edges, [
+            ("master_id:m1", "phone:9800000001", "mobile", 1),
+            ("master_id:m1", "phone:9800000002", "mobile", 2),
+            ("master_id:m1", "phone:9800000009", "alt", 1),
+            ("master_id:m2", "phone:9800000001", "alt", 1),
+            ("master_id:m2", "phone:9800000003", "mobile", 1),
+        ]);
+        assert_eq!(graph.nodes["phone:9800000001"], ("phone".to_string(), "9800000001".to_string()));
+        // The seed number is what joins the two keys
+        assert_eq!(graph.adjacency()["phone:9800000001"], ["master_id:m1", "master_id:m2"]);
+        assert!(!graph.nodes.contains_key("master_id:m3"));
+
+        assert_eq!(service.fanout_graph("master_id", "m2").unwrap().edges.len(), 2);
+        let err = service.fanout_graph("name", "ravi").unwrap_err();
+        assert_eq!(err.downcast::<QueryError>().unwrap().code, "INVALID_REQUEST");
+    }
+
+    #[test]
+    fn stored_bytes_grow_with_the_returned_results() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m2,9800000002,amit,amit verma,,,delhi",
+            "m3,9800000003,neha,neha gupta,,,delhi",
+            "m4,9800000004,sita,sita verma,,,delhi",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let stored_bytes = |limit: usize| {
+            let options = SearchOptions { stored_bytes: true, limit: Some(limit), ..SearchOptions::default() };
+            service.search("address:delhi", &options).unwrap().stored_bytes.unwrap()
+        };
+
+        assert!(service.search("address:delhi", &SearchOptions::default()).unwrap().stored_bytes.is_none());
+        let (two, four) = (stored_bytes(2), stored_bytes(4));
+        assert_eq!(two["name"], 2 * 10);
+        assert_eq!(two["mobile"], 2 * 10);
+        assert_eq!(four["address"], 4 * "delhi".len() as u64);
+        // Every row here is the same size, so the total scales with the count
+        assert_eq!(four.values().sum::<u64>(), 2 * two.values().sum::<u64>());
+    }
+
+    #[test]
+    fn unreadable_documents_are_skipped_when_asked() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[
+            &["a1,9800000001,ravi,ravi kumar,,,delhi", "a2,9800000002,ravi,ravi verma,,,delhi"],
+            &["b1,9800000003,ravi,ravi gupta,,,delhi"],
+        ]);
+        // Garble the compressed doc store blocks of the first segment,
+        // leaving its footer intact so the index still opens
+        let searcher = SearchService::new(&index_dir, SearchConfig::default()).unwrap().searcher().unwrap();
+        let segment = searcher.segment_readers().iter().find(|s| s.max_doc() == 2).unwrap().segment_id().uuid_string();
+        let store = std::path::Path::new(&index_dir).join(format!("{}.store", segment));
+        let mut bytes = std::fs::read(&store).unwrap();
+        bytes[..16].fill(0xff);
+        std::fs::write(&store, bytes).unwrap();
+        drop(searcher);
+
+        let strict = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        assert!(strict.search("name:ravi", &SearchOptions::default()).is_err());
+
+        let resilient = SearchService::new(&index_dir, SearchConfig { skip_failed_docs: true, ..SearchConfig::default() }).unwrap();
+        let results = resilient.search("name:ravi", &SearchOptions::default()).unwrap();
+        assert_eq!(results.total_matches, 3);
+        assert_eq!(ids(&results), ["b1"]);
+        assert_eq!(results.failed_retrievals, Some(2));
+        // Profiles compared by /compare skip them the same way
+        assert!(strict.record_profile("a1").is_err());
+        assert!(resilient.record_profile("a1").unwrap().unwrap().name_tokens.is_empty());
+        assert!(resilient.record_profile("b1").unwrap().unwrap().name_tokens.contains("gupta"));
+    }
+
+    #[test]
+    fn result_transform_masks_returned_mobiles_but_not_the_search() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["m1,9876543210,ravi,ravi kumar,9800000001,,delhi"]]);
+        let masked = SearchConfig {
+            result_transform: Arc::new(crate::transform::MaskDigits::new(vec!["mobile".to_string()])),
+            ..SearchConfig::default()
+        };
+        let service = SearchService::new(&index_dir, masked).unwrap();
+
+        let results = service.search("mobile:9876543210", &SearchOptions::default()).unwrap();
+        assert_eq!(results.results[0]["mobile"], "******3210");
+        assert_eq!(results.results[0]["alt"], "9800000001");
+        assert_eq!(service.lookup_records(&["m1".to_string()]).unwrap().records["m1"][0]["mobile"], "******3210");
+    }
+
+    #[test]
+    fn recency_boost_ranks_the_newer_of_identical_records_first() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["old,9800000001,ravi,ravi kumar,,,delhi"]]);
+        // Stamps have second resolution
+        std::thread::sleep(Duration::from_millis(1100));
+        let csv = write_csv(&dir, "new.csv", &["new,9800000002,ravi,ravi kumar,,,delhi"]);
+        append_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+
+        assert_eq!(service.search("name:ravi kumar", &SearchOptions::default()).unwrap().total_matches, 2);
+        let recent = SearchOptions { recency_half_life: Some(Duration::from_secs(1)), ..SearchOptions::default() };
+        let boosted = service.search("name:ravi kumar", &recent).unwrap();
+        assert_eq!(ids(&boosted), ["new", "old"]);
+
+        let day = Duration::from_secs(86_400);
+        assert_eq!(recency_decay(None, 1_000_000, day), 1.0);
+        assert_eq!(recency_decay(Some(DateTime::from_timestamp_secs(1_000_000 - 86_400)), 1_000_000, day), 0.5);
+    }
+
+    #[test]
+    fn segment_limit_searches_only_the_newest_segments() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["a1,9800000001,ravi,ravi kumar,,,delhi", "a2,9800000002,ravi,ravi verma,,,delhi"]]);
+        for (batch, row) in ["b1,9800000003,ravi,ravi gupta,,,delhi", "c1,9800000001,ravi,ravi shah,,,delhi"].iter().enumerate() {
+            // Stamps have second resolution
+            std::thread::sleep(Duration::from_millis(1100));
+            let csv = write_csv(&dir, &format!("later{}.csv", batch), &[row]);
+            append_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+        }
+        let matches = |max_search_segments: Option<usize>, query: &str| {
+            let service = SearchService::new(&index_dir, SearchConfig { max_search_segments, ..SearchConfig::default() }).unwrap();
+            let results = service.search(query, &SearchOptions::default()).unwrap();
+            let mut keys: Vec<String> = ids(&results).into_iter().map(String::from).collect();
+            keys.sort();
+            (results.total_matches, keys)
+        };
+
+        assert_eq!(matches(None, "name:ravi").0, 4);
+        assert_eq!(matches(Some(1), "name:ravi"), (1, vec!["c1".to_string()]));
+        assert_eq!(matches(Some(2), "name:ravi"), (2, vec!["b1".to_string(), "c1".to_string()]));
+        assert_eq!(matches(Some(3), "name:ravi").0, 4);
+        // Fan-outs still reach the oldest segment
+        assert_eq!(matches(Some(1), "mobile:9800000001").1, ["a1", "c1"]);
+    }
+
+    #[test]
+    fn empty_fields_are_omitted_only_when_asked() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["m1,9800000001,,ravi kumar,,,delhi"]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+
+        let record = &service.search("name:ravi", &SearchOptions::default()).unwrap().results[0];
+        assert_eq!(record["fname"], "");
+        assert_eq!(record["alt"], "");
+        let options = SearchOptions { omit_empty: true, ..SearchOptions::default() };
+        let record = &service.search("name:ravi", &options).unwrap().results[0];
+        for field in ["fname", "alt", "email"] {
+            assert!(record.get(field).is_none(), "{field}: {record}");
+        }
+        assert_eq!(record["name"], "ravi kumar");
+        assert_eq!(record["mobile"], "9800000001");
+    }
+
+    #[test]
+    fn zero_reason_tells_the_empty_result_causes_apart() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&["m1,9800000001,ravi,ravi kumar,,,delhi"]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let reason = |query: &str, limit: Option<usize>| {
+            let results = service.search(query, &SearchOptions { limit, ..SearchOptions::default() }).unwrap();
+            results.zero_reason
+        };
+
+        assert_eq!(reason("name:ravi", None), None);
+        assert_eq!(reason("name:nobody", None), Some("no_match"));
+        // The name clause had text, but none of it searchable
+        assert_eq!(reason("name:!!! AND address:pune", None), Some("all_tokens_filtered"));
+        assert_eq!(reason("name: AND address:pune", None), Some("empty_value"));
+        assert_eq!(reason("name:ravi", Some(0)), Some("limit"));
+    }
+
+    #[test]
+    fn fanout_lists_direct_matches_before_linked_records() {
+        let dir = TempDir::new().unwrap();
+        // Linked records come first in index order
+        let index_dir = index_batches(&dir, &[&[
+            "k2,9800000005,sita,sita ram,9800000001,,delhi",
+            "k1,9800000009,ravi,ravi k,,,pune",
+            "k1,9800000001,ravi,ravi kumar,,,delhi",
+            "k3,9800000003,amit,amit shah,,,delhi",
+        ]]);
+        let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+        let options = SearchOptions { found_via: true, ..SearchOptions::default() };
+
+        let results = service.search("mobile:9800000001", &options).unwrap();
+        let found: Vec<(&str, &str)> = results.results.iter()
+            .map(|r| (r["name"].as_str().unwrap(), r["found_via"].as_str().unwrap()))
+            .collect();
+        assert_eq!(found, [("ravi kumar", "direct"), ("ravi k", "key_linked"), ("sita ram", "alt_linked")]);
+
+        // Rows of a query that doesn't fan out are all direct matches
+        let results = service.search("address:delhi", &options).unwrap();
+        assert!(results.results.iter().all(|r| r["found_via"] == "direct"));
+        assert!(service.search("mobile:9800000001", &SearchOptions::default()).unwrap().results[0].get("found_via").is_none());
+    }
+
+    #[test]
+    fn unsearchable_fields_are_returned_but_cannot_be_queried() {
+        let dir = TempDir::new().unwrap();
+        let csv = dir.path().join("people.csv");
+        std::fs::write(&csv, "master_id,mobile,fname,name,alt,email,address,notes\n\
+            m1,9800000001,ravi,ravi kumar,9811111111,,delhi,called twice\n").unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(csv.to_str().unwrap(), &index_dir, &IndexOptions::default()).unwrap();
+        let service = SearchService::new(&index_dir, SearchConfig {
+            unsearchable_fields: HashSet::from(["notes".to_string(), "alt".to_string()]),
+            ..SearchConfig::default()
+        }).unwrap();
+        let options = SearchOptions::default();
+
+        let results = service.search("name:ravi", &options).unwrap();
+        assert_eq!(results.results[0]["notes"], "called twice");
+        assert_eq!(results.results[0]["alt"], "9811111111");
+
+        let code = |query: &str| service.search(query, &options).unwrap_err().downcast::<QueryError>().unwrap().code;
+        assert_eq!(code("notes:twice"), "FIELD_NOT_SEARCHABLE");
+        assert_eq!(code("name:ravi AND NOT notes:twice"), "FIELD_NOT_SEARCHABLE");
+        // Point lookups on alt take no shortcut around the rule
+        assert_eq!(code("alt:9811111111"), "FIELD_NOT_SEARCHABLE");
+        let err = service.normalize("notes", "twice").unwrap_err().downcast::<QueryError>().unwrap();
+        assert_eq!(err.code, "FIELD_NOT_SEARCHABLE");
+
+        // Ordering or grouping on them would reveal their values just the same
+        let rejected = |options: &SearchOptions| {
+            service.search("name:ravi", options).unwrap_err().downcast::<QueryError>().unwrap().code
+        };
+        let sorted = SearchOptions {
+            sort: vec![SortKey { field: "notes".to_string(), descending: false }],
+            ..SearchOptions::default()
+        };
+        assert_eq!(rejected(&sorted), "FIELD_NOT_SEARCHABLE");
+        let deduped = SearchOptions { dedup_key: vec!["alt".to_string()], ..SearchOptions::default() };
+        assert_eq!(rejected(&deduped), "FIELD_NOT_SEARCHABLE");
+    }
+
+    #[test]
+    fn result_limits_are_read_from_the_environment() {
+        // No other test reads these variables
+        unsafe {
+            std::env::set_var("DEFAULT_RESULT_LIMIT", "7");
+            std::env::set_var("MAX_RESULT_LIMIT", "40");
+        }
+        let config = SearchConfig::from_env();
+        assert_eq!((config.default_limit, config.max_limit), (7, 40));
+        assert_eq!(config.resolve_limit(None), (7, false));
+        assert_eq!(config.resolve_limit(Some(25)), (25, false));
+        assert_eq!(config.resolve_limit(Some(100)), (40, true));
+
+        // The older page size names take precedence
+        unsafe { std::env::set_var("MAX_PAGE_SIZE", "30") };
+        assert_eq!(SearchConfig::from_env().max_limit, 30);
+        unsafe {
+            for name in ["DEFAULT_RESULT_LIMIT", "MAX_RESULT_LIMIT", "MAX_PAGE_SIZE"] {
+                std::env::remove_var(name);
+            }
+        }
+        let defaults = SearchConfig::default();
+        assert_eq!(SearchConfig::from_env().max_limit, defaults.max_limit);
+    }
+
+    #[test]
+    fn results_hash_is_stable_until_a_matching_record_is_added() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = index_batches(&dir, &[&[
+            "m2,9800000002,ravi,ravi shah,,,delhi",
+            "m1,9800000001,ravi,ravi kumar,,,delhi",
+            "m1,9800000009,ravi,ravi k,,,pune",
+        ]]);
+        let hash = |query: &str, limit: usize| {
+            let service = SearchService::new(&index_dir, SearchConfig::default()).unwrap();
+            let options = SearchOptions { results_hash: true, limit: Some(limit), ..SearchOptions::default() };
+            service.search(query, &options).unwrap().results_hash.unwrap()
+        };
+
+        // FNV-1a over the sorted, distinct keys: fixed across runs and releases
+        let first = hash("name:ravi", 10);
+        assert_eq!(first, "218e5ac29c64e636");
+        assert_eq!(hash("name:ravi", 10), first);
+        // Every match counts, not just the returned page, in any order
+        assert_eq!(hash("name:ravi", 1), first);
+        assert_eq!(hash("master_id:m2 OR master_id:m1", 10), first);
+        assert_ne!(hash("address:delhi AND name:shah", 10), first);
+
+        let unrelated = write_csv(&dir, "unrelated.csv", &["m3,9800000003,amit,amit shah,,,pune"]);
+        append_index(&unrelated, &index_dir, &IndexOptions::default()).unwrap();
+        assert_eq!(hash("name:ravi", 10), first);
+
+        let matching = write_csv(&dir, "matching.csv", &["m4,9800000004,ravi,ravi verma,,,delhi"]);
+        append_index(&matching, &index_dir, &IndexOptions::default()).unwrap();
+        assert_ne!(hash("name:ravi", 10), first);
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{Result, anyhow};
+use serde_json::json;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use tantivy::schema::FieldType;
+use tantivy::termdict::TermMerger;
+use tantivy::{Index, ReloadPolicy};
+
+/// Write every term of a text field with its document frequency to
+/// `out_file`: NDJSON (`{"term": ..., "doc_freq": ...}` per line) when the
+/// name ends in `.json`, `.jsonl` or `.ndjson`, tab-separated otherwise.
+///
+/// The per-segment term dictionaries are merged as a stream, so memory use
+/// doesn't grow with the number of terms. Frequencies come from the term
+/// dictionaries, so deleted docs still count until segments merge.
+pub fn export_terms(index_dir: &str, field_name: &str, out_file: &str) -> Result<()> {
+    println!("Opening index from: {}", index_dir);
+    let index = Index::open_in_dir(index_dir)?;
+    let schema = index.schema();
+    let field = schema.get_field(field_name)
+        .map_err(|_| anyhow!("Index has no field '{}'", field_name))?;
+    let indexed = match schema.get_field_entry(field).field_type() {
+        FieldType::Str(options) => options.get_indexing_options().is_some(),
+        _ => false,
+    };
+    if !indexed {
+        return Err(anyhow!("Field '{}' has no text terms to export", field_name));
+    }
+
+    let reader = index.reader_builder()
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()?;
+    let searcher = reader.searcher();
+    let inverted = searcher.segment_readers()
+        .iter()
+        .map(|segment| segment.inverted_index(field))
+        .collect::<tantivy::Result<Vec<_>>>()?;
+    let streams = inverted.iter()
+        .map(|inv| inv.terms().stream())
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let mut merger = TermMerger::new(streams);
+
+    let as_json = [".json", ".jsonl", ".ndjson"].iter().any(|ext| out_file.ends_with(ext));
+    let mut out = BufWriter::new(File::create(out_file)
+        .map_err(|e| anyhow!("Cannot create {}: {}", out_file, e))?);
+
+    let mut exported = 0u64;
+    while merger.advance() {
+        let doc_freq: u64 = merger.current_segment_ords_and_term_infos()
+            .map(|(_, info)| info.doc_freq as u64)
+            .sum();
+        let term = String::from_utf8_lossy(merger.key());
+        if as_json {
+            writeln!(out, "{}", json!({"term": term, "doc_freq": doc_freq}))?;
+        } else {
+            writeln!(out, "{}\t{}", escape_tsv(&term), doc_freq)?;
+        }
+        exported += 1;
+    }
+    out.flush()?;
+
+    println!("Exported {} terms of '{}' to {}", exported, field_name, out_file);
+    Ok(())
+}
+
+/// Backslash-escape the characters that would break a TSV row
+fn escape_tsv(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::{append_index, build_index, IndexOptions};
+    use crate::test_support::write_csv;
+    use tempfile::TempDir;
+
+    #[test]
+    fn exported_terms_carry_their_doc_freq_across_segments() {
+        let dir = TempDir::new().unwrap();
+        let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+        std::fs::create_dir_all(&index_dir).unwrap();
+        build_index(&write_csv(&dir, "a.csv", &["m1,,,,,,mg road delhi", "m2,,,,,,delhi"]), &index_dir, &IndexOptions::default()).unwrap();
+        append_index(&write_csv(&dir, "b.csv", &["m3,,,,,,Delhi", "m4,,,,,,pune road"]), &index_dir, &IndexOptions::default()).unwrap();
+
+        let tsv = dir.path().join("address.tsv");
+        export_terms(&index_dir, "address", tsv.to_str().unwrap()).unwrap();
+        assert_eq!(std::fs::read_to_string(&tsv).unwrap(), "delhi\t3\nmg\t1\npune\t1\nroad\t2\n");
+
+        let ndjson = dir.path().join("master_id.ndjson");
+        export_terms(&index_dir, "master_id", ndjson.to_str().unwrap()).unwrap();
+        let first = std::fs::read_to_string(&ndjson).unwrap().lines().next().unwrap().to_string();
+        assert_eq!(first, r#"{"doc_freq":1,"term":"m1"}"#);
+
+        let err = export_terms(&index_dir, "indexed_at", tsv.to_str().unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "Field 'indexed_at' has no text terms to export");
+        assert_eq!(escape_tsv("a\tb\\c"), "a\\tb\\\\c");
+    }
+}
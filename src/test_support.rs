@@ -0,0 +1,37 @@
+use crate::indexer::{append_index, build_index, IndexOptions};
+use tempfile::TempDir;
+
+/// Header of the seven columns every fixture CSV carries
+pub(crate) const HEADER: &str = "master_id,mobile,fname,name,alt,email,address";
+
+/// CSV file of the given rows under the standard header
+pub(crate) fn write_csv(dir: &TempDir, file: &str, rows: &[&str]) -> String {
+    let path = dir.path().join(file);
+    std::fs::write(&path, format!("{}\n{}\n", HEADER, rows.join("\n"))).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+/// Index of the given rows in its own directory under `dir`
+pub(crate) fn build(dir: &TempDir, name: &str, rows: &[&str]) -> String {
+    let csv = write_csv(dir, &format!("{}.csv", name), rows);
+    let index_dir = dir.path().join(name).to_str().unwrap().to_string();
+    std::fs::create_dir_all(&index_dir).unwrap();
+    build_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+    index_dir
+}
+
+/// Index built from the first batch of CSV rows and appended with each
+/// further one, so every batch lands in its own segment
+pub(crate) fn index_batches(dir: &TempDir, batches: &[&[&str]]) -> String {
+    let index_dir = dir.path().join("index").to_str().unwrap().to_string();
+    std::fs::create_dir_all(&index_dir).unwrap();
+    for (i, rows) in batches.iter().enumerate() {
+        let csv = write_csv(dir, &format!("batch{}.csv", i), rows);
+        if i == 0 {
+            build_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+        } else {
+            append_index(&csv, &index_dir, &IndexOptions::default()).unwrap();
+        }
+    }
+    index_dir
+}
@@ -0,0 +1,279 @@
+use anyhow::{Result, anyhow};
+use std::sync::Mutex;
+use tantivy::Index;
+use tantivy::tokenizer::{
+    AsciiFoldingFilter, Language, LowerCaser, NgramTokenizer, RemoveLongFilter, SimpleTokenizer, Stemmer,
+    StopWordFilter, TextAnalyzer, Token, TokenStream, Tokenizer, TokenizerManager,
+};
+
+/// Name the email tokenizer is registered under
+pub const EMAIL_TOKENIZER: &str = "email";
+
+/// Name of the tokenizer of substring companions (see `schema::SUBSTRING_FIELDS`):
+/// every lowercased substring of `SUBSTRING_MIN_GRAM` to `SUBSTRING_MAX_GRAM`
+/// characters becomes a term
+pub const SUBSTRING_TOKENIZER: &str = "substring";
+pub const SUBSTRING_MIN_GRAM: usize = 3;
+pub const SUBSTRING_MAX_GRAM: usize = 20;
+
+/// Tokenizers added through `register_custom_tokenizer`, by name
+static CUSTOM_TOKENIZERS: Mutex<Vec<(String, TextAnalyzer)>> = Mutex::new(Vec::new());
+
+/// Register the crate's custom tokenizers on an index.
+/// Must run on every `Index` handle (build, append and search) before any
+/// document is indexed or query parsed, or fields using them fail to resolve.
+pub fn register_tokenizers(index: &Index) {
+    index.tokenizers().register(
+        EMAIL_TOKENIZER,
+        TextAnalyzer::builder(EmailTokenizer).filter(LowerCaser).build(),
+    );
+    let ngrams = NgramTokenizer::all_ngrams(SUBSTRING_MIN_GRAM, SUBSTRING_MAX_GRAM)
+        .expect("valid substring gram bounds");
+    index.tokenizers().register(
+        SUBSTRING_TOKENIZER,
+        TextAnalyzer::builder(ngrams).filter(LowerCaser).build(),
+    );
+    for (name, analyzer) in CUSTOM_TOKENIZERS.lock().unwrap().iter() {
+        index.tokenizers().register(name, analyzer.clone());
+    }
+}
+
+/// Make a tokenizer available to every index opened from now on, so index
+/// builds can assign it to TEXT fields (`--tokenizer field=name`). It has to
+/// be registered under the same name wherever such an index is searched.
+/// Built-in names (`default`, `raw`, `email`...) can't be replaced.
+pub fn register_custom_tokenizer(name: &str, analyzer: TextAnalyzer) -> Result<()> {
+    if name == EMAIL_TOKENIZER || name == SUBSTRING_TOKENIZER || TokenizerManager::default().get(name).is_some() {
+        return Err(anyhow!("Tokenizer '{}' is built in and cannot be replaced", name));
+    }
+    let mut custom = CUSTOM_TOKENIZERS.lock().unwrap();
+    custom.retain(|(existing, _)| existing != name);
+    custom.push((name.to_string(), analyzer));
+    Ok(())
+}
+
+/// Register the tokenizers of `CUSTOM_TOKENIZERS`, `name=spec,...` with specs
+/// as in `build_query_analyzer` (e.g. `address_words=stopwords(house|flat)`)
+pub fn register_env_tokenizers() -> Result<()> {
+    let Ok(tokenizers) = std::env::var("CUSTOM_TOKENIZERS") else {
+        return Ok(());
+    };
+    for (name, spec) in tokenizers.split(',').filter_map(|pair| pair.split_once('=')) {
+        register_custom_tokenizer(name.trim(), build_query_analyzer(spec)?)?;
+    }
+    Ok(())
+}
+
+/// Whether a tokenizer name resolves: built in or registered as custom
+pub fn has_tokenizer(name: &str) -> bool {
+    name == EMAIL_TOKENIZER
+        || name == SUBSTRING_TOKENIZER
+        || TokenizerManager::default().get(name).is_some()
+        || CUSTOM_TOKENIZERS.lock().unwrap().iter().any(|(existing, _)| existing == name)
+}
+
+/// Build a query-time analyzer for a TEXT field from a spec of `+`-separated
+/// filters applied after the default tokenization and lowercasing:
+/// - `stopwords(w1|w2|...)`: drop the listed words
+/// - `stopwords(<language>)`: drop a language's stop words, e.g. `stopwords(english)`
+/// - `asciifold`: fold accented letters to ASCII
+/// - `stem(<language>)`: reduce words to their stem, e.g. `stem(english)`
+///
+/// The index is untouched, so only filters that remove words or produce terms
+/// the index also contains are safe. Folding or stemming a field that was
+/// indexed without them yields terms that don't exist in the index and silently
+/// misses documents.
+pub fn build_query_analyzer(spec: &str) -> Result<TextAnalyzer> {
+    let mut builder = TextAnalyzer::builder(SimpleTokenizer::default())
+        .filter(RemoveLongFilter::limit(40))
+        .filter(LowerCaser)
+        .dynamic();
+
+    for filter in spec.split('+').map(str::trim).filter(|f| !f.is_empty()) {
+        let (name, arg) = match filter.split_once('(') {
+            Some((name, rest)) => {
+                let arg = rest.strip_suffix(')')
+                    .ok_or_else(|| anyhow!("Unclosed '(' in analyzer filter: {}", filter))?;
+                (name.trim().to_lowercase(), Some(arg.trim()))
+            }
+            None => (filter.to_lowercase(), None),
+        };
+
+        builder = match (name.as_str(), arg) {
+            ("asciifold", None) => builder.filter_dynamic(AsciiFoldingFilter),
+            ("stem", Some(language)) => builder.filter_dynamic(Stemmer::new(parse_language(language)?)),
+            ("stopwords", Some(words)) if !words.contains('|') && parse_language(words).is_ok() => {
+                let language = parse_language(words)?;
+                let filter = StopWordFilter::new(language)
+                    .ok_or_else(|| anyhow!("No stop word list for language: {}", words))?;
+                builder.filter_dynamic(filter)
+            }
+            ("stopwords", Some(words)) => builder.filter_dynamic(StopWordFilter::remove(
+                words.split('|').map(|w| w.trim().to_lowercase()).filter(|w| !w.is_empty()),
+            )),
+            _ => return Err(anyhow!("Unknown analyzer filter: {}", filter)),
+        };
+    }
+
+    Ok(builder.build())
+}
+
+/// Parse a language name such as `english` for stemming and stop words
+fn parse_language(name: &str) -> Result<Language> {
+    let mut chars = name.trim().chars();
+    let capitalized: String = chars.next().map(|c| c.to_ascii_uppercase()).into_iter()
+        .chain(chars.flat_map(char::to_lowercase))
+        .collect();
+    serde_json::from_value(serde_json::Value::String(capitalized))
+        .map_err(|_| anyhow!("Unknown language: {}", name))
+}
+
+/// Email-aware tokenizer.
+///
+/// `john.doe@gmail.com` yields the whole address, the local part `john.doe`,
+/// the domain `gmail.com` and each domain label (`gmail`, `com`). Searching by
+/// full address, local part or domain thus each hit a dedicated term instead of
+/// depending on how a generic tokenizer happens to split dots.
+/// Several addresses in one value may be separated by whitespace, `,` or `;`.
+#[derive(Clone, Default)]
+pub struct EmailTokenizer;
+
+pub struct EmailTokenStream {
+    tokens: Vec<Token>,
+    index: usize,
+}
+
+impl Tokenizer for EmailTokenizer {
+    type TokenStream<'a> = EmailTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> EmailTokenStream {
+        let mut tokens = Vec::new();
+        let mut push = |from: usize, to: usize| {
+            if from < to {
+                tokens.push(Token {
+                    offset_from: from,
+                    offset_to: to,
+                    position: tokens.len(),
+                    text: text[from..to].to_string(),
+                    position_length: 1,
+                });
+            }
+        };
+
+        for part in text.split(|c: char| c.is_whitespace() || c == ',' || c == ';') {
+            // Byte offset of the part; separators may be multi-byte whitespace
+            let part_start = part.as_ptr() as usize - text.as_ptr() as usize;
+
+            // Drop wrapping punctuation such as <john@x.com> or "john@x.com"
+            let trimmed = part.trim_matches(|c: char| !c.is_alphanumeric());
+            if trimmed.is_empty() {
+                continue;
+            }
+            let from = part_start + part.find(trimmed).unwrap_or(0);
+            let to = from + trimmed.len();
+            push(from, to);
+
+            if let Some(at) = trimmed.find('@') {
+                let at = from + at;
+                push(from, at); // local part
+                push(at + 1, to); // domain
+                let mut label_start = at + 1;
+                for label in text[at + 1..to].split('.') {
+                    push(label_start, label_start + label.len());
+                    label_start += label.len() + 1;
+                }
+            }
+        }
+
+        EmailTokenStream { tokens, index: 0 }
+    }
+}
+
+impl TokenStream for EmailTokenStream {
+    fn advance(&mut self) -> bool {
+        if self.index < self.tokens.len() {
+            self.index += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.index - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.index - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<(String, usize, usize)> {
+        let index = Index::create_in_ram(tantivy::schema::Schema::builder().build());
+        register_tokenizers(&index);
+        let mut analyzer = index.tokenizers().get(EMAIL_TOKENIZER).unwrap();
+        let mut tokens = Vec::new();
+        analyzer.token_stream(text).process(&mut |token| tokens.push((token.text.clone(), token.offset_from, token.offset_to)));
+        tokens
+    }
+
+    fn texts(text: &str) -> Vec<String> {
+        tokens(text).into_iter().map(|(text, _, _)| text).collect()
+    }
+
+    #[test]
+    fn email_splits_into_address_local_part_domain_and_labels() {
+        assert_eq!(texts("John.Doe@Gmail.com"), ["john.doe@gmail.com", "john.doe", "gmail.com", "gmail", "com"]);
+        assert_eq!(texts("<a_b@mail.co.in>"), ["a_b@mail.co.in", "a_b", "mail.co.in", "mail", "co", "in"]);
+        // Not an address: kept whole
+        assert_eq!(texts("john.doe"), ["john.doe"]);
+    }
+
+    #[test]
+    fn several_addresses_keep_their_own_offsets() {
+        let text = "a@x.com;\u{3000}b@y.org";
+        let tokens = tokens(text);
+        assert_eq!(tokens.len(), 10);
+        for (token, from, to) in &tokens {
+            assert_eq!(text[*from..*to].to_lowercase(), *token);
+        }
+        assert_eq!(tokens[5], ("b@y.org".to_string(), 11, 18));
+    }
+
+    #[test]
+    fn a_registered_tokenizer_decides_what_a_field_matches() {
+        use crate::indexer::{build_index, IndexOptions};
+        use crate::search_service::{SearchConfig, SearchOptions, SearchService};
+        use crate::test_support::write_csv;
+        use std::collections::HashMap;
+        use tantivy::tokenizer::WhitespaceTokenizer;
+
+        let whitespace = TextAnalyzer::builder(WhitespaceTokenizer::default()).filter(LowerCaser).build();
+        register_custom_tokenizer("test_whitespace", whitespace).unwrap();
+        assert!(has_tokenizer("test_whitespace"));
+        assert!(register_custom_tokenizer("raw", TextAnalyzer::from(WhitespaceTokenizer::default())).is_err());
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let csv = write_csv(&dir, "rows.csv", &["m1,,,,,,Flat A-12 MG Road"]);
+        let service = |name: &str, tokenizers: HashMap<String, String>| {
+            let index_dir = dir.path().join(name).to_str().unwrap().to_string();
+            std::fs::create_dir_all(&index_dir).unwrap();
+            build_index(&csv, &index_dir, &IndexOptions { tokenizers, ..IndexOptions::default() }).unwrap();
+            SearchService::new(&index_dir, SearchConfig::default()).unwrap()
+        };
+        let default = service("default", HashMap::new());
+        let custom = service("custom", HashMap::from([("address".to_string(), "test_whitespace".to_string())]));
+        let matches = |service: &SearchService, query: &str| service.search(query, &SearchOptions::default()).unwrap().total_matches;
+
+        // The default splits "a-12" apart; whitespace splitting keeps it whole
+        assert_eq!(matches(&default, "address:12"), 1);
+        assert_eq!(matches(&custom, "address:12"), 0);
+        assert_eq!(matches(&custom, "address:a-12"), 1);
+        assert_eq!(matches(&custom, "address:mg road"), 1);
+    }
+}
+
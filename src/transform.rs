@@ -0,0 +1,76 @@
+use serde_json::Value;
+use std::fmt::Debug;
+
+/// Rewrites each result record before it is returned, so deployments can
+/// mask or reformat values without changing the search code. Set one on
+/// `SearchConfig::result_transform`; it runs on every record of search,
+/// template, /context and /records responses.
+pub trait ResultTransform: Debug + Send + Sync {
+    fn apply(&self, record: &mut Value);
+}
+
+/// Returns records unchanged (the default)
+#[derive(Debug, Default)]
+pub struct PassThrough;
+
+impl ResultTransform for PassThrough {
+    fn apply(&self, _record: &mut Value) {}
+}
+
+/// Replaces all but the last `visible` digits of the given fields with `*`,
+/// e.g. `9876543210` -> `******3210`. Other characters are kept as-is.
+#[derive(Debug)]
+pub struct MaskDigits {
+    pub fields: Vec<String>,
+    pub visible: usize,
+}
+
+/// Digits left readable by `MASK_FIELDS` masking
+pub const DEFAULT_VISIBLE_DIGITS: usize = 4;
+
+impl MaskDigits {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self { fields, visible: DEFAULT_VISIBLE_DIGITS }
+    }
+}
+
+impl ResultTransform for MaskDigits {
+    fn apply(&self, record: &mut Value) {
+        for field in &self.fields {
+            if let Some(Value::String(value)) = record.get_mut(field) {
+                *value = mask_digits(value, self.visible);
+            }
+        }
+    }
+}
+
+fn mask_digits(value: &str, visible: usize) -> String {
+    let masked = value.chars().filter(char::is_ascii_digit).count().saturating_sub(visible);
+    let mut seen = 0;
+    value.chars()
+        .map(|c| {
+            if !c.is_ascii_digit() {
+                return c;
+            }
+            seen += 1;
+            if seen <= masked { '*' } else { c }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masking_keeps_the_last_digits_and_other_characters() {
+        let transform = MaskDigits::new(vec!["mobile".to_string(), "alt".to_string()]);
+        let mut record = serde_json::json!({"mobile": "+91 98765-43210", "alt": "123", "name": "ravi 42", "email": null});
+        transform.apply(&mut record);
+        assert_eq!(record, serde_json::json!({"mobile": "+** *****-*3210", "alt": "123", "name": "ravi 42", "email": null}));
+
+        let mut record = serde_json::json!({"mobile": "9876543210"});
+        PassThrough.apply(&mut record);
+        assert_eq!(record["mobile"], "9876543210");
+    }
+}